@@ -50,5 +50,6 @@ pub use self::stdio::*;
 pub use self::task::*;
 pub use self::time::*;
 
+pub use axhal::misc::exit as ax_exit;
 pub use axhal::misc::terminate as ax_terminate;
 pub use axio::PollState as AxPollState;
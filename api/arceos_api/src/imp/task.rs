@@ -1,4 +1,10 @@
 pub fn ax_sleep_until(deadline: crate::time::AxTimeValue) {
+    #[cfg(feature = "multitask")]
+    if ax_in_async_task() {
+        warn_blocking_in_poll("sleep");
+        ax_block_in_place(&mut || axtask::sleep_until(deadline));
+        return;
+    }
     #[cfg(feature = "multitask")]
     axtask::sleep_until(deadline);
     #[cfg(not(feature = "multitask"))]
@@ -59,6 +65,40 @@ cfg_task! {
         axtask::current().id().as_u64()
     }
 
+    pub fn ax_in_async_task() -> bool {
+        axtask::current().is_nonblocking()
+    }
+
+    pub fn ax_block_in_place(f: &mut dyn FnMut()) {
+        #[cfg(feature = "async")]
+        axasync::block_in_place(f);
+
+        // Nothing to hand the other async tasks to, only the check of
+        // blocking calls is lifted.
+        #[cfg(not(feature = "async"))]
+        if ax_in_async_task() {
+            axtask::current().exit_nonblocking();
+            f();
+            axtask::current().enter_nonblocking();
+        } else {
+            f();
+        }
+    }
+
+    /// Reports a blocking call in the poll of an async task, once, as it
+    /// stalls the other tasks of the executor.
+    fn warn_blocking_in_poll(call: &str) {
+        use core::sync::atomic::{AtomicBool, Ordering};
+        static WARNED: AtomicBool = AtomicBool::new(false);
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            axlog::warn!(
+                "blocking {} in the poll of an async task {}, await the async version instead",
+                call,
+                ax_current_task_id()
+            );
+        }
+    }
+
     pub fn ax_spawn<F>(f: F, name: alloc::string::String, stack_size: usize) -> AxTaskHandle
     where
         F: FnOnce() + Send + 'static,
@@ -32,6 +32,9 @@ pub mod sys {
     define_api! {
         /// Shutdown the whole system and all CPUs.
         pub fn ax_terminate() -> !;
+        /// Shutdown the whole system and all CPUs, reporting `code` as the
+        /// exit status of the machine where possible.
+        pub fn ax_exit(code: u32) -> !;
     }
 }
 
@@ -127,7 +130,9 @@ pub mod task {
     define_api! {
         /// Current task is going to sleep, it will be woken up at the given deadline.
         ///
-        /// If the feature `multitask` is not enabled, it uses busy-wait instead
+        /// If the feature `multitask` is not enabled, it uses busy-wait instead.
+        /// In the poll of an async task, it warns and sleeps in place, see
+        /// [`ax_block_in_place`].
         pub fn ax_sleep_until(deadline: crate::time::AxTimeValue);
 
         /// Current task gives up the CPU time voluntarily, and switches to another
@@ -145,6 +150,14 @@ pub mod task {
 
         /// Returns the current task's ID.
         pub fn ax_current_task_id() -> u64;
+        /// Returns whether the current task is polling an async task, in
+        /// which it must not block.
+        pub fn ax_in_async_task() -> bool;
+        /// Runs the blocking function `f`, in the poll of an async task.
+        ///
+        /// With the feature `async`, another task runs the other async tasks
+        /// until it returns.
+        pub fn ax_block_in_place(f: &mut dyn FnMut());
         /// Spawns a new task with the given entry point and other arguments.
         pub fn ax_spawn(
             f: impl FnOnce() + Send + 'static,
@@ -441,6 +441,29 @@ pub fn sys_shutdown(
     })
 }
 
+/// Get the host name, see [`axnet::config::hostname`].
+///
+/// The name is truncated to `len` bytes, without the NUL terminator, if it is
+/// too long.
+pub fn sys_gethostname(name: *mut c_char, len: usize) -> c_int {
+    debug!("sys_gethostname <= {:#x} {}", name as usize, len);
+    syscall_body!(sys_gethostname, {
+        if name.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(name as *mut u8, len) };
+        let hostname = axnet::config::hostname();
+        let hostname = hostname.as_bytes();
+        if hostname.len() < len {
+            dst[..hostname.len()].copy_from_slice(hostname);
+            dst[hostname.len()] = 0;
+        } else {
+            dst.copy_from_slice(&hostname[..len]);
+        }
+        Ok(0)
+    })
+}
+
 /// Query addresses for a domain name.
 ///
 /// Only IPv4. Ports are always 0. Ignore servname and hint.
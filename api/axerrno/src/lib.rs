@@ -4,6 +4,9 @@
 
 #![no_std]
 
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
 use core::fmt;
 
 /// A specialized [`Result`] type for ArceOS operations.
@@ -165,11 +168,63 @@ pub enum AxError {
     SocketShutdown = 72,
     /// Disk error
     DiskError = 73,
+    /// Object is in an invalid state for the operation
+    BadState = 74,
+    /// Directory not empty
+    DirectoryNotEmpty = 75,
+    /// Data not valid for the operation
+    InvalidData = 76,
+    /// Unexpected end of file
+    UnexpectedEof = 77,
+    /// Failed to write the whole buffer
+    WriteZero = 78,
 }
 
-impl fmt::Display for AxError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self {
+#[allow(non_upper_case_globals)]
+impl AxError {
+    /// The largest error code.
+    pub const MAX_CODE: i32 = Self::WriteZero as i32;
+
+    /// Alias of [`AxError::IoError`].
+    pub const Io: Self = Self::IoError;
+    /// Alias of [`AxError::Busy`].
+    pub const ResourceBusy: Self = Self::Busy;
+    /// Alias of [`AxError::NoSpaceLeftOnDevice`].
+    pub const StorageFull: Self = Self::NoSpaceLeftOnDevice;
+
+    /// Returns the error code.
+    ///
+    /// Codes are stable: a variant keeps its code once assigned, and new
+    /// variants get codes above [`MAX_CODE`](Self::MAX_CODE).
+    #[inline]
+    pub const fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Returns the Linux errno corresponding to the error.
+    #[inline]
+    pub fn errno(self) -> i32 {
+        LinuxError::from(self).code()
+    }
+}
+
+impl TryFrom<i32> for AxError {
+    type Error = ();
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        if (1..=Self::MAX_CODE).contains(&value) {
+            // SAFETY: the discriminants are contiguous from 1 to `MAX_CODE`.
+            Ok(unsafe { core::mem::transmute::<i32, Self>(value) })
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl AxError {
+    /// Returns the description of the error.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
             Self::PermissionDenied => "permission denied",
             Self::NotFound => "not found",
             Self::NoProcess => "no such process",
@@ -240,92 +295,192 @@ impl fmt::Display for AxError {
             Self::TransportEndpointAlreadyConnected => "transport endpoint is already connected",
             Self::TransportEndpointNotConnected => "transport endpoint is not connected",
             Self::HostLookupFailed => "hostname lookup failed",
-            Self::OperationNotSupportedOnEndpoint =>
-                "operation not supported on transport endpoint",
+            Self::OperationNotSupportedOnEndpoint => {
+                "operation not supported on transport endpoint"
+            }
             Self::SocketShutdown => "socket is shut down",
             Self::DiskError => "disk error",
-        })
+            Self::BadState => "bad internal state",
+            Self::DirectoryNotEmpty => "directory not empty",
+            Self::InvalidData => "invalid data",
+            Self::UnexpectedEof => "unexpected end of file",
+            Self::WriteZero => "write zero",
+        }
+    }
+}
+
+impl fmt::Display for AxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
 /// Linux error codes.
+///
+/// The values are the generic Linux errno numbers (`asm-generic/errno.h`), so
+/// they can be returned to applications as-is.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(i32)]
 #[non_exhaustive]
 pub enum LinuxError {
-    EPERM = 1,            /* Operation not permitted */
-    ENOENT = 2,           /* No such file or directory */
-    ESRCH = 3,            /* No such process */
-    EINTR = 4,            /* Interrupted system call */
-    EIO = 5,              /* I/O error */
-    ENXIO = 6,            /* No such device or address */
-    E2BIG = 7,            /* Argument list too long */
-    ENOEXEC = 8,          /* Exec format error */
-    EBADF = 9,            /* Bad file number */
-    ECHILD = 10,          /* No child processes */
-    EAGAIN = 11,          /* Try again */
-    ENOMEM = 12,          /* Out of memory */
-    EACCES = 13,          /* Permission denied */
-    EFAULT = 14,          /* Bad address */
-    ENOTBLK = 15,         /* Block device required */
-    EBUSY = 16,           /* Device or resource busy */
-    EEXIST = 17,          /* File exists */
-    EXDEV = 18,           /* Cross-device link */
-    ENODEV = 19,          /* No such device */
-    ENOTDIR = 20,         /* Not a directory */
-    EISDIR = 21,          /* Is a directory */
-    EINVAL = 22,          /* Invalid argument */
-    ENFILE = 23,          /* File table overflow */
-    EMFILE = 24,          /* Too many open files */
-    ENOTTY = 25,          /* Not a typewriter */
-    ETXTBSY = 26,         /* Text file busy */
-    EFBIG = 27,           /* File too large */
-    ENOSPC = 28,          /* No space left on device */
-    ESPIPE = 29,          /* Illegal seek */
-    EROFS = 30,           /* Read-only file system */
-    EMLINK = 31,          /* Too many links */
-    EPIPE = 32,           /* Broken pipe */
-    EDOM = 33,            /* Math argument out of domain of func */
-    ERANGE = 34,          /* Math result not representable */
-    ENOSYS = 35,          /* Function not implemented */
-    ELOOP = 36,           /* Too many symbolic links encountered */
-    ENAMETOOLONG = 37,    /* File name too long */
-    EBADFD = 38,          /* File descriptor in bad state */
-    EADDRINUSE = 39,      /* Address already in use */
-    EADDRNOTAVAIL = 40,   /* Cannot assign requested address */
-    ENETDOWN = 41,        /* Network is down */
-    ENETUNREACH = 42,     /* Network is unreachable */
-    ENETRESET = 43,       /* Network dropped connection because of reset */
-    ECONNRESET = 44,      /* Connection reset by peer */
-    ENOBUFS = 45,         /* No buffer space available */
-    EISCONN = 46,         /* Transport endpoint is already connected */
-    ENOTCONN = 47,        /* Transport endpoint is not connected */
-    ETIMEDOUT = 48,       /* Connection timed out */
-    ECONNREFUSED = 49,    /* Connection refused */
-    EHOSTUNREACH = 50,    /* No route to host */
-    EALREADY = 51,        /* Operation already in progress */
-    EINPROGRESS = 52,     /* Operation now in progress */
-    EWOULDBLOCK = 53,     /* Operation would block */
-    ENOTSOCK = 54,        /* Socket operation on non-socket */
-    EMSGSIZE = 55,        /* Message too long */
-    EPROTOTYPE = 56,      /* Protocol wrong type for socket */
-    ENOPROTOOPT = 57,     /* Protocol not available */
-    EPROTONOSUPPORT = 58, /* Protocol not supported */
-    EAFNOSUPPORT = 59,    /* Address family not supported by protocol */
-    ENOTSUP = 60,         /* Operation not supported on transport endpoint */
-    ENOSYS2 = 61,         /* Function not implemented */
-    EPROTO = 62,          /* Protocol error */
-    EOVERFLOW = 63,       /* Value too large for defined data type */
-    EBADMSG = 64,         /* Not a data message */
+    EPERM = 1,             /* Operation not permitted */
+    ENOENT = 2,            /* No such file or directory */
+    ESRCH = 3,             /* No such process */
+    EINTR = 4,             /* Interrupted system call */
+    EIO = 5,               /* I/O error */
+    ENXIO = 6,             /* No such device or address */
+    E2BIG = 7,             /* Argument list too long */
+    ENOEXEC = 8,           /* Exec format error */
+    EBADF = 9,             /* Bad file number */
+    ECHILD = 10,           /* No child processes */
+    EAGAIN = 11,           /* Try again */
+    ENOMEM = 12,           /* Out of memory */
+    EACCES = 13,           /* Permission denied */
+    EFAULT = 14,           /* Bad address */
+    ENOTBLK = 15,          /* Block device required */
+    EBUSY = 16,            /* Device or resource busy */
+    EEXIST = 17,           /* File exists */
+    EXDEV = 18,            /* Cross-device link */
+    ENODEV = 19,           /* No such device */
+    ENOTDIR = 20,          /* Not a directory */
+    EISDIR = 21,           /* Is a directory */
+    EINVAL = 22,           /* Invalid argument */
+    ENFILE = 23,           /* File table overflow */
+    EMFILE = 24,           /* Too many open files */
+    ENOTTY = 25,           /* Not a typewriter */
+    ETXTBSY = 26,          /* Text file busy */
+    EFBIG = 27,            /* File too large */
+    ENOSPC = 28,           /* No space left on device */
+    ESPIPE = 29,           /* Illegal seek */
+    EROFS = 30,            /* Read-only file system */
+    EMLINK = 31,           /* Too many links */
+    EPIPE = 32,            /* Broken pipe */
+    EDOM = 33,             /* Math argument out of domain of func */
+    ERANGE = 34,           /* Math result not representable */
+    EDEADLK = 35,          /* Resource deadlock would occur */
+    ENAMETOOLONG = 36,     /* File name too long */
+    ENOLCK = 37,           /* No record locks available */
+    ENOSYS = 38,           /* Invalid system call number */
+    ENOTEMPTY = 39,        /* Directory not empty */
+    ELOOP = 40,            /* Too many symbolic links encountered */
+    ENOMSG = 42,           /* No message of desired type */
+    EIDRM = 43,            /* Identifier removed */
+    ECHRNG = 44,           /* Channel number out of range */
+    EL2NSYNC = 45,         /* Level 2 not synchronized */
+    EL3HLT = 46,           /* Level 3 halted */
+    EL3RST = 47,           /* Level 3 reset */
+    ELNRNG = 48,           /* Link number out of range */
+    EUNATCH = 49,          /* Protocol driver not attached */
+    ENOCSI = 50,           /* No CSI structure available */
+    EL2HLT = 51,           /* Level 2 halted */
+    EBADE = 52,            /* Invalid exchange */
+    EBADR = 53,            /* Invalid request descriptor */
+    EXFULL = 54,           /* Exchange full */
+    ENOANO = 55,           /* No anode */
+    EBADRQC = 56,          /* Invalid request code */
+    EBADSLT = 57,          /* Invalid slot */
+    EBFONT = 59,           /* Bad font file format */
+    ENOSTR = 60,           /* Device not a stream */
+    ENODATA = 61,          /* No data available */
+    ETIME = 62,            /* Timer expired */
+    ENOSR = 63,            /* Out of streams resources */
+    ENONET = 64,           /* Machine is not on the network */
+    ENOPKG = 65,           /* Package not installed */
+    EREMOTE = 66,          /* Object is remote */
+    ENOLINK = 67,          /* Link has been severed */
+    EADV = 68,             /* Advertise error */
+    ESRMNT = 69,           /* Srmount error */
+    ECOMM = 70,            /* Communication error on send */
+    EPROTO = 71,           /* Protocol error */
+    EMULTIHOP = 72,        /* Multihop attempted */
+    EDOTDOT = 73,          /* RFS specific error */
+    EBADMSG = 74,          /* Not a data message */
+    EOVERFLOW = 75,        /* Value too large for defined data type */
+    ENOTUNIQ = 76,         /* Name not unique on network */
+    EBADFD = 77,           /* File descriptor in bad state */
+    EREMCHG = 78,          /* Remote address changed */
+    ELIBACC = 79,          /* Can not access a needed shared library */
+    ELIBBAD = 80,          /* Accessing a corrupted shared library */
+    ELIBSCN = 81,          /* .lib section in a.out corrupted */
+    ELIBMAX = 82,          /* Attempting to link in too many shared libraries */
+    ELIBEXEC = 83,         /* Cannot exec a shared library directly */
+    EILSEQ = 84,           /* Illegal byte sequence */
+    ERESTART = 85,         /* Interrupted system call should be restarted */
+    ESTRPIPE = 86,         /* Streams pipe error */
+    EUSERS = 87,           /* Too many users */
+    ENOTSOCK = 88,         /* Socket operation on non-socket */
+    EDESTADDRREQ = 89,     /* Destination address required */
+    EMSGSIZE = 90,         /* Message too long */
+    EPROTOTYPE = 91,       /* Protocol wrong type for socket */
+    ENOPROTOOPT = 92,      /* Protocol not available */
+    EPROTONOSUPPORT = 93,  /* Protocol not supported */
+    ESOCKTNOSUPPORT = 94,  /* Socket type not supported */
+    EOPNOTSUPP = 95,       /* Operation not supported on transport endpoint */
+    EPFNOSUPPORT = 96,     /* Protocol family not supported */
+    EAFNOSUPPORT = 97,     /* Address family not supported by protocol */
+    EADDRINUSE = 98,       /* Address already in use */
+    EADDRNOTAVAIL = 99,    /* Cannot assign requested address */
+    ENETDOWN = 100,        /* Network is down */
+    ENETUNREACH = 101,     /* Network is unreachable */
+    ENETRESET = 102,       /* Network dropped connection because of reset */
+    ECONNABORTED = 103,    /* Software caused connection abort */
+    ECONNRESET = 104,      /* Connection reset by peer */
+    ENOBUFS = 105,         /* No buffer space available */
+    EISCONN = 106,         /* Transport endpoint is already connected */
+    ENOTCONN = 107,        /* Transport endpoint is not connected */
+    ESHUTDOWN = 108,       /* Cannot send after transport endpoint shutdown */
+    ETOOMANYREFS = 109,    /* Too many references: cannot splice */
+    ETIMEDOUT = 110,       /* Connection timed out */
+    ECONNREFUSED = 111,    /* Connection refused */
+    EHOSTDOWN = 112,       /* Host is down */
+    EHOSTUNREACH = 113,    /* No route to host */
+    EALREADY = 114,        /* Operation already in progress */
+    EINPROGRESS = 115,     /* Operation now in progress */
+    ESTALE = 116,          /* Stale file handle */
+    EUCLEAN = 117,         /* Structure needs cleaning */
+    ENOTNAM = 118,         /* Not a XENIX named type file */
+    ENAVAIL = 119,         /* No XENIX semaphores available */
+    EISNAM = 120,          /* Is a named type file */
+    EREMOTEIO = 121,       /* Remote I/O error */
+    EDQUOT = 122,          /* Quota exceeded */
+    ENOMEDIUM = 123,       /* No medium found */
+    EMEDIUMTYPE = 124,     /* Wrong medium type */
+    ECANCELED = 125,       /* Operation Canceled */
+    ENOKEY = 126,          /* Required key not available */
+    EKEYEXPIRED = 127,     /* Key has expired */
+    EKEYREVOKED = 128,     /* Key has been revoked */
+    EKEYREJECTED = 129,    /* Key was rejected by service */
+    EOWNERDEAD = 130,      /* Owner died */
+    ENOTRECOVERABLE = 131, /* State not recoverable */
+    ERFKILL = 132,         /* Operation not possible due to RF-kill */
+    EHWPOISON = 133,       /* Memory page has hardware error */
 }
 
+#[allow(non_upper_case_globals)]
 impl LinuxError {
+    /// The largest error code.
+    pub const MAX_CODE: i32 = Self::EHWPOISON as i32;
+
+    /// Alias of [`LinuxError::EAGAIN`].
+    pub const EWOULDBLOCK: Self = Self::EAGAIN;
+    /// Alias of [`LinuxError::EDEADLK`].
+    pub const EDEADLOCK: Self = Self::EDEADLK;
+    /// Alias of [`LinuxError::EOPNOTSUPP`].
+    pub const ENOTSUP: Self = Self::EOPNOTSUPP;
+
     /// Returns the corresponding error code.
     #[inline]
     pub const fn code(self) -> i32 {
         self as i32
     }
 
+    /// Returns the value to be returned from a syscall, i.e. the negated
+    /// error code.
+    #[inline]
+    pub const fn as_syscall_ret(self) -> isize {
+        -(self as isize)
+    }
+
     /// Returns the corresponding error string.
     #[inline]
     pub const fn as_str(self) -> &'static str {
@@ -364,36 +519,103 @@ impl LinuxError {
             Self::EPIPE => "Broken pipe",
             Self::EDOM => "Math argument out of domain of func",
             Self::ERANGE => "Math result not representable",
-            Self::ENOSYS => "Function not implemented",
-            Self::ELOOP => "Too many symbolic links encountered",
+            Self::EDEADLK => "Resource deadlock would occur",
             Self::ENAMETOOLONG => "File name too long",
+            Self::ENOLCK => "No record locks available",
+            Self::ENOSYS => "Invalid system call number",
+            Self::ENOTEMPTY => "Directory not empty",
+            Self::ELOOP => "Too many symbolic links encountered",
+            Self::ENOMSG => "No message of desired type",
+            Self::EIDRM => "Identifier removed",
+            Self::ECHRNG => "Channel number out of range",
+            Self::EL2NSYNC => "Level 2 not synchronized",
+            Self::EL3HLT => "Level 3 halted",
+            Self::EL3RST => "Level 3 reset",
+            Self::ELNRNG => "Link number out of range",
+            Self::EUNATCH => "Protocol driver not attached",
+            Self::ENOCSI => "No CSI structure available",
+            Self::EL2HLT => "Level 2 halted",
+            Self::EBADE => "Invalid exchange",
+            Self::EBADR => "Invalid request descriptor",
+            Self::EXFULL => "Exchange full",
+            Self::ENOANO => "No anode",
+            Self::EBADRQC => "Invalid request code",
+            Self::EBADSLT => "Invalid slot",
+            Self::EBFONT => "Bad font file format",
+            Self::ENOSTR => "Device not a stream",
+            Self::ENODATA => "No data available",
+            Self::ETIME => "Timer expired",
+            Self::ENOSR => "Out of streams resources",
+            Self::ENONET => "Machine is not on the network",
+            Self::ENOPKG => "Package not installed",
+            Self::EREMOTE => "Object is remote",
+            Self::ENOLINK => "Link has been severed",
+            Self::EADV => "Advertise error",
+            Self::ESRMNT => "Srmount error",
+            Self::ECOMM => "Communication error on send",
+            Self::EPROTO => "Protocol error",
+            Self::EMULTIHOP => "Multihop attempted",
+            Self::EDOTDOT => "RFS specific error",
+            Self::EBADMSG => "Not a data message",
+            Self::EOVERFLOW => "Value too large for defined data type",
+            Self::ENOTUNIQ => "Name not unique on network",
             Self::EBADFD => "File descriptor in bad state",
+            Self::EREMCHG => "Remote address changed",
+            Self::ELIBACC => "Can not access a needed shared library",
+            Self::ELIBBAD => "Accessing a corrupted shared library",
+            Self::ELIBSCN => ".lib section in a.out corrupted",
+            Self::ELIBMAX => "Attempting to link in too many shared libraries",
+            Self::ELIBEXEC => "Cannot exec a shared library directly",
+            Self::EILSEQ => "Illegal byte sequence",
+            Self::ERESTART => "Interrupted system call should be restarted",
+            Self::ESTRPIPE => "Streams pipe error",
+            Self::EUSERS => "Too many users",
+            Self::ENOTSOCK => "Socket operation on non-socket",
+            Self::EDESTADDRREQ => "Destination address required",
+            Self::EMSGSIZE => "Message too long",
+            Self::EPROTOTYPE => "Protocol wrong type for socket",
+            Self::ENOPROTOOPT => "Protocol not available",
+            Self::EPROTONOSUPPORT => "Protocol not supported",
+            Self::ESOCKTNOSUPPORT => "Socket type not supported",
+            Self::EOPNOTSUPP => "Operation not supported on transport endpoint",
+            Self::EPFNOSUPPORT => "Protocol family not supported",
+            Self::EAFNOSUPPORT => "Address family not supported by protocol",
             Self::EADDRINUSE => "Address already in use",
             Self::EADDRNOTAVAIL => "Cannot assign requested address",
             Self::ENETDOWN => "Network is down",
             Self::ENETUNREACH => "Network is unreachable",
             Self::ENETRESET => "Network dropped connection because of reset",
+            Self::ECONNABORTED => "Software caused connection abort",
             Self::ECONNRESET => "Connection reset by peer",
             Self::ENOBUFS => "No buffer space available",
             Self::EISCONN => "Transport endpoint is already connected",
             Self::ENOTCONN => "Transport endpoint is not connected",
+            Self::ESHUTDOWN => "Cannot send after transport endpoint shutdown",
+            Self::ETOOMANYREFS => "Too many references: cannot splice",
             Self::ETIMEDOUT => "Connection timed out",
             Self::ECONNREFUSED => "Connection refused",
+            Self::EHOSTDOWN => "Host is down",
             Self::EHOSTUNREACH => "No route to host",
             Self::EALREADY => "Operation already in progress",
             Self::EINPROGRESS => "Operation now in progress",
-            Self::EWOULDBLOCK => "Operation would block",
-            Self::ENOTSOCK => "Socket operation on non-socket",
-            Self::EMSGSIZE => "Message too long",
-            Self::EPROTOTYPE => "Protocol wrong type for socket",
-            Self::ENOPROTOOPT => "Protocol not available",
-            Self::EPROTONOSUPPORT => "Protocol not supported",
-            Self::EAFNOSUPPORT => "Address family not supported by protocol",
-            Self::ENOTSUP => "Operation not supported on transport endpoint",
-            Self::ENOSYS2 => "Function not implemented",
-            Self::EPROTO => "Protocol error",
-            Self::EOVERFLOW => "Value too large for defined data type",
-            Self::EBADMSG => "Not a data message",
+            Self::ESTALE => "Stale file handle",
+            Self::EUCLEAN => "Structure needs cleaning",
+            Self::ENOTNAM => "Not a XENIX named type file",
+            Self::ENAVAIL => "No XENIX semaphores available",
+            Self::EISNAM => "Is a named type file",
+            Self::EREMOTEIO => "Remote I/O error",
+            Self::EDQUOT => "Quota exceeded",
+            Self::ENOMEDIUM => "No medium found",
+            Self::EMEDIUMTYPE => "Wrong medium type",
+            Self::ECANCELED => "Operation Canceled",
+            Self::ENOKEY => "Required key not available",
+            Self::EKEYEXPIRED => "Key has expired",
+            Self::EKEYREVOKED => "Key has been revoked",
+            Self::EKEYREJECTED => "Key was rejected by service",
+            Self::EOWNERDEAD => "Owner died",
+            Self::ENOTRECOVERABLE => "State not recoverable",
+            Self::ERFKILL => "Operation not possible due to RF-kill",
+            Self::EHWPOISON => "Memory page has hardware error",
         }
     }
 }
@@ -402,46 +624,421 @@ impl TryFrom<i32> for LinuxError {
     type Error = ();
 
     fn try_from(value: i32) -> Result<Self, Self::Error> {
-        if value >= 1 && value <= 64 {
-            // SAFETY: We checked the range and the enum has that many variants
-            Ok(unsafe { core::mem::transmute(value) })
+        // 41 and 58 are not assigned.
+        if (1..=Self::MAX_CODE).contains(&value) && value != 41 && value != 58 {
+            // SAFETY: We checked the value is one of the discriminants
+            Ok(unsafe { core::mem::transmute::<i32, Self>(value) })
         } else {
             Err(())
         }
     }
 }
 
-/// Creates a new AxError with the specified type and message.
+impl fmt::Display for LinuxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<LinuxError> for AxError {
+    fn from(e: LinuxError) -> Self {
+        use LinuxError::*;
+        match e {
+            EPERM => Self::PermissionDenied,
+            ENOENT => Self::NotFound,
+            ESRCH => Self::NoProcess,
+            EINTR | ERESTART => Self::Interrupted,
+            EIO | EREMOTEIO => Self::IoError,
+            ENXIO => Self::NoDevice,
+            E2BIG => Self::ArgListTooLong,
+            ENOEXEC | ELIBBAD | ELIBEXEC => Self::ExecFormatError,
+            EBADF => Self::BadFileNumber,
+            ECHILD => Self::NoChildProcess,
+            EAGAIN => Self::Again,
+            ENOMEM | ENOBUFS | ENOSR => Self::NoMemory,
+            EACCES => Self::PermDenied,
+            EFAULT => Self::BadAddress,
+            ENOTBLK => Self::BlockDeviceRequired,
+            EBUSY | EDEADLK => Self::Busy,
+            EEXIST => Self::AlreadyExists,
+            EXDEV => Self::CrossDeviceLink,
+            ENODEV | ENOMEDIUM => Self::NoSuchDevice,
+            ENOTDIR => Self::NotADirectory,
+            EISDIR => Self::IsADirectory,
+            EINVAL | ELOOP | ENAMETOOLONG | EOVERFLOW | EDOM | EILSEQ => Self::InvalidInput,
+            ENFILE => Self::FileTableOverflow,
+            EMFILE => Self::TooManyOpenFiles,
+            ENOTTY => Self::NotATty,
+            ETXTBSY => Self::TextFileBusy,
+            EFBIG => Self::FileTooLarge,
+            ENOSPC | EDQUOT => Self::NoSpaceLeftOnDevice,
+            ESPIPE => Self::IllegalSeek,
+            EROFS => Self::ReadOnlyFileSystem,
+            EMLINK => Self::TooManyLinks,
+            EPIPE => Self::BrokenPipe,
+            ERANGE => Self::MathNotRepresentable,
+            ENOSYS => Self::NotImplemented,
+            ENOTEMPTY => Self::DirectoryNotEmpty,
+            ETIME => Self::TimedOut,
+            ENODATA => Self::UnexpectedEof,
+            EPROTO | EBADMSG | EBADE | EBADR | EBADRQC | EBADSLT | EUCLEAN => Self::InvalidData,
+            EBADFD | ESTALE | EOWNERDEAD | ENOTRECOVERABLE => Self::BadState,
+            ENOTSOCK => Self::NotASocket,
+            EDESTADDRREQ => Self::DestinationAddressRequired,
+            EMSGSIZE => Self::MessageTooLarge,
+            EPROTOTYPE => Self::ProtocolWrongType,
+            ENOPROTOOPT => Self::ProtocolNotAvailable,
+            EPROTONOSUPPORT => Self::ProtocolNotSupported,
+            ESOCKTNOSUPPORT => Self::SocketTypeNotSupported,
+            EOPNOTSUPP => Self::Unsupported,
+            EPFNOSUPPORT => Self::ProtocolFamilyNotSupported,
+            EAFNOSUPPORT => Self::AddressFamilyNotSupported,
+            EADDRINUSE => Self::AddrInUse,
+            EADDRNOTAVAIL => Self::AddrNotAvailable,
+            ENETDOWN | ENONET => Self::NetworkDown,
+            ENETUNREACH | EHOSTUNREACH | EHOSTDOWN => Self::NetworkUnreachable,
+            ENETRESET => Self::NetworkReset,
+            ECONNABORTED => Self::SoftwareConnectionAbort,
+            ECONNRESET => Self::ConnectionResetByPeer,
+            EISCONN => Self::AlreadyConnected,
+            ENOTCONN => Self::NotConnected,
+            ESHUTDOWN => Self::SocketShutdown,
+            ETIMEDOUT => Self::ConnectionTimedOut,
+            ECONNREFUSED => Self::ConnectionRefused,
+            EALREADY => Self::InProgress,
+            EINPROGRESS => Self::ConnectionInProgress,
+            ECANCELED => Self::ConnectionAborted,
+            // The remaining codes have no specific counterpart.
+            _ => Self::IoError,
+        }
+    }
+}
+
+impl From<AxError> for LinuxError {
+    fn from(e: AxError) -> Self {
+        use AxError::*;
+        match e {
+            PermissionDenied => Self::EPERM,
+            NotFound => Self::ENOENT,
+            NoProcess => Self::ESRCH,
+            Interrupted => Self::EINTR,
+            IoError | BlockIoError | DiskError => Self::EIO,
+            NoDevice => Self::ENXIO,
+            ArgListTooLong => Self::E2BIG,
+            ExecFormatError => Self::ENOEXEC,
+            BadFileNumber => Self::EBADF,
+            NoChildProcess => Self::ECHILD,
+            Again | WouldBlock => Self::EAGAIN,
+            NoMemory => Self::ENOMEM,
+            PermDenied => Self::EACCES,
+            BadAddress | NonExistantMapping | InvalidMemRange => Self::EFAULT,
+            BlockDeviceRequired => Self::ENOTBLK,
+            Busy => Self::EBUSY,
+            AlreadyExists => Self::EEXIST,
+            CrossDeviceLink => Self::EXDEV,
+            NoSuchDevice => Self::ENODEV,
+            NotADirectory => Self::ENOTDIR,
+            IsADirectory => Self::EISDIR,
+            InvalidInput | InvalidData => Self::EINVAL,
+            FileTableOverflow => Self::ENFILE,
+            TooManyOpenFiles => Self::EMFILE,
+            NotATty => Self::ENOTTY,
+            TextFileBusy => Self::ETXTBSY,
+            FileTooLarge => Self::EFBIG,
+            NoSpaceLeftOnDevice => Self::ENOSPC,
+            IllegalSeek => Self::ESPIPE,
+            ReadOnlyFileSystem => Self::EROFS,
+            TooManyLinks => Self::EMLINK,
+            BrokenPipe | WriteZero => Self::EPIPE,
+            MathOutOfDomain => Self::EDOM,
+            MathNotRepresentable => Self::ERANGE,
+            NotImplemented => Self::ENOSYS,
+            TimedOut | ConnectionTimedOut => Self::ETIMEDOUT,
+            ConnectionRefused => Self::ECONNREFUSED,
+            ConnectionAborted | SoftwareConnectionAbort => Self::ECONNABORTED,
+            ConnectionInProgress => Self::EINPROGRESS,
+            AlreadyConnected | TransportEndpointAlreadyConnected => Self::EISCONN,
+            ConnectionReset | ConnectionResetByPeer => Self::ECONNRESET,
+            NotConnected | TransportEndpointNotConnected => Self::ENOTCONN,
+            AddrInUse => Self::EADDRINUSE,
+            AddrNotAvailable => Self::EADDRNOTAVAIL,
+            NetworkDown => Self::ENETDOWN,
+            NetworkUnreachable => Self::ENETUNREACH,
+            NetworkReset => Self::ENETRESET,
+            InProgress => Self::EALREADY,
+            Unsupported | OperationNotSupportedOnEndpoint => Self::EOPNOTSUPP,
+            ProtocolFamilyNotSupported => Self::EPFNOSUPPORT,
+            ProtocolNotSupported | UnknownProtocol => Self::EPROTONOSUPPORT,
+            ProtocolWrongType | WrongProtocolType => Self::EPROTOTYPE,
+            DestinationAddressRequired => Self::EDESTADDRREQ,
+            MessageTooLarge => Self::EMSGSIZE,
+            ProtocolNotAvailable => Self::ENOPROTOOPT,
+            NotASocket => Self::ENOTSOCK,
+            AddressFamilyNotSupported => Self::EAFNOSUPPORT,
+            SocketTypeNotSupported => Self::ESOCKTNOSUPPORT,
+            HostLookupFailed => Self::EHOSTUNREACH,
+            SocketShutdown => Self::ESHUTDOWN,
+            BadState => Self::EBADFD,
+            DirectoryNotEmpty => Self::ENOTEMPTY,
+            UnexpectedEof => Self::ENODATA,
+        }
+    }
+}
+
+/// Converts to the closest [`std::io::ErrorKind`], [`Other`] if none.
+///
+/// [`Other`]: std::io::ErrorKind::Other
+#[cfg(any(test, feature = "std"))]
+impl From<AxError> for std::io::ErrorKind {
+    fn from(e: AxError) -> Self {
+        use AxError::*;
+        match e {
+            PermissionDenied | PermDenied => Self::PermissionDenied,
+            NotFound => Self::NotFound,
+            Interrupted => Self::Interrupted,
+            ArgListTooLong => Self::ArgumentListTooLong,
+            Again | WouldBlock => Self::WouldBlock,
+            NoMemory => Self::OutOfMemory,
+            Busy => Self::ResourceBusy,
+            AlreadyExists => Self::AlreadyExists,
+            CrossDeviceLink => Self::CrossesDevices,
+            NotADirectory => Self::NotADirectory,
+            IsADirectory => Self::IsADirectory,
+            InvalidInput => Self::InvalidInput,
+            TextFileBusy => Self::ExecutableFileBusy,
+            FileTooLarge => Self::FileTooLarge,
+            NoSpaceLeftOnDevice => Self::StorageFull,
+            IllegalSeek => Self::NotSeekable,
+            ReadOnlyFileSystem => Self::ReadOnlyFilesystem,
+            TooManyLinks => Self::TooManyLinks,
+            BrokenPipe => Self::BrokenPipe,
+            NotImplemented | Unsupported | OperationNotSupportedOnEndpoint => Self::Unsupported,
+            TimedOut | ConnectionTimedOut => Self::TimedOut,
+            ConnectionRefused => Self::ConnectionRefused,
+            ConnectionAborted | SoftwareConnectionAbort => Self::ConnectionAborted,
+            ConnectionReset | ConnectionResetByPeer => Self::ConnectionReset,
+            NotConnected | TransportEndpointNotConnected => Self::NotConnected,
+            AddrInUse => Self::AddrInUse,
+            AddrNotAvailable => Self::AddrNotAvailable,
+            NetworkDown => Self::NetworkDown,
+            NetworkUnreachable => Self::NetworkUnreachable,
+            DirectoryNotEmpty => Self::DirectoryNotEmpty,
+            InvalidData => Self::InvalidData,
+            UnexpectedEof => Self::UnexpectedEof,
+            WriteZero => Self::WriteZero,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Converts from a [`std::io::ErrorKind`], the kinds without an [`AxError`]
+/// are [`IoError`](AxError::IoError).
+#[cfg(any(test, feature = "std"))]
+impl From<std::io::ErrorKind> for AxError {
+    fn from(kind: std::io::ErrorKind) -> Self {
+        use std::io::ErrorKind::*;
+        match kind {
+            NotFound => Self::NotFound,
+            PermissionDenied => Self::PermissionDenied,
+            ConnectionRefused => Self::ConnectionRefused,
+            ConnectionReset => Self::ConnectionReset,
+            HostUnreachable | NetworkUnreachable => Self::NetworkUnreachable,
+            ConnectionAborted => Self::ConnectionAborted,
+            NotConnected => Self::NotConnected,
+            AddrInUse => Self::AddrInUse,
+            AddrNotAvailable => Self::AddrNotAvailable,
+            NetworkDown => Self::NetworkDown,
+            BrokenPipe => Self::BrokenPipe,
+            AlreadyExists => Self::AlreadyExists,
+            WouldBlock => Self::WouldBlock,
+            NotADirectory => Self::NotADirectory,
+            IsADirectory => Self::IsADirectory,
+            DirectoryNotEmpty => Self::DirectoryNotEmpty,
+            ReadOnlyFilesystem => Self::ReadOnlyFileSystem,
+            InvalidInput => Self::InvalidInput,
+            InvalidData => Self::InvalidData,
+            TimedOut => Self::TimedOut,
+            WriteZero => Self::WriteZero,
+            StorageFull => Self::NoSpaceLeftOnDevice,
+            NotSeekable => Self::IllegalSeek,
+            FileTooLarge => Self::FileTooLarge,
+            ResourceBusy => Self::Busy,
+            ExecutableFileBusy => Self::TextFileBusy,
+            CrossesDevices => Self::CrossDeviceLink,
+            TooManyLinks => Self::TooManyLinks,
+            ArgumentListTooLong => Self::ArgListTooLong,
+            Interrupted => Self::Interrupted,
+            Unsupported => Self::Unsupported,
+            UnexpectedEof => Self::UnexpectedEof,
+            OutOfMemory => Self::NoMemory,
+            _ => Self::IoError,
+        }
+    }
+}
+
+/// Conversions of [`LinuxResult`].
+pub trait LinuxResultExt<T> {
+    /// Converts an [`AxResult`] to a [`LinuxResult`].
+    fn from_ax(res: AxResult<T>) -> Self;
+}
+
+impl<T> LinuxResultExt<T> for LinuxResult<T> {
+    #[inline]
+    fn from_ax(res: AxResult<T>) -> Self {
+        res.map_err(LinuxError::from)
+    }
+}
+
+/// The aliases of [`AxError`] variants, imported with them by
+/// [`ax_err_type!`].
+#[doc(hidden)]
+#[allow(non_upper_case_globals)]
+pub mod __aliases {
+    use super::AxError;
+
+    pub const Io: AxError = AxError::Io;
+    pub const ResourceBusy: AxError = AxError::ResourceBusy;
+    pub const StorageFull: AxError = AxError::StorageFull;
+}
+
+/// Creates an [`AxError`] of the specified type, with an optional message.
+///
+/// The type can also be an expression evaluating to an [`AxError`].
 ///
 /// # Examples
 ///
 /// ```
-/// use axerrno::{ax_err, AxError};
+/// use axerrno::{ax_err_type, AxError};
 ///
-/// let err = ax_err!(NotFound, "file not found");
-/// assert_eq!(err, Err(AxError::NotFound));
+/// assert_eq!(ax_err_type!(NotFound), AxError::NotFound);
+/// assert_eq!(ax_err_type!(BadState, "not initialized"), AxError::BadState);
 /// ```
 #[macro_export]
-macro_rules! ax_err {
-    ($err_type:ident, $msg:expr) => {
-        Err($crate::AxError::$err_type)
-    };
+macro_rules! ax_err_type {
+    ($err:ident) => {{
+        #[allow(unused_imports)]
+        use $crate::{__aliases::*, AxError::*};
+        $err
+    }};
+    ($err:ident, $msg:expr) => {{
+        let _ = $msg;
+        $crate::ax_err_type!($err)
+    }};
 }
 
-/// Creates a new AxError with the specified type and returns it immediately.
+/// Creates an `Err` of [`AxError`] of the specified type, with an optional
+/// message.
 ///
 /// # Examples
 ///
 /// ```
-/// use axerrno::{ax_err_type, AxError};
+/// use axerrno::{ax_err, AxError, AxResult};
 ///
-/// fn may_fail() -> axerrno::AxResult<()> {
-///     ax_err_type!(NotFound, "file not found")
-/// }
+/// let err: AxResult = ax_err!(NotFound, "file not found");
+/// assert_eq!(err, Err(AxError::NotFound));
 /// ```
 #[macro_export]
-macro_rules! ax_err_type {
-    ($err_type:ident, $msg:expr) => {
-        return Err($crate::AxError::$err_type)
+macro_rules! ax_err {
+    ($err:ident) => {
+        Err($crate::ax_err_type!($err))
     };
+    ($err:ident, $msg:expr) => {
+        Err($crate::ax_err_type!($err, $msg))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ax_error_codes_round_trip() {
+        for code in 1..=AxError::MAX_CODE {
+            let err = AxError::try_from(code).unwrap();
+            assert_eq!(err.code(), code);
+        }
+        assert!(AxError::try_from(0).is_err());
+        assert!(AxError::try_from(AxError::MAX_CODE + 1).is_err());
+    }
+
+    #[test]
+    fn ax_error_codes_are_stable() {
+        assert_eq!(AxError::PermissionDenied.code(), 1);
+        assert_eq!(AxError::InvalidInput.code(), 22);
+        assert_eq!(AxError::WouldBlock.code(), 52);
+        assert_eq!(AxError::DiskError.code(), 73);
+        assert_eq!(AxError::BadState.code(), 74);
+        assert_eq!(AxError::Io, AxError::IoError);
+    }
+
+    #[test]
+    fn linux_error_codes() {
+        for code in 1..=LinuxError::MAX_CODE {
+            match LinuxError::try_from(code) {
+                Ok(err) => assert_eq!(err.code(), code),
+                Err(()) => assert!(code == 41 || code == 58),
+            }
+        }
+        assert!(LinuxError::try_from(0).is_err());
+        assert!(LinuxError::try_from(LinuxError::MAX_CODE + 1).is_err());
+        assert_eq!(LinuxError::ENOTEMPTY.code(), 39);
+        assert_eq!(LinuxError::ECANCELED.code(), 125);
+        assert_eq!(LinuxError::EWOULDBLOCK, LinuxError::EAGAIN);
+        assert_eq!(LinuxError::EINVAL.as_syscall_ret(), -22);
+    }
+
+    #[test]
+    fn ax_error_to_linux_error() {
+        for code in 1..=AxError::MAX_CODE {
+            let err = AxError::try_from(code).unwrap();
+            let _ = LinuxError::from(err);
+        }
+        assert_eq!(AxError::WouldBlock.errno(), LinuxError::EAGAIN.code());
+        assert_eq!(
+            LinuxError::from(AxError::DirectoryNotEmpty),
+            LinuxError::ENOTEMPTY
+        );
+        assert_eq!(
+            LinuxResult::from_ax(AxResult::<()>::Err(AxError::NotFound)),
+            Err(LinuxError::ENOENT)
+        );
+    }
+
+    #[test]
+    fn error_kind_round_trip() {
+        use std::io::ErrorKind;
+
+        for code in 1..=AxError::MAX_CODE {
+            let err = AxError::try_from(code).unwrap();
+            let kind = ErrorKind::from(err);
+            assert_eq!(ErrorKind::from(AxError::from(kind)), kind, "{err:?}");
+        }
+        assert_eq!(ErrorKind::from(AxError::Again), ErrorKind::WouldBlock);
+        assert_eq!(AxError::from(ErrorKind::StorageFull), AxError::StorageFull);
+        assert_eq!(AxError::from(ErrorKind::Other), AxError::Io);
+        assert_eq!(ErrorKind::from(AxError::Io), ErrorKind::Other);
+    }
+
+    #[test]
+    fn linux_error_to_ax_error() {
+        for code in 1..=LinuxError::MAX_CODE {
+            if let Ok(linux) = LinuxError::try_from(code) {
+                let _ = AxError::from(linux);
+            }
+        }
+        assert_eq!(AxError::from(LinuxError::ENOENT), AxError::NotFound);
+        assert_eq!(AxError::from(LinuxError::EAGAIN), AxError::Again);
+        assert_eq!(AxError::from(LinuxError::EBADFD), AxError::BadState);
+    }
+
+    #[test]
+    fn macros() {
+        let e = AxError::Busy;
+        assert_eq!(ax_err_type!(e), AxError::Busy);
+        assert_eq!(ax_err_type!(Io, "alias"), AxError::IoError);
+        assert_eq!(
+            ax_err!(BadState, "msg"),
+            AxResult::<()>::Err(AxError::BadState)
+        );
+    }
 }
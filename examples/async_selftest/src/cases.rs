@@ -0,0 +1,279 @@
+//! The test cases.
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use axasync::codec::{Framed, LinesCodec};
+use axasync::executor::channel::oneshot;
+use axasync::futures_util::future::join_all;
+use axasync::futures_util::{SinkExt, StreamExt};
+use axasync::sync::{Mutex, RwLock, Semaphore};
+use axasync::{sleep, spawn, yield_now, JoinError, LocalStream, TimeoutExt};
+use axstd::time::{Duration, Instant};
+
+use crate::{ensure, CaseResult};
+
+pub async fn sleep_duration() -> CaseResult {
+    let start = Instant::now();
+    sleep(Duration::from_millis(50)).await;
+    let elapsed = start.elapsed();
+    ensure!(
+        elapsed >= Duration::from_millis(50),
+        "woke up early, after {:?}",
+        elapsed
+    );
+    ensure!(
+        elapsed < Duration::from_millis(500),
+        "woke up late, after {:?}",
+        elapsed
+    );
+    Ok(())
+}
+
+pub async fn wake_order() -> CaseResult {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let sleeper = |id: u64| {
+        let order = order.clone();
+        async move {
+            sleep(Duration::from_millis(20 * id)).await;
+            order.lock().await.push(id);
+        }
+    };
+    axasync::join!(sleeper(3), sleeper(1), sleeper(2));
+    let order = order.lock().await;
+    ensure!(*order == [1, 2, 3], "woke up in the order {:?}", *order);
+    Ok(())
+}
+
+pub async fn timeout() -> CaseResult {
+    let res = sleep(Duration::from_secs(1))
+        .timeout(Duration::from_millis(20))
+        .await;
+    ensure!(res.is_err(), "a 1 s sleep did not time out after 20 ms");
+    let res = async { 42 }.timeout(Duration::from_millis(20)).await;
+    ensure!(res == Ok(42), "a ready future timed out: {:?}", res);
+    Ok(())
+}
+
+pub async fn oneshot() -> CaseResult {
+    let (tx, mut rx) = oneshot::channel();
+    let sender = spawn(async move {
+        sleep(Duration::from_millis(10)).await;
+        tx.send(String::from("hello")).is_ok()
+    });
+    let value = poll_fn(|cx| rx.poll(cx)).await;
+    ensure!(sender.await == Ok(true), "the receiver was dropped");
+    ensure!(value.as_deref() == Ok("hello"), "received {:?}", value);
+
+    let (tx, mut rx) = oneshot::channel::<u32>();
+    drop(tx);
+    let value = poll_fn(|cx| rx.poll(cx)).await;
+    ensure!(value.is_err(), "received {:?} from a dropped sender", value);
+    Ok(())
+}
+
+pub async fn local_stream() -> CaseResult {
+    const LINES: usize = 100;
+    // A small capacity, so that the writer waits for the reader.
+    let (a, b) = LocalStream::pair_with_capacity(16);
+    let writer = spawn(async move {
+        let mut framed = Framed::new(a, LinesCodec::new());
+        for i in 0..LINES {
+            framed.send(i.to_string()).await?;
+        }
+        Ok::<_, axasync::io::AxError>(())
+    });
+
+    let mut framed = Framed::new(b, LinesCodec::new());
+    let mut received = 0;
+    while let Some(line) = framed.next().await {
+        let line = line.map_err(|e| alloc::format!("read error: {:?}", e))?;
+        ensure!(
+            line == received.to_string(),
+            "line {} is {:?}",
+            received,
+            line
+        );
+        received += 1;
+    }
+    ensure!(matches!(writer.await, Ok(Ok(()))), "write error");
+    ensure!(
+        received == LINES,
+        "received {} of {} lines",
+        received,
+        LINES
+    );
+    Ok(())
+}
+
+pub async fn mutex() -> CaseResult {
+    const TASKS: usize = 8;
+    const ITERATIONS: usize = 50;
+    let counter = Mutex::new(0);
+    let tasks = (0..TASKS).map(|_| async {
+        for _ in 0..ITERATIONS {
+            let mut guard = counter.lock().await;
+            let value = *guard;
+            // Others run meanwhile, but must not get the lock.
+            yield_now().await;
+            *guard = value + 1;
+        }
+    });
+    join_all(tasks).await;
+    let count = *counter.lock().await;
+    ensure!(
+        count == TASKS * ITERATIONS,
+        "counted {} increments of {}",
+        count,
+        TASKS * ITERATIONS
+    );
+    Ok(())
+}
+
+pub async fn rwlock() -> CaseResult {
+    let lock = RwLock::new(0);
+    {
+        let _r1 = lock.read().await;
+        let r2 = lock.try_read();
+        ensure!(r2.is_some(), "a second reader was refused");
+        ensure!(lock.try_write().is_none(), "a writer got in with readers");
+    }
+    {
+        let mut w = lock.write().await;
+        *w += 1;
+        ensure!(lock.try_read().is_none(), "a reader got in with a writer");
+    }
+    let value = *lock.read().await;
+    ensure!(value == 1, "read {} after the write", value);
+    Ok(())
+}
+
+pub async fn semaphore() -> CaseResult {
+    const PERMITS: usize = 2;
+    let semaphore = Semaphore::new(PERMITS);
+    let active = AtomicUsize::new(0);
+    let max_active = AtomicUsize::new(0);
+    let tasks = (0..6).map(|_| async {
+        let _permit = semaphore.acquire().await;
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        max_active.fetch_max(now, Ordering::SeqCst);
+        sleep(Duration::from_millis(10)).await;
+        active.fetch_sub(1, Ordering::SeqCst);
+    });
+    join_all(tasks).await;
+    let max = max_active.load(Ordering::SeqCst);
+    ensure!(max <= PERMITS, "{} tasks held {} permits", max, PERMITS);
+    ensure!(
+        semaphore.available_permits() == PERMITS,
+        "{} permits left of {}",
+        semaphore.available_permits(),
+        PERMITS
+    );
+    Ok(())
+}
+
+pub async fn cancel_cleanup() -> CaseResult {
+    let cleaned = Arc::new(AtomicBool::new(false));
+    let task = {
+        let cleaned = cleaned.clone();
+        async move {
+            let _guard = axasync::defer(async move {
+                cleaned.store(true, Ordering::SeqCst);
+            });
+            sleep(Duration::from_secs(1)).await;
+        }
+    };
+    let res = task.timeout(Duration::from_millis(20)).await;
+    ensure!(res.is_err(), "the task was not cancelled");
+    // The cleanup is spawned on the executor.
+    for _ in 0..10 {
+        if cleaned.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(5)).await;
+    }
+    Err("the cleanup of the cancelled task did not run".into())
+}
+
+pub async fn cancel_abort() -> CaseResult {
+    let polls = Arc::new(AtomicUsize::new(0));
+    let task = {
+        let polls = polls.clone();
+        spawn(async move {
+            loop {
+                polls.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+    };
+    sleep(Duration::from_millis(20)).await;
+    task.abort_handle().abort();
+    let res = task.timeout(Duration::from_millis(100)).await;
+    ensure!(
+        matches!(res, Ok(Err(JoinError::Cancelled))),
+        "the join did not resolve to Cancelled"
+    );
+    let count = polls.load(Ordering::SeqCst);
+    sleep(Duration::from_millis(20)).await;
+    ensure!(
+        polls.load(Ordering::SeqCst) == count,
+        "the aborted task was polled again"
+    );
+    Ok(())
+}
+
+pub async fn cancel_lock_waiter() -> CaseResult {
+    let mutex = Mutex::new(());
+    let guard = mutex.lock().await;
+    let res = mutex.lock().timeout(Duration::from_millis(20)).await;
+    ensure!(res.is_err(), "got a lock that was held");
+    drop(guard);
+    // The cancelled waiter must not keep the lock from the next one.
+    let res = mutex.lock().timeout(Duration::from_millis(100)).await;
+    ensure!(res.is_ok(), "the lock was not released to the next waiter");
+    Ok(())
+}
+
+#[cfg(feature = "net")]
+pub async fn tcp_loopback() -> CaseResult {
+    use axnet::TcpSocket;
+    use core::net::{Ipv4Addr, SocketAddr};
+
+    const PORT: u16 = 5555;
+    /// The QEMU user-mode gateway, which forwards `PORT` back to the guest.
+    const HOST: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+    fn err<E: core::fmt::Debug>(what: &'static str) -> impl FnOnce(E) -> String {
+        move |e| alloc::format!("{}: {:?}", what, e)
+    }
+
+    let listener = TcpSocket::new();
+    listener
+        .bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT)))
+        .map_err(err("bind"))?;
+    listener.listen().map_err(err("listen"))?;
+
+    let client = TcpSocket::new();
+    let (accepted, connected) = axasync::join!(
+        listener.accept_async(),
+        client.connect_async(SocketAddr::from((HOST, PORT))),
+    );
+    let (server, _) = accepted.map_err(err("accept"))?;
+    connected.map_err(err("connect"))?;
+
+    let mut buf = [0; 16];
+    client.send_async(b"ping").await.map_err(err("send"))?;
+    let n = server.recv_async(&mut buf).await.map_err(err("recv"))?;
+    ensure!(&buf[..n] == b"ping", "the server received {:?}", &buf[..n]);
+    server.send_async(&buf[..n]).await.map_err(err("echo"))?;
+    let n = client.recv_async(&mut buf).await.map_err(err("recv"))?;
+    ensure!(&buf[..n] == b"ping", "the client received {:?}", &buf[..n]);
+
+    let _ = client.shutdown();
+    let _ = server.shutdown();
+    let _ = listener.shutdown();
+    Ok(())
+}
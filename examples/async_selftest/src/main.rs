@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use axasync::{block_on, BoxFuture, TimeoutExt};
+use axstd::println;
+use axstd::time::Duration;
+
+mod cases;
+
+/// The result of a test case, with the reason of the failure.
+type CaseResult = Result<(), String>;
+
+/// Fails the test case with a formatted message if the condition is false.
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err(alloc::format!($($arg)+));
+        }
+    };
+}
+pub(crate) use ensure;
+
+/// The time given to each case, after which it fails.
+const CASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The test cases, by name.
+const CASES: &[(&str, fn() -> BoxFuture<CaseResult>)] = &[
+    ("timer::sleep", || Box::pin(cases::sleep_duration())),
+    ("timer::wake_order", || Box::pin(cases::wake_order())),
+    ("timer::timeout", || Box::pin(cases::timeout())),
+    ("channel::oneshot", || Box::pin(cases::oneshot())),
+    ("channel::local_stream", || Box::pin(cases::local_stream())),
+    ("sync::mutex", || Box::pin(cases::mutex())),
+    ("sync::rwlock", || Box::pin(cases::rwlock())),
+    ("sync::semaphore", || Box::pin(cases::semaphore())),
+    ("cancel::cleanup", || Box::pin(cases::cancel_cleanup())),
+    ("cancel::abort", || Box::pin(cases::cancel_abort())),
+    ("cancel::lock_waiter", || {
+        Box::pin(cases::cancel_lock_waiter())
+    }),
+    #[cfg(feature = "net")]
+    ("net::tcp_loopback", || Box::pin(cases::tcp_loopback())),
+];
+
+#[no_mangle]
+fn main() {
+    axasync::init();
+    println!("Async self-test: {} cases", CASES.len());
+
+    let mut failed = 0;
+    for (name, case) in CASES {
+        let result = match block_on(case().timeout(CASE_TIMEOUT)) {
+            Ok(result) => result,
+            Err(_) => Err(alloc::format!("timed out after {:?}", CASE_TIMEOUT)),
+        };
+        match result {
+            Ok(()) => println!("[PASS] {}", name),
+            Err(reason) => {
+                println!("[FAIL] {}: {}", name, reason);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Async self-test: {} passed, {} failed",
+        CASES.len() - failed,
+        failed
+    );
+    axasync::shutdown();
+    if failed > 0 {
+        fail();
+    }
+}
+
+/// Shuts the system down as failed, so that the runner sees the failure.
+fn fail() -> ! {
+    // OpenSBI makes QEMU exit with a failure status, through its test device.
+    #[cfg(target_arch = "riscv64")]
+    sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure);
+    // Without the SRST extension, or on the other architectures.
+    axstd::process::exit(1)
+}
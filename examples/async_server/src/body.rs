@@ -0,0 +1,239 @@
+//! Bodies of responses, sent at once or produced incrementally.
+//!
+//! A [`Body::Stream`] is sent with the chunked transfer encoding, framed by
+//! [`ChunkedCodec`], so that a response can start before its whole body is
+//! known, e.g. while reading a file or sampling a sensor:
+//!
+//! ```ignore
+//! let samples = stream::unfold(sensor, |sensor| async move {
+//!     let sample = sensor.read().await;
+//!     Some((Bytes::from(format!("{}\n", sample).into_bytes()), sensor))
+//! });
+//! Response::ok("text/plain", Body::from_stream(samples))
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::pin::Pin;
+
+use axasync::codec::{Decoder, Encoder};
+use axasync::futures_util::{Stream, StreamExt};
+use axasync::io::{AxError, AxResult};
+use axasync::Bytes;
+
+/// The longest chunk-size or trailer line decoded.
+const MAX_LINE_LEN: usize = 1024;
+/// The most trailer fields decoded.
+const MAX_TRAILERS: usize = 32;
+
+/// A piece of a streamed body.
+pub enum Frame {
+    /// Bytes of the body.
+    Data(Bytes),
+    /// Header fields sent after the body, e.g. a checksum of it. They end the
+    /// body.
+    Trailers(Vec<(String, String)>),
+}
+
+/// The body of a response.
+pub enum Body {
+    /// Sent at once, with a `Content-Length`.
+    Full(Bytes),
+    /// Sent as its frames come, with `Transfer-Encoding: chunked`.
+    Stream(Pin<Box<dyn Stream<Item = Frame> + Send>>),
+}
+
+impl Body {
+    /// Creates a body of the bytes yielded by `stream`.
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Bytes> + Send + 'static,
+    {
+        Self::from_frames(stream.map(Frame::Data))
+    }
+
+    /// Creates a body of the frames yielded by `stream`, which may end with
+    /// trailers.
+    pub fn from_frames<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Frame> + Send + 'static,
+    {
+        Self::Stream(Box::pin(stream))
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self::Full(bytes)
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Full(bytes.into())
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Full(bytes.into())
+    }
+}
+
+/// Where the decoder of a chunked body is.
+enum DecodeState {
+    /// Before a chunk-size line.
+    Size,
+    /// In the data of a chunk, with the number of bytes left.
+    Data(usize),
+    /// Before the CRLF that ends the data of a chunk.
+    DataEnd,
+    /// In the trailer section, with the fields so far.
+    Trailers(Vec<(String, String)>),
+    /// After the body.
+    Done,
+}
+
+/// A codec of the chunked transfer encoding of HTTP/1.1.
+///
+/// The decoded frames are the data of the chunks as it arrives, so that a
+/// chunk is not buffered whole, then the trailers, empty if there are none.
+/// Encoding a [`Frame::Trailers`] ends the body.
+pub struct ChunkedCodec {
+    state: DecodeState,
+}
+
+impl ChunkedCodec {
+    /// Creates a codec at the start of a body.
+    pub const fn new() -> Self {
+        Self {
+            state: DecodeState::Size,
+        }
+    }
+
+    /// Returns whether the decoded body has ended.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, DecodeState::Done)
+    }
+}
+
+impl Default for ChunkedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Removes the line at the front of `src` and returns it without its CRLF,
+/// or returns `None` if it is not complete yet.
+fn take_line(src: &mut Vec<u8>) -> AxResult<Option<String>> {
+    let Some(pos) = src.windows(2).position(|w| w == b"\r\n") else {
+        if src.len() > MAX_LINE_LEN {
+            return Err(AxError::InvalidData);
+        }
+        return Ok(None);
+    };
+    if pos > MAX_LINE_LEN {
+        return Err(AxError::InvalidData);
+    }
+    let line: Vec<u8> = src.drain(..pos + 2).take(pos).collect();
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| AxError::InvalidData)
+}
+
+impl Decoder for ChunkedCodec {
+    type Item = Frame;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Frame>> {
+        loop {
+            match &mut self.state {
+                DecodeState::Size => {
+                    let Some(line) = take_line(src)? else {
+                        return Ok(None);
+                    };
+                    // Chunk extensions are ignored.
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = usize::from_str_radix(size, 16).map_err(|_| AxError::InvalidData)?;
+                    self.state = match size {
+                        0 => DecodeState::Trailers(Vec::new()),
+                        size => DecodeState::Data(size),
+                    };
+                }
+                DecodeState::Data(left) => {
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = (*left).min(src.len());
+                    let data: Vec<u8> = src.drain(..n).collect();
+                    *left -= n;
+                    if *left == 0 {
+                        self.state = DecodeState::DataEnd;
+                    }
+                    return Ok(Some(Frame::Data(data.into())));
+                }
+                DecodeState::DataEnd => {
+                    if src.len() < 2 {
+                        return Ok(None);
+                    }
+                    if src[..2] != *b"\r\n" {
+                        return Err(AxError::InvalidData);
+                    }
+                    src.drain(..2);
+                    self.state = DecodeState::Size;
+                }
+                DecodeState::Trailers(fields) => {
+                    let Some(line) = take_line(src)? else {
+                        return Ok(None);
+                    };
+                    if line.is_empty() {
+                        let fields = core::mem::take(fields);
+                        self.state = DecodeState::Done;
+                        return Ok(Some(Frame::Trailers(fields)));
+                    }
+                    if fields.len() == MAX_TRAILERS {
+                        return Err(AxError::InvalidData);
+                    }
+                    let (name, value) = line.split_once(':').ok_or(AxError::InvalidData)?;
+                    fields.push((name.trim().into(), value.trim().into()));
+                }
+                DecodeState::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Frame>> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if self.is_done() => Ok(None),
+            // Cut short, e.g. by a peer that went away.
+            None => Err(AxError::UnexpectedEof),
+        }
+    }
+}
+
+impl Encoder<Frame> for ChunkedCodec {
+    fn encode(&mut self, frame: Frame, dst: &mut Vec<u8>) -> AxResult {
+        let mut head = String::new();
+        match frame {
+            // An empty chunk would end the body.
+            Frame::Data(data) if data.is_empty() => {}
+            Frame::Data(data) => {
+                let _ = write!(head, "{:x}\r\n", data.len());
+                dst.extend_from_slice(head.as_bytes());
+                dst.extend_from_slice(&data);
+                dst.extend_from_slice(b"\r\n");
+            }
+            Frame::Trailers(fields) => {
+                head.push_str("0\r\n");
+                for (name, value) in &fields {
+                    let _ = write!(head, "{}: {}\r\n", name, value);
+                }
+                head.push_str("\r\n");
+                dst.extend_from_slice(head.as_bytes());
+            }
+        }
+        Ok(())
+    }
+}
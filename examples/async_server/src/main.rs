@@ -6,25 +6,41 @@ extern crate axruntime;
 
 extern crate alloc;
 
+mod body;
+mod metrics;
+mod middleware;
+
 use alloc::format;
-use axasync::{block_on, init, shutdown, spawn};
-use axlog::{debug, error, info};
-use axnet::TcpSocket;
+use alloc::vec;
+use alloc::vec::Vec;
+use axasync::codec::Encoder;
+use axasync::futures_util::{stream, Stream, StreamExt};
+use axasync::{block_on, init, shutdown, sleep, spawn_named, Bytes, BytesMut};
+use axlog::{debug, error, info, warn};
+use axnet::{IdleReaper, TcpSocket, TcpStream};
 use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::time::Duration;
 
-const LOCAL_PORT: u16 = 5555;
+use body::{Body, ChunkedCodec, Frame};
+use middleware::{handler, limit_connections, logging, timeout, Handler, Request, Response};
 
-macro_rules! header {
-    () => {
-        "\
-HTTP/1.1 200 OK\r\n\
-Content-Type: text/html\r\n\
-Content-Length: {}\r\n\
-Connection: close\r\n\
-\r\n\
-{}"
-    };
-}
+const LOCAL_PORT: u16 = 5555;
+/// The largest request head accepted.
+const MAX_HEAD_LEN: usize = 4096;
+/// The most requests handled at once.
+const MAX_CONNECTIONS: usize = 32;
+/// The longest time a request is handled.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+/// The number of lines of the streamed pages.
+const TICKS: usize = 5;
+/// The time between the lines of the streamed pages.
+const TICK_INTERVAL: Duration = Duration::from_millis(200);
+/// The longest time a client may connect without sending anything.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest time a connection may stay silent before it is reset.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often the free memory is sampled, the sockets back off under pressure.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
 
 const CONTENT: &str = r#"<html>
 <head>
@@ -66,6 +82,7 @@ async fn run_server() -> Result<(), &'static str> {
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), LOCAL_PORT);
 
     let socket = TcpSocket::new();
+    socket.set_handshake_timeout(Some(HANDSHAKE_TIMEOUT));
     socket.bind(addr).map_err(|_| "Failed to bind to address")?;
     socket.listen().map_err(|_| "Failed to listen")?;
 
@@ -75,6 +92,26 @@ async fn run_server() -> Result<(), &'static str> {
         LOCAL_PORT
     );
 
+    let app = logging(timeout(
+        limit_connections(handler(route), MAX_CONNECTIONS),
+        REQUEST_TIMEOUT,
+    ));
+
+    let reaper = IdleReaper::new(IDLE_TIMEOUT);
+    spawn_named("idle-reaper", reaper.clone().run());
+    let events = reaper.clone();
+    spawn_named("idle-reaper-log", async move {
+        loop {
+            let event = events.next_event().await;
+            warn!("Reset {:?}, idle for {:?}", event.peer, event.idle);
+        }
+    });
+
+    spawn_named(
+        "mem-watcher",
+        axasync::mem::watch_memory(MEMORY_SAMPLE_INTERVAL),
+    );
+
     // Keep track of how many connections we've handled
     let mut connection_count = 0;
 
@@ -83,20 +120,20 @@ async fn run_server() -> Result<(), &'static str> {
         debug!("Waiting for connection {}...", connection_count + 1);
 
         match socket.accept_async().await {
-            Ok(mut client) => {
+            Ok((client, peer_addr)) => {
+                let client = TcpStream::from(client);
+                reaper.track(&client);
                 connection_count += 1;
                 let connection_count = connection_count;
-                spawn(async move {
-                    let peer_addr = client
-                        .peer_addr()
-                        .unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0));
+                let app = app.clone();
+                spawn_named(format!("http-conn-{}", connection_count), async move {
                     debug!(
                         "Client connected from {} (connection {})",
                         peer_addr, connection_count
                     );
 
                     // Handle HTTP request
-                    if let Err(e) = handle_http_request(&mut client).await {
+                    if let Err(e) = handle_http_request(&client, peer_addr, app).await {
                         error!("Error handling HTTP request: {}", e);
                     }
 
@@ -110,36 +147,129 @@ async fn run_server() -> Result<(), &'static str> {
     }
 }
 
-/// Handle an HTTP request and send an HTML response
-async fn handle_http_request(client: &mut TcpSocket) -> Result<(), &'static str> {
-    let mut buffer = [0u8; 4096];
-
-    // Read the HTTP request
-    let bytes_read = client
-        .recv_async(&mut buffer)
-        .await
-        .map_err(|_| "Failed to read HTTP request")?;
+/// Reads the head of an HTTP request, up to the empty line that ends it.
+///
+/// Returns `None` if the client closed the connection first.
+async fn read_request_head(client: &TcpStream) -> Result<Option<Bytes>, &'static str> {
+    let mut buffer = BytesMut::with_capacity(MAX_HEAD_LEN);
+    let mut searched = 0;
+    loop {
+        if let Some(pos) = buffer[searched..].windows(4).position(|w| w == b"\r\n\r\n") {
+            return Ok(Some(buffer.split_to(searched + pos + 4).freeze()));
+        }
+        if buffer.len() >= MAX_HEAD_LEN {
+            return Err("HTTP request head too long");
+        }
+        // The terminator may straddle the next read.
+        searched = buffer.len().saturating_sub(3);
+        let bytes_read = client
+            .recv_buf(&mut buffer)
+            .await
+            .map_err(|_| "Failed to read HTTP request")?;
+        if bytes_read == 0 {
+            return Ok(None);
+        }
+        debug!("Received {} bytes", bytes_read);
+    }
+}
 
-    if bytes_read == 0 {
-        // Client closed the connection
-        return Ok(());
+/// Serves the pages of the site.
+async fn route(req: Request) -> Response {
+    match req.path() {
+        "/" | "/index.html" => Response::ok("text/html", CONTENT.as_bytes()),
+        // Cut short by the timeout middleware.
+        "/slow" => {
+            sleep(REQUEST_TIMEOUT * 2).await;
+            Response::ok("text/plain", b"done".as_slice())
+        }
+        // Sent a line at a time, after the handler has returned.
+        "/stream" => Response::ok("text/plain", Body::from_stream(ticks())),
+        // The same, with the number of lines in a trailer.
+        "/stream-trailers" => {
+            let trailers = vec![("X-Ticks".into(), format!("{}", TICKS))];
+            let frames = ticks()
+                .map(Frame::Data)
+                .chain(stream::once(async { Frame::Trailers(trailers) }));
+            Response::ok("text/plain", Body::from_frames(frames))
+        }
+        // The live tasks, to find the connection that hangs.
+        "/tasks" => Response::ok("text/plain", axasync::dump_tasks().into_bytes()),
+        // The statistics of the kernel, for a Prometheus scraper.
+        "/metrics" => Response::ok(metrics::CONTENT_TYPE, metrics::render().into_bytes()),
+        _ => Response::error(404, "Not Found"),
     }
-    debug!("Received {} bytes", bytes_read);
+}
 
-    // Log the request (first line only)
-    if let Ok(request_str) = core::str::from_utf8(&buffer[..core::cmp::min(bytes_read, 100)]) {
-        if let Some(first_line) = request_str.lines().next() {
-            debug!("HTTP Request: {}", first_line);
+/// Yields [`TICKS`] lines, one every [`TICK_INTERVAL`].
+fn ticks() -> impl Stream<Item = Bytes> {
+    stream::unfold(0, |n| async move {
+        if n == TICKS {
+            return None;
         }
+        sleep(TICK_INTERVAL).await;
+        Some((format!("tick {}\n", n + 1).into_bytes().into(), n + 1))
+    })
+}
+
+/// Writes all of `data` to `client`.
+async fn write_all(client: &TcpStream, mut data: &[u8]) -> Result<(), &'static str> {
+    while !data.is_empty() {
+        let n = client
+            .write(data)
+            .await
+            .map_err(|_| "Failed to send HTTP response")?;
+        data = &data[n..];
     }
+    Ok(())
+}
 
-    let response = format!(header!(), CONTENT.len(), CONTENT);
+/// Sends a body, chunked if it is streamed.
+async fn write_body(client: &TcpStream, body: Body) -> Result<(), &'static str> {
+    let mut frames = match body {
+        // The body is shared, not copied.
+        Body::Full(body) => return write_all(client, &body).await,
+        Body::Stream(frames) => frames,
+    };
+    let mut codec = ChunkedCodec::new();
+    let mut buf = Vec::new();
+    let mut ended = false;
+    while !ended {
+        // The trailers end the body, an empty section if there are none.
+        let frame = frames
+            .next()
+            .await
+            .unwrap_or_else(|| Frame::Trailers(Vec::new()));
+        ended = matches!(frame, Frame::Trailers(_));
+        buf.clear();
+        codec
+            .encode(frame, &mut buf)
+            .map_err(|_| "Failed to encode HTTP response")?;
+        write_all(client, &buf).await?;
+    }
+    Ok(())
+}
 
-    // Send the hardcoded HTTP response
-    client
-        .send_async(response.as_bytes())
-        .await
-        .map_err(|_| "Failed to send HTTP response")?;
+/// Handle an HTTP request with `app` and send its response
+async fn handle_http_request(
+    client: &TcpStream,
+    peer: SocketAddr,
+    app: Handler,
+) -> Result<(), &'static str> {
+    let Some(head) = read_request_head(client).await? else {
+        // Client closed the connection
+        return Ok(());
+    };
+
+    let response = match Request::parse(peer, head) {
+        Some(req) => {
+            debug!("HTTP Request: {} {}", req.method(), req.path());
+            app(req).await
+        }
+        None => Response::error(400, "Bad Request"),
+    };
+
+    write_all(client, response.head().as_bytes()).await?;
+    write_body(client, response.body).await?;
 
     // Close the connection
     client
@@ -0,0 +1,197 @@
+//! The statistics of the kernel in the Prometheus text format, for
+//! `/metrics`.
+//!
+//! It gathers the statistics the modules keep: of the executor, of the
+//! network stack, of the interrupts and of the global allocator. They are
+//! sampled when scraped, nothing is recorded in between.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use axasync::mem::MemoryPressure;
+use axnet::ACCEPT_WAIT_BUCKETS_MICROS;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// The content type of the Prometheus text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Appends the help and the type of a metric.
+fn describe(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Appends a metric without labels.
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl core::fmt::Display) {
+    describe(out, name, kind, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn executor_metrics(out: &mut String) {
+    let stats = axasync::executor().stats();
+    metric(
+        out,
+        "axasync_polls_total",
+        "counter",
+        "Polls of the tasks.",
+        stats.polls,
+    );
+    metric(
+        out,
+        "axasync_slow_polls_total",
+        "counter",
+        "Polls longer than the slow-poll threshold.",
+        stats.slow_polls,
+    );
+    metric(
+        out,
+        "axasync_rejected_spawns_total",
+        "counter",
+        "Spawns rejected by a full run queue or the task limit.",
+        stats.rejected_spawns,
+    );
+    metric(
+        out,
+        "axasync_coalesced_wakes_total",
+        "counter",
+        "Wakes of tasks already queued.",
+        stats.coalesced_wakes,
+    );
+    metric(
+        out,
+        "axasync_missed_deadlines_total",
+        "counter",
+        "Tasks that completed after their deadline.",
+        stats.missed_deadlines,
+    );
+    metric(
+        out,
+        "axasync_queue_high_watermark",
+        "gauge",
+        "Most tasks in the run queue at once.",
+        stats.queue_high_watermark,
+    );
+    metric(
+        out,
+        "axasync_tasks",
+        "gauge",
+        "Live tasks of all the executors.",
+        axasync::task_list().len(),
+    );
+}
+
+fn net_metrics(out: &mut String) {
+    let polls = axnet::poll_stats();
+    metric(
+        out,
+        "axnet_polls_total",
+        "counter",
+        "Polls of the network interface.",
+        polls.polls,
+    );
+    metric(
+        out,
+        "axnet_polled_sockets_total",
+        "counter",
+        "Sockets processed by the polls.",
+        polls.sockets,
+    );
+    metric(
+        out,
+        "axnet_poll_max_seconds",
+        "gauge",
+        "The longest poll of the network interface.",
+        polls.max_nanos as f64 / 1e9,
+    );
+
+    let listen = axnet::listen_stats();
+    metric(
+        out,
+        "axnet_listening_ports",
+        "gauge",
+        "Ports listened on.",
+        listen.listening_ports,
+    );
+    metric(
+        out,
+        "axnet_syn_queued",
+        "gauge",
+        "Connections in the SYN queues.",
+        listen.syn_queued,
+    );
+    metric(
+        out,
+        "axnet_syn_overflows_total",
+        "counter",
+        "Connections dropped by a full SYN queue.",
+        listen.syn_overflows,
+    );
+
+    // The waits are not summed, there is no `_sum`.
+    let name = "axnet_accept_wait_seconds";
+    describe(
+        out,
+        name,
+        "histogram",
+        "Time from the SYN of a connection to its accept.",
+    );
+    let mut cumulative = 0;
+    for (bound, count) in ACCEPT_WAIT_BUCKETS_MICROS.iter().zip(listen.accept_wait) {
+        cumulative += count;
+        let le = *bound as f64 / 1e6;
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, listen.accepts);
+    let _ = writeln!(out, "{}_count {}", name, listen.accepts);
+}
+
+fn irq_metrics(out: &mut String) {
+    let name = "axhal_irqs_total";
+    describe(out, name, "counter", "Occurrences of each IRQ.");
+    for (irq, count) in axhal::irq::irq_counts() {
+        let _ = writeln!(out, "{}{{irq=\"{}\"}} {}", name, irq, count);
+    }
+}
+
+fn memory_metrics(out: &mut String) {
+    let allocator = axalloc::global_allocator();
+    let free_pages = allocator.available_pages();
+    let total_pages = allocator.used_pages() + free_pages;
+    metric(
+        out,
+        "axalloc_total_bytes",
+        "gauge",
+        "Memory of the global allocator.",
+        total_pages * PAGE_SIZE,
+    );
+    metric(
+        out,
+        "axalloc_free_bytes",
+        "gauge",
+        "Free memory of the global allocator.",
+        free_pages * PAGE_SIZE + allocator.available_bytes(),
+    );
+    let pressure = match axasync::mem::pressure() {
+        MemoryPressure::Normal => 0,
+        MemoryPressure::Low => 1,
+        MemoryPressure::Critical => 2,
+    };
+    metric(
+        out,
+        "axasync_memory_pressure",
+        "gauge",
+        "Memory pressure: 0 normal, 1 low, 2 critical.",
+        pressure,
+    );
+}
+
+/// Renders all the metrics.
+pub fn render() -> String {
+    let mut out = String::new();
+    executor_metrics(&mut out);
+    net_metrics(&mut out);
+    irq_metrics(&mut out);
+    memory_metrics(&mut out);
+    out
+}
@@ -0,0 +1,169 @@
+//! Requests, responses, and the middleware composed around the handler.
+//!
+//! A [`Handler`] turns a request into a response. A middleware is an async
+//! function that takes the request and the next handler, and may answer on
+//! its own, or call the next handler and look at its response. Wrapping a
+//! handler in a middleware gives a new handler, so they stack:
+//!
+//! ```ignore
+//! let app = logging(timeout(limit_connections(handler(route), 16), Duration::from_secs(5)));
+//! ```
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::net::SocketAddr;
+use core::time::Duration;
+
+use axasync::sync::Semaphore;
+use axasync::{BoxFuture, Bytes, TimeoutExt};
+use axlog::{info, warn};
+use axstd::time::Instant;
+
+use crate::body::Body;
+
+/// A parsed request head.
+pub struct Request {
+    /// The address of the client.
+    pub peer: SocketAddr,
+    method: Bytes,
+    path: Bytes,
+}
+
+impl Request {
+    /// Parses the head of a request, up to and including the empty line.
+    ///
+    /// The method and the path are slices of `head`, not copies. The headers
+    /// are not used by the handlers yet.
+    pub fn parse(peer: SocketAddr, head: Bytes) -> Option<Self> {
+        let line_end = head.windows(2).position(|w| w == b"\r\n")?;
+        let line = head.slice(..line_end);
+        let mut fields = line.split(|&b| b == b' ');
+        let method_len = fields.next()?.len();
+        let path_len = fields.next()?.len();
+        core::str::from_utf8(&line[..method_len + 1 + path_len]).ok()?;
+        Some(Self {
+            peer,
+            method: line.slice(..method_len),
+            path: line.slice(method_len + 1..method_len + 1 + path_len),
+        })
+    }
+
+    /// Returns the method, e.g. `GET`.
+    pub fn method(&self) -> &str {
+        // Checked in `parse`.
+        core::str::from_utf8(&self.method).unwrap_or_default()
+    }
+
+    /// Returns the path, e.g. `/index.html`.
+    pub fn path(&self) -> &str {
+        core::str::from_utf8(&self.path).unwrap_or_default()
+    }
+}
+
+/// A response, sent with `Connection: close`.
+pub struct Response {
+    pub status: u16,
+    pub reason: &'static str,
+    pub content_type: &'static str,
+    pub body: Body,
+}
+
+impl Response {
+    /// Creates a `200 OK` response.
+    pub fn ok(content_type: &'static str, body: impl Into<Body>) -> Self {
+        Self {
+            status: 200,
+            reason: "OK",
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    /// Creates an error response, whose body is the reason.
+    pub fn error(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            content_type: "text/plain",
+            body: reason.as_bytes().into(),
+        }
+    }
+
+    /// Returns the status line and the headers.
+    pub fn head(&self) -> String {
+        let framing = match &self.body {
+            Body::Full(body) => format!("Content-Length: {}", body.len()),
+            Body::Stream(_) => "Transfer-Encoding: chunked".into(),
+        };
+        format!(
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: {}\r\n\
+             {}\r\n\
+             Connection: close\r\n\
+             \r\n",
+            self.status, self.reason, self.content_type, framing
+        )
+    }
+}
+
+/// Turns a request into a response.
+pub type Handler = Arc<dyn Fn(Request) -> BoxFuture<Response> + Send + Sync>;
+
+/// Makes a handler of an async function.
+pub fn handler<F, Fut>(f: F) -> Handler
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    Arc::new(move |req| Box::pin(f(req)))
+}
+
+/// Wraps `next` in the middleware `f`, an async function that is given the
+/// request and the handler to pass it on to.
+pub fn layer<F, Fut>(next: Handler, f: F) -> Handler
+where
+    F: Fn(Request, Handler) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    Arc::new(move |req| Box::pin(f(req, next.clone())))
+}
+
+/// Logs each request with its status and duration.
+pub fn logging(next: Handler) -> Handler {
+    layer(next, |req, next| async move {
+        let start = Instant::now();
+        let line = format!("{} {} from {}", req.method(), req.path(), req.peer);
+        let res = next(req).await;
+        info!("{} -> {} in {:?}", line, res.status, start.elapsed());
+        res
+    })
+}
+
+/// Answers `504 Gateway Timeout` if the handler takes longer than
+/// `duration`, and cancels it.
+pub fn timeout(next: Handler, duration: Duration) -> Handler {
+    layer(next, move |req, next| async move {
+        next(req).timeout(duration).await.unwrap_or_else(|_| {
+            warn!("request timed out after {:?}", duration);
+            Response::error(504, "Gateway Timeout")
+        })
+    })
+}
+
+/// Answers `503 Service Unavailable` while `max` requests are already being
+/// handled, instead of queueing more of them.
+pub fn limit_connections(next: Handler, max: usize) -> Handler {
+    let permits = Semaphore::new(max);
+    layer(next, move |req, next| {
+        let permit = permits.try_acquire();
+        async move {
+            let Some(_permit) = permit else {
+                return Response::error(503, "Service Unavailable");
+            };
+            next(req).await
+        }
+    })
+}
@@ -0,0 +1,69 @@
+//! The demo, on the alarm of the RTC: waiting for an interrupt, cancelling
+//! the wait, and coalescing the interrupts.
+
+use alloc::sync::Arc;
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::Poll;
+
+use axasync::TimeoutExt;
+use axstd::mmio::{wait_for_event, MmioEvent};
+use axstd::println;
+use axstd::time::Duration;
+
+use crate::rtc::{Rtc, RTC_IRQ};
+
+/// Waits for the alarm, and prints when it rang.
+async fn wait(rtc: &Arc<Rtc>) {
+    let set_at = Rtc::now();
+    rtc.set_alarm(Duration::from_millis(10));
+    let alarm = wait_for_event(rtc).await.expect("alarm not registered");
+    println!(
+        "wait: the alarm rang {} us after it was set",
+        (alarm.rang_at - set_at) / 1000
+    );
+}
+
+/// Gives up waiting for the alarm: the dropped event is cancelled, and the
+/// alarm rings for no task.
+async fn cancel(rtc: &Arc<Rtc>) {
+    rtc.set_alarm(Duration::from_millis(50));
+    let res = wait_for_event(rtc).timeout(Duration::from_millis(10)).await;
+    assert!(res.is_err(), "the alarm rang too early");
+    println!("cancel: timed out, {} tasks waiting", rtc.waiting());
+
+    let irqs = axhal::irq::irq_count(RTC_IRQ);
+    axasync::sleep(Duration::from_millis(60)).await;
+    println!(
+        "cancel: the alarm rang {} times for no task",
+        axhal::irq::irq_count(RTC_IRQ) - irqs
+    );
+}
+
+/// Lets the alarm ring twice while the task does not run: it is woken once,
+/// with the payloads of both.
+async fn coalesce(rtc: &Arc<Rtc>) {
+    let mut event = pin!(MmioEvent::new(rtc.clone()));
+    // Registers the event.
+    poll_fn(|cx| {
+        assert!(event.as_mut().poll(cx).is_pending());
+        Poll::Ready(())
+    })
+    .await;
+
+    // Blocks the executor, the interrupts are still handled.
+    for _ in 0..2 {
+        rtc.set_alarm(Duration::from_millis(2));
+        axhal::time::busy_wait(Duration::from_millis(5));
+    }
+    let alarm = event.await.expect("alarm not registered");
+    println!("coalesce: woken once for {} alarms", alarm.count);
+}
+
+/// Runs the demo.
+pub async fn run() {
+    let rtc = Rtc::init();
+    wait(&rtc).await;
+    cancel(&rtc).await;
+    coalesce(&rtc).await;
+}
@@ -3,13 +3,21 @@
 
 extern crate alloc;
 
-use alloc::sync::Arc;
-use axstd::{io, println, thread, time::Duration};
-use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(target_arch = "riscv64")]
+mod demo;
+#[cfg(target_arch = "riscv64")]
+mod rtc;
+
+use axstd::println;
 
 #[no_mangle]
-fn main() -> io::Result<()> {
+fn main() {
     println!("MMIO Async Demo");
+    axasync::init();
+    #[cfg(target_arch = "riscv64")]
+    axasync::block_on(demo::run());
+    #[cfg(not(target_arch = "riscv64"))]
+    println!("The demo device is on the RISC-V QEMU virt machine, run it with ARCH=riscv64");
+    axasync::shutdown();
     println!("MMIO Async Demo completed!");
-    Ok(())
 }
@@ -0,0 +1,117 @@
+//! The Goldfish RTC of the QEMU `virt` machine, whose alarm interrupt is
+//! routed by the PLIC.
+//!
+//! The interrupt handler reads the time and delivers it to the tasks waiting
+//! for the alarm, as the payload of their [`MmioEvent`](axstd::mmio::MmioEvent).
+
+use alloc::sync::Arc;
+use core::task::Waker;
+use core::time::Duration;
+
+use axhal::mem::{phys_to_virt, PhysAddr};
+use axstd::mmio::{MmioEventHandler, MmioEventId, MmioWakerSet};
+
+/// The physical address of the registers of the RTC.
+const RTC_PADDR: usize = 0x10_1000;
+/// The IRQ of the RTC at the PLIC.
+pub const RTC_IRQ: usize = 11;
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+const ALARM_LOW: usize = 0x08;
+const ALARM_HIGH: usize = 0x0c;
+const IRQ_ENABLED: usize = 0x10;
+const CLEAR_INTERRUPT: usize = 0x1c;
+
+/// The payload of the alarm events.
+#[derive(Debug, Clone, Copy)]
+pub struct Alarm {
+    /// The time of the RTC when the alarm last rang, in nanoseconds.
+    pub rang_at: u64,
+    /// How many times it rang before the waiting task ran.
+    pub count: u32,
+}
+
+/// Merges the alarms that rang before the waiting task ran.
+fn coalesce(pending: &mut Alarm, alarm: Alarm) {
+    pending.rang_at = alarm.rang_at;
+    pending.count += alarm.count;
+}
+
+/// The tasks waiting for the alarm, and the alarms they have not seen yet.
+static EVENTS: MmioWakerSet<Alarm> = MmioWakerSet::with_coalesce(coalesce);
+
+fn read(offset: usize) -> u32 {
+    let reg = phys_to_virt(PhysAddr::from_usize(RTC_PADDR + offset));
+    // SAFETY: The registers of the RTC are mapped by the platform.
+    unsafe { reg.as_ptr().cast::<u32>().read_volatile() }
+}
+
+fn write(offset: usize, value: u32) {
+    let reg = phys_to_virt(PhysAddr::from_usize(RTC_PADDR + offset));
+    // SAFETY: The registers of the RTC are mapped by the platform.
+    unsafe { reg.as_mut_ptr().cast::<u32>().write_volatile(value) }
+}
+
+fn handle_irq() {
+    // The line is level-triggered, it must be cleared before the PLIC
+    // completes the IRQ.
+    write(CLEAR_INTERRUPT, 1);
+    let alarm = Alarm {
+        rang_at: Rtc::now(),
+        count: 1,
+    };
+    EVENTS.wake_matching(|_| true, alarm);
+}
+
+/// The RTC, whose events are the rings of its alarm.
+pub struct Rtc;
+
+impl Rtc {
+    /// Enables the alarm interrupt, and registers its handler.
+    pub fn init() -> Arc<Self> {
+        write(IRQ_ENABLED, 1);
+        assert!(
+            axhal::irq::register_handler(RTC_IRQ, handle_irq),
+            "RTC IRQ already taken"
+        );
+        Arc::new(Self)
+    }
+
+    /// Returns the time of the RTC, in nanoseconds since the epoch.
+    pub fn now() -> u64 {
+        // Reading the low half latches the high half.
+        let low = read(TIME_LOW);
+        let high = read(TIME_HIGH);
+        ((high as u64) << 32) | low as u64
+    }
+
+    /// Sets the alarm to ring `after` from now, replacing the previous one.
+    pub fn set_alarm(&self, after: Duration) {
+        let at = Self::now() + after.as_nanos() as u64;
+        // Writing the low half arms the alarm.
+        write(ALARM_HIGH, (at >> 32) as u32);
+        write(ALARM_LOW, at as u32);
+    }
+
+    /// Returns the number of tasks waiting for the alarm.
+    pub fn waiting(&self) -> usize {
+        EVENTS.waiting()
+    }
+}
+
+impl MmioEventHandler for Rtc {
+    type Data = Alarm;
+
+    fn register_event(&self, event_id: MmioEventId, waker: Waker) -> bool {
+        EVENTS.register(event_id, waker)
+    }
+
+    fn cancel_event(&self, event_id: MmioEventId) -> bool {
+        EVENTS.cancel(event_id)
+    }
+
+    fn take_event(&self, event_id: MmioEventId) -> Option<Alarm> {
+        EVENTS.take(event_id)
+    }
+}
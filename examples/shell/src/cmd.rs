@@ -8,15 +8,16 @@ use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 use crate::path_to_str;
 
 macro_rules! print_err {
-    ($cmd: literal, $msg: expr) => {
-        println!("{}: {}", $cmd, $msg);
+    ($out: expr, $cmd: literal, $msg: expr) => {
+        writeln!($out, "{}: {}", $cmd, $msg)?
     };
-    ($cmd: literal, $arg: expr, $err: expr) => {
-        println!("{}: {}: {}", $cmd, $arg, $err);
+    ($out: expr, $cmd: literal, $arg: expr, $err: expr) => {
+        writeln!($out, "{}: {}: {}", $cmd, $arg, $err)?
     };
 }
 
-type CmdHandler = fn(&str);
+/// Runs a command, writing its output to the first argument.
+type CmdHandler = fn(&mut dyn Write, &str) -> io::Result<()>;
 
 const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("cat", do_cat),
@@ -24,9 +25,17 @@ const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("echo", do_echo),
     ("exit", do_exit),
     ("help", do_help),
+    #[cfg(feature = "lockstat")]
+    ("lockstat", do_lockstat),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    #[cfg(feature = "async")]
+    ("poweroff", do_poweroff),
+    #[cfg(feature = "async")]
+    ("ps", do_ps),
     ("pwd", do_pwd),
+    #[cfg(feature = "async")]
+    ("reboot", do_reboot),
     ("rm", do_rm),
     ("uname", do_uname),
 ];
@@ -68,7 +77,7 @@ const fn file_perm_to_rwx(mode: u32) -> [u8; 9] {
     perm
 }
 
-fn do_ls(args: &str) {
+fn do_ls(out: &mut dyn Write, args: &str) -> io::Result<()> {
     let current_dir = std::env::current_dir().unwrap();
     let args = if args.is_empty() {
         path_to_str(&current_dir)
@@ -77,25 +86,25 @@ fn do_ls(args: &str) {
     };
     let name_count = args.split_whitespace().count();
 
-    fn show_entry_info(path: &str, entry: &str) -> io::Result<()> {
+    fn show_entry_info(out: &mut dyn Write, path: &str, entry: &str) -> io::Result<()> {
         let metadata = fs::metadata(path)?;
         let size = metadata.len();
         let file_type = metadata.file_type();
         let file_type_char = file_type_to_char(file_type);
         let rwx = file_perm_to_rwx(metadata.permissions().mode());
         let rwx = unsafe { core::str::from_utf8_unchecked(&rwx) };
-        println!("{}{} {:>8} {}", file_type_char, rwx, size, entry);
+        writeln!(out, "{}{} {:>8} {}", file_type_char, rwx, size, entry)?;
         Ok(())
     }
 
-    fn list_one(name: &str, print_name: bool) -> io::Result<()> {
+    fn list_one(out: &mut dyn Write, name: &str, print_name: bool) -> io::Result<()> {
         let is_dir = fs::metadata(name)?.is_dir();
         if !is_dir {
-            return show_entry_info(name, name);
+            return show_entry_info(out, name, name);
         }
 
         if print_name {
-            println!("{}:", name);
+            writeln!(out, "{}:", name)?;
         }
         let mut entries = fs::read_dir(name)?
             .filter_map(|e| e.ok())
@@ -106,8 +115,8 @@ fn do_ls(args: &str) {
         for entry in entries {
             let entry = path_to_str(&entry);
             let path = String::from(name) + "/" + entry;
-            if let Err(e) = show_entry_info(&path, entry) {
-                print_err!("ls", path, e);
+            if let Err(e) = show_entry_info(out, &path, entry) {
+                print_err!(out, "ls", path, e);
             }
         }
         Ok(())
@@ -115,27 +124,28 @@ fn do_ls(args: &str) {
 
     for (i, name) in args.split_whitespace().enumerate() {
         if i > 0 {
-            println!();
+            writeln!(out)?;
         }
-        if let Err(e) = list_one(name, name_count > 1) {
-            print_err!("ls", name, e);
+        if let Err(e) = list_one(out, name, name_count > 1) {
+            print_err!(out, "ls", name, e);
         }
     }
+    Ok(())
 }
 
-fn do_cat(args: &str) {
+fn do_cat(out: &mut dyn Write, args: &str) -> io::Result<()> {
     if args.is_empty() {
-        print_err!("cat", "no file specified");
-        return;
+        print_err!(out, "cat", "no file specified");
+        return Ok(());
     }
 
-    fn cat_one(fname: &str) -> io::Result<()> {
+    fn cat_one(out: &mut dyn Write, fname: &str) -> io::Result<()> {
         let mut buf = [0; 1024];
         let mut file = File::open(fname)?;
         loop {
             let n = file.read(&mut buf)?;
             if n > 0 {
-                io::stdout().write_all(&buf[..n])?;
+                out.write_all(&buf[..n])?;
             } else {
                 return Ok(());
             }
@@ -143,13 +153,14 @@ fn do_cat(args: &str) {
     }
 
     for fname in args.split_whitespace() {
-        if let Err(e) = cat_one(fname) {
-            print_err!("cat", fname, e);
+        if let Err(e) = cat_one(out, fname) {
+            print_err!(out, "cat", fname, e);
         }
     }
+    Ok(())
 }
 
-fn do_echo(args: &str) {
+fn do_echo(out: &mut dyn Write, args: &str) -> io::Result<()> {
     fn echo_file(fname: &str, text_list: &[&str]) -> io::Result<()> {
         let mut file = File::create(fname)?;
         for text in text_list {
@@ -162,8 +173,8 @@ fn do_echo(args: &str) {
         let text_before = args[..pos].trim();
         let (fname, text_after) = split_whitespace(&args[pos + 1..]);
         if fname.is_empty() {
-            print_err!("echo", "no file specified");
-            return;
+            print_err!(out, "echo", "no file specified");
+            return Ok(());
         };
 
         let text_list = [
@@ -173,17 +184,18 @@ fn do_echo(args: &str) {
             "\n",
         ];
         if let Err(e) = echo_file(fname, &text_list) {
-            print_err!("echo", fname, e);
+            print_err!(out, "echo", fname, e);
         }
     } else {
-        println!("{}", args)
+        writeln!(out, "{}", args)?
     }
+    Ok(())
 }
 
-fn do_mkdir(args: &str) {
+fn do_mkdir(out: &mut dyn Write, args: &str) -> io::Result<()> {
     if args.is_empty() {
-        print_err!("mkdir", "missing operand");
-        return;
+        print_err!(out, "mkdir", "missing operand");
+        return Ok(());
     }
 
     fn mkdir_one(path: &str) -> io::Result<()> {
@@ -192,15 +204,21 @@ fn do_mkdir(args: &str) {
 
     for path in args.split_whitespace() {
         if let Err(e) = mkdir_one(path) {
-            print_err!("mkdir", format_args!("cannot create directory '{path}'"), e);
+            print_err!(
+                out,
+                "mkdir",
+                format_args!("cannot create directory '{path}'"),
+                e
+            );
         }
     }
+    Ok(())
 }
 
-fn do_rm(args: &str) {
+fn do_rm(out: &mut dyn Write, args: &str) -> io::Result<()> {
     if args.is_empty() {
-        print_err!("rm", "missing operand");
-        return;
+        print_err!(out, "rm", "missing operand");
+        return Ok(());
     }
     let mut rm_dir = false;
     for arg in args.split_whitespace() {
@@ -222,30 +240,32 @@ fn do_rm(args: &str) {
             continue;
         }
         if let Err(e) = rm_one(path, rm_dir) {
-            print_err!("rm", format_args!("cannot remove '{path}'"), e);
+            print_err!(out, "rm", format_args!("cannot remove '{path}'"), e);
         }
     }
+    Ok(())
 }
 
-fn do_cd(mut args: &str) {
+fn do_cd(out: &mut dyn Write, mut args: &str) -> io::Result<()> {
     if args.is_empty() {
         args = "/";
     }
     if !args.contains(char::is_whitespace) {
         if let Err(e) = std::env::set_current_dir(args) {
-            print_err!("cd", args, e);
+            print_err!(out, "cd", args, e);
         }
     } else {
-        print_err!("cd", "too many arguments");
+        print_err!(out, "cd", "too many arguments");
     }
+    Ok(())
 }
 
-fn do_pwd(_args: &str) {
+fn do_pwd(out: &mut dyn Write, _args: &str) -> io::Result<()> {
     let pwd = std::env::current_dir().unwrap();
-    println!("{}", path_to_str(&pwd));
+    writeln!(out, "{}", path_to_str(&pwd))
 }
 
-fn do_uname(_args: &str) {
+fn do_uname(out: &mut dyn Write, _args: &str) -> io::Result<()> {
     let arch = option_env!("AX_ARCH").unwrap_or("");
     let platform = option_env!("AX_PLATFORM").unwrap_or("");
     let smp = match option_env!("AX_SMP") {
@@ -253,39 +273,125 @@ fn do_uname(_args: &str) {
         _ => " SMP",
     };
     let version = option_env!("CARGO_PKG_VERSION").unwrap_or("0.1.0");
-    println!(
+    writeln!(
+        out,
         "ArceOS {ver}{smp} {arch} {plat}",
         ver = version,
         smp = smp,
         arch = arch,
         plat = platform,
-    );
+    )
 }
 
-fn do_help(_args: &str) {
-    println!("Available commands:");
+#[cfg(feature = "lockstat")]
+fn do_lockstat(out: &mut dyn Write, args: &str) -> io::Result<()> {
+    use std::os::arceos::modules::axsync::lockstat;
+
+    if args == "reset" {
+        lockstat::reset_lock_stats();
+        return Ok(());
+    }
+    let top = match args {
+        "" => 10,
+        n => match n.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                print_err!(out, "lockstat", args, "usage: lockstat [COUNT | reset]");
+                return Ok(());
+            }
+        },
+    };
+
+    let mut stats = Vec::new();
+    lockstat::for_each_lock_stat(|s| stats.push(s));
+    stats.sort_by(|a, b| b.wait_nanos.cmp(&a.wait_nanos));
+    writeln!(
+        out,
+        "{:>12} {:>12} {:>14}  NAME",
+        "ACQUIRED", "CONTENDED", "WAIT(us)"
+    )?;
+    for s in stats.iter().take(top) {
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>14}  {}",
+            s.acquisitions,
+            s.contentions,
+            s.wait_nanos / 1000,
+            s.name
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+fn do_ps(out: &mut dyn Write, _args: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>6} {:<8} {:>12} {:>10} {:>12} {:>10}  NAME",
+        "ID", "STATE", "PENDING(ms)", "POLLS", "TIME(us)", "MAX(us)"
+    )?;
+    for t in axasync::task_list() {
+        writeln!(
+            out,
+            "{:>6} {:<8} {:>12} {:>10} {:>12} {:>10}  {}",
+            t.id.as_u64(),
+            t.state,
+            t.pending.as_millis(),
+            t.polls,
+            t.poll_nanos / 1000,
+            t.max_poll_nanos / 1000,
+            t.name.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+fn do_poweroff(out: &mut dyn Write, _args: &str) -> io::Result<()> {
+    writeln!(out, "Powering off...")?;
+    axasync::poweroff()
+}
+
+#[cfg(feature = "async")]
+fn do_reboot(out: &mut dyn Write, args: &str) -> io::Result<()> {
+    let reason = match args.trim() {
+        "" => axasync::ResetReason::Requested,
+        "failure" => axasync::ResetReason::SystemFailure,
+        _ => {
+            print_err!(out, "reboot", "usage: reboot [failure]");
+            return Ok(());
+        }
+    };
+    writeln!(out, "Rebooting...")?;
+    axasync::reboot(reason)
+}
+
+fn do_help(out: &mut dyn Write, _args: &str) -> io::Result<()> {
+    writeln!(out, "Available commands:")?;
     for (name, _) in CMD_TABLE {
-        println!("  {}", name);
+        writeln!(out, "  {}", name)?;
     }
+    Ok(())
 }
 
-fn do_exit(_args: &str) {
-    println!("Bye~");
+fn do_exit(out: &mut dyn Write, _args: &str) -> io::Result<()> {
+    writeln!(out, "Bye~")?;
     std::process::exit(0);
 }
 
-pub fn run_cmd(line: &[u8]) {
+/// Runs the command `line`, writing its output to `out`.
+pub fn run_cmd(out: &mut dyn Write, line: &[u8]) -> io::Result<()> {
     let line_str = unsafe { core::str::from_utf8_unchecked(line) };
     let (cmd, args) = split_whitespace(line_str);
     if !cmd.is_empty() {
         for (name, func) in CMD_TABLE {
             if cmd == *name {
-                func(args);
-                return;
+                return func(out, args);
             }
         }
-        println!("{}: command not found", cmd);
+        writeln!(out, "{}: command not found", cmd)?;
     }
+    Ok(())
 }
 
 fn split_whitespace(str: &str) -> (&str, &str) {
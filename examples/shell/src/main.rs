@@ -20,6 +20,9 @@ mod cmd;
 #[cfg(feature = "use-ramfs")]
 mod ramfs;
 
+#[cfg(feature = "telnet")]
+mod telnet;
+
 use std::io::prelude::*;
 
 const LF: u8 = b'\n';
@@ -30,6 +33,10 @@ const SPACE: u8 = b' ';
 
 const MAX_CMD_LEN: usize = 256;
 
+/// The port of the telnet service, forwarded from the host by `make run NET=y`.
+#[cfg(feature = "telnet")]
+const TELNET_PORT: u16 = 5555;
+
 fn print_prompt() {
     print!(
         "arceos:{}$ ",
@@ -45,7 +52,9 @@ fn main() {
 
     let mut buf = [0; MAX_CMD_LEN];
     let mut cursor = 0;
-    cmd::run_cmd("help".as_bytes());
+    #[cfg(feature = "telnet")]
+    telnet::start(TELNET_PORT);
+    cmd::run_cmd(&mut stdout, "help".as_bytes()).unwrap();
     print_prompt();
 
     loop {
@@ -59,7 +68,7 @@ fn main() {
             CR | LF => {
                 println!();
                 if cursor > 0 {
-                    cmd::run_cmd(&buf[..cursor]);
+                    cmd::run_cmd(&mut stdout, &buf[..cursor]).unwrap();
                     cursor = 0;
                 }
                 print_prompt();
@@ -0,0 +1,260 @@
+//! A telnet service that gives access to the shell over TCP.
+//!
+//! The client is put in character mode, and the line is edited here, as on
+//! the console. Each command runs in its own thread, so that the executor is
+//! not blocked, and the client can interrupt the wait for it with Ctrl-C.
+
+use std::format;
+use std::io::{self, Write};
+use std::thread;
+use std::vec::Vec;
+
+use axasync::executor::channel::oneshot;
+use axasync::{Either, select2, spawn_named};
+use axnet::{TcpSocket, TcpStream};
+use core::future::poll_fn;
+use core::net::{Ipv4Addr, SocketAddr};
+
+use crate::{BS, CR, DL, LF, MAX_CMD_LEN, SPACE, cmd};
+
+// Telnet commands and options, see RFC 854, 857 and 858.
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPT_ECHO: u8 = 1;
+const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+
+/// Starts a thread that accepts telnet clients on `port`.
+pub fn start(port: u16) {
+    thread::spawn(move || {
+        axasync::init();
+        axasync::spawn_named("telnetd", async move {
+            if let Err(e) = serve(port).await {
+                println!("telnetd: {:?}", e);
+            }
+        });
+        axasync::executor_run();
+    });
+}
+
+async fn serve(port: u16) -> io::Result<()> {
+    let listener = TcpSocket::new();
+    listener.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))?;
+    listener.listen()?;
+    loop {
+        let (socket, peer) = listener.accept_async().await?;
+        spawn_named(format!("telnet-{}", peer), async move {
+            let stream = TcpStream::from(socket);
+            if let Err(e) = session(&stream).await {
+                println!("telnetd: {}: {:?}", peer, e);
+            }
+            let _ = stream.shutdown();
+        });
+    }
+}
+
+/// What a byte from the client completes.
+enum Input {
+    None,
+    Line(Vec<u8>),
+    Interrupt,
+    Eof,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Data,
+    /// After an `IAC`.
+    Command,
+    /// After an option negotiation command, before its option.
+    Option,
+    /// In a subnegotiation, skipped up to `IAC SE`.
+    Sub,
+    SubIac,
+    /// After a CR, which may be followed by a LF or a NUL.
+    Cr,
+}
+
+/// Edits the command line from the bytes sent by the client.
+struct LineDiscipline {
+    state: State,
+    line: Vec<u8>,
+}
+
+impl LineDiscipline {
+    fn new() -> Self {
+        Self {
+            state: State::Data,
+            line: Vec::new(),
+        }
+    }
+
+    /// Feeds a byte from the client, pushes what is to be echoed to `echo`.
+    fn feed(&mut self, byte: u8, echo: &mut Vec<u8>) -> Input {
+        match (self.state, byte) {
+            (State::Command, WILL..=DONT) => self.state = State::Option,
+            (State::Command, SB) => self.state = State::Sub,
+            (State::Command | State::Option, _) => self.state = State::Data,
+            (State::Sub, IAC) => self.state = State::SubIac,
+            (State::Sub, _) => {}
+            (State::SubIac, SE) => self.state = State::Data,
+            (State::SubIac, _) => self.state = State::Sub,
+            (State::Cr, LF | 0) => self.state = State::Data,
+            (State::Data | State::Cr, _) => {
+                self.state = State::Data;
+                return self.feed_data(byte, echo);
+            }
+        }
+        Input::None
+    }
+
+    fn feed_data(&mut self, byte: u8, echo: &mut Vec<u8>) -> Input {
+        match byte {
+            IAC => self.state = State::Command,
+            CR | LF => {
+                if byte == CR {
+                    self.state = State::Cr;
+                }
+                echo.extend_from_slice(b"\r\n");
+                return Input::Line(core::mem::take(&mut self.line));
+            }
+            BS | DL => {
+                if self.line.pop().is_some() {
+                    echo.extend_from_slice(&[BS, SPACE, BS]);
+                }
+            }
+            CTRL_C => {
+                self.line.clear();
+                echo.extend_from_slice(b"^C\r\n");
+                return Input::Interrupt;
+            }
+            CTRL_D if self.line.is_empty() => return Input::Eof,
+            0..=31 | 128.. => {}
+            c => {
+                if self.line.len() < MAX_CMD_LEN - 1 {
+                    self.line.push(c);
+                    echo.push(c);
+                }
+            }
+        }
+        Input::None
+    }
+}
+
+/// The output of a command, with the line feeds translated for telnet.
+struct Output(Vec<u8>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &b in buf {
+            if b == LF {
+                self.0.push(CR);
+            }
+            self.0.push(b);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn send(stream: &TcpStream, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = stream.write(buf).await?;
+        buf = &buf[n..];
+    }
+    Ok(())
+}
+
+/// The prompt, without the current directory: it is behind a blocking lock,
+/// which must not be taken by a poll.
+async fn send_prompt(stream: &TcpStream) -> io::Result<()> {
+    send(stream, b"arceos$ ").await
+}
+
+async fn session(stream: &TcpStream) -> io::Result<()> {
+    // We echo the input, and do not need the go-ahead signals.
+    send(
+        stream,
+        &[IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SUPPRESS_GO_AHEAD],
+    )
+    .await?;
+    send_prompt(stream).await?;
+
+    let mut input = LineDiscipline::new();
+    let mut buf = [0; 256];
+    let mut echo = Vec::new();
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        for &byte in &buf[..n] {
+            match input.feed(byte, &mut echo) {
+                Input::None => continue,
+                Input::Line(line) => {
+                    send(stream, &echo).await?;
+                    echo.clear();
+                    if line.trim_ascii() == b"exit" {
+                        return send(stream, b"Bye~\r\n").await;
+                    }
+                    if !line.trim_ascii().is_empty() && !run_command(stream, line).await? {
+                        return Ok(());
+                    }
+                }
+                Input::Interrupt => {}
+                Input::Eof => return send(stream, b"\r\n").await,
+            }
+            send(stream, &echo).await?;
+            echo.clear();
+            send_prompt(stream).await?;
+        }
+        send(stream, &echo).await?;
+        echo.clear();
+    }
+}
+
+/// Runs a command and sends its output, returns `false` if the client
+/// closed the connection meanwhile.
+///
+/// The bytes the client sends while the command runs are discarded, except
+/// for Ctrl-C, which stops the wait for the command. The command itself
+/// cannot be cancelled, its output is dropped when it finishes.
+async fn run_command(stream: &TcpStream, line: Vec<u8>) -> io::Result<bool> {
+    let (tx, mut rx) = oneshot::channel();
+    thread::spawn(move || {
+        let mut output = Output(Vec::new());
+        let _ = cmd::run_cmd(&mut output, &line);
+        let _ = tx.send(output.0);
+    });
+
+    let output = poll_fn(|cx| rx.poll(cx));
+    let interrupt = async {
+        let mut buf = [0; 64];
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 || buf[..n].contains(&CTRL_C) {
+                return io::Result::Ok(n);
+            }
+        }
+    };
+    match select2(output, interrupt).await {
+        Either::Left(output) => {
+            send(stream, &output.unwrap_or_default()).await?;
+            Ok(true)
+        }
+        Either::Right(Ok(0)) => Ok(false),
+        Either::Right(res) => {
+            res?;
+            send(stream, b"^C\r\n").await?;
+            Ok(true)
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Tasks in static storage, e.g. for the bootstrap before the heap is up.
+//!
+//! A [`TaskPool`] is a static arena of `N` slots of `SIZE` bytes, like the
+//! `#[task(pool_size = N)]` of embassy: a future spawned in it is moved into
+//! a free slot and polled in place, neither the future nor its task is
+//! allocated. The slot is freed when the future completes.
+//!
+//! ```ignore
+//! static BLINKERS: TaskPool<2> = TaskPool::new();
+//!
+//! BLINKERS.spawn(blink(LED0))?;
+//! BLINKERS.spawn(blink(LED1))?;
+//! axasync::run_static();
+//! ```
+//!
+//! Once the heap is up, [`static_tasks`] hands the static tasks to an
+//! [`Executor`](crate::Executor): a single task of the executor polls them as
+//! they are woken.
+
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::mem::{MaybeUninit, align_of, size_of};
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use axerrno::{AxResult, ax_err};
+use kspin::SpinNoIrq;
+
+/// The size of the slots of a [`TaskPool`] by default, in bytes.
+pub const DEFAULT_TASK_SIZE: usize = 512;
+
+/// The largest alignment of a future in a [`TaskPool`].
+const SLOT_ALIGN: usize = 16;
+
+/// The slot holds a future.
+const SPAWNED: u8 = 1 << 0;
+/// The task is in the ready list, or is to be put back once polled.
+const QUEUED: u8 = 1 << 1;
+/// The task is being polled.
+const RUNNING: u8 = 1 << 2;
+
+/// Polls the future of a slot, and drops it once completed.
+type PollFn = unsafe fn(&Header, &mut Context<'_>) -> Poll<()>;
+
+/// The state of a slot, linked in the ready list.
+struct Header {
+    state: AtomicU8,
+    /// The next task of the ready list, under its lock.
+    next: UnsafeCell<*const Header>,
+    /// Set by the spawn, for the type of the future.
+    poll: UnsafeCell<Option<PollFn>>,
+}
+
+// SAFETY: `next` is only used under the lock of the ready list, `poll` and
+// the future only by the spawn and by the poll, which the state keeps apart.
+unsafe impl Sync for Header {}
+
+#[repr(C, align(16))]
+struct Storage<const SIZE: usize>([MaybeUninit<u8>; SIZE]);
+
+/// A slot of a pool, the header first so that it points to the slot.
+#[repr(C)]
+struct Slot<const SIZE: usize> {
+    header: Header,
+    future: UnsafeCell<Storage<SIZE>>,
+}
+
+impl<const SIZE: usize> Slot<SIZE> {
+    const fn new() -> Self {
+        Self {
+            header: Header {
+                state: AtomicU8::new(0),
+                next: UnsafeCell::new(ptr::null()),
+                poll: UnsafeCell::new(None),
+            },
+            future: UnsafeCell::new(Storage([MaybeUninit::uninit(); SIZE])),
+        }
+    }
+}
+
+/// A static arena of `N` tasks, whose futures are at most `SIZE` bytes.
+///
+/// See the [module documentation](self).
+pub struct TaskPool<const N: usize, const SIZE: usize = DEFAULT_TASK_SIZE> {
+    slots: [Slot<SIZE>; N],
+}
+
+// SAFETY: The futures spawned are `Send`, see `Header`.
+unsafe impl<const N: usize, const SIZE: usize> Sync for TaskPool<N, SIZE> {}
+
+impl<const N: usize, const SIZE: usize> TaskPool<N, SIZE> {
+    /// Creates a pool with all its slots free.
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { Slot::new() }; N],
+        }
+    }
+
+    /// Moves `future` into a free slot, and queues it.
+    ///
+    /// Fails with [`NoMemory`](axerrno::AxError::NoMemory) if all the slots
+    /// are taken. A future larger than `SIZE` does not compile.
+    pub fn spawn<F>(&'static self, future: F) -> AxResult
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        const {
+            assert!(size_of::<F>() <= SIZE, "future larger than the slots");
+            assert!(align_of::<F>() <= SLOT_ALIGN, "future alignment too large");
+        }
+        let free = self.slots.iter().find(|slot| {
+            slot.header
+                .state
+                .compare_exchange(0, SPAWNED | QUEUED, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        });
+        let Some(slot) = free else {
+            return ax_err!(NoMemory, "spawn: static task pool full");
+        };
+        // SAFETY: The slot is free, and nothing polls it before it is queued.
+        unsafe {
+            slot.future.get().cast::<F>().write(future);
+            *slot.header.poll.get() = Some(poll_slot::<F, SIZE>);
+        }
+        LIVE_TASKS.fetch_add(1, Ordering::Release);
+        push_ready(&slot.header);
+        Ok(())
+    }
+}
+
+impl<const N: usize, const SIZE: usize> Default for TaskPool<N, SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Safety
+///
+/// The slot of `header` must hold a future `F`, and be polled by one CPU at a
+/// time.
+unsafe fn poll_slot<F: Future<Output = ()>, const SIZE: usize>(
+    header: &Header,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    // SAFETY: The header is the first field of its slot.
+    let slot = unsafe { &*(header as *const Header).cast::<Slot<SIZE>>() };
+    let future = slot.future.get().cast::<F>();
+    // SAFETY: The future is never moved out of its slot.
+    let poll = unsafe { Pin::new_unchecked(&mut *future) }.poll(cx);
+    if poll.is_ready() {
+        // SAFETY: It is not polled again, the slot is freed.
+        unsafe { ptr::drop_in_place(future) };
+    }
+    poll
+}
+
+/// The static tasks woken and not polled yet, linked through their headers.
+struct ReadyList {
+    head: *const Header,
+    tail: *const Header,
+    len: usize,
+}
+
+// SAFETY: The headers are in static pools.
+unsafe impl Send for ReadyList {}
+
+static READY: SpinNoIrq<ReadyList> = SpinNoIrq::new(ReadyList {
+    head: ptr::null(),
+    tail: ptr::null(),
+    len: 0,
+});
+
+/// Number of static tasks spawned and not completed.
+static LIVE_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// The waker of the [`StaticTasks`] polling the static tasks, if any.
+static DRIVER: SpinNoIrq<Option<Waker>> = SpinNoIrq::new(None);
+
+fn push_ready(header: &'static Header) {
+    {
+        let mut ready = READY.lock();
+        // SAFETY: Under the lock.
+        unsafe { *header.next.get() = ptr::null() };
+        if ready.tail.is_null() {
+            ready.head = header;
+        } else {
+            // SAFETY: Under the lock.
+            unsafe { *(*ready.tail).next.get() = header };
+        }
+        ready.tail = header;
+        ready.len += 1;
+    }
+    if let Some(driver) = DRIVER.lock().as_ref() {
+        driver.wake_by_ref();
+    }
+}
+
+fn pop_ready() -> Option<&'static Header> {
+    let mut ready = READY.lock();
+    // SAFETY: The headers in the list are static.
+    let header = unsafe { ready.head.as_ref()? };
+    // SAFETY: Under the lock.
+    ready.head = unsafe { *header.next.get() };
+    if ready.head.is_null() {
+        ready.tail = ptr::null();
+    }
+    ready.len -= 1;
+    Some(header)
+}
+
+/// Queues the task of `header` if it is spawned, once until it is polled.
+fn wake(header: &'static Header) {
+    let mut state = header.state.load(Ordering::Acquire);
+    loop {
+        if state & SPAWNED == 0 || state & QUEUED != 0 {
+            return;
+        }
+        match header.state.compare_exchange_weak(
+            state,
+            state | QUEUED,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => break,
+            Err(actual) => state = actual,
+        }
+    }
+    // Put back by its poll otherwise.
+    if state & RUNNING == 0 {
+        push_ready(header);
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |header| RawWaker::new(header, &VTABLE),
+    // SAFETY: The wakers point to the static headers.
+    |header| wake(unsafe { &*header.cast::<Header>() }),
+    |header| wake(unsafe { &*header.cast::<Header>() }),
+    |_| {},
+);
+
+fn run_task(header: &'static Header) {
+    let _ = header
+        .state
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+            Some((state | RUNNING) & !QUEUED)
+        });
+    let raw = RawWaker::new((header as *const Header).cast(), &VTABLE);
+    // SAFETY: The vtable only queues the task.
+    let waker = unsafe { Waker::from_raw(raw) };
+    let mut cx = Context::from_waker(&waker);
+    // SAFETY: Set by the spawn, and the task is only polled once queued.
+    let poll = unsafe { (*header.poll.get()).unwrap() };
+    // SAFETY: The task is not in the ready list while `RUNNING`.
+    if unsafe { poll(header, &mut cx) }.is_ready() {
+        header.state.store(0, Ordering::Release);
+        LIVE_TASKS.fetch_sub(1, Ordering::Release);
+        if let Some(driver) = DRIVER.lock().as_ref() {
+            driver.wake_by_ref();
+        }
+        return;
+    }
+    let prev = header.state.fetch_and(!RUNNING, Ordering::AcqRel);
+    if prev & QUEUED != 0 {
+        // Woken while running.
+        push_ready(header);
+    }
+}
+
+/// Polls the static tasks that are ready, each at most once, and returns
+/// whether any was polled.
+pub fn poll_static_tasks() -> bool {
+    // The tasks woken meanwhile wait for the next call.
+    let count = READY.lock().len;
+    for _ in 0..count {
+        match pop_ready() {
+            Some(header) => run_task(header),
+            None => break,
+        }
+    }
+    count > 0
+}
+
+/// Runs the static tasks until they have all completed, e.g. in the
+/// bootstrap, before the heap is up.
+///
+/// It spins while no task is ready: only the wakes from interrupt handlers or
+/// from other static tasks make progress.
+pub fn run_static() {
+    while LIVE_TASKS.load(Ordering::Acquire) > 0 {
+        if !poll_static_tasks() {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Returns a future that polls the static tasks as they are woken, until they
+/// have all completed. Spawn it on an [`Executor`](crate::Executor) to run
+/// them once the heap is up.
+pub fn static_tasks() -> StaticTasks {
+    StaticTasks { _private: () }
+}
+
+/// The future of [`static_tasks`].
+pub struct StaticTasks {
+    _private: (),
+}
+
+impl Future for StaticTasks {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Before polling, so that no wake is missed.
+        *DRIVER.lock() = Some(cx.waker().clone());
+        poll_static_tasks();
+        if LIVE_TASKS.load(Ordering::Acquire) == 0 {
+            DRIVER.lock().take();
+            return Poll::Ready(());
+        }
+        if READY.lock().len > 0 {
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
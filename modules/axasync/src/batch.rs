@@ -0,0 +1,240 @@
+//! Batched and deferred wakeups.
+//!
+//! Waking a task pushes it onto the executor's ready queue, which takes the
+//! queue lock. Drivers and protocol stacks often discover many ready tasks
+//! while holding their own locks (e.g. the socket set during a NIC poll);
+//! waking them there lengthens the critical section and lets the woken task
+//! re-enter the stack while it is still locked. Instead, collect the wakers
+//! and wake them once the locks are released.
+
+use alloc::vec::Vec;
+use core::task::Waker;
+
+use kspin::SpinNoIrq;
+
+/// Wakers collected by [`defer_wakes`] on the current CPU, [`None`] when no
+/// deferral is in progress.
+#[percpu::def_percpu]
+static DEFERRED_WAKES: SpinNoIrq<Option<Deferral>> = SpinNoIrq::new(None);
+
+/// Number of task wakers that [`defer_wakes`] holds, the later wakeups are
+/// immediate.
+pub const MAX_DEFERRED_WAKES: usize = 64;
+
+/// The wakers deferred by an axtask in [`defer_wakes`].
+///
+/// Its room is fixed, the executor defers from its wake path, which must not
+/// allocate.
+struct Deferral {
+    /// The context of the axtask, see [`current_context`], only its own
+    /// wakeups are deferred.
+    owner: u64,
+    wakers: [Option<Waker>; MAX_DEFERRED_WAKES],
+    len: usize,
+}
+
+impl Deferral {
+    /// Adds a waker, or returns it if the deferral is full. Wakers that
+    /// would wake the same task are only kept once.
+    fn push(&mut self, waker: Waker) -> Result<(), Waker> {
+        let deferred = &self.wakers[..self.len];
+        if deferred.iter().flatten().any(|w| w.will_wake(&waker)) {
+            return Ok(());
+        }
+        match self.wakers.get_mut(self.len) {
+            Some(slot) => {
+                *slot = Some(waker);
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(waker),
+        }
+    }
+}
+
+impl Drop for Deferral {
+    fn drop(&mut self) {
+        for waker in self.wakers[..self.len].iter_mut().filter_map(Option::take) {
+            waker.wake();
+        }
+    }
+}
+
+/// Returns the context whose wakeups are deferred: the ID of the current
+/// axtask, `0` before there is one, or [`None`] in an IRQ handler.
+///
+/// An IRQ handler is not the axtask it interrupted, its wakeups are never
+/// deferred, nor is its own [`defer_wakes`].
+fn current_context() -> Option<u64> {
+    #[cfg(feature = "irq")]
+    if axhal::irq::in_irq() {
+        return None;
+    }
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        return Some(curr.id().as_u64());
+    }
+    Some(0)
+}
+
+/// A set of wakers to be woken together.
+///
+/// Wakers that would wake the same task are only kept once. Wakers that are
+/// still in the batch when it is dropped are woken.
+#[derive(Default)]
+pub struct WakeBatch {
+    wakers: Vec<Waker>,
+}
+
+impl WakeBatch {
+    /// Creates an empty batch.
+    pub const fn new() -> Self {
+        Self { wakers: Vec::new() }
+    }
+
+    /// Adds a waker to the batch.
+    pub fn push(&mut self, waker: Waker) {
+        if !self.wakers.iter().any(|w| w.will_wake(&waker)) {
+            self.wakers.push(waker);
+        }
+    }
+
+    /// Returns the number of wakers in the batch.
+    pub fn len(&self) -> usize {
+        self.wakers.len()
+    }
+
+    /// Returns whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.wakers.is_empty()
+    }
+
+    /// Wakes all wakers in the batch.
+    pub fn wake_all(mut self) {
+        self.wake_pending();
+    }
+
+    fn wake_pending(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for WakeBatch {
+    fn drop(&mut self) {
+        self.wake_pending();
+    }
+}
+
+impl Extend<Waker> for WakeBatch {
+    fn extend<I: IntoIterator<Item = Waker>>(&mut self, iter: I) {
+        for waker in iter {
+            self.push(waker);
+        }
+    }
+}
+
+/// Wakes all the given wakers, waking each task at most once.
+pub fn wake_batch(wakers: impl IntoIterator<Item = Waker>) {
+    let mut batch = WakeBatch::new();
+    batch.extend(wakers);
+    batch.wake_all();
+}
+
+/// Runs `f`, and defers the wakeups of axasync tasks that the current
+/// axtask makes during `f` until it returns.
+///
+/// Only the wakers of the tasks of an [`Executor`](crate::Executor) are
+/// deferred, the wakeup of any other waker is immediate, as are those over
+/// [`MAX_DEFERRED_WAKES`]. Nested calls are flattened into the outermost one.
+/// The deferred wakeups are made even if `f` unwinds.
+///
+/// The axtask is kept on its CPU during the outermost call, and the wakeups
+/// of the other axtasks that run there meanwhile, e.g. while `f` sleeps on a
+/// lock, are not deferred. If one of them is itself in `defer_wakes`, those
+/// of `f` are not deferred either. In an IRQ handler, nothing is deferred.
+pub fn defer_wakes<R>(f: impl FnOnce() -> R) -> R {
+    let _deferral = DeferralGuard::enter();
+    f()
+}
+
+/// Installs the deferral of the current axtask on its CPU, if it is the
+/// outermost [`defer_wakes`], and makes its wakeups when dropped.
+struct DeferralGuard {
+    outermost: bool,
+    /// Dropped after the wakeups.
+    #[cfg(feature = "multitask")]
+    _pin: Option<crate::executor::PinToCpu>,
+}
+
+impl DeferralGuard {
+    fn enter() -> Self {
+        let untouched = Self {
+            outermost: false,
+            #[cfg(feature = "multitask")]
+            _pin: None,
+        };
+        // Neither deferred nor pinned in an IRQ handler.
+        let Some(owner) = current_context() else {
+            return untouched;
+        };
+        // Nested, the outermost call keeps the axtask on the CPU of its
+        // deferral.
+        let nested = unsafe { DEFERRED_WAKES.current_ref_raw() }
+            .lock()
+            .as_ref()
+            .is_some_and(|deferral| deferral.owner == owner);
+        if nested {
+            return untouched;
+        }
+        #[cfg(feature = "multitask")]
+        let pin = crate::executor::PinToCpu::new(axhal::cpu::this_cpu_id());
+        let outermost = {
+            let mut deferred = unsafe { DEFERRED_WAKES.current_ref_raw() }.lock();
+            if deferred.is_none() {
+                *deferred = Some(Deferral {
+                    owner,
+                    wakers: [const { None }; MAX_DEFERRED_WAKES],
+                    len: 0,
+                });
+                true
+            } else {
+                false
+            }
+        };
+        Self {
+            outermost,
+            #[cfg(feature = "multitask")]
+            _pin: outermost.then_some(pin),
+        }
+    }
+}
+
+impl Drop for DeferralGuard {
+    fn drop(&mut self) {
+        if self.outermost {
+            let deferral = unsafe { DEFERRED_WAKES.current_ref_raw() }.lock().take();
+            // Woken outside the lock.
+            drop(deferral);
+        }
+    }
+}
+
+/// Adds the waker of a task to the deferral of [`defer_wakes`] of the
+/// current axtask, and returns `true`, or returns `false` if it is not
+/// deferring or its deferral is full.
+pub(crate) fn defer_wake(waker: impl FnOnce() -> Waker) -> bool {
+    let Some(context) = current_context() else {
+        return false;
+    };
+    let mut deferred = unsafe { DEFERRED_WAKES.current_ref_raw() }.lock();
+    match deferred.as_mut() {
+        Some(deferral) if deferral.owner == context => match deferral.push(waker()) {
+            Ok(()) => true,
+            // Dropped, not woken.
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,198 @@
+//! A pool of axtasks that run blocking calls off the executor.
+//!
+//! A task must not block in `poll()`, as it stalls all the other tasks of
+//! its executor. [`spawn_blocking`] runs a synchronous call, e.g. of `axfs`
+//! or of a driver, on a worker axtask instead, and lets the task await its
+//! result. [`block_in_place`] runs it in the poll, while another axtask runs
+//! the other tasks.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axtask::{AxTaskRef, WaitQueue};
+use kspin::SpinNoIrq;
+
+use crate::executor::channel::oneshot;
+use crate::{Executor, JoinHandle};
+
+/// The most worker axtasks of the pool. The calls beyond are queued until a
+/// worker is free.
+pub const MAX_BLOCKING_WORKERS: usize = 16;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    jobs: VecDeque<Job>,
+    /// Number of workers, they are never stopped.
+    workers: usize,
+    /// Number of workers waiting for a job.
+    idle: usize,
+}
+
+static POOL: SpinNoIrq<Pool> = SpinNoIrq::new(Pool {
+    jobs: VecDeque::new(),
+    workers: 0,
+    idle: 0,
+});
+
+/// The idle workers wait for a job in it.
+static JOB_QUEUED: WaitQueue = WaitQueue::new();
+
+/// Runs the blocking function `f` on a worker axtask, and returns a handle
+/// that resolves to its result.
+///
+/// Aborting the handle does not stop `f`, only the wait for its result.
+///
+/// ```ignore
+/// let data = axasync::spawn_blocking(|| axfs::api::read("/etc/config")).await?;
+/// ```
+pub fn spawn_blocking<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (sender, mut receiver) = oneshot::channel();
+    submit(Box::new(move || {
+        let _ = sender.send(f());
+    }));
+    crate::spawn(async move {
+        // The sender is only dropped after sending, a panic stops the kernel.
+        poll_fn(|cx| receiver.poll(cx))
+            .await
+            .expect("spawn_blocking: worker dropped the call")
+    })
+}
+
+/// Runs the blocking function `f` in place, in the poll of a task.
+///
+/// The poll may block in `f`, e.g. on an `axsync::Mutex` held by another
+/// axtask for long. A replacement axtask runs the other tasks of the
+/// executor polling the task, global or not, until `f` returns, so that they
+/// are not stalled meanwhile. The tasks of a local executor, e.g. of
+/// [`spawn_local`](crate::spawn_local), only run in its own axtask: they
+/// wait. The task itself is held up, prefer
+/// [`spawn_blocking`] where it can await.
+///
+/// Outside of a poll, it just calls `f`.
+///
+/// ```ignore
+/// let config = axasync::block_in_place(|| axfs::api::read("/etc/config"))?;
+/// ```
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    // The poll is the one of the current axtask, not of another one that
+    // polled on this CPU before: its context is only recorded on the axtask.
+    let Some(context) = crate::executor::poll_context() else {
+        return f();
+    };
+    if !axtask::current().is_nonblocking() {
+        return f();
+    }
+    // SAFETY: the executor outlives the poll of the current axtask, which
+    // `block_in_place` is called from, and the replacement is joined before
+    // it returns.
+    let executor = unsafe { &*context.executor };
+    // The tasks of a local executor are only polled by the axtask it belongs
+    // to, they wait for `f`.
+    if executor.is_local() {
+        return f();
+    }
+    let _replacement = Replacement::start(executor);
+    f()
+}
+
+/// A pointer to the executor that a replacement axtask runs.
+struct ExecutorPtr(*const Executor);
+
+// SAFETY: the executor is `Sync`, and outlives the replacement.
+unsafe impl Send for ExecutorPtr {}
+
+impl ExecutorPtr {
+    /// # Safety
+    ///
+    /// The executor must still be alive.
+    unsafe fn get(&self) -> &Executor {
+        unsafe { &*self.0 }
+    }
+}
+
+/// The axtask that runs the other tasks while one blocks in
+/// [`block_in_place`]. When dropped, also if `f` panics, it is stopped and
+/// the poll is resumed.
+struct Replacement<'a> {
+    executor: &'a Executor,
+    done: Arc<AtomicBool>,
+    worker: AxTaskRef,
+}
+
+impl<'a> Replacement<'a> {
+    fn start(executor: &'a Executor) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let worker_done = done.clone();
+        let ptr = ExecutorPtr(executor);
+        let worker = axtask::spawn(move || {
+            // SAFETY: the worker is joined before the executor goes.
+            unsafe { ptr.get() }.run_until_done(&worker_done);
+        });
+
+        axtask::current().exit_nonblocking();
+        Self {
+            executor,
+            done,
+            worker,
+        }
+    }
+}
+
+impl Drop for Replacement<'_> {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Release);
+        self.executor.notify();
+        // The executor may be dropped once the poll returns.
+        self.worker.join();
+        axtask::current().enter_nonblocking();
+    }
+}
+
+/// Queues `job`, and starts a worker for it if none is idle.
+fn submit(job: Job) {
+    let start_worker = {
+        let mut pool = POOL.lock();
+        pool.jobs.push_back(job);
+        let start = pool.jobs.len() > pool.idle && pool.workers < MAX_BLOCKING_WORKERS;
+        if start {
+            pool.workers += 1;
+        }
+        start
+    };
+    if start_worker {
+        axtask::spawn(worker);
+    }
+    JOB_QUEUED.notify_one(false);
+}
+
+/// Runs the queued jobs, one at a time.
+fn worker() {
+    loop {
+        let job = {
+            let mut pool = POOL.lock();
+            let job = pool.jobs.pop_front();
+            if job.is_none() {
+                pool.idle += 1;
+            }
+            job
+        };
+        match job {
+            Some(job) => job(),
+            None => {
+                JOB_QUEUED.wait_until(|| !POOL.lock().jobs.is_empty());
+                POOL.lock().idle -= 1;
+            }
+        }
+    }
+}
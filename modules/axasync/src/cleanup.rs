@@ -0,0 +1,124 @@
+//! Async cleanup on drop.
+//!
+//! Rust has no async `Drop`, so a task that is cancelled (e.g. by a
+//! [`Timeout`](crate::time::Timeout)) cannot await the shutdown of the
+//! sockets or files it owns. A [`CleanupGuard`] holds such a shutdown future,
+//! and spawns it on the executor when dropped.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use crate::BoxFuture;
+
+/// Cleanup futures of dropped guards, waiting to be spawned by the step of
+/// their executor.
+///
+/// Guards are often dropped while a task is being polled, when the ready
+/// queue of the executor is locked, so they cannot spawn directly.
+pub(crate) struct CleanupQueue {
+    /// The length of `futures`, so that a step does not lock it when empty.
+    len: AtomicUsize,
+    futures: SpinNoIrq<Vec<BoxFuture<()>>>,
+}
+
+impl CleanupQueue {
+    pub(crate) const fn new() -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            futures: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn push(&self, cleanup: BoxFuture<()>) {
+        let mut futures = self.futures.lock();
+        futures.push(cleanup);
+        self.len.store(futures.len(), Ordering::Release);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len.load(Ordering::Acquire) == 0
+    }
+
+    /// Takes the cleanup futures of the guards dropped so far.
+    pub(crate) fn take(&self) -> Vec<BoxFuture<()>> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut futures = self.futures.lock();
+        self.len.store(0, Ordering::Release);
+        core::mem::take(&mut *futures)
+    }
+}
+
+/// A guard that runs an async cleanup when dropped, created by [`defer`].
+///
+/// # Examples
+///
+/// ```ignore
+/// let socket = Arc::new(TcpSocket::new());
+/// let _guard = axasync::defer({
+///     let socket = socket.clone();
+///     async move {
+///         let _ = socket.shutdown();
+///     }
+/// });
+/// socket.recv_async(&mut buf).await?; // the socket is shut down even if cancelled here
+/// ```
+#[must_use = "the cleanup runs as soon as the guard is dropped"]
+pub struct CleanupGuard {
+    cleanup: Option<BoxFuture<()>>,
+}
+
+impl CleanupGuard {
+    /// Creates a guard that spawns `cleanup` when dropped.
+    pub fn new<F>(cleanup: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            cleanup: Some(Box::pin(cleanup)),
+        }
+    }
+
+    /// Disarms the guard, the cleanup never runs.
+    pub fn cancel(mut self) {
+        self.cleanup = None;
+    }
+
+    /// Runs the cleanup in place, instead of spawning it.
+    ///
+    /// Use it on the normal exit path, so that the cleanup is finished when
+    /// the task is.
+    pub async fn run(mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            cleanup.await;
+        }
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        let Some(cleanup) = self.cleanup.take() else {
+            return;
+        };
+        match crate::executor::poll_context() {
+            // SAFETY: the executor outlives the poll of the current axtask,
+            // which drops the guard.
+            Some(context) => unsafe { &*context.executor }.queue_cleanup(cleanup),
+            None => crate::executor().queue_cleanup(cleanup),
+        }
+    }
+}
+
+/// Returns a guard that spawns `cleanup` when dropped, on the executor of
+/// the task dropping it, or on the global executor outside of a task.
+pub fn defer<F>(cleanup: F) -> CleanupGuard
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    CleanupGuard::new(cleanup)
+}
@@ -0,0 +1,587 @@
+//! Framing of async byte streams.
+//!
+//! Protocols on top of a byte stream (e.g. MQTT, or a custom RPC) exchange
+//! frames, not bytes. [`Framed`] buffers the stream and splits it into
+//! frames with a codec, as a [`Stream`] of decoded frames and a [`Sink`] of
+//! frames to encode:
+//!
+//! ```ignore
+//! use axasync::codec::{Framed, LinesCodec};
+//! use axasync::futures_util::{SinkExt, StreamExt};
+//!
+//! let mut lines = Framed::new(stream, LinesCodec::new());
+//! while let Some(line) = lines.next().await {
+//!     lines.send(line?).await?;
+//! }
+//! ```
+//!
+//! With the `serde` feature, `PostcardCodec` frames messages serialized with
+//! [postcard](https://docs.rs/postcard), and `encode_to` and `decode_from`
+//! write and read a single message in the same format, so that all layers
+//! (e.g. RPC, telemetry) share one wire format.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use axerrno::{AxError, AxResult, ax_err};
+use futures_util::{Sink, Stream};
+
+use crate::io::{AsyncRead, AsyncWrite};
+
+#[cfg(feature = "serde")]
+mod postcard;
+
+#[cfg(feature = "serde")]
+pub use self::postcard::{PostcardCodec, decode_from, encode_to};
+
+/// Size of the chunks read from the stream.
+const READ_CHUNK_SIZE: usize = 512;
+
+/// [`Framed::poll_ready`](Sink::poll_ready) flushes once this many bytes of
+/// encoded frames are buffered.
+const WRITE_HIGH_WATERMARK: usize = 8 * 1024;
+
+/// Decodes frames from a buffer of bytes.
+pub trait Decoder {
+    /// The decoded frame.
+    type Item;
+
+    /// Decodes a frame from the front of `src`, and removes its bytes.
+    ///
+    /// Returns [`None`] if `src` does not hold a full frame yet.
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Self::Item>>;
+
+    /// Decodes a frame at the end of the stream, when no more bytes will
+    /// arrive.
+    ///
+    /// By default, it fails with [`AxError::UnexpectedEof`] if bytes remain
+    /// that are not a full frame.
+    fn decode_eof(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Self::Item>> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => ax_err!(UnexpectedEof, "incomplete frame at end of stream"),
+        }
+    }
+}
+
+/// Encodes frames into a buffer of bytes.
+pub trait Encoder<Item> {
+    /// Appends the encoding of `item` to `dst`.
+    fn encode(&mut self, item: Item, dst: &mut Vec<u8>) -> AxResult;
+}
+
+/// A codec of `\n` terminated UTF-8 lines.
+///
+/// A trailing `\r` is removed from decoded lines. At the end of the stream,
+/// the unterminated rest is a line.
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    max_length: usize,
+    /// Where to resume the search for `\n`.
+    next_index: usize,
+}
+
+impl LinesCodec {
+    /// Creates a codec of lines of any length.
+    pub const fn new() -> Self {
+        Self::with_max_length(usize::MAX)
+    }
+
+    /// Creates a codec that fails with [`AxError::InvalidData`] on lines
+    /// longer than `max_length` bytes.
+    pub const fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            next_index: 0,
+        }
+    }
+
+    /// Returns the maximum length of a line.
+    pub const fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    fn take_line(&mut self, src: &mut Vec<u8>, len: usize, consumed: usize) -> AxResult<String> {
+        self.next_index = 0;
+        let mut line: Vec<u8> = src.drain(..consumed).collect();
+        line.truncate(len);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|_| AxError::InvalidData)
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<String>> {
+        let start = self.next_index.min(src.len());
+        match src[start..].iter().position(|&b| b == b'\n') {
+            Some(pos) => {
+                let len = start + pos;
+                if len > self.max_length {
+                    self.next_index = 0;
+                    src.drain(..=len);
+                    return ax_err!(InvalidData, "line too long");
+                }
+                self.take_line(src, len, len + 1).map(Some)
+            }
+            None if src.len() > self.max_length => {
+                self.next_index = 0;
+                src.clear();
+                ax_err!(InvalidData, "line too long")
+            }
+            None => {
+                self.next_index = src.len();
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut Vec<u8>) -> AxResult<Option<String>> {
+        match self.decode(src)? {
+            Some(line) => Ok(Some(line)),
+            None if src.is_empty() => Ok(None),
+            None => {
+                let len = src.len();
+                self.take_line(src, len, len).map(Some)
+            }
+        }
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    fn encode(&mut self, line: T, dst: &mut Vec<u8>) -> AxResult {
+        let line = line.as_ref();
+        if line.len() > self.max_length {
+            return ax_err!(InvalidData, "line too long");
+        }
+        dst.extend_from_slice(line.as_bytes());
+        dst.push(b'\n');
+        Ok(())
+    }
+}
+
+/// A codec of frames prefixed with their length, as a big-endian `u32`.
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    max_frame_length: usize,
+}
+
+impl LengthDelimitedCodec {
+    /// The default maximum length of a frame, 8 MiB.
+    pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+    /// Bytes of the length prefix.
+    const HEADER_LEN: usize = 4;
+
+    /// Creates a codec of frames of at most 8 MiB.
+    pub const fn new() -> Self {
+        Self::with_max_frame_length(Self::DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    /// Creates a codec that fails with [`AxError::MessageTooLarge`] on frames
+    /// longer than `max_frame_length` bytes.
+    pub const fn with_max_frame_length(max_frame_length: usize) -> Self {
+        Self { max_frame_length }
+    }
+
+    /// Returns the maximum length of a frame.
+    pub const fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Vec<u8>>> {
+        let Some(header) = src.first_chunk::<{ Self::HEADER_LEN }>() else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(*header) as usize;
+        if len > self.max_frame_length {
+            // The stream cannot be resynchronized.
+            return ax_err!(MessageTooLarge, "frame too long");
+        }
+        if src.len() < Self::HEADER_LEN + len {
+            src.reserve(Self::HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+        let frame = src[Self::HEADER_LEN..Self::HEADER_LEN + len].to_vec();
+        src.drain(..Self::HEADER_LEN + len);
+        Ok(Some(frame))
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for LengthDelimitedCodec {
+    fn encode(&mut self, frame: T, dst: &mut Vec<u8>) -> AxResult {
+        let frame = frame.as_ref();
+        if frame.len() > self.max_frame_length || frame.len() > u32::MAX as usize {
+            return ax_err!(MessageTooLarge, "frame too long");
+        }
+        dst.reserve(Self::HEADER_LEN + frame.len());
+        dst.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        dst.extend_from_slice(frame);
+        Ok(())
+    }
+}
+
+/// A codec of frames encoded with Consistent Overhead Byte Stuffing, and
+/// terminated by a zero byte.
+///
+/// COBS suits unreliable links such as serial ports: a frame never contains
+/// a zero byte, so the decoder resynchronizes on the next zero after a
+/// corrupted frame. Empty encoded frames (consecutive zeros) are skipped.
+#[derive(Debug, Clone)]
+pub struct CobsCodec {
+    max_frame_length: usize,
+    /// Where to resume the search for the terminator.
+    next_index: usize,
+}
+
+impl CobsCodec {
+    /// Creates a codec of frames of any length.
+    pub const fn new() -> Self {
+        Self::with_max_frame_length(usize::MAX)
+    }
+
+    /// Creates a codec that fails with [`AxError::MessageTooLarge`] on frames
+    /// whose encoding is longer than `max_frame_length` bytes.
+    pub const fn with_max_frame_length(max_frame_length: usize) -> Self {
+        Self {
+            max_frame_length,
+            next_index: 0,
+        }
+    }
+
+    /// Returns the maximum length of an encoded frame.
+    pub const fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+}
+
+impl Default for CobsCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes a COBS encoded frame, without its terminator.
+fn cobs_decode(src: &[u8]) -> AxResult<Vec<u8>> {
+    let mut frame = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        let code = src[i] as usize;
+        let end = i + code;
+        if code == 0 || end > src.len() {
+            return ax_err!(InvalidData, "malformed COBS frame");
+        }
+        frame.extend_from_slice(&src[i + 1..end]);
+        i = end;
+        if code < 0xff && i < src.len() {
+            frame.push(0);
+        }
+    }
+    Ok(frame)
+}
+
+impl Decoder for CobsCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<Vec<u8>>> {
+        loop {
+            let start = self.next_index.min(src.len());
+            let Some(pos) = src[start..].iter().position(|&b| b == 0) else {
+                if src.len() > self.max_frame_length {
+                    self.next_index = 0;
+                    src.clear();
+                    return ax_err!(MessageTooLarge, "frame too long");
+                }
+                self.next_index = src.len();
+                return Ok(None);
+            };
+            let len = start + pos;
+            self.next_index = 0;
+            if len == 0 {
+                src.remove(0);
+                continue;
+            }
+            let ret = if len > self.max_frame_length {
+                ax_err!(MessageTooLarge, "frame too long")
+            } else {
+                cobs_decode(&src[..len]).map(Some)
+            };
+            src.drain(..=len);
+            return ret;
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for CobsCodec {
+    fn encode(&mut self, frame: T, dst: &mut Vec<u8>) -> AxResult {
+        let frame = frame.as_ref();
+        // One code byte per 254 bytes, and the terminator.
+        let encoded_len = frame.len() + frame.len() / 254 + 1;
+        if encoded_len > self.max_frame_length {
+            return ax_err!(MessageTooLarge, "frame too long");
+        }
+        dst.reserve(encoded_len + 1);
+        let mut code_index = dst.len();
+        let mut code = 1u8;
+        dst.push(0);
+        for &b in frame {
+            if b != 0 {
+                dst.push(b);
+                code += 1;
+            }
+            if b == 0 || code == 0xff {
+                dst[code_index] = code;
+                code_index = dst.len();
+                code = 1;
+                dst.push(0);
+            }
+        }
+        dst[code_index] = code;
+        dst.push(0);
+        Ok(())
+    }
+}
+
+/// An async byte stream framed by a codec.
+///
+/// It is a [`Stream`] of the frames decoded from the stream, which ends at
+/// the end of the stream, and a [`Sink`] of the frames to encode into it.
+/// Encoded frames are buffered until flushed, or until 8 KiB are buffered.
+pub struct Framed<T, C> {
+    io: T,
+    codec: C,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    /// Whether the end of the stream has been reached.
+    eof: bool,
+}
+
+impl<T, C> Framed<T, C> {
+    /// Frames `io` with `codec`.
+    pub fn new(io: T, codec: C) -> Self {
+        Self {
+            io,
+            codec,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Returns a reference to the stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Returns a mutable reference to the stream.
+    ///
+    /// Reading or writing it directly bypasses the buffered data.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Returns a reference to the codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns the bytes read but not decoded yet.
+    pub fn read_buffer(&self) -> &[u8] {
+        &self.read_buf
+    }
+
+    /// Returns the encoded frames not written yet.
+    pub fn write_buffer(&self) -> &[u8] {
+        &self.write_buf
+    }
+
+    /// Returns the stream. Buffered data is lost.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<T: AsyncWrite + Unpin, C> Framed<T, C> {
+    /// Writes all encoded frames to the stream.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<AxResult> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.io).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(ax_err!(WriteZero)),
+                Poll::Ready(Ok(n)) => {
+                    self.write_buf.drain(..n);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncRead + Unpin, C: Decoder + Unpin> Stream for Framed<T, C> {
+    type Item = AxResult<C::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.eof {
+                let ret = this.codec.decode_eof(&mut this.read_buf);
+                if ret.is_err() {
+                    this.read_buf.clear();
+                }
+                return Poll::Ready(ret.transpose());
+            }
+            if let Some(frame) = this.codec.decode(&mut this.read_buf)? {
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            match Pin::new(&mut this.io).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) => this.eof = true,
+                Poll::Ready(Ok(n)) => this.read_buf.extend_from_slice(&chunk[..n]),
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T, C, I> Sink<I> for Framed<T, C>
+where
+    T: AsyncWrite + Unpin,
+    C: Encoder<I> + Unpin,
+{
+    type Error = AxError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult> {
+        let this = self.get_mut();
+        if this.write_buf.len() >= WRITE_HIGH_WATERMARK {
+            this.poll_write_buf(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> AxResult {
+        let this = self.get_mut();
+        this.codec.encode(item, &mut this.write_buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult> {
+        let this = self.get_mut();
+        if let Err(e) = core::task::ready!(this.poll_write_buf(cx)) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<C>(codec: &mut C, frames: &[&[u8]]) -> Vec<Vec<u8>>
+    where
+        C: Decoder<Item = Vec<u8>> + for<'a> Encoder<&'a [u8]>,
+    {
+        let mut buf = Vec::new();
+        for frame in frames {
+            codec.encode(*frame, &mut buf).unwrap();
+        }
+        let mut decoded = Vec::new();
+        // Feed the bytes one by one, as a slow stream would.
+        let mut src = Vec::new();
+        for b in buf {
+            src.push(b);
+            while let Some(frame) = codec.decode(&mut src).unwrap() {
+                decoded.push(frame);
+            }
+        }
+        assert!(src.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn test_lines_codec() {
+        let mut codec = LinesCodec::with_max_length(8);
+        let mut src = b"hello\r\nworld\nrest".to_vec();
+        assert_eq!(codec.decode(&mut src).unwrap().as_deref(), Some("hello"));
+        assert_eq!(codec.decode(&mut src).unwrap().as_deref(), Some("world"));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut src).unwrap().as_deref(), Some("rest"));
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+
+        let mut src = b"much too long\nok\n".to_vec();
+        assert_eq!(codec.decode(&mut src), Err(AxError::InvalidData));
+        assert_eq!(codec.decode(&mut src).unwrap().as_deref(), Some("ok"));
+    }
+
+    #[test]
+    fn test_length_delimited_codec() {
+        let long = [7; 300];
+        let frames: [&[u8]; 3] = [b"", b"abc", &long];
+        assert_eq!(
+            roundtrip(&mut LengthDelimitedCodec::new(), &frames),
+            frames.map(<[u8]>::to_vec)
+        );
+
+        let mut src = 9u32.to_be_bytes().to_vec();
+        let mut codec = LengthDelimitedCodec::with_max_frame_length(8);
+        assert_eq!(codec.decode(&mut src), Err(AxError::MessageTooLarge));
+        assert_eq!(
+            codec.decode_eof(&mut [0, 0].to_vec()),
+            Err(AxError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_cobs_codec() {
+        let long: Vec<u8> = (0..600).map(|i| (i % 255) as u8 + 1).collect();
+        let frames: [&[u8]; 5] = [b"", b"\0", b"\x11\0\x22", b"\0\0x\0", &long];
+        assert_eq!(
+            roundtrip(&mut CobsCodec::new(), &frames),
+            frames.map(<[u8]>::to_vec)
+        );
+
+        let mut buf = Vec::new();
+        CobsCodec::new().encode(b"\x11\0\x22", &mut buf).unwrap();
+        assert_eq!(buf, b"\x02\x11\x02\x22\0");
+
+        // A corrupted frame is skipped up to the next terminator.
+        let mut src = b"\x05\x11\0\0\x02\x33\0".to_vec();
+        let mut codec = CobsCodec::new();
+        assert_eq!(codec.decode(&mut src), Err(AxError::InvalidData));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(b"\x33".to_vec()));
+    }
+}
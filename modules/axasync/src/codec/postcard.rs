@@ -0,0 +1,181 @@
+//! Serialization of messages with [postcard](::postcard).
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+use axerrno::{AxError, AxResult, ax_err};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::{Decoder, Encoder, LengthDelimitedCodec};
+use crate::io::{AsyncRead, AsyncWrite};
+
+fn to_bytes<M: Serialize + ?Sized>(msg: &M) -> AxResult<Vec<u8>> {
+    ::postcard::to_allocvec(msg).map_err(|e| {
+        warn!("failed to serialize message: {}", e);
+        AxError::InvalidData
+    })
+}
+
+fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> AxResult<T> {
+    ::postcard::from_bytes(bytes).map_err(|e| {
+        warn!("failed to deserialize message: {}", e);
+        AxError::InvalidData
+    })
+}
+
+/// A codec of messages serialized with postcard, and framed by
+/// [`LengthDelimitedCodec`].
+///
+/// It decodes messages of type `T`, and encodes messages of any serializable
+/// type. A message that fails to deserialize is skipped with
+/// [`AxError::InvalidData`], the following ones can still be decoded.
+pub struct PostcardCodec<T> {
+    frames: LengthDelimitedCodec,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> PostcardCodec<T> {
+    /// Creates a codec of messages of at most 8 MiB.
+    pub const fn new() -> Self {
+        Self::with_max_frame_length(LengthDelimitedCodec::DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    /// Creates a codec that fails with [`AxError::MessageTooLarge`] on
+    /// serialized messages longer than `max_frame_length` bytes.
+    pub const fn with_max_frame_length(max_frame_length: usize) -> Self {
+        Self {
+            frames: LengthDelimitedCodec::with_max_frame_length(max_frame_length),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for PostcardCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for PostcardCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            frames: self.frames.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for PostcardCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostcardCodec")
+            .field("max_frame_length", &self.frames.max_frame_length())
+            .finish()
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for PostcardCodec<T> {
+    type Item = T;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> AxResult<Option<T>> {
+        match self.frames.decode(src)? {
+            Some(frame) => from_bytes(&frame).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T, M: Serialize> Encoder<M> for PostcardCodec<T> {
+    fn encode(&mut self, msg: M, dst: &mut Vec<u8>) -> AxResult {
+        self.frames.encode(to_bytes(&msg)?, dst)
+    }
+}
+
+/// Writes `msg` to `writer`, serialized with postcard and prefixed with its
+/// length, and flushes it.
+///
+/// It is the format of [`PostcardCodec`], so the message can be read with
+/// [`decode_from`], or as a frame of a [`Framed`](super::Framed) stream.
+pub async fn encode_to<W, M>(writer: &mut W, msg: &M) -> AxResult
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    M: Serialize + ?Sized,
+{
+    let mut buf = Vec::new();
+    PostcardCodec::<()>::new().encode(msg, &mut buf)?;
+    let mut written = 0;
+    while written < buf.len() {
+        match poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, &buf[written..])).await? {
+            0 => return ax_err!(WriteZero),
+            n => written += n,
+        }
+    }
+    poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await
+}
+
+/// Reads a message written by [`encode_to`] from `reader`.
+///
+/// It reads exactly the bytes of the message, so that `reader` can be used
+/// for other data afterwards. If it is cancelled, the message is partially
+/// read, and the stream is out of sync: use [`PostcardCodec`] with a
+/// [`Framed`](super::Framed) stream for reads that may be cancelled.
+pub async fn decode_from<R, T>(reader: &mut R) -> AxResult<T>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    T: DeserializeOwned,
+{
+    let mut header = [0; 4];
+    read_exact(reader, &mut header).await?;
+    let len = u32::from_be_bytes(header) as usize;
+    if len > LengthDelimitedCodec::DEFAULT_MAX_FRAME_LENGTH {
+        return ax_err!(MessageTooLarge, "message too long");
+    }
+    let mut body = vec![0; len];
+    read_exact(reader, &mut body).await?;
+    from_bytes(&body)
+}
+
+async fn read_exact<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, buf: &mut [u8]) -> AxResult {
+    let mut read = 0;
+    while read < buf.len() {
+        match poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf[read..])).await? {
+            0 => return ax_err!(UnexpectedEof),
+            n => read += n,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::LocalStream;
+
+    #[test]
+    fn test_encode_decode() {
+        let (mut a, mut b) = LocalStream::pair();
+        futures_executor::block_on(async {
+            encode_to(&mut a, &(1u8, "one")).await.unwrap();
+            encode_to(&mut a, &[2u16; 3]).await.unwrap();
+            drop(a);
+            let one: (u8, alloc::string::String) = decode_from(&mut b).await.unwrap();
+            assert_eq!((one.0, one.1.as_str()), (1, "one"));
+            assert_eq!(decode_from::<_, [u16; 3]>(&mut b).await, Ok([2; 3]));
+            assert_eq!(
+                decode_from::<_, u8>(&mut b).await,
+                Err(AxError::UnexpectedEof)
+            );
+        });
+
+        let mut buf = Vec::new();
+        let mut codec = PostcardCodec::<bool>::new();
+        codec.encode(2u8, &mut buf).unwrap();
+        codec.encode(true, &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf), Err(AxError::InvalidData));
+        assert_eq!(codec.decode(&mut buf), Ok(Some(true)));
+    }
+}
@@ -0,0 +1,225 @@
+//! Combinators to await several futures in the same task.
+//!
+//! They take the futures by value and pin them in place, so there is no
+//! `pin_mut!` or boxing, and no dependency on the macros of `futures-util`:
+//!
+//! ```ignore
+//! match axasync::select2(stream.read(&mut buf), sleep(timeout)).await {
+//!     Either::Left(res) => handle(res?),
+//!     Either::Right(()) => return Err(AxError::TimedOut),
+//! }
+//! let (a, b, c) = axasync::join!(fetch(a), fetch(b), fetch(c));
+//! ```
+//!
+//! The futures are polled in order, so the first one wins when several are
+//! ready in the same poll.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// The output of [`select2`], of the future that completed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> {
+    /// The first future completed.
+    Left(A),
+    /// The second future completed.
+    Right(B),
+}
+
+/// The output of [`select3`], of the future that completed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either3<A, B, C> {
+    /// The first future completed.
+    First(A),
+    /// The second future completed.
+    Second(B),
+    /// The third future completed.
+    Third(C),
+}
+
+/// Pins the field `$field` of the pinned struct `$this`.
+macro_rules! project {
+    ($this:ident.$field:ident) => {
+        // SAFETY: the fields are structurally pinned, they are never moved
+        // out of the pinned struct.
+        unsafe { $this.as_mut().map_unchecked_mut(|this| &mut this.$field) }
+    };
+}
+
+/// The future returned by [`select2`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select2<A, B> {
+    a: A,
+    b: B,
+}
+
+/// Waits for the first of two futures to complete, the other one is
+/// dropped.
+pub fn select2<A: Future, B: Future>(a: A, b: B) -> Select2<A, B> {
+    Select2 { a, b }
+}
+
+impl<A: Future, B: Future> Future for Select2<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(a) = project!(self.a).poll(cx) {
+            return Poll::Ready(Either::Left(a));
+        }
+        if let Poll::Ready(b) = project!(self.b).poll(cx) {
+            return Poll::Ready(Either::Right(b));
+        }
+        Poll::Pending
+    }
+}
+
+/// The future returned by [`select3`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select3<A, B, C> {
+    a: A,
+    b: B,
+    c: C,
+}
+
+/// Waits for the first of three futures to complete, the others are
+/// dropped.
+pub fn select3<A: Future, B: Future, C: Future>(a: A, b: B, c: C) -> Select3<A, B, C> {
+    Select3 { a, b, c }
+}
+
+impl<A: Future, B: Future, C: Future> Future for Select3<A, B, C> {
+    type Output = Either3<A::Output, B::Output, C::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(a) = project!(self.a).poll(cx) {
+            return Poll::Ready(Either3::First(a));
+        }
+        if let Poll::Ready(b) = project!(self.b).poll(cx) {
+            return Poll::Ready(Either3::Second(b));
+        }
+        if let Poll::Ready(c) = project!(self.c).poll(cx) {
+            return Poll::Ready(Either3::Third(c));
+        }
+        Poll::Pending
+    }
+}
+
+/// A future of a join, or its output once it completed.
+enum MaybeDone<F: Future> {
+    Pending(F),
+    Done(F::Output),
+    Taken,
+}
+
+impl<F: Future> MaybeDone<F> {
+    /// Polls the future if it is not done yet, returns whether it is done.
+    fn poll_done(self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        // SAFETY: the future is never moved until it is dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Self::Pending(future) = this {
+            match unsafe { Pin::new_unchecked(future) }.poll(cx) {
+                Poll::Ready(output) => *this = Self::Done(output),
+                Poll::Pending => return false,
+            }
+        }
+        true
+    }
+
+    fn take(self: Pin<&mut Self>) -> F::Output {
+        // SAFETY: only the output is moved out, the future is dropped.
+        let this = unsafe { self.get_unchecked_mut() };
+        match core::mem::replace(this, Self::Taken) {
+            Self::Done(output) => output,
+            _ => panic!("join polled after completion"),
+        }
+    }
+}
+
+/// The future returned by [`join2`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Join2<A: Future, B: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+}
+
+/// Waits for both futures to complete, see [`join!`](crate::join).
+pub fn join2<A: Future, B: Future>(a: A, b: B) -> Join2<A, B> {
+    Join2 {
+        a: MaybeDone::Pending(a),
+        b: MaybeDone::Pending(b),
+    }
+}
+
+impl<A: Future, B: Future> Future for Join2<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Poll all of them, each registers its waker.
+        let done = [
+            project!(self.a).poll_done(cx),
+            project!(self.b).poll_done(cx),
+        ];
+        if done.contains(&false) {
+            return Poll::Pending;
+        }
+        let a = project!(self.a).take();
+        let b = project!(self.b).take();
+        Poll::Ready((a, b))
+    }
+}
+
+/// The future returned by [`join3`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Join3<A: Future, B: Future, C: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+    c: MaybeDone<C>,
+}
+
+/// Waits for the three futures to complete, see [`join!`](crate::join).
+pub fn join3<A: Future, B: Future, C: Future>(a: A, b: B, c: C) -> Join3<A, B, C> {
+    Join3 {
+        a: MaybeDone::Pending(a),
+        b: MaybeDone::Pending(b),
+        c: MaybeDone::Pending(c),
+    }
+}
+
+impl<A: Future, B: Future, C: Future> Future for Join3<A, B, C> {
+    type Output = (A::Output, B::Output, C::Output);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let done = [
+            project!(self.a).poll_done(cx),
+            project!(self.b).poll_done(cx),
+            project!(self.c).poll_done(cx),
+        ];
+        if done.contains(&false) {
+            return Poll::Pending;
+        }
+        let a = project!(self.a).take();
+        let b = project!(self.b).take();
+        let c = project!(self.c).take();
+        Poll::Ready((a, b, c))
+    }
+}
+
+/// Awaits two or three futures concurrently, in the current task, and
+/// returns the tuple of their outputs.
+///
+/// Use it in async contexts only. For more futures, or a dynamic number of
+/// them, spawn them or use `futures_util::future::join_all`.
+///
+/// ```ignore
+/// let (a, b) = axasync::join!(read_config(), connect(addr));
+/// ```
+#[macro_export]
+macro_rules! join {
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::join2($a, $b).await
+    };
+    ($a:expr, $b:expr, $c:expr $(,)?) => {
+        $crate::join3($a, $b, $c).await
+    };
+}
@@ -0,0 +1,130 @@
+//! Preemption of long polls.
+//!
+//! Tasks are scheduled cooperatively: a task runs until it awaits something
+//! that is not ready. A task whose awaits are always ready (e.g. a loop over
+//! a stream that always has data) would keep its CPU forever.
+//!
+//! The leaf futures of the runtime check with [`poll_proceed`] whether the
+//! task may proceed, and return `Pending` instead of proceeding otherwise, so
+//! the task is moved to the back of the run queue at its next await. A task
+//! is asked to yield:
+//!
+//! - once it has proceeded a number of times in one poll, its budget (see
+//!   [`set_poll_budget`]);
+//! - once enabled with [`set_preemption`], when the timer interrupt fires
+//!   while it is being polled (see [`on_timer_tick`]).
+
+use core::cell::Cell;
+use core::future::{Future, poll_fn};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::task::{Context, Poll};
+
+/// Whether the timer interrupt requests polls to yield.
+static PREEMPTION: AtomicBool = AtomicBool::new(false);
+
+/// Whether the poll running on the current CPU should yield.
+#[percpu::def_percpu]
+static SHOULD_YIELD: Cell<bool> = Cell::new(false);
+
+/// The default [poll budget](set_poll_budget).
+pub const DEFAULT_POLL_BUDGET: u32 = 128;
+
+/// The poll budget, `0` if unlimited.
+static POLL_BUDGET: AtomicU32 = AtomicU32::new(DEFAULT_POLL_BUDGET);
+
+/// Number of times the poll running on the current CPU has proceeded.
+#[percpu::def_percpu]
+static PROCEEDED: Cell<u32> = Cell::new(0);
+
+/// Sets the number of times a task may proceed in a single poll before it
+/// has to yield, or lifts the limit with `None`.
+pub fn set_poll_budget(budget: Option<u32>) {
+    let budget = budget.map_or(0, |b| b.max(1));
+    POLL_BUDGET.store(budget, Ordering::Relaxed);
+}
+
+/// Enables or disables time-slice preemption of polls, disabled by default.
+pub fn set_preemption(enabled: bool) {
+    PREEMPTION.store(enabled, Ordering::Relaxed);
+}
+
+/// Requests the poll running on the current CPU to yield, if preemption is
+/// enabled.
+///
+/// It is called by the timer interrupt handler, so a poll that outlives a
+/// timer tick yields at its next await.
+pub fn on_timer_tick() {
+    if PREEMPTION.load(Ordering::Relaxed) {
+        // SAFETY: The flag is only accessed from the current CPU.
+        unsafe { SHOULD_YIELD.current_ref_raw() }.set(true);
+    }
+}
+
+/// Starts a new time slice on the current CPU, with a full budget, before a
+/// poll.
+pub(crate) fn reset() {
+    // SAFETY: The flags are only accessed from the current CPU.
+    unsafe {
+        SHOULD_YIELD.current_ref_raw().set(false);
+        PROCEEDED.current_ref_raw().set(0);
+    }
+}
+
+/// Checks whether the current task may proceed.
+///
+/// If the time slice or the budget of the task is over, it wakes the task
+/// and returns `Pending`, and the caller must return `Pending` as well,
+/// without doing anything. The request is then taken and the budget is
+/// refilled, so the next call proceeds.
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    // SAFETY: The flags are only accessed from the current CPU.
+    let (should_yield, proceeded) =
+        unsafe { (SHOULD_YIELD.current_ref_raw(), PROCEEDED.current_ref_raw()) };
+    let budget = POLL_BUDGET.load(Ordering::Relaxed);
+    let exhausted = budget != 0 && proceeded.get() >= budget;
+    if should_yield.replace(false) || exhausted {
+        proceeded.set(0);
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    } else {
+        proceeded.set(proceeded.get().saturating_add(1));
+        Poll::Ready(())
+    }
+}
+
+/// Yields if the time slice or the budget of the current task is over, for
+/// long computations that never await anything else.
+pub async fn consume_budget() {
+    poll_fn(poll_proceed).await
+}
+
+/// Yields to the other tasks once, the async counterpart of
+/// [`axtask::yield_now`].
+///
+/// The task is moved to the back of the run queue of its priority, so that
+/// a long computation can let the other tasks run whether its time slice is
+/// over or not, see [`consume_budget`] otherwise.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by [`yield_now`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        // Woken while running, the executor queues it again.
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
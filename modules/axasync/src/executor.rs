@@ -1,17 +1,82 @@
 //! Task executor for async tasks.
 
 use alloc::boxed::Box;
-use alloc::collections::VecDeque;
-use core::cell::RefCell;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell, UnsafeCell};
 use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
 use core::pin::Pin;
-use core::task::{Context, Poll, Waker};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::time::TimeValue;
+use axsync::lockstat::LockStat;
+use kspin::SpinNoIrq;
+
+#[cfg(any(feature = "irq", feature = "multitask"))]
+use crate::cleanup::CleanupQueue;
+use crate::park::Parker;
+use crate::signal::{self, TaskId};
+use crate::task_local;
+use crate::tasks::{self, TaskExit, TaskState};
 use lazyinit::LazyInit;
 use spin::Mutex;
 
 /// Type alias for a pinned and boxed future.
 pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
 
+static READY_TASKS_STAT: LockStat = LockStat::new("axasync::executor.ready_tasks");
+
+/// The default [slow-poll threshold](set_slow_poll_threshold).
+pub const DEFAULT_SLOW_POLL_THRESHOLD: Duration = Duration::from_millis(10);
+
+/// The slow-poll threshold in nanoseconds, `0` if disabled.
+static SLOW_POLL_THRESHOLD_NANOS: AtomicU64 =
+    AtomicU64::new(DEFAULT_SLOW_POLL_THRESHOLD.as_nanos() as u64);
+
+/// Sets the duration over which a single `poll()` of a task is reported, or
+/// disables the check with `None`.
+///
+/// A task must not block in `poll()` (e.g. on a synchronous socket call), as
+/// it stalls all the other tasks of its executor. A slow poll is logged as a
+/// warning with the ID of the task, and counted in
+/// [`ExecutorStats::slow_polls`]. The time stolen by the hypervisor is not
+/// counted.
+pub fn set_slow_poll_threshold(threshold: Option<Duration>) {
+    let nanos = threshold.map_or(0, |t| (t.as_nanos() as u64).max(1));
+    SLOW_POLL_THRESHOLD_NANOS.store(nanos, Ordering::Relaxed);
+}
+
+/// The default [grace period](set_shutdown_grace_period) of
+/// [`shutdown`](crate::shutdown).
+pub const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// The shutdown grace period in nanoseconds.
+static SHUTDOWN_GRACE_PERIOD_NANOS: AtomicU64 =
+    AtomicU64::new(DEFAULT_SHUTDOWN_GRACE_PERIOD.as_nanos() as u64);
+
+/// Sets how long [`shutdown`](crate::shutdown) runs the ready tasks of the
+/// global executor before it aborts them, see [`Executor::shutdown`].
+pub fn set_shutdown_grace_period(grace: Duration) {
+    SHUTDOWN_GRACE_PERIOD_NANOS.store(grace.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Shuts the global executor down, with the configured grace period.
+pub(crate) fn shutdown_global() {
+    if GLOBAL_EXECUTOR.is_inited() {
+        let grace = SHUTDOWN_GRACE_PERIOD_NANOS.load(Ordering::Relaxed);
+        GLOBAL_EXECUTOR.shutdown(Duration::from_nanos(grace));
+    }
+}
+
 // Global executor singleton
 static GLOBAL_EXECUTOR: LazyInit<Executor> = LazyInit::new();
 
@@ -19,6 +84,11 @@ static GLOBAL_EXECUTOR: LazyInit<Executor> = LazyInit::new();
 #[percpu::def_percpu]
 static CPU_LOCAL_EXECUTOR: RefCell<Option<Executor>> = RefCell::new(None);
 
+/// The [`PollContext`] of the poll in progress on the current CPU, where no
+/// axtask records it: without `multitask`, or before the axtasks are set up.
+#[percpu::def_percpu]
+static CPU_POLL_CONTEXT: Cell<usize> = Cell::new(0);
+
 /// Helper function to get the global executor, initializing it if needed.
 pub fn executor() -> &'static Executor {
     if !GLOBAL_EXECUTOR.is_inited() {
@@ -38,11 +108,81 @@ where
     executor().spawn(future)
 }
 
-/// Initialize the global executor runtime.
+/// Spawns a new asynchronous task named `name` on the global executor.
+///
+/// The name is shown with the ID of the task in logs (see [`TaskId`]), in
+/// [`task_list`](crate::task_list) and in [`dump_tasks`](crate::dump_tasks).
+pub fn spawn_named<F>(name: impl Into<String>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor().spawn_named(name, future)
+}
+
+/// Spawns a new asynchronous task on the global executor, or fails if its
+/// run queue is full or it has too many tasks, see [`Executor::try_spawn`].
+pub fn try_spawn<F>(future: F) -> AxResult<JoinHandle<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor().try_spawn(future)
+}
+
+/// Spawns a new asynchronous task on the global executor, waiting for room
+/// if it is full, see [`Executor::spawn_async`].
+pub async fn spawn_async<F>(future: F) -> AxResult<JoinHandle<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor().spawn_async(future).await
+}
+
+/// Spawns a new asynchronous task with `priority` on the global executor,
+/// see [`Executor::spawn_with_priority`].
+pub fn spawn_with_priority<F>(future: F, priority: Priority) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor().spawn_with_priority(future, priority)
+}
+
+/// Spawns a new asynchronous task that should complete by `deadline` on the
+/// global executor, see [`Executor::spawn_with_deadline`].
+pub fn spawn_with_deadline<F>(future: F, deadline: TimeValue) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor().spawn_with_deadline(future, deadline)
+}
+
+/// Spawns the future returned by `make(arg)` on the global executor, from an
+/// interrupt handler, see [`Executor::spawn_from_irq`].
+///
+/// Fails with [`BadState`](AxError::BadState) if the global executor is not
+/// initialized, it is not created from an interrupt handler.
+pub fn spawn_from_irq<F>(make: fn(usize) -> F, arg: usize) -> AxResult
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    GLOBAL_EXECUTOR
+        .get()
+        .ok_or(AxError::BadState)?
+        .spawn_from_irq(make, arg)
+}
+
+/// Initialize the global executor runtime, or restart it after
+/// [`shutdown`](crate::shutdown).
 pub fn init() {
     // Initialize the global executor if it hasn't been initialized yet
     if !GLOBAL_EXECUTOR.is_inited() {
         GLOBAL_EXECUTOR.init_once(Executor::new());
+    } else {
+        GLOBAL_EXECUTOR.restart();
     }
 }
 
@@ -51,43 +191,77 @@ pub fn run() {
     executor().run();
 }
 
+/// Polls `future` while running the tasks of the global executor, until it
+/// completes, see [`Executor::run_until`].
+pub fn run_until<F: Future>(future: F) -> F::Output {
+    executor().run_until(future)
+}
+
 /// Initialize the per-CPU local executor.
 fn ensure_local_executor() -> &'static RefCell<Option<Executor>> {
     let cell = unsafe { CPU_LOCAL_EXECUTOR.current_ptr() };
     let cell_ref = unsafe { &*cell };
     if cell_ref.borrow().is_none() {
-        *cell_ref.borrow_mut() = Some(Executor::new());
+        let mut executor = Executor::new();
+        executor.local_owner = Some(LocalOwner::current());
+        *cell_ref.borrow_mut() = Some(executor);
     }
     cell_ref
 }
 
 /// Spawns a future on the current CPU's local executor.
+///
+/// The future need not be [`Send`], e.g. it may hold a `RefCell` or a
+/// pointer to the registers of a device: it is only ever polled, and
+/// dropped, by [`run_local`] in the axtask the local executor of the CPU
+/// belongs to, the first one to use it. Its output is sent to the
+/// [`JoinHandle`], which may be awaited anywhere, so it must be [`Send`].
+///
+/// # Panics
+///
+/// Panics if the spawn fails, like [`Executor::spawn`], or if the local
+/// executor of the CPU belongs to another axtask.
 pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
 where
-    F: Future + Send + 'static,
+    F: Future + 'static,
     F::Output: Send + 'static,
 {
     let cell = ensure_local_executor();
-    // Initialize the executor if needed
-    if cell.borrow().is_none() {
-        *cell.borrow_mut() = Some(Executor::new());
-    }
-
-    // Unwrap is safe because we just ensured the executor exists
     let executor = cell.borrow();
+    // Unwrap is safe because we just ensured the executor exists
     let executor = executor.as_ref().unwrap();
-
-    // We need to use raw pointers since we can't return a reference from the RefCell borrow
-    let executor_ptr = executor as *const Executor;
-
-    // SAFETY: We ensure the pointer is valid during the scope of this call
-    unsafe { (*executor_ptr).spawn(future) }
+    let owner = executor.local_owner.unwrap();
+    assert!(
+        owner.is_current(),
+        "spawn_local: the local executor belongs to another axtask"
+    );
+    let future = LocalFuture {
+        future: ManuallyDrop::new(future),
+        owner,
+    };
+    executor
+        .spawn_bounded(future, None, Priority::Normal, None)
+        .expect("spawn failed")
 }
 
 /// Run the current CPU's local executor until completion.
+///
+/// With `multitask`, the calling task is pinned to the current CPU meanwhile,
+/// so that it keeps running the local executor of the CPU.
+///
+/// # Panics
+///
+/// Panics if the local executor of the CPU belongs to another axtask, see
+/// [`spawn_local`].
 pub fn run_local() {
+    #[cfg(feature = "multitask")]
+    let _pin = PinToCpu::new(axhal::cpu::this_cpu_id());
     let cell = ensure_local_executor();
     if let Some(executor) = cell.borrow().as_ref() {
+        assert!(
+            executor.local_owner.unwrap().is_current(),
+            "run_local: the local executor belongs to another axtask"
+        );
         // We need to use raw pointers since we can't modify through a shared reference
         let executor_ptr = executor as *const Executor;
         // SAFETY: We ensure the pointer is valid during the scope of this call
@@ -95,67 +269,1298 @@ pub fn run_local() {
     }
 }
 
+/// Pins the current task to `cpu`, where it migrates if needed, until
+/// dropped.
+///
+/// A task already pinned there, e.g. in [`run_local`], keeps its affinity.
+#[cfg(feature = "multitask")]
+pub(crate) struct PinToCpu(Option<axtask::AxCpuMask>);
+
+#[cfg(feature = "multitask")]
+impl PinToCpu {
+    pub(crate) fn new(cpu: usize) -> Self {
+        let cpumask = axtask::current().cpumask();
+        let pinned = axtask::AxCpuMask::one_shot(cpu);
+        if cpumask == pinned {
+            return Self(None);
+        }
+        // Migrated back if preempted in between.
+        axtask::set_current_affinity(pinned);
+        Self(Some(cpumask))
+    }
+}
+
+#[cfg(feature = "multitask")]
+impl Drop for PinToCpu {
+    fn drop(&mut self) {
+        if let Some(cpumask) = self.0 {
+            axtask::set_current_affinity(cpumask);
+        }
+    }
+}
+
+/// The thread a local executor belongs to: its axtask, on its CPU. Without
+/// `multitask`, or before the axtasks are set up, a CPU runs a single thread.
+///
+/// Two axtasks on the same CPU are two threads: one may preempt the other in
+/// the middle of a poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LocalOwner {
+    cpu: usize,
+    #[cfg(feature = "multitask")]
+    axtask: Option<u64>,
+}
+
+impl LocalOwner {
+    fn current() -> Self {
+        Self {
+            cpu: axhal::cpu::this_cpu_id(),
+            #[cfg(feature = "multitask")]
+            axtask: axtask::current_may_uninit().map(|curr| curr.id().as_u64()),
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        *self == Self::current()
+    }
+}
+
+/// A future of a local executor, which may not be [`Send`]. It is only
+/// polled, and dropped, by the thread its executor belongs to.
+struct LocalFuture<F> {
+    future: ManuallyDrop<F>,
+    owner: LocalOwner,
+}
+
+// SAFETY: The future is never used by another thread: it is only polled by
+// the owner of its executor, an abort from another thread is left to the
+// executor, and it is leaked rather than dropped elsewhere.
+unsafe impl<F> Send for LocalFuture<F> {}
+
+impl<F: Future> Future for LocalFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(
+            self.owner.is_current(),
+            "local task of {:?} polled by {:?}",
+            self.owner,
+            LocalOwner::current()
+        );
+        // SAFETY: The future is pinned with `self`, and dropped in place.
+        unsafe { self.map_unchecked_mut(|this| &mut *this.future) }.poll(cx)
+    }
+}
+
+impl<F> Drop for LocalFuture<F> {
+    fn drop(&mut self) {
+        if self.owner.is_current() {
+            // SAFETY: It is not used again.
+            unsafe { ManuallyDrop::drop(&mut self.future) };
+        } else {
+            warn!(
+                "local task of {:?} dropped by {:?}, leaked",
+                self.owner,
+                LocalOwner::current()
+            );
+        }
+    }
+}
+
+/// A set of tasks that run on one CPU, together with a main future, inside
+/// the calling axtask.
+///
+/// Like [`spawn_local`], its tasks need not be [`Send`], but they run only
+/// while [`run_until`](Self::run_until) drives the set, e.g. to batch the
+/// per-CPU futures of a driver and run them deterministically, in the order
+/// they are woken:
+///
+/// ```ignore
+/// let set = LocalSet::new();
+/// for queue in queues {
+///     set.spawn_local(queue.serve());
+/// }
+/// set.run_until(shutdown_requested()).await;
+/// ```
+///
+/// The set belongs to the axtask, and the CPU, where it is created. The tasks
+/// still pending when it is dropped are aborted.
+pub struct LocalSet {
+    /// Boxed, the tasks point to it.
+    executor: Box<Executor>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LocalSet {
+    /// Creates an empty set, owned by the current axtask on the current CPU.
+    pub fn new() -> Self {
+        let mut executor = Box::new(Executor::new());
+        executor.local_owner = Some(LocalOwner::current());
+        Self {
+            executor,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Spawns a future in the set. It is first polled by the next
+    /// [`run_until`](Self::run_until).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spawn fails, like [`Executor::spawn`].
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: Send + 'static,
+    {
+        let future = LocalFuture {
+            future: ManuallyDrop::new(future),
+            owner: self.executor.local_owner.unwrap(),
+        };
+        self.executor
+            .spawn_bounded(future, None, Priority::Normal, None)
+            .expect("spawn failed")
+    }
+
+    /// Polls `future` and the tasks of the set as they are woken, until
+    /// `future` completes, and returns its output. The tasks left pending
+    /// run again at the next call.
+    ///
+    /// It blocks the calling axtask, which is pinned to the CPU of the set
+    /// meanwhile with `multitask`.
+    pub fn run_until<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "multitask")]
+        let _pin = PinToCpu::new(self.cpu());
+        self.executor.run_until(future)
+    }
+
+    /// Returns the number of tasks of the set that have not completed.
+    pub fn len(&self) -> usize {
+        self.executor.live_tasks.load(Ordering::Acquire)
+    }
+
+    /// Returns whether all the tasks of the set have completed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[cfg(feature = "multitask")]
+    fn cpu(&self) -> usize {
+        self.executor.local_owner.unwrap().cpu
+    }
+}
+
+impl Default for LocalSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LocalSet {
+    fn drop(&mut self) {
+        // The futures are dropped by their axtask, on their CPU.
+        #[cfg(feature = "multitask")]
+        let _pin = PinToCpu::new(self.cpu());
+        self.executor.abort_all();
+    }
+}
+
+/// Runtime statistics of an [`Executor`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutorStats {
+    /// Number of times a task has been polled.
+    pub polls: u64,
+    /// Number of polls that took longer than the
+    /// [slow-poll threshold](set_slow_poll_threshold).
+    pub slow_polls: u64,
+    /// The largest number of tasks that have been in the run queue at once.
+    pub queue_high_watermark: usize,
+    /// Number of spawns rejected because the run queue was full, or the
+    /// executor had too many tasks.
+    pub rejected_spawns: u64,
+    /// Number of wakes of a task that was already queued or being polled,
+    /// which did not queue it again.
+    pub coalesced_wakes: u64,
+    /// Number of tasks that completed after their deadline, see
+    /// [`Executor::spawn_with_deadline`].
+    pub missed_deadlines: u64,
+    /// Time in nanoseconds the current CPU was stolen by the hypervisor.
+    ///
+    /// A large value explains latency spikes that are not caused by the
+    /// tasks themselves. It is always `0` on bare metal.
+    pub steal_time_nanos: u64,
+}
+
+/// What an [`Executor`] does when a task is spawned while its run queue is
+/// full, see [`Executor::set_capacity`].
+///
+/// A woken task is always queued, so that it is not lost: the capacity only
+/// bounds the spawns. A task is in the queue at most once, however many times
+/// it is woken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The spawn fails with [`WouldBlock`](axerrno::AxError::WouldBlock).
+    Reject,
+    /// The spawn waits until a task leaves the queue.
+    ///
+    /// Only producers running outside the executor may wait, a task of the
+    /// executor that waits for room in its own queue never gets it.
+    Block,
+}
+
+/// The priority of a task, see [`Executor::spawn_with_priority`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Latency-sensitive work, e.g. the processing of received packets.
+    High = 0,
+    /// The priority of the tasks spawned without one.
+    #[default]
+    Normal = 1,
+    /// Bulk background work.
+    Low = 2,
+}
+
+impl Priority {
+    const COUNT: usize = 3;
+}
+
+/// The order in which an [`Executor`] polls its ready tasks, see
+/// [`Executor::set_schedule`].
+///
+/// The pinned orders make the interleavings of the tasks reproducible, so
+/// that a test can replay the one that triggers a bug, or try many. The time
+/// slices of [`coop`](crate::coop) should be left disabled meanwhile.
+#[cfg(feature = "deterministic")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Schedule {
+    /// By [`Priority`], then in FIFO order, the default.
+    #[default]
+    Priority,
+    /// Strictly in the order the tasks were queued, whatever their priority.
+    Fifo,
+    /// A ready task drawn at random, from a counter-based generator started
+    /// at the seed: a seed always gives the same order.
+    Seeded(u64),
+}
+
+/// The most polls recorded by [`Executor::take_poll_trace`], the later ones
+/// are dropped.
+#[cfg(feature = "deterministic")]
+pub const MAX_POLL_TRACE: usize = 4096;
+
+/// The ready tasks of an [`Executor`], one FIFO queue per [`Priority`], and
+/// the tasks with a deadline ahead of them.
+struct RunQueue {
+    tasks: [VecDeque<Arc<Task>>; Priority::COUNT],
+    /// The tasks with a deadline, by deadline then in FIFO order.
+    deadlines: BTreeMap<(TimeValue, u64), Arc<Task>>,
+    /// The order of the pushes, that breaks the ties of the deadlines.
+    pushes: u64,
+    len: usize,
+    /// The number of tasks over which spawns overflow.
+    capacity: usize,
+    /// The number of live tasks of the executor over which spawns overflow.
+    max_tasks: usize,
+    policy: OverflowPolicy,
+    high_watermark: usize,
+    #[cfg(feature = "deterministic")]
+    schedule: Schedule,
+    /// The counter of the generator of [`Schedule::Seeded`].
+    #[cfg(feature = "deterministic")]
+    draws: u64,
+}
+
+impl RunQueue {
+    /// Returns whether a task can be spawned while the executor has
+    /// `live_tasks` tasks.
+    fn has_room(&self, live_tasks: usize) -> bool {
+        self.len < self.capacity && live_tasks < self.max_tasks
+    }
+
+    fn push(&mut self, task: Arc<Task>) {
+        #[cfg(feature = "deterministic")]
+        if self.schedule != Schedule::Priority {
+            // A single queue, in the order of the pushes.
+            self.tasks[0].push_back(task);
+            self.len += 1;
+            self.high_watermark = self.high_watermark.max(self.len);
+            return;
+        }
+        if let Some(deadline) = task.deadline {
+            self.pushes += 1;
+            self.deadlines.insert((deadline, self.pushes), task);
+        } else {
+            self.tasks[task.priority as usize].push_back(task);
+        }
+        self.len += 1;
+        self.high_watermark = self.high_watermark.max(self.len);
+    }
+
+    /// Takes the task with the earliest deadline, or the first task of the
+    /// highest priority.
+    fn pop(&mut self) -> Option<Arc<Task>> {
+        #[cfg(feature = "deterministic")]
+        if let Schedule::Seeded(seed) = self.schedule {
+            let queue = self.tasks.iter_mut().find(|queue| !queue.is_empty())?;
+            self.draws = self.draws.wrapping_add(1);
+            let index = splitmix64(seed.wrapping_add(self.draws)) % queue.len() as u64;
+            let task = queue.remove(index as usize)?;
+            self.len -= 1;
+            return Some(task);
+        }
+        let task = match self.deadlines.pop_first() {
+            Some((_, task)) => task,
+            None => self.tasks.iter_mut().find_map(|queue| queue.pop_front())?,
+        };
+        self.len -= 1;
+        Some(task)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The tasks woken from interrupt handlers, until [`Executor::step`] moves
+/// them to the run queue.
+///
+/// A lock-free stack linked through [`Task::inject_next`]: an interrupt
+/// handler neither spins on the run queue of the executor, which another CPU
+/// may hold across a whole pop, nor allocates. A task is pushed at most once
+/// until it is taken, as it is only queued by the wake that sets its
+/// [`SCHEDULED`] flag.
+///
+/// The handler still unparks the executor, which notifies its `WaitQueue`
+/// and unblocks the parked workers: it takes their `SpinNoIrq` locks, and
+/// that of the axtask run queues. These are only held with the IRQs off, for
+/// a few instructions, and never across a poll.
+struct InjectQueue {
+    head: AtomicPtr<Task>,
+}
+
+impl InjectQueue {
+    const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn push(&self, task: Arc<Task>) {
+        let task = Arc::into_raw(task).cast_mut();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the task is not in the stack, nobody else links it.
+            unsafe { (*task).inject_next.store(head, Ordering::Relaxed) };
+            match self
+                .head
+                .compare_exchange_weak(head, task, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes all the tasks, in the order they were pushed.
+    fn take_all(&self) -> Vec<Arc<Task>> {
+        let mut next = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut tasks = Vec::new();
+        while !next.is_null() {
+            // SAFETY: the pointer comes from `Arc::into_raw` in `push`, and
+            // the swap made the stack ours.
+            let task = unsafe { Arc::from_raw(next) };
+            next = task.inject_next.swap(ptr::null_mut(), Ordering::Relaxed);
+            tasks.push(task);
+        }
+        tasks.reverse();
+        tasks
+    }
+
+    fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl Drop for InjectQueue {
+    fn drop(&mut self) {
+        drop(self.take_all());
+    }
+}
+
+/// Number of spawns from interrupt handlers that an executor holds until it
+/// takes them, see [`Executor::spawn_from_irq`].
+pub const IRQ_SPAWN_CAPACITY: usize = 64;
+
+/// A spawn from an interrupt handler: the future is only created, and
+/// allocated, by the executor.
+#[derive(Clone, Copy)]
+struct IrqSpawn {
+    /// Spawns the future of `make(arg)`, with the type of `make` erased.
+    spawn: fn(&Executor, *const (), usize),
+    make: *const (),
+    arg: usize,
+}
+
+struct IrqSpawnSlot {
+    /// The position of the next push to the slot, or that after it plus one
+    /// once the spawn is in.
+    seq: AtomicUsize,
+    spawn: UnsafeCell<Option<IrqSpawn>>,
+}
+
+/// The spawns from interrupt handlers, moved to the run queue by
+/// [`Executor::step`].
+///
+/// A bounded lock-free queue, with a sequence number per slot, allocated
+/// with the executor: an interrupt handler neither spins on the run queue
+/// nor allocates.
+struct IrqSpawnQueue {
+    slots: [IrqSpawnSlot; IRQ_SPAWN_CAPACITY],
+    push_pos: AtomicUsize,
+    pop_pos: AtomicUsize,
+}
+
+// SAFETY: a slot is only accessed by the one that claimed its position.
+unsafe impl Sync for IrqSpawnQueue {}
+unsafe impl Send for IrqSpawnQueue {}
+
+impl IrqSpawnQueue {
+    fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|i| IrqSpawnSlot {
+                seq: AtomicUsize::new(i),
+                spawn: UnsafeCell::new(None),
+            }),
+            push_pos: AtomicUsize::new(0),
+            pop_pos: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes `spawn`, or returns `false` if the queue is full.
+    fn push(&self, spawn: IrqSpawn) -> bool {
+        let mut pos = self.push_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % IRQ_SPAWN_CAPACITY];
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == pos {
+                match self.push_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the position is ours until the store below.
+                        unsafe { *slot.spawn.get() = Some(spawn) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if (seq.wrapping_sub(pos) as isize) < 0 {
+                // Not popped since the last round.
+                return false;
+            } else {
+                pos = self.push_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<IrqSpawn> {
+        let mut pos = self.pop_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % IRQ_SPAWN_CAPACITY];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let next = pos.wrapping_add(1);
+            if seq == next {
+                match self.pop_pos.compare_exchange_weak(
+                    pos,
+                    next,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the position is ours until the store below.
+                        let spawn = unsafe { (*slot.spawn.get()).take() };
+                        slot.seq
+                            .store(pos.wrapping_add(IRQ_SPAWN_CAPACITY), Ordering::Release);
+                        return spawn;
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if (seq.wrapping_sub(next) as isize) < 0 {
+                // Not pushed yet.
+                return None;
+            } else {
+                pos = self.pop_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pop_pos.load(Ordering::Acquire) == self.push_pos.load(Ordering::Acquire)
+    }
+}
+
+/// Spawns the future of `make(arg)` for [`Executor::spawn_from_irq`].
+fn spawn_irq_work<F>(executor: &Executor, make: *const (), arg: usize)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    // SAFETY: `make` was erased from this type by `spawn_from_irq`.
+    let make: fn(usize) -> F = unsafe { core::mem::transmute(make) };
+    // Detached. Like the cleanups, it runs even if the queue is full, the
+    // spawns from interrupt handlers are bounded already.
+    let (task, _) = Task::new(make(arg), executor, None, Priority::Normal, None);
+    READY_TASKS_STAT.lock(&executor.ready_tasks).push(task);
+}
+
+/// Returns whether a wake comes from an interrupt handler, or from code that
+/// disabled the interrupts and must not spin on the run queue.
+fn in_irq_context() -> bool {
+    #[cfg(feature = "irq")]
+    {
+        !axhal::arch::irqs_enabled() || axhal::irq::nesting_depth() > 0
+    }
+    #[cfg(not(feature = "irq"))]
+    false
+}
+
+/// Mixes `x` into a pseudo-random number, the output function of SplitMix64.
+#[cfg(feature = "deterministic")]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 /// An executor that can run futures to completion.
+///
+/// # Scheduling
+///
+/// The ready tasks are polled by [`Priority`], and in FIFO order within a
+/// priority: a task is only polled when no task of a higher priority is
+/// ready, so tasks that are always ready starve the lower priorities. After
+/// a task returns [`Poll::Pending`]:
+///
+/// - If it was woken during the poll, e.g. yielding, it is queued again at
+///   the back, behind all the tasks of its priority that were ready.
+/// - If a clone of its waker was ever kept (e.g. a timer or a socket
+///   registered it), it is parked until the waker is woken.
+/// - Otherwise it cannot be woken, so it is also queued again at the back,
+///   and polled again in its turn.
+///   Such a task makes the executor busy-poll, but it is never starved by
+///   the tasks of its priority.
+///
+/// A task woken while it is queued or being polled is queued only once.
+///
+/// The ready tasks spawned with a deadline, see
+/// [`spawn_with_deadline`](Self::spawn_with_deadline), are polled before the
+/// others, earliest deadline first (EDF), whatever the priorities.
+///
+/// With the `deterministic` feature, the order can be pinned for tests, see
+/// [`set_schedule`](Self::set_schedule).
 pub struct Executor {
-    // Task queue
-    ready_tasks: Mutex<VecDeque<Task>>,
+    ready_tasks: SpinNoIrq<RunQueue>,
+    /// The tasks woken by interrupt handlers, moved to `ready_tasks` by
+    /// [`step`](Self::step).
+    injected: InjectQueue,
+    /// The spawns from interrupt handlers, see
+    /// [`spawn_from_irq`](Self::spawn_from_irq).
+    irq_spawns: IrqSpawnQueue,
+    polls: AtomicU64,
+    slow_polls: AtomicU64,
+    rejected_spawns: AtomicU64,
+    coalesced_wakes: AtomicU64,
+    missed_deadlines: AtomicU64,
+    /// Number of tasks spawned and not completed, queued or parked.
+    live_tasks: AtomicUsize,
+    /// The tasks spawned and not completed, to abort them at shutdown. It
+    /// keeps the parked tasks alive, also those without a waker.
+    all_tasks: SpinNoIrq<BTreeMap<TaskId, Arc<Task>>>,
+    /// Whether the executor is shut down, spawns fail meanwhile.
+    shut_down: AtomicBool,
+    /// The wakers of the [`spawn_async`](Self::spawn_async) waiting for room.
+    spawn_waiters: SpinNoIrq<Vec<Waker>>,
+    /// The cleanups of the guards dropped by its tasks, see [`CleanupGuard`].
+    ///
+    /// [`CleanupGuard`]: crate::CleanupGuard
+    cleanups: CleanupQueue,
+    #[cfg(any(feature = "irq", feature = "multitask"))]
+    parker: Arc<Parker>,
+    /// The thread a local executor belongs to, whose futures may not be
+    /// `Send`, see [`spawn_local`].
+    local_owner: Option<LocalOwner>,
+    /// Number of the wakes and aborts of its tasks in progress, see
+    /// [`ExecutorUse`]. Shared with the tasks, which may outlive it.
+    uses: Arc<AtomicUsize>,
+    /// The tasks polled, in order, see [`take_poll_trace`](Self::take_poll_trace).
+    #[cfg(feature = "deterministic")]
+    poll_trace: SpinNoIrq<Vec<TaskId>>,
 }
 
 impl Executor {
-    /// Creates a new executor.
+    /// Creates a new executor, with an unbounded run queue.
     pub fn new() -> Self {
         Self {
-            ready_tasks: Mutex::new(VecDeque::new()),
+            ready_tasks: SpinNoIrq::new(RunQueue {
+                tasks: [const { VecDeque::new() }; Priority::COUNT],
+                deadlines: BTreeMap::new(),
+                pushes: 0,
+                len: 0,
+                capacity: usize::MAX,
+                max_tasks: usize::MAX,
+                policy: OverflowPolicy::Reject,
+                high_watermark: 0,
+                #[cfg(feature = "deterministic")]
+                schedule: Schedule::Priority,
+                #[cfg(feature = "deterministic")]
+                draws: 0,
+            }),
+            injected: InjectQueue::new(),
+            irq_spawns: IrqSpawnQueue::new(),
+            polls: AtomicU64::new(0),
+            slow_polls: AtomicU64::new(0),
+            rejected_spawns: AtomicU64::new(0),
+            coalesced_wakes: AtomicU64::new(0),
+            missed_deadlines: AtomicU64::new(0),
+            live_tasks: AtomicUsize::new(0),
+            all_tasks: SpinNoIrq::new(BTreeMap::new()),
+            shut_down: AtomicBool::new(false),
+            spawn_waiters: SpinNoIrq::new(Vec::new()),
+            cleanups: CleanupQueue::new(),
+            #[cfg(any(feature = "irq", feature = "multitask"))]
+            parker: Arc::new(Parker::new()),
+            local_owner: None,
+            uses: Arc::new(AtomicUsize::new(0)),
+            #[cfg(feature = "deterministic")]
+            poll_trace: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    /// Creates a new executor that runs at most `max_tasks` tasks at once,
+    /// see [`set_max_tasks`](Self::set_max_tasks).
+    ///
+    /// Its run queue is unbounded, with [`OverflowPolicy::Block`]: a spawn
+    /// over `max_tasks` waits for a task to complete rather than failing.
+    pub fn new_with_capacity(max_tasks: usize) -> Self {
+        let executor = Self::new();
+        executor.set_capacity(None, OverflowPolicy::Block);
+        executor.set_max_tasks(Some(max_tasks));
+        executor
+    }
+
+    /// Bounds the run queue to `capacity` tasks, or makes it unbounded with
+    /// `None`, and sets what happens when a task is spawned while it is full.
+    pub fn set_capacity(&self, capacity: Option<usize>, policy: OverflowPolicy) {
+        let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+        queue.capacity = capacity.unwrap_or(usize::MAX);
+        queue.policy = policy;
+    }
+
+    /// Bounds the number of tasks of the executor, ready or parked, to
+    /// `max_tasks`, or removes the bound with `None`.
+    ///
+    /// Each task holds its future until it completes, so this bounds the
+    /// memory of the tasks, e.g. on a board with little of it. A spawn over
+    /// the bound overflows as if the run queue were full, see
+    /// [`set_capacity`](Self::set_capacity): with [`OverflowPolicy::Reject`],
+    /// the default of [`new`](Self::new), [`spawn`](Self::spawn) panics and
+    /// [`try_spawn`](Self::try_spawn) fails, with
+    /// [`OverflowPolicy::Block`] they wait for a task to complete.
+    /// [`spawn_async`](Self::spawn_async) waits whatever the policy.
+    pub fn set_max_tasks(&self, max_tasks: Option<usize>) {
+        READY_TASKS_STAT.lock(&self.ready_tasks).max_tasks = max_tasks.unwrap_or(usize::MAX);
+        self.wake_spawn_waiters();
+    }
+
+    /// Sets the order in which the ready tasks are polled from now on, and
+    /// restarts the generator of [`Schedule::Seeded`].
+    ///
+    /// The tasks already queued keep their place, it should be set before
+    /// spawning any.
+    #[cfg(feature = "deterministic")]
+    pub fn set_schedule(&self, schedule: Schedule) {
+        let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+        queue.schedule = schedule;
+        queue.draws = 0;
+    }
+
+    /// Returns the IDs of the tasks polled since the last call, in the order
+    /// of the polls, a task once per poll.
+    ///
+    /// Two runs of the same tasks under the same [`Schedule`] give the same
+    /// trace, once the IDs are mapped to the order of the spawns.
+    #[cfg(feature = "deterministic")]
+    pub fn take_poll_trace(&self) -> Vec<TaskId> {
+        core::mem::take(&mut *self.poll_trace.lock())
+    }
+
+    /// Returns the runtime statistics of this executor.
+    pub fn stats(&self) -> ExecutorStats {
+        ExecutorStats {
+            polls: self.polls.load(Ordering::Relaxed),
+            slow_polls: self.slow_polls.load(Ordering::Relaxed),
+            queue_high_watermark: READY_TASKS_STAT.lock(&self.ready_tasks).high_watermark,
+            rejected_spawns: self.rejected_spawns.load(Ordering::Relaxed),
+            coalesced_wakes: self.coalesced_wakes.load(Ordering::Relaxed),
+            missed_deadlines: self.missed_deadlines.load(Ordering::Relaxed),
+            steal_time_nanos: axhal::time::steal_time_nanos(),
         }
     }
 
     /// Adds a task to the executor's queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue is full, or the executor has
+    /// [too many tasks](Self::set_max_tasks), and the [`OverflowPolicy`]
+    /// rejects the task, use [`try_spawn`](Self::try_spawn) to handle it.
+    /// With [`OverflowPolicy::Block`], it waits for room instead.
+    /// It also panics if the executor is [shut down](Self::shutdown).
     pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
     where
         F: Future + Send + 'static,
         F::Output: Send + 'static,
     {
-        let (task, handle) = Task::new(future, self);
-        self.ready_tasks.lock().push_back(task);
-        handle
+        self.try_spawn(future).expect("spawn failed")
+    }
+
+    /// Adds a task named `name` to the executor's queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue is full, or the executor has
+    /// [too many tasks](Self::set_max_tasks), and the [`OverflowPolicy`]
+    /// rejects the task, use [`try_spawn_named`](Self::try_spawn_named) to
+    /// handle it. With [`OverflowPolicy::Block`], it waits for room instead.
+    /// It also panics if the executor is [shut down](Self::shutdown).
+    pub fn spawn_named<F>(&self, name: impl Into<String>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.try_spawn_named(name, future).expect("spawn failed")
     }
 
-    /// Runs the executor until all tasks are complete.
+    /// Adds a task with `priority` to the executor's queue. It is polled
+    /// before the ready tasks of lower priorities, see [`Executor`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue is full, or the executor has
+    /// [too many tasks](Self::set_max_tasks), and the [`OverflowPolicy`]
+    /// rejects the task, or if the executor is [shut down](Self::shutdown).
+    /// With [`OverflowPolicy::Block`], it waits for room instead.
+    pub fn spawn_with_priority<F>(&self, future: F, priority: Priority) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_bounded(future, None, priority, None)
+            .expect("spawn failed")
+    }
+
+    /// Adds a task that should complete by `deadline`, in monotonic time.
+    /// The ready tasks with a deadline are polled before the others, the
+    /// earliest deadline first, see [`Executor`].
+    ///
+    /// The deadline is not enforced: a task that completes after it is
+    /// counted in [`ExecutorStats::missed_deadlines`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue is full, or the executor has
+    /// [too many tasks](Self::set_max_tasks), and the [`OverflowPolicy`]
+    /// rejects the task, or if the executor is [shut down](Self::shutdown).
+    /// With [`OverflowPolicy::Block`], it waits for room instead.
+    pub fn spawn_with_deadline<F>(&self, future: F, deadline: TimeValue) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_bounded(future, None, Priority::Normal, Some(deadline))
+            .expect("spawn failed")
+    }
+
+    /// Adds a task to the executor's queue, or fails with
+    /// [`WouldBlock`](axerrno::AxError::WouldBlock) if the queue is full, or
+    /// the executor has [too many tasks](Self::set_max_tasks), and the
+    /// [`OverflowPolicy`] rejects the task, or with
+    /// [`BadState`](axerrno::AxError::BadState) if the executor is
+    /// [shut down](Self::shutdown).
+    pub fn try_spawn<F>(&self, future: F) -> AxResult<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_bounded(future, None, Priority::Normal, None)
+    }
+
+    /// Adds a task named `name` to the executor's queue, like
+    /// [`try_spawn`](Self::try_spawn).
+    pub fn try_spawn_named<F>(
+        &self,
+        name: impl Into<String>,
+        future: F,
+    ) -> AxResult<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_bounded(future, Some(name.into()), Priority::Normal, None)
+    }
+
+    fn spawn_bounded<F>(
+        &self,
+        future: F,
+        name: Option<String>,
+        priority: Priority,
+        deadline: Option<TimeValue>,
+    ) -> AxResult<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        loop {
+            if self.shut_down.load(Ordering::Acquire) {
+                return ax_err!(BadState, "spawn: executor shut down");
+            }
+            let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+            if queue.has_room(self.live_tasks.load(Ordering::Acquire)) {
+                let (task, handle) = Task::new(future, self, name, priority, deadline);
+                queue.push(task);
+                drop(queue);
+                #[cfg(any(feature = "irq", feature = "multitask"))]
+                self.parker.unpark();
+                return Ok(handle);
+            }
+            if queue.policy != OverflowPolicy::Block {
+                self.rejected_spawns.fetch_add(1, Ordering::Relaxed);
+                if queue.len < queue.capacity {
+                    return ax_err!(WouldBlock, "spawn: too many tasks");
+                }
+                return ax_err!(WouldBlock, "spawn: run queue full");
+            }
+            drop(queue);
+            #[cfg(feature = "multitask")]
+            axtask::yield_now();
+            #[cfg(not(feature = "multitask"))]
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Adds a task to the executor's queue, waiting for room if the queue is
+    /// full or the executor has [too many tasks](Self::set_max_tasks),
+    /// whatever the [`OverflowPolicy`].
+    ///
+    /// Fails with [`BadState`](axerrno::AxError::BadState) if the executor
+    /// is [shut down](Self::shutdown). Like the `Block` policy, a task of the
+    /// executor that waits for room must leave some to the tasks it waits
+    /// for.
+    pub async fn spawn_async<F>(&self, future: F) -> AxResult<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let mut future = Some(future);
+        core::future::poll_fn(|cx| {
+            if self.shut_down.load(Ordering::Acquire) {
+                return Poll::Ready(ax_err!(BadState, "spawn: executor shut down"));
+            }
+            // Before the check, so that room made meanwhile wakes it.
+            {
+                let mut waiters = self.spawn_waiters.lock();
+                if !waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    waiters.push(cx.waker().clone());
+                }
+            }
+            let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+            if !queue.has_room(self.live_tasks.load(Ordering::Acquire)) {
+                return Poll::Pending;
+            }
+            let future = future.take().expect("spawn_async polled after completion");
+            let (task, handle) = Task::new(future, self, None, Priority::Normal, None);
+            queue.push(task);
+            drop(queue);
+            #[cfg(any(feature = "irq", feature = "multitask"))]
+            self.parker.unpark();
+            Poll::Ready(Ok(handle))
+        })
+        .await
+    }
+
+    /// Wakes the [`spawn_async`](Self::spawn_async) waiting for room, when a
+    /// task completes or leaves a full queue.
+    fn wake_spawn_waiters(&self) {
+        let waiters = core::mem::take(&mut *self.spawn_waiters.lock());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Spawns the future returned by `make(arg)` from an interrupt handler,
+    /// e.g. to process a received frame out of it.
+    ///
+    /// It neither allocates nor takes a lock that spins: the spawn is pushed
+    /// to a queue allocated with the executor, and `make` is only called by
+    /// the next [`step`](Self::step), which then queues the task regardless
+    /// of the [capacity](Self::set_capacity). The task is detached.
+    ///
+    /// Fails with [`WouldBlock`](AxError::WouldBlock) if
+    /// [`IRQ_SPAWN_CAPACITY`] spawns are already waiting, or with
+    /// [`BadState`](AxError::BadState) if the executor is
+    /// [shut down](Self::shutdown). Nothing is logged, from an interrupt
+    /// handler.
+    pub fn spawn_from_irq<F>(&self, make: fn(usize) -> F, arg: usize) -> AxResult
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.shut_down.load(Ordering::Acquire) {
+            return Err(AxError::BadState);
+        }
+        let spawn = IrqSpawn {
+            spawn: spawn_irq_work::<F>,
+            make: make as *const (),
+            arg,
+        };
+        if !self.irq_spawns.push(spawn) {
+            self.rejected_spawns.fetch_add(1, Ordering::Relaxed);
+            return Err(AxError::WouldBlock);
+        }
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        self.parker.unpark();
+        Ok(())
+    }
+
+    /// Runs the executor until all tasks are complete, waiting for the
+    /// parked tasks to be woken.
+    ///
+    /// Without the `irq` feature, it also polls the timers and the devices,
+    /// see [`polling`](crate::polling). With it, it parks while no task is
+    /// ready, see `park`.
     pub fn run(&self) {
-        while self.step() {}
+        #[cfg(not(feature = "irq"))]
+        crate::polling::run(self);
+
+        #[cfg(feature = "irq")]
+        {
+            let mut unparks = self.parker.unparks();
+            loop {
+                expire_timers();
+                if self.step() {
+                    continue;
+                }
+                if self.live_tasks.load(Ordering::Acquire) == 0 {
+                    break;
+                }
+                arm_timer();
+                self.parker.park(&mut unparks, || false);
+            }
+        }
+    }
+
+    /// Polls `future` while running the tasks of the executor, until `future`
+    /// completes, and returns its output.
+    ///
+    /// Unlike [`block_on`](Self::block_on), which polls `future` at each step,
+    /// `future` is only polled when it is woken, and the ready tasks run in
+    /// between: e.g. the main loop of a server that serves the connections
+    /// spawned in the background. The tasks left pending when it returns stay
+    /// on the executor, to be aborted with their [`JoinHandle`]s or with
+    /// [`shutdown`](Self::shutdown), or to run again at the next call.
+    pub fn run_until<F: Future>(&self, future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let root = self.root_waker();
+        let waker = Waker::from(root.clone());
+        let mut cx = Context::from_waker(&waker);
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        let mut unparks = self.parker.unparks();
+        loop {
+            // The tasks of the timers due are polled in this iteration.
+            expire_timers();
+            if root.woken.swap(false, Ordering::AcqRel) {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+            if self.step() || root.woken.load(Ordering::Acquire) {
+                continue;
+            }
+            // Nothing else would wake it without interrupts.
+            #[cfg(not(feature = "irq"))]
+            crate::polling::poll_events();
+            #[cfg(feature = "irq")]
+            arm_timer();
+            #[cfg(any(feature = "irq", feature = "multitask"))]
+            self.parker
+                .park(&mut unparks, || root.woken.load(Ordering::Acquire));
+        }
+    }
+
+    /// Runs the tasks until `done` is set, for a worker that stands in for
+    /// one blocked in a poll, see `block_in_place`.
+    #[cfg(feature = "multitask")]
+    pub(crate) fn run_until_done(&self, done: &AtomicBool) {
+        let mut unparks = self.parker.unparks();
+        while !done.load(Ordering::Acquire) {
+            expire_timers();
+            if self.step() {
+                continue;
+            }
+            #[cfg(feature = "irq")]
+            arm_timer();
+            #[cfg(not(feature = "irq"))]
+            crate::polling::poll_events();
+            self.parker
+                .park(&mut unparks, || done.load(Ordering::Acquire));
+        }
+    }
+
+    /// Returns whether it is a local executor, whose tasks are only polled
+    /// by the axtask it belongs to.
+    #[cfg(feature = "multitask")]
+    pub(crate) fn is_local(&self) -> bool {
+        self.local_owner.is_some()
+    }
+
+    /// Wakes up the parked workers of the executor, e.g. for the one in
+    /// `run_until_done` to see that it is done.
+    #[cfg(feature = "multitask")]
+    pub(crate) fn notify(&self) {
+        self.parker.notify();
+    }
+
+    /// Shuts the executor down: spawns fail from now on, the ready tasks run
+    /// for at most `grace`, then all the remaining tasks are aborted, parked
+    /// or not, and the run queue is freed.
+    ///
+    /// The [`JoinHandle`]s of the aborted tasks resolve to
+    /// [`JoinError::Cancelled`]. The executor accepts spawns again after
+    /// [`restart`](Self::restart).
+    pub fn shutdown(&self, grace: Duration) {
+        self.shut_down.store(true, Ordering::Release);
+        let deadline = axhal::time::monotonic_time().saturating_add(grace);
+        while self.step() && axhal::time::monotonic_time() < deadline {}
+        self.abort_all();
+    }
+
+    /// Aborts all the tasks, parked or not, and frees the run queue.
+    fn abort_all(&self) {
+        let tasks: Vec<_> = core::mem::take(&mut *self.all_tasks.lock())
+            .into_values()
+            .collect();
+        info!("executor: aborting {} tasks", tasks.len());
+        for task in &tasks {
+            task.abort();
+        }
+        // The aborted tasks left in the queue are only dropped, not under the
+        // lock.
+        let queued = {
+            let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+            queue.len = 0;
+            (
+                core::mem::take(&mut queue.tasks),
+                core::mem::take(&mut queue.deadlines),
+            )
+        };
+        drop(queued);
+        drop(self.injected.take_all());
+        while self.irq_spawns.pop().is_some() {}
+        self.wake_spawn_waiters();
+    }
+
+    /// Accepts spawns again after a [`shutdown`](Self::shutdown).
+    pub fn restart(&self) {
+        self.shut_down.store(false, Ordering::Release);
+    }
+
+    /// Queues the cleanup of a dropped guard, spawned by the next step.
+    pub(crate) fn queue_cleanup(&self, cleanup: BoxFuture<()>) {
+        self.cleanups.push(cleanup);
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        self.parker.unpark();
     }
 
     /// Runs a single step of the executor.
     ///
-    /// Returns `true` if there are still tasks in the queue.
+    /// Returns `true` if there are still tasks in the queue, parked tasks are
+    /// not counted.
     pub fn step(&self) -> bool {
-        let mut ready_tasks = self.ready_tasks.lock();
-        if let Some(mut task) = ready_tasks.pop_front() {
-            // Create a waker and poll the task
-            let waker = task.waker();
-            let mut cx = Context::from_waker(&waker);
-
-            let future = unsafe { Pin::new_unchecked(&mut task.future) };
-
-            if future.poll(&mut cx).is_pending() {
-                // Task is still pending, only re-queue if it hasn't been manually queued
-                if !task.was_woken {
-                    ready_tasks.push_back(task);
+        for cleanup in self.cleanups.take() {
+            // Detached, nobody waits for a cleanup. It must run even if the
+            // queue is full.
+            let (task, _) = Task::new(cleanup, self, None, Priority::Normal, None);
+            READY_TASKS_STAT.lock(&self.ready_tasks).push(task);
+        }
+        self.drain_injected();
+        while let Some(spawn) = self.irq_spawns.pop() {
+            (spawn.spawn)(self, spawn.make, spawn.arg);
+        }
+
+        let (task, was_full) = {
+            let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+            let was_full = queue.len >= queue.capacity;
+            (queue.pop(), was_full)
+        };
+        if was_full {
+            self.wake_spawn_waiters();
+        }
+        let Some(task) = task else {
+            return !self.injected.is_empty()
+                || !self.irq_spawns.is_empty()
+                || !self.cleanups.is_empty();
+        };
+        // Wakes from now on are merged with the re-queue below. `COMPLETED`
+        // is kept, so that the wakes of a task cancelled while queued still
+        // do not touch the executor.
+        let prev = task
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                Some((state | RUNNING) & !SCHEDULED)
+            })
+            .unwrap();
+        if prev & (ABORTED | COMPLETED) != 0 {
+            task.cancel();
+            task.state.fetch_and(!RUNNING, Ordering::AcqRel);
+            return self.has_queued_tasks();
+        }
+
+        // The queue is not locked while polling, so that the task can spawn
+        // and wake other tasks.
+        let waker = task.clone().waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = task.future.lock();
+        if let Some(fut) = future.as_mut() {
+            self.polls.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "deterministic")]
+            {
+                let mut trace = self.poll_trace.lock();
+                if trace.len() < MAX_POLL_TRACE {
+                    trace.push(task.id);
+                }
+            }
+            let context = PollContext {
+                task: task.id,
+                executor: self,
+            };
+            let _context = context.enter();
+            let _nonblocking = NonBlockingGuard::enter();
+            crate::coop::reset();
+            let timer = PollTimer::start();
+            let poll = fut.as_mut().poll(&mut cx);
+            let elapsed = timer.elapsed();
+            tasks::record_poll(task.id, elapsed);
+            self.check_slow_poll(task.id, elapsed);
+            if poll.is_ready() {
+                *future = None;
+                drop(future);
+                if let Some(deadline) = task.deadline {
+                    let late = axhal::time::monotonic_time().saturating_sub(deadline);
+                    if !late.is_zero() {
+                        self.missed_deadlines.fetch_add(1, Ordering::Relaxed);
+                        debug!("task {}: deadline missed by {:?}", task.id, late);
+                    }
+                }
+                task.state.store(COMPLETED, Ordering::Release);
+                self.all_tasks.lock().remove(&task.id);
+                self.live_tasks.fetch_sub(1, Ordering::Release);
+                self.wake_spawn_waiters();
+            } else {
+                drop(future);
+                drop(waker);
+                // Without a waker, it is scheduled as if woken while running.
+                let prev = task
+                    .state
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                        let state = state & !RUNNING;
+                        if state & WAKER_CLONED == 0 {
+                            Some(state | SCHEDULED)
+                        } else {
+                            Some(state)
+                        }
+                    })
+                    .unwrap();
+                if prev & ABORTED != 0 {
+                    // Aborted while running.
+                    task.cancel();
+                } else if prev & SCHEDULED != 0 || prev & WAKER_CLONED == 0 {
+                    // Woken while running, or never to be woken: poll it
+                    // again in its turn. An abort from now on is seen when it
+                    // is popped.
+                    READY_TASKS_STAT.lock(&self.ready_tasks).push(task);
                 }
             }
+        }
 
-            !ready_tasks.is_empty()
-        } else {
-            false
+        self.has_queued_tasks()
+    }
+
+    /// Moves the tasks woken by interrupt handlers to the run queue.
+    fn drain_injected(&self) {
+        if self.injected.is_empty() {
+            return;
+        }
+        let tasks = self.injected.take_all();
+        let mut queue = READY_TASKS_STAT.lock(&self.ready_tasks);
+        for task in tasks {
+            queue.push(task);
         }
     }
 
+    /// Returns `true` if there are tasks in the queue, or woken or spawned by
+    /// interrupt handlers.
+    fn has_queued_tasks(&self) -> bool {
+        !self.injected.is_empty()
+            || !self.irq_spawns.is_empty()
+            || !self.cleanups.is_empty()
+            || !READY_TASKS_STAT.lock(&self.ready_tasks).is_empty()
+    }
+
+    fn check_slow_poll(&self, task: TaskId, elapsed: u64) {
+        let threshold = SLOW_POLL_THRESHOLD_NANOS.load(Ordering::Relaxed);
+        if threshold != 0 && elapsed > threshold {
+            self.slow_polls.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "task {}: poll took {} us, over the threshold of {} us, is it blocking?",
+                task,
+                elapsed / 1000,
+                threshold / 1000
+            );
+        }
+    }
+
+    /// Returns `true` if there are tasks in the queue.
+    #[cfg(not(feature = "irq"))]
+    pub(crate) fn has_ready_tasks(&self) -> bool {
+        self.has_queued_tasks()
+    }
+
+    /// Returns `true` if all the tasks are complete.
+    #[cfg(not(feature = "irq"))]
+    pub(crate) fn is_idle(&self) -> bool {
+        self.live_tasks.load(Ordering::Acquire) == 0
+    }
+
     // Queue a task, used by the waker
-    fn queue_task(&self, task: Task) {
-        self.ready_tasks.lock().push_back(task);
+    fn queue_task(&self, task: Arc<Task>) {
+        if in_irq_context() {
+            self.injected.push(task);
+        } else {
+            READY_TASKS_STAT.lock(&self.ready_tasks).push(task);
+        }
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        self.parker.unpark();
     }
 
     /// Blocks on a future until it completes, using this executor.
+    ///
+    /// The future is polled with a waker of its own, and the caller parks
+    /// while neither the future nor a task is woken: with `multitask`, the
+    /// calling axtask blocks until then, see `park`. Waking the future wakes
+    /// up this caller, even if other axtasks are blocked on the executor.
+    /// Without `irq` nor `multitask`, it polls the events in a loop instead.
     pub fn block_on<F>(&self, mut fut: F) -> F::Output
     where
         F: Future,
@@ -163,10 +1568,17 @@ impl Executor {
         // safety: we don't move the future after this line.
         let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
 
-        let waker = dummy_waker();
+        let root = self.root_waker();
+        let waker = Waker::from(root.clone());
         let mut cx = Context::from_waker(&waker);
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        let mut unparks = self.parker.unparks();
         loop {
-            // Poll the future
+            // The tasks of the timers due are polled in this iteration.
+            expire_timers();
+
+            // Poll the future, the wakes from now on are seen by the park
+            root.woken.store(false, Ordering::Release);
             if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
                 return res;
             }
@@ -174,13 +1586,204 @@ impl Executor {
             // Run a step of this executor to make progress on other tasks
             self.step();
 
-            // If the future is still not ready, yield to other tasks
-            if self.ready_tasks.lock().is_empty() {
-                // TODO: yield_now
-                // axtask::yield_now();
+            // If the future is still not ready, wait for a wake
+            if !self.has_queued_tasks() {
+                // Nothing else would wake it without interrupts.
+                #[cfg(not(feature = "irq"))]
+                crate::polling::poll_events();
+                #[cfg(feature = "irq")]
+                arm_timer();
+                #[cfg(any(feature = "irq", feature = "multitask"))]
+                self.parker
+                    .park(&mut unparks, || root.woken.load(Ordering::Acquire));
             }
         }
     }
+
+    /// Creates the waker of the future of `run_until` or `block_on`, marked
+    /// as woken.
+    fn root_waker(&self) -> Arc<RootWaker> {
+        Arc::new(RootWaker {
+            woken: AtomicBool::new(true),
+            #[cfg(any(feature = "irq", feature = "multitask"))]
+            parker: self.parker.clone(),
+        })
+    }
+}
+
+/// Wakes the tasks whose timers are due, instead of waiting for the next
+/// timer interrupt.
+fn expire_timers() {
+    #[cfg(feature = "timer")]
+    crate::waker::expire_timers(axhal::time::monotonic_time());
+}
+
+/// Programs the hardware timer for the earliest deadline of the timers,
+/// before the executor parks, so that it is woken up on time rather than at
+/// the next tick.
+///
+/// Only the deadlines within a tick are programmed, the later ones are left
+/// to the periodic ticks. The tick may come up to a period later meanwhile,
+/// the timer interrupt handler programs the next one.
+#[cfg(feature = "irq")]
+fn arm_timer() {
+    #[cfg(feature = "timer")]
+    if let Some(deadline) = crate::waker::next_timer_deadline() {
+        let tick =
+            Duration::from_nanos(axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64);
+        if deadline < axhal::time::monotonic_time().saturating_add(tick) {
+            axhal::time::set_oneshot_timer(axhal::time::saturating_nanos(deadline));
+        }
+    }
+}
+
+/// The waker of the future of [`Executor::run_until`] or
+/// [`Executor::block_on`], which marks it to be polled and wakes up its
+/// caller if it is parked.
+struct RootWaker {
+    woken: AtomicBool,
+    #[cfg(any(feature = "irq", feature = "multitask"))]
+    parker: Arc<Parker>,
+}
+
+impl Wake for RootWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        // Only its caller has something to do.
+        #[cfg(any(feature = "irq", feature = "multitask"))]
+        self.parker.notify();
+    }
+}
+
+/// The task an axtask is polling, see [`poll_context`].
+///
+/// It is kept on the axtask rather than on its CPU: the axtask may be
+/// preempted by another one polling on the same CPU, or resume on another
+/// CPU after sleeping in the poll, e.g. on a lock.
+#[derive(Clone, Copy)]
+pub(crate) struct PollContext {
+    pub task: TaskId,
+    /// It lives at least until the poll returns.
+    pub executor: *const Executor,
+}
+
+impl PollContext {
+    /// Records the context as the poll of the current axtask, until the
+    /// returned guard is dropped.
+    fn enter(&self) -> PollContextGuard<'_> {
+        let prev = swap_poll_context(self as *const Self as usize);
+        PollContextGuard {
+            prev,
+            _context: PhantomData,
+        }
+    }
+}
+
+/// Restores the previous poll of the axtask when dropped, see
+/// [`PollContext::enter`].
+struct PollContextGuard<'a> {
+    prev: usize,
+    _context: PhantomData<&'a PollContext>,
+}
+
+impl Drop for PollContextGuard<'_> {
+    fn drop(&mut self) {
+        swap_poll_context(self.prev);
+    }
+}
+
+fn swap_poll_context(context: usize) -> usize {
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        return curr.swap_poll_context(context);
+    }
+    unsafe { CPU_POLL_CONTEXT.current_ref_raw() }.replace(context)
+}
+
+/// Returns what the current axtask is polling, [`None`] outside of a poll.
+///
+/// In an IRQ handler, it is the poll of the interrupted axtask.
+pub(crate) fn poll_context() -> Option<PollContext> {
+    #[cfg(feature = "multitask")]
+    let context = match axtask::current_may_uninit() {
+        Some(curr) => curr.poll_context(),
+        None => unsafe { CPU_POLL_CONTEXT.current_ref_raw() }.get(),
+    };
+    #[cfg(not(feature = "multitask"))]
+    let context = unsafe { CPU_POLL_CONTEXT.current_ref_raw() }.get();
+    // SAFETY: the context lives on the stack of the poll until it returns,
+    // when the guard restores the previous one.
+    unsafe { (context as *const PollContext).as_ref() }.copied()
+}
+
+/// Marks the current thread as polling a task, see `block_in_place`, and
+/// checks that the poll does not return with an `axsync::Mutex` held, i.e.
+/// held across an await: the other tasks locking it would block the executor
+/// until the task is polled again.
+///
+/// A lock that is only contended for a moment within a poll is fine.
+struct NonBlockingGuard {
+    #[cfg(feature = "multitask")]
+    held_locks: usize,
+}
+
+impl NonBlockingGuard {
+    fn enter() -> Self {
+        #[cfg(feature = "multitask")]
+        let held_locks = match axtask::current_may_uninit() {
+            Some(curr) => {
+                curr.enter_nonblocking();
+                curr.held_locks()
+            }
+            None => 0,
+        };
+        Self {
+            #[cfg(feature = "multitask")]
+            held_locks,
+        }
+    }
+}
+
+impl Drop for NonBlockingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "multitask")]
+        if let Some(curr) = axtask::current_may_uninit() {
+            curr.exit_nonblocking();
+            debug_assert!(
+                curr.held_locks() <= self.held_locks,
+                "{} returned from the poll of an async task holding a Mutex, \
+                 use `axasync::sync::Mutex` or release the lock before awaiting",
+                curr.id_name()
+            );
+        }
+    }
+}
+
+/// Measures the duration of a poll.
+struct PollTimer {
+    start: u64,
+    start_steal: u64,
+}
+
+impl PollTimer {
+    fn start() -> Self {
+        Self {
+            start: axhal::time::monotonic_time_nanos(),
+            start_steal: axhal::time::steal_time_nanos(),
+        }
+    }
+
+    /// Returns the nanoseconds since the start, without the time stolen by
+    /// the hypervisor.
+    fn elapsed(self) -> u64 {
+        let elapsed = axhal::time::monotonic_time_nanos() - self.start;
+        let stolen = axhal::time::steal_time_nanos() - self.start_steal;
+        elapsed.saturating_sub(stolen)
+    }
 }
 
 impl Default for Executor {
@@ -189,154 +1792,360 @@ impl Default for Executor {
     }
 }
 
-// Task definition - boxed future
+/// The wakers of the parked tasks may outlive the executor, e.g. in the timer
+/// wheel or in a socket: all the tasks are cancelled, so that their wakes do
+/// not touch it anymore, then the wakes in progress are waited for.
+///
+/// A local executor must be dropped by the axtask it belongs to, on its CPU,
+/// as [`LocalSet`] does.
+impl Drop for Executor {
+    fn drop(&mut self) {
+        let tasks: Vec<_> = core::mem::take(&mut *self.all_tasks.lock())
+            .into_values()
+            .collect();
+        if !tasks.is_empty() {
+            debug!("executor: dropped with {} tasks", tasks.len());
+        }
+        for task in &tasks {
+            task.state.fetch_or(ABORTED, Ordering::AcqRel);
+            task.cancel();
+        }
+        while self.uses.load(Ordering::Acquire) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Marks a use of the executor of a task, from before the check of
+/// [`COMPLETED`], so that the `Drop` of the executor waits for it.
+///
+/// The count is that of the executor, held by the task, so that it is valid
+/// once the executor is dropped. No future of a task may be dropped meanwhile:
+/// one that owns an executor would wait for the uses in progress.
+struct ExecutorUse<'a>(&'a AtomicUsize);
+
+impl<'a> ExecutorUse<'a> {
+    fn enter(uses: &'a AtomicUsize) -> Self {
+        uses.fetch_add(1, Ordering::AcqRel);
+        Self(uses)
+    }
+}
+
+impl Drop for ExecutorUse<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// The task is in the run queue.
+const SCHEDULED: u8 = 1 << 0;
+/// The task is being polled.
+const RUNNING: u8 = 1 << 1;
+/// The task has completed, its future is dropped.
+const COMPLETED: u8 = 1 << 2;
+/// The task is aborted, its future is to be dropped.
+const ABORTED: u8 = 1 << 3;
+/// A waker of the task was cloned, e.g. stored to wake it later. A task that
+/// is pending without one ever cloned is queued again, see [`Executor`].
+const WAKER_CLONED: u8 = 1 << 4;
+
+// A spawned task, shared by the run queue and the wakers of the task.
 pub(crate) struct Task {
-    future: BoxFuture<()>,
+    id: TaskId,
+    future: Mutex<Option<BoxFuture<()>>>,
     executor: *const Executor,
-    was_woken: bool,
+    /// The uses of the executor in progress, see [`ExecutorUse`].
+    executor_uses: Arc<AtomicUsize>,
+    priority: Priority,
+    /// When the task should complete, see [`Executor::spawn_with_deadline`].
+    deadline: Option<TimeValue>,
+    /// [`SCHEDULED`], [`RUNNING`], [`COMPLETED`] and [`ABORTED`] flags, so
+    /// that a task is only queued by a wake if it is neither queued nor being
+    /// polled.
+    state: AtomicU8,
+    /// The next task of the [`InjectQueue`] it is in.
+    inject_next: AtomicPtr<Task>,
 }
 
 // Tasks must be Send to be spawned on other threads
 unsafe impl Send for Task {}
+unsafe impl Sync for Task {}
 
 impl Task {
-    fn new<F>(future: F, executor: &Executor) -> (Self, JoinHandle<F::Output>)
+    fn new<F>(
+        future: F,
+        executor: &Executor,
+        name: Option<String>,
+        priority: Priority,
+        deadline: Option<TimeValue>,
+    ) -> (Arc<Self>, JoinHandle<F::Output>)
     where
         F: Future + Send + 'static,
         F::Output: Send + 'static,
     {
         let (output_sender, output_receiver) = channel::oneshot::channel();
+        let id = signal::register_task();
+        executor.live_tasks.fetch_add(1, Ordering::Relaxed);
 
         // Create a future that sends the output through the channel
         let future = async move {
-            let output = future.await;
+            #[cfg(feature = "unwind")]
+            let output = crate::unwind::CatchUnwind::new(future).await.map_err(|()| {
+                warn!("task {}: panicked, dropped", id);
+                JoinError::Panicked
+            });
+            #[cfg(not(feature = "unwind"))]
+            let output = Ok(future.await);
+            signal::unregister_task(id);
+            let exit = match &output {
+                Ok(_) => TaskExit::Completed,
+                Err(_) => TaskExit::Panicked,
+            };
+            tasks::exit(id, exit);
+            task_local::clear(id);
             let _ = output_sender.send(output);
         };
 
-        let task = Task {
-            future: Box::pin(future),
+        let task = Arc::new(Task {
+            id,
+            future: Mutex::new(Some(Box::pin(future))),
             executor: executor as *const _,
-            was_woken: false,
-        };
-
+            executor_uses: executor.uses.clone(),
+            priority,
+            deadline,
+            // It is queued by the spawn.
+            state: AtomicU8::new(SCHEDULED),
+            inject_next: AtomicPtr::new(ptr::null_mut()),
+        });
+
+        tasks::register(id, name, Arc::downgrade(&task));
+        executor.all_tasks.lock().insert(id, task.clone());
         let handle = JoinHandle {
+            id,
             receiver: output_receiver,
+            task: Arc::downgrade(&task),
         };
 
         (task, handle)
     }
 
-    fn waker(&mut self) -> Waker {
-        // SAFETY: We ensure the executor ptr always lives as long as the task
-        let executor = unsafe { &*self.executor };
+    /// Returns where the task is, for [`task_list`](crate::task_list).
+    pub(crate) fn state(&self) -> TaskState {
+        let state = self.state.load(Ordering::Acquire);
+        if state & RUNNING != 0 {
+            TaskState::Running
+        } else if state & SCHEDULED != 0 {
+            TaskState::Ready
+        } else {
+            TaskState::Pending
+        }
+    }
 
-        // Create a waker that will queue this task in the executor
-        TaskWaker {
-            task: self,
-            executor,
+    /// Aborts the task. A task being polled is cancelled by the executor
+    /// once the poll returns.
+    ///
+    /// The future of a task of a local executor may only be dropped by the
+    /// axtask the executor belongs to: aborted from another thread, the task
+    /// is queued, and cancelled when popped.
+    fn abort(self: &Arc<Self>) {
+        let future = {
+            let _use = ExecutorUse::enter(&self.executor_uses);
+            let prev = self.state.fetch_or(ABORTED, Ordering::AcqRel);
+            if prev & (RUNNING | COMPLETED) != 0 {
+                return;
+            }
+            // SAFETY: The executor is not dropped before the use ends, and
+            // the task is not completed.
+            let executor = unsafe { &*self.executor };
+            match executor.local_owner {
+                Some(owner) if !owner.is_current() => {
+                    self.wake_by_ref();
+                    return;
+                }
+                _ => self.take_future(),
+            }
+        };
+        // Once the use ends, the future may own an executor, whose drop
+        // waits for the uses of its own.
+        if let Some(future) = future {
+            drop(future);
+            tasks::exit(self.id, TaskExit::Aborted);
         }
-        .into_waker()
-    }
-}
-
-struct TaskWaker<'a> {
-    task: *mut Task,
-    executor: &'a Executor,
-}
-
-// TaskWaker must be Send+Sync to be used across threads
-unsafe impl<'a> Send for TaskWaker<'a> {}
-unsafe impl<'a> Sync for TaskWaker<'a> {}
-
-impl<'a> TaskWaker<'a> {
-    fn into_waker(self) -> Waker {
-        use core::task::{RawWaker, RawWakerVTable};
-
-        // Convert TaskWaker to raw pointer
-        let ptr = Box::into_raw(Box::new(self)) as *const ();
-
-        // Define vtable with wake, clone, etc. functions
-        const VTABLE: RawWakerVTable = RawWakerVTable::new(
-            // Clone
-            |ptr| {
-                let original = unsafe { &*(ptr as *const TaskWaker) };
-                let cloned = TaskWaker {
-                    task: original.task,
-                    executor: original.executor,
-                };
-                let ptr = Box::into_raw(Box::new(cloned)) as *const ();
-                RawWaker::new(ptr, &VTABLE)
-            },
-            // Wake
-            |ptr| {
-                let waker = unsafe { Box::from_raw(ptr as *mut TaskWaker) };
-                waker.wake_task();
-            },
-            // Wake by reference
-            |ptr| {
-                let waker = unsafe { &*(ptr as *const TaskWaker) };
-                waker.wake_task_by_ref();
-            },
-            // Drop
-            |ptr| {
-                unsafe {
-                    drop(Box::from_raw(ptr as *mut TaskWaker));
-                };
-            },
-        );
+    }
 
-        unsafe { Waker::from_raw(RawWaker::new(ptr, &VTABLE)) }
+    /// Drops the future of an aborted task, unless it has completed. The
+    /// output sender is dropped with it, which makes the [`JoinHandle`]
+    /// resolve to [`JoinError::Cancelled`].
+    fn cancel(&self) {
+        if let Some(future) = self.take_future() {
+            drop(future);
+            tasks::exit(self.id, TaskExit::Aborted);
+        }
     }
 
-    fn wake_task(self) {
-        // Mark the task as woken and queue it for execution
-        unsafe {
-            (*self.task).was_woken = true;
+    /// Removes an aborted task from its executor, unless it has completed,
+    /// and returns its future for the caller to drop, not under the lock: its
+    /// destructors may wake other tasks.
+    fn take_future(&self) -> Option<BoxFuture<()>> {
+        let future = self.future.lock().take()?;
+        self.state.fetch_or(COMPLETED, Ordering::AcqRel);
+        signal::unregister_task(self.id);
+        task_local::clear(self.id);
+        // SAFETY: We ensure the executor always lives as long as the task
+        let executor = unsafe { &*self.executor };
+        executor.all_tasks.lock().remove(&self.id);
+        executor.live_tasks.fetch_sub(1, Ordering::Release);
+        executor.wake_spawn_waiters();
+        Some(future)
+    }
+}
 
-            // Create a clone of the task to queue
-            let future = core::ptr::read(&(*self.task).future);
-            let task = Task {
-                future,
-                executor: (*self.task).executor,
-                was_woken: true,
-            };
+/// The vtable of the wakers of the tasks. Unlike those made with [`Wake`],
+/// their clones are tracked, see [`WAKER_CLONED`].
+static TASK_WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_waker, wake_waker, wake_waker_by_ref, drop_waker);
+
+// SAFETY (all of them): `ptr` comes from `Arc::into_raw` of a task, whose
+// reference is held by the waker.
+unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
+    let task = unsafe { &*ptr.cast::<Task>() };
+    task.state.fetch_or(WAKER_CLONED, Ordering::Relaxed);
+    unsafe { Arc::increment_strong_count(ptr.cast::<Task>()) };
+    RawWaker::new(ptr, &TASK_WAKER_VTABLE)
+}
 
-            self.executor.queue_task(task);
-        }
+unsafe fn wake_waker(ptr: *const ()) {
+    let task = unsafe { Arc::from_raw(ptr.cast::<Task>()) };
+    task.wake_by_ref();
+}
+
+unsafe fn wake_waker_by_ref(ptr: *const ()) {
+    let task = ManuallyDrop::new(unsafe { Arc::from_raw(ptr.cast::<Task>()) });
+    task.wake_by_ref();
+}
+
+unsafe fn drop_waker(ptr: *const ()) {
+    unsafe { Arc::decrement_strong_count(ptr.cast::<Task>()) };
+}
+
+impl Task {
+    /// Returns a waker of the task.
+    fn waker(self: Arc<Self>) -> Waker {
+        let raw = RawWaker::new(Arc::into_raw(self).cast(), &TASK_WAKER_VTABLE);
+        // SAFETY: The vtable keeps the reference of the task.
+        unsafe { Waker::from_raw(raw) }
     }
 
-    fn wake_task_by_ref(&self) {
-        unsafe {
-            if !(*self.task).was_woken {
-                (*self.task).was_woken = true;
+    fn wake_by_ref(self: &Arc<Self>) {
+        // Woken again once the deferral ends, see `defer_wakes`.
+        if crate::batch::defer_wake(|| self.clone().waker()) {
+            return;
+        }
+        let _use = ExecutorUse::enter(&self.executor_uses);
+        let prev = self.state.fetch_or(SCHEDULED, Ordering::AcqRel);
+        if prev & COMPLETED != 0 {
+            // A waker may outlive its task, and the executor with it: a
+            // completed task does not touch the executor again, and the
+            // executor cancels all its tasks when dropped.
+            return;
+        }
+        // SAFETY: The executor is not dropped before the use ends, and the
+        // task is not completed.
+        let executor = unsafe { &*self.executor };
+        if prev & (SCHEDULED | RUNNING) == 0 {
+            // Woken by an IRQ handler, it is not run before the interrupted
+            // poll returns, whatever its priority: preempting the poller's axtask
+            // would not run it, the executor does not have a worker of its
+            // own to switch to.
+            executor.queue_task(self.clone());
+        } else {
+            executor.coalesced_wakes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
 
-                // Create a clone of the task to queue
-                let future = core::ptr::read(&(*self.task).future);
-                let task = Task {
-                    future,
-                    executor: (*self.task).executor,
-                    was_woken: true,
-                };
+/// The error of a [`JoinHandle`] whose task did not complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was aborted, see [`AbortHandle::abort`].
+    Cancelled,
+    /// A poll of the task panicked, and the panic was caught by the executor
+    /// (requires `unwind`). Without it, a panic stops the kernel.
+    Panicked,
+}
 
-                self.executor.queue_task(task);
-            }
+impl core::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Cancelled => f.write_str("task cancelled"),
+            Self::Panicked => f.write_str("task panicked"),
         }
     }
 }
 
-/// A handle to a spawned task.
+/// A handle to a spawned task, that resolves to its output.
+///
+/// Dropping the handle detaches the task, it keeps running.
 pub struct JoinHandle<T> {
-    receiver: channel::oneshot::Receiver<T>,
+    id: TaskId,
+    receiver: channel::oneshot::Receiver<Result<T, JoinError>>,
+    // Weak, so that it is not counted as a waker of the task.
+    task: Weak<Task>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns the ID of the task, e.g. to [`signal`](crate::signal()) it.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Aborts the task, see [`AbortHandle::abort`].
+    pub fn abort(&self) {
+        self.abort_handle().abort();
+    }
+
+    /// Returns a handle that aborts the task, e.g. from another task.
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle {
+            id: self.id,
+            task: self.task.clone(),
+        }
+    }
 }
 
 impl<T: Send + 'static> Future for JoinHandle<T> {
-    type Output = T;
+    type Output = Result<T, JoinError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.receiver.poll(cx) {
-            Poll::Ready(Ok(value)) => Poll::Ready(value),
-            Poll::Ready(Err(_)) => panic!("Task failed to complete"),
-            Poll::Pending => Poll::Pending,
+        // The output is only dropped unsent if the task is aborted.
+        self.receiver
+            .poll(cx)
+            .map(|res| res.unwrap_or(Err(JoinError::Cancelled)))
+    }
+}
+
+/// A handle that aborts a spawned task, see [`JoinHandle::abort_handle`].
+#[derive(Clone)]
+pub struct AbortHandle {
+    id: TaskId,
+    task: Weak<Task>,
+}
+
+impl AbortHandle {
+    /// Returns the ID of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Aborts the task: it is removed from the executor and its future is
+    /// dropped, so that it is never polled again, and its [`JoinHandle`]
+    /// resolves to [`JoinError::Cancelled`].
+    ///
+    /// A task being polled is aborted when the poll returns. It does nothing
+    /// if the task has completed.
+    pub fn abort(&self) {
+        if let Some(task) = self.task.upgrade() {
+            task.abort();
         }
     }
 }
@@ -363,6 +2172,8 @@ pub mod channel {
         struct Inner<T> {
             value: UnsafeCell<Option<T>>,
             complete: AtomicBool,
+            /// The sender is dropped without sending.
+            closed: AtomicBool,
             waker: Mutex<Option<Waker>>,
         }
 
@@ -374,6 +2185,7 @@ pub mod channel {
             let inner = Arc::new(Inner {
                 value: UnsafeCell::new(None),
                 complete: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
                 waker: Mutex::new(None),
             });
 
@@ -406,13 +2218,36 @@ pub mod channel {
             }
         }
 
+        impl<T> Drop for Sender<T> {
+            fn drop(&mut self) {
+                if !self.inner.complete.load(Ordering::Acquire) {
+                    self.inner.closed.store(true, Ordering::Release);
+                    if let Some(waker) = self.inner.waker.lock().take() {
+                        waker.wake();
+                    }
+                }
+            }
+        }
+
         impl<T> Receiver<T> {
+            /// Returns `Err` if the sender is dropped without sending.
             pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, ()>> {
+                if let Poll::Ready(res) = self.try_take() {
+                    return Poll::Ready(res);
+                }
+                *self.inner.waker.lock() = Some(cx.waker().clone());
+                // Check again, the sender may have finished before the waker
+                // was registered.
+                self.try_take()
+            }
+
+            fn try_take(&mut self) -> Poll<Result<T, ()>> {
                 if self.inner.complete.load(Ordering::Acquire) {
                     let value = unsafe { (*self.inner.value.get()).take() };
-                    Poll::Ready(Ok(value.unwrap()))
+                    Poll::Ready(value.ok_or(()))
+                } else if self.inner.closed.load(Ordering::Acquire) {
+                    Poll::Ready(Err(()))
                 } else {
-                    *self.inner.waker.lock() = Some(cx.waker().clone());
                     Poll::Pending
                 }
             }
@@ -448,8 +2283,6 @@ where
 
 /// Creates a new [`Waker`] that is a no-op.
 pub fn dummy_waker() -> Waker {
-    use core::task::{RawWaker, RawWakerVTable};
-
     const VTABLE: RawWakerVTable = RawWakerVTable::new(
         |_| RawWaker::new(core::ptr::null(), &VTABLE),
         |_| {},
@@ -468,3 +2301,90 @@ where
     let mut cx = Context::from_waker(&waker);
     unsafe { Pin::new_unchecked(fut) }.poll(&mut cx)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_injected_wakes() {
+        let executor = Executor::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for i in 0..3 {
+            let order = order.clone();
+            let mut parked = false;
+            let mut waker = None;
+            handles.push(executor.spawn(core::future::poll_fn(move |cx| {
+                if core::mem::replace(&mut parked, true) {
+                    order.lock().push(i);
+                    return Poll::Ready(());
+                }
+                // Kept, so that it is parked until woken.
+                waker = Some(cx.waker().clone());
+                Poll::Pending
+            })));
+        }
+        while executor.step() {}
+
+        // Woken as by interrupt handlers, in reverse order.
+        for handle in handles.iter().rev() {
+            let task = executor.all_tasks.lock()[&handle.id()].clone();
+            task.state.fetch_or(SCHEDULED, Ordering::AcqRel);
+            executor.injected.push(task);
+        }
+        assert!(executor.has_queued_tasks());
+        while executor.step() {}
+        assert_eq!(*order.lock(), [2, 1, 0]);
+        assert!(executor.injected.is_empty());
+    }
+
+    #[test]
+    fn test_spawn_from_irq() {
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+        async fn work(arg: usize) {
+            SUM.fetch_add(arg, Ordering::Relaxed);
+        }
+
+        let executor = Executor::new();
+        for arg in 1..=IRQ_SPAWN_CAPACITY {
+            executor.spawn_from_irq(work, arg).unwrap();
+        }
+        assert_eq!(executor.spawn_from_irq(work, 1), Err(AxError::WouldBlock));
+        assert!(executor.has_queued_tasks());
+        while executor.step() {}
+        let n = IRQ_SPAWN_CAPACITY;
+        assert_eq!(SUM.load(Ordering::Relaxed), n * (n + 1) / 2);
+
+        // The slots are reused.
+        executor.spawn_from_irq(work, 0).unwrap();
+        while executor.step() {}
+        assert!(executor.irq_spawns.is_empty());
+    }
+
+    #[test]
+    fn test_wake_after_executor_dropped() {
+        let executor = Executor::new();
+        let waker = Arc::new(Mutex::new(None));
+        let parked = {
+            let waker = waker.clone();
+            executor.spawn(core::future::poll_fn(move |cx| {
+                *waker.lock() = Some(cx.waker().clone());
+                Poll::<()>::Pending
+            }))
+        };
+        // Cancelled while queued: popping it must not clear `COMPLETED`.
+        let queued = executor.spawn(async {});
+        queued.abort();
+        while executor.step() {}
+        drop(executor);
+
+        // As by a timer or a socket, after the executor is gone.
+        let waker = waker.lock().take().unwrap();
+        waker.wake_by_ref();
+        parked.abort();
+        waker.wake();
+        assert_eq!(block_on(parked), Err(JoinError::Cancelled));
+        assert_eq!(block_on(queued), Err(JoinError::Cancelled));
+    }
+}
@@ -0,0 +1,381 @@
+//! Async byte streams.
+//!
+//! The streams fail with an [`AxError`] code, which is `Copy` and never
+//! allocates, so that errors cost nothing on the packet path.
+
+mod bytes;
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker, ready};
+
+use axerrno::ax_err;
+use kspin::SpinNoIrq;
+
+/// The error types of the streams, re-exported for crates that use another
+/// version of `axerrno`.
+pub use axerrno::{AxError, AxResult};
+pub use bytes::{Bytes, BytesMut};
+
+/// The number of bytes reserved by [`AsyncRead::poll_read_buf`] in a full
+/// buffer.
+const READ_BUF_RESERVE: usize = 4096;
+
+/// The default limit of [`read_to_end`], see [`set_read_to_end_limit`].
+pub const DEFAULT_READ_TO_END_LIMIT: usize = 16 * 1024 * 1024;
+
+/// The limit of [`read_to_end`] in bytes.
+static READ_TO_END_LIMIT: AtomicUsize = AtomicUsize::new(DEFAULT_READ_TO_END_LIMIT);
+
+/// Sets the most bytes [`read_to_end`] reads, or removes the limit with
+/// `None`.
+pub fn set_read_to_end_limit(limit: Option<usize>) {
+    READ_TO_END_LIMIT.store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// A source of bytes that can be read asynchronously.
+pub trait AsyncRead {
+    /// Attempts to read into `buf`, returns the number of bytes read, `0` at
+    /// end of stream.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>>;
+
+    /// Attempts to read into the spare capacity of `buf`, appending to its
+    /// bytes, returns the number of bytes read, `0` at end of stream.
+    ///
+    /// A full buffer is grown first. The default implementation zero-fills
+    /// the spare capacity and reads into it with
+    /// [`poll_read`](Self::poll_read); streams that can copy into
+    /// uninitialized memory override it.
+    fn poll_read_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<AxResult<usize>> {
+        if buf.len() == buf.capacity() {
+            buf.reserve(READ_BUF_RESERVE);
+        }
+        let spare = buf.spare_capacity_mut();
+        spare.fill(MaybeUninit::new(0));
+        // SAFETY: The spare capacity is initialized.
+        let dst = unsafe { &mut *(spare as *mut [MaybeUninit<u8>] as *mut [u8]) };
+        let n = ready!(self.poll_read(cx, dst))?;
+        // SAFETY: The bytes read are initialized.
+        unsafe { buf.advance_mut(n) };
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// A sink of bytes that can be written asynchronously.
+pub trait AsyncWrite {
+    /// Attempts to write `buf`, returns the number of bytes written.
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>>;
+
+    /// Attempts to flush the written bytes to their destination.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult>;
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for &mut T {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+
+    fn poll_read_buf(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut **self).poll_read_buf(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut T {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+}
+
+impl AsyncWrite for Vec<u8> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<AxResult<usize>> {
+        self.get_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<AxResult> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads `reader` to its end, appending to `buf`, returns the number of
+/// bytes read.
+///
+/// It fails with [`FileTooLarge`](AxError::FileTooLarge) past the limit set
+/// with [`set_read_to_end_limit`], see [`read_to_end_limited`].
+pub async fn read_to_end<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut BytesMut,
+) -> AxResult<usize> {
+    read_to_end_limited(reader, buf, READ_TO_END_LIMIT.load(Ordering::Relaxed)).await
+}
+
+/// Reads `reader` to its end, appending to `buf`, returns the number of
+/// bytes read, or fails with [`FileTooLarge`](AxError::FileTooLarge) if
+/// there are more than `max_len`.
+///
+/// A peer cannot make it grow `buf` without bounds: the buffer grows by at
+/// most one read past `max_len` before it fails. The bytes read are left in
+/// `buf` on failure.
+pub async fn read_to_end_limited<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut BytesMut,
+    max_len: usize,
+) -> AxResult<usize> {
+    let start = buf.len();
+    loop {
+        let read = buf.len() - start;
+        if read == max_len {
+            // Only the end of stream fits.
+            let mut probe = [0; 1];
+            return match poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut probe)).await? {
+                0 => Ok(read),
+                _ => ax_err!(FileTooLarge, "read_to_end: too long"),
+            };
+        }
+        if buf.len() == buf.capacity() {
+            buf.reserve((max_len - read).min(READ_BUF_RESERVE));
+        }
+        if poll_fn(|cx| Pin::new(&mut *reader).poll_read_buf(cx, buf)).await? == 0 {
+            return Ok(read);
+        }
+        if buf.len() - start > max_len {
+            return ax_err!(FileTooLarge, "read_to_end: too long");
+        }
+    }
+}
+
+/// One direction of a [`LocalStream`].
+struct Channel {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// The task waiting for data.
+    read_waker: Option<Waker>,
+    /// The task waiting for space.
+    write_waker: Option<Waker>,
+    /// Whether the end that reads from the channel is dropped.
+    reader_closed: bool,
+    /// Whether the end that writes to the channel is dropped.
+    writer_closed: bool,
+}
+
+impl Channel {
+    fn new(capacity: usize) -> Arc<SpinNoIrq<Self>> {
+        Arc::new(SpinNoIrq::new(Self {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+            read_waker: None,
+            write_waker: None,
+            reader_closed: false,
+            writer_closed: false,
+        }))
+    }
+}
+
+/// One end of an in-memory, bidirectional byte stream, created by
+/// [`LocalStream::pair`].
+///
+/// It connects components on the same machine through the same interface as
+/// a socket. Reading returns the end of stream once the other end is dropped
+/// and the data is drained, writing fails with [`BrokenPipe`] once the other
+/// end is dropped.
+///
+/// [`BrokenPipe`]: axerrno::AxError::BrokenPipe
+pub struct LocalStream {
+    rx: Arc<SpinNoIrq<Channel>>,
+    tx: Arc<SpinNoIrq<Channel>>,
+}
+
+impl LocalStream {
+    /// The default capacity of each direction, in bytes.
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// Creates a connected pair of streams.
+    pub fn pair() -> (Self, Self) {
+        Self::pair_with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    /// Creates a connected pair of streams, buffering at most `capacity`
+    /// bytes in each direction.
+    pub fn pair_with_capacity(capacity: usize) -> (Self, Self) {
+        let capacity = capacity.max(1);
+        let (a, b) = (Channel::new(capacity), Channel::new(capacity));
+        (
+            Self {
+                rx: a.clone(),
+                tx: b.clone(),
+            },
+            Self { rx: b, tx: a },
+        )
+    }
+}
+
+impl AsyncRead for LocalStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>> {
+        ready!(crate::coop::poll_proceed(cx));
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut rx = self.rx.lock();
+        if rx.data.is_empty() {
+            if rx.writer_closed {
+                return Poll::Ready(Ok(0));
+            }
+            rx.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(rx.data.len());
+        for (dst, src) in buf.iter_mut().zip(rx.data.drain(..n)) {
+            *dst = src;
+        }
+        let waker = rx.write_waker.take();
+        drop(rx);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_read_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<AxResult<usize>> {
+        ready!(crate::coop::poll_proceed(cx));
+        let mut rx = self.rx.lock();
+        if rx.data.is_empty() {
+            if rx.writer_closed {
+                return Poll::Ready(Ok(0));
+            }
+            rx.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        // Take all the data, the buffer grows as needed.
+        let n = rx.data.len();
+        let (front, back) = rx.data.as_slices();
+        buf.extend_from_slice(front);
+        buf.extend_from_slice(back);
+        rx.data.clear();
+        let waker = rx.write_waker.take();
+        drop(rx);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for LocalStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        ready!(crate::coop::poll_proceed(cx));
+        let mut tx = self.tx.lock();
+        if tx.reader_closed {
+            return Poll::Ready(ax_err!(BrokenPipe, "LocalStream: peer dropped"));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let space = tx.capacity - tx.data.len();
+        if space == 0 {
+            tx.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(space);
+        tx.data.extend(&buf[..n]);
+        let waker = tx.read_waker.take();
+        drop(tx);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<AxResult> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for LocalStream {
+    fn drop(&mut self) {
+        let mut wakers = [None, None];
+        {
+            let mut rx = self.rx.lock();
+            rx.reader_closed = true;
+            wakers[0] = rx.write_waker.take();
+        }
+        {
+            let mut tx = self.tx.lock();
+            tx.writer_closed = true;
+            wakers[1] = tx.read_waker.take();
+        }
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_to_end_limited() {
+        futures_executor::block_on(async {
+            let (mut a, mut b) = LocalStream::pair();
+            poll_fn(|cx| Pin::new(&mut a).poll_write(cx, b"hello"))
+                .await
+                .unwrap();
+            drop(a);
+            let mut buf = BytesMut::new();
+            assert_eq!(read_to_end_limited(&mut b, &mut buf, 5).await, Ok(5));
+            assert_eq!(&buf[..], b"hello");
+
+            let (mut a, mut b) = LocalStream::pair();
+            poll_fn(|cx| Pin::new(&mut a).poll_write(cx, b"hello!"))
+                .await
+                .unwrap();
+            drop(a);
+            let mut buf = BytesMut::new();
+            assert_eq!(
+                read_to_end_limited(&mut b, &mut buf, 5).await,
+                Err(AxError::FileTooLarge)
+            );
+        });
+    }
+}
@@ -0,0 +1,403 @@
+//! Reference-counted byte buffers.
+//!
+//! A [`BytesMut`] is filled by reads without zero-filling its spare capacity
+//! first, and split into frames that share its allocation. A frozen frame is
+//! a [`Bytes`], which is cloned and sliced without copying the data.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
+use core::{fmt, ptr, slice};
+
+/// The allocation shared by the buffers split from one another.
+///
+/// Its length stays `0` when it is allocated by a [`BytesMut`], so that the
+/// `Vec` never touches the bytes, which are accessed through raw pointers.
+type Shared = Arc<Vec<u8>>;
+
+/// An immutable, cheaply cloneable slice of bytes.
+#[derive(Clone)]
+pub struct Bytes {
+    shared: Option<Shared>,
+    ptr: *const u8,
+    len: usize,
+}
+
+// SAFETY: The bytes are never mutated once in a `Bytes`.
+unsafe impl Send for Bytes {}
+unsafe impl Sync for Bytes {}
+
+impl Bytes {
+    /// Creates an empty slice.
+    pub const fn new() -> Self {
+        Self {
+            shared: None,
+            ptr: ptr::NonNull::dangling().as_ptr(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no bytes.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bytes in `range`, sharing the allocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "range {}..{} out of bounds of {} bytes",
+            start,
+            end,
+            self.len
+        );
+        Self {
+            shared: self.shared.clone(),
+            // SAFETY: `start` is in bounds.
+            ptr: unsafe { self.ptr.add(start) },
+            len: end - start,
+        }
+    }
+
+    /// Splits off the first `at` bytes, and returns them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let head = self.slice(..at);
+        *self = self.slice(at..);
+        head
+    }
+
+    /// Splits off the bytes from `at`, and returns them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let tail = self.slice(at..);
+        self.len = at;
+        tail
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: The bytes are initialized, and kept alive by `shared`.
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(vec: Vec<u8>) -> Self {
+        let (ptr, len) = (vec.as_ptr(), vec.len());
+        Self {
+            shared: Some(Arc::new(vec)),
+            ptr,
+            len,
+        }
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(bytes: &[u8]) -> Self {
+        bytes.to_vec().into()
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Bytes {}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b\"{}\"", self.escape_ascii())
+    }
+}
+
+/// A growable buffer of bytes, that can be split without copying.
+///
+/// The buffers split from one another share their allocation, but each one
+/// owns a distinct part of it.
+pub struct BytesMut {
+    shared: Option<Shared>,
+    ptr: *mut u8,
+    /// Number of initialized bytes.
+    len: usize,
+    /// Number of bytes owned, from `ptr`.
+    cap: usize,
+}
+
+// SAFETY: The bytes from `ptr` to `ptr + cap` are only accessed through this
+// buffer.
+unsafe impl Send for BytesMut {}
+unsafe impl Sync for BytesMut {}
+
+impl BytesMut {
+    /// Creates an empty buffer, it does not allocate.
+    pub const fn new() -> Self {
+        Self {
+            shared: None,
+            ptr: ptr::NonNull::dangling().as_ptr(),
+            len: 0,
+            cap: 0,
+        }
+    }
+
+    /// Creates an empty buffer that can hold `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity == 0 {
+            return Self::new();
+        }
+        let mut vec = Vec::with_capacity(capacity);
+        let ptr = vec.as_mut_ptr();
+        Self {
+            shared: Some(Arc::new(vec)),
+            ptr,
+            len: 0,
+            cap: capacity,
+        }
+    }
+
+    /// Returns the number of bytes.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no bytes.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes the buffer can hold without reallocating.
+    pub const fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Makes room for at least `additional` more bytes.
+    ///
+    /// The bytes are moved to a new allocation if they do not fit in the
+    /// part of the current one that the buffer owns. The buffers split from
+    /// it keep the old allocation.
+    pub fn reserve(&mut self, additional: usize) {
+        if self.cap - self.len >= additional {
+            return;
+        }
+        let mut other = Self::with_capacity((self.len + additional).max(self.cap * 2));
+        other.extend_from_slice(self);
+        *self = other;
+    }
+
+    /// Appends `bytes`.
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+        // SAFETY: There is room for `bytes`, which cannot overlap with the
+        // spare capacity since it is not initialized.
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(self.len), bytes.len());
+        }
+        self.len += bytes.len();
+    }
+
+    /// Returns the spare capacity, to be filled and committed with
+    /// [`advance_mut`](Self::advance_mut).
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        // SAFETY: The buffer owns the bytes up to `cap`.
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.ptr.add(self.len).cast::<MaybeUninit<u8>>(),
+                self.cap - self.len,
+            )
+        }
+    }
+
+    /// Commits `n` bytes written to the spare capacity.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` bytes of the spare capacity must be initialized.
+    pub unsafe fn advance_mut(&mut self, n: usize) {
+        debug_assert!(n <= self.cap - self.len);
+        self.len += n;
+    }
+
+    /// Removes all the bytes, keeping the capacity.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Splits off the first `at` bytes, and returns them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split at {} of {} bytes", at, self.len);
+        let head = Self {
+            shared: self.shared.clone(),
+            ptr: self.ptr,
+            len: at,
+            cap: at,
+        };
+        // SAFETY: `at` is in bounds.
+        self.ptr = unsafe { self.ptr.add(at) };
+        self.len -= at;
+        self.cap -= at;
+        head
+    }
+
+    /// Splits off the bytes from `at`, and returns them with the spare
+    /// capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split at {} of {} bytes", at, self.len);
+        let tail = Self {
+            shared: self.shared.clone(),
+            // SAFETY: `at` is in bounds.
+            ptr: unsafe { self.ptr.add(at) },
+            len: self.len - at,
+            cap: self.cap - at,
+        };
+        self.len = at;
+        self.cap = at;
+        tail
+    }
+
+    /// Splits off all the bytes, and returns them. The buffer keeps the
+    /// spare capacity.
+    pub fn split(&mut self) -> Self {
+        self.split_to(self.len)
+    }
+
+    /// Converts the buffer into an immutable [`Bytes`].
+    pub fn freeze(self) -> Bytes {
+        Bytes {
+            shared: self.shared,
+            ptr: self.ptr,
+            len: self.len,
+        }
+    }
+}
+
+impl Default for BytesMut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for BytesMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: The first `len` bytes are initialized and owned.
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for BytesMut {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: The first `len` bytes are initialized and owned.
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for BytesMut {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<&[u8]> for BytesMut {
+    fn from(bytes: &[u8]) -> Self {
+        let mut buf = Self::with_capacity(bytes.len());
+        buf.extend_from_slice(bytes);
+        buf
+    }
+}
+
+impl fmt::Debug for BytesMut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b\"{}\"", self.escape_ascii())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_shares_allocation() {
+        let mut buf = BytesMut::with_capacity(16);
+        buf.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let line = buf.split_to(16).freeze();
+        assert_eq!(buf.capacity(), 0);
+        assert_eq!(&*line.slice(..3), b"GET");
+        assert_eq!(line.slice(4..5).as_ptr(), line[4..].as_ptr());
+
+        // The frozen bytes are not overwritten by a reallocation.
+        buf.extend_from_slice(b"Host: arceos\r\n");
+        assert_eq!(&*buf, b"Host: arceos\r\n");
+        assert_eq!(&*line, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_spare_capacity() {
+        let mut buf = BytesMut::with_capacity(4);
+        let spare = buf.spare_capacity_mut();
+        assert_eq!(spare.len(), 4);
+        spare[0].write(b'a');
+        spare[1].write(b'b');
+        unsafe { buf.advance_mut(2) };
+        let tail = buf.split_off(1);
+        assert_eq!((&*buf, &*tail), (b"a".as_slice(), b"b".as_slice()));
+        assert_eq!(tail.capacity(), 3);
+    }
+}
@@ -2,15 +2,33 @@
 //!
 //! This module provides a lightweight async runtime that resembles the design of
 //! [embassy-rs](https://github.com/embassy-rs/embassy), but tailored for ArceOS.
+//! Like in embassy, tasks can also live in static storage, without the heap,
+//! see [`TaskPool`].
+//!
+//! In the hosted test harness, `#[axasync::test]` (or `#[axasync::async_test]`)
+//! runs an async test on a fresh executor, and fails it at a timeout, see
+//! [`async_test`].
 //!
 //! # Cargo Features
 //!
-//! - `multitask`: Enable multi-task support.
-//! - `irq`: Enable interrupt handling support.
-//! - `timer`: Enable async timer functionality (requires `irq`).
+//! - `multitask`: Enable multi-task support, and `spawn_blocking` to run
+//!   blocking calls on a pool of axtasks, or `block_in_place` to run them in
+//!   a poll.
+//! - `irq`: Enable interrupt handling support. Without it, the executor polls
+//!   the timers and the devices between its time slices, see [`polling`].
+//!   With it, an idle executor parks until a task is woken, see `park`, as
+//!   does `Executor::block_on` with `multitask` too.
+//! - `timer`: Enable async timer functionality.
 //! - `file`: Enable async filesystem functionality.
 //! - `net`: Enable async networking functionality.
 //! - `mmio`: Enable async MMIO functionality (requires `irq`).
+//! - `serde`: Enable serialization of messages with postcard, see [`codec`].
+//! - `unwind`: Catch the panics of the tasks, so that they do not stop the
+//!   kernel, see `unwind`.
+//! - `deterministic`: Enable pinning the order of the polls of an executor in
+//!   tests, see `Schedule`.
+//! - `memwatch`: Enable watching the free memory, and publishing the pressure
+//!   on it to the other modules, see `mem`.
 
 #![no_std]
 #![feature(doc_auto_cfg)]
@@ -21,9 +39,35 @@ extern crate axlog;
 
 extern crate alloc;
 
+// For the expansion of `#[axasync::async_test]` in the tests.
+#[cfg(test)]
+extern crate self as axasync;
+#[cfg(test)]
+extern crate std;
+
+mod arena;
+mod batch;
+#[cfg(feature = "multitask")]
+mod blocking;
+mod cleanup;
+pub mod codec;
+mod combinator;
+pub mod coop;
 pub mod executor;
+pub mod io;
+#[cfg(feature = "memwatch")]
+pub mod mem;
+#[cfg(any(feature = "irq", feature = "multitask"))]
+pub mod park;
+pub mod polling;
+mod scope;
+mod signal;
 pub mod sync;
+mod task_local;
+mod tasks;
 pub mod time;
+#[cfg(feature = "unwind")]
+pub mod unwind;
 mod waker;
 use alloc::collections::BinaryHeap;
 use core::pin::Pin;
@@ -32,10 +76,36 @@ use core::task::{Context, Poll};
 #[cfg(feature = "mmio")]
 pub mod mmio;
 
+pub use arena::{
+    DEFAULT_TASK_SIZE, StaticTasks, TaskPool, poll_static_tasks, run_static, static_tasks,
+};
+pub use axasync_macros::async_test;
+/// The same as [`async_test`], for `#[axasync::test]`.
+pub use axasync_macros::async_test as test;
+pub use batch::{MAX_DEFERRED_WAKES, WakeBatch, defer_wakes, wake_batch};
+#[cfg(feature = "multitask")]
+pub use blocking::{MAX_BLOCKING_WORKERS, block_in_place, spawn_blocking};
+pub use cleanup::{CleanupGuard, defer};
+pub use combinator::{
+    Either, Either3, Join2, Join3, Select2, Select3, join2, join3, select2, select3,
+};
+pub use coop::{
+    DEFAULT_POLL_BUDGET, consume_budget, on_timer_tick, poll_proceed, set_poll_budget,
+    set_preemption, yield_now,
+};
 pub use executor::{
+    AbortHandle,
     BoxFuture,
+    DEFAULT_SHUTDOWN_GRACE_PERIOD,
+    DEFAULT_SLOW_POLL_THRESHOLD,
     Executor,
+    ExecutorStats,
+    IRQ_SPAWN_CAPACITY,
+    JoinError,
     JoinHandle,
+    LocalSet,
+    OverflowPolicy,
+    Priority,
     // Global executor functions
     block_on,
     dummy_waker,
@@ -44,13 +114,39 @@ pub use executor::{
     poll_once,
     run as executor_run,
     run_local,
+    run_until,
+    set_shutdown_grace_period,
+    set_slow_poll_threshold,
     spawn,
+    spawn_async,
+    spawn_from_irq,
     spawn_local,
+    spawn_named,
+    spawn_with_deadline,
+    spawn_with_priority,
+    try_spawn,
 };
+#[cfg(feature = "deterministic")]
+pub use executor::{MAX_POLL_TRACE, Schedule};
 pub use futures_util;
+pub use io::{
+    AsyncRead, AsyncWrite, Bytes, BytesMut, LocalStream, read_to_end, read_to_end_limited,
+};
+pub use polling::register_poll_hook;
+pub use scope::{Scope, ScopedJoinHandle, scope};
+pub use signal::{
+    Interruptible, Sig, SigSet, TaskId, current_task_id, interruptible, pending_signals,
+    set_signal_mask, signal, take_signal,
+};
+pub use task_local::{AccessError, LocalKey};
+pub use tasks::{
+    TaskExit, TaskInfo, TaskState, dump_tasks, register_exit_hook, task_list, task_name,
+};
 pub use time::{TimeoutExt, sleep};
 pub use waker::*;
 
+pub use axhal::misc::ResetReason;
+
 // Timer event definition for our TimerList implementation
 #[cfg(feature = "timer")]
 use axhal::time::TimeValue;
@@ -79,13 +175,19 @@ impl<E: TimerEvent> TimerList<E> {
         }
     }
 
+    /// Sets `event` to be fired at `deadline`.
+    ///
+    /// A deadline past the range of the clock in nanoseconds is clamped to
+    /// it, so that it is never truncated to an earlier time. A deadline
+    /// further than [`MAX_TIMER_HORIZON`](axhal::time::MAX_TIMER_HORIZON) is
+    /// reached through the periodic timer interrupts.
     pub fn set(&self, deadline: TimeValue, event: E) {
+        let deadline = deadline.min(TimeValue::from_nanos(u64::MAX));
         {
             let entry = TimerEventEntry { deadline, event };
             let mut events = self.events.borrow_mut();
             let _ = events.push(entry); // Ignore if the heap is full
         }
-        // self.set_timer();
     }
 
     pub fn expire_one(&self, now: TimeValue) -> Option<(TimeValue, E)> {
@@ -102,15 +204,13 @@ impl<E: TimerEvent> TimerList<E> {
             };
             entry
         };
-        // self.set_timer();
         Some((entry.deadline, entry.event))
     }
 
-    fn set_timer(&self) {
-        if let Some(entry) = self.events.borrow().peek() {
-            debug!("Setting timer for {:?}", entry.deadline);
-            axhal::time::set_oneshot_timer(entry.deadline.as_nanos() as u64);
-        }
+    /// Returns the earliest deadline of the events, for the executor to
+    /// program the hardware timer before it parks.
+    pub fn next_deadline(&self) -> Option<TimeValue> {
+        self.events.borrow().peek().map(|entry| entry.deadline)
     }
 }
 
@@ -145,14 +245,44 @@ pub fn init() {
 }
 
 /// Shutdown the async runtime.
+///
+/// It shuts the global executor down (see [`Executor::shutdown`]), running
+/// its ready tasks for the [grace period](set_shutdown_grace_period) and
+/// aborting the rest. Then it executes the teardown callbacks registered by
+/// subsystems with `axruntime::on_shutdown` (see
+/// [`axhal::misc::on_shutdown`]), in reverse order of registration.
+///
+/// [`init`] restarts the runtime.
 pub fn shutdown() {
+    executor::shutdown_global();
+    axhal::misc::run_shutdown_hooks();
     info!("Async runtime shut down");
 }
 
+/// Powers the system off, after shutting the async runtime down with
+/// [`shutdown`].
+///
+/// It may be called from a task, e.g. of a shell: the other ready tasks run
+/// for the grace period, then all the tasks are aborted and the teardown
+/// callbacks run, before the power is cut.
+pub fn poweroff() -> ! {
+    shutdown();
+    axhal::misc::terminate()
+}
+
+/// Reboots the system for `reason`, after shutting the async runtime down
+/// like [`poweroff`], see [`axhal::misc::reboot`].
+pub fn reboot(reason: ResetReason) -> ! {
+    shutdown();
+    axhal::misc::reboot(reason)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use alloc::sync::Arc;
+    // The built-in one, not `crate::test`.
+    use core::prelude::v1::test;
     use core::sync::atomic::{AtomicBool, Ordering};
 
     #[test]
@@ -175,4 +305,643 @@ mod tests {
         // Verify the task completed
         assert!(completed.load(Ordering::SeqCst));
     }
+
+    #[test]
+    fn test_run_queue_capacity() {
+        let executor = Executor::new();
+        executor.set_capacity(Some(1), OverflowPolicy::Reject);
+
+        let _handle = executor.try_spawn(async {}).unwrap();
+        let res = executor.try_spawn(async {});
+        assert_eq!(res.err(), Some(axerrno::AxError::WouldBlock));
+
+        executor.run();
+        let stats = executor.stats();
+        assert_eq!(stats.queue_high_watermark, 1);
+        assert_eq!(stats.rejected_spawns, 1);
+        assert!(executor.try_spawn(async {}).is_ok());
+    }
+
+    #[test]
+    fn test_max_tasks() {
+        let executor = Executor::new_with_capacity(1);
+        executor.set_capacity(None, OverflowPolicy::Reject);
+        let (sender, mut receiver) = executor::channel::oneshot::channel::<()>();
+        let parked = executor.spawn(core::future::poll_fn(move |cx| receiver.poll(cx)));
+        executor.step();
+        let res = executor.try_spawn(async {});
+        assert_eq!(res.err(), Some(axerrno::AxError::WouldBlock));
+
+        // Waits for the parked task to complete.
+        let mut spawn = core::pin::pin!(executor.spawn_async(async { 7 }));
+        assert!(poll_once(&mut spawn).is_pending());
+        sender.send(()).unwrap();
+        executor.block_on(parked).unwrap().unwrap();
+        let handle = executor.block_on(spawn).unwrap();
+        assert_eq!(executor.block_on(handle), Ok(7));
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let executor = Executor::new();
+        let order = Arc::new(spin::Mutex::new(alloc::vec::Vec::new()));
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let order = order.clone();
+            let _handle =
+                executor.spawn_with_priority(async move { order.lock().push(priority) }, priority);
+        }
+
+        executor.run();
+        assert_eq!(
+            *order.lock(),
+            [Priority::High, Priority::Normal, Priority::Low]
+        );
+    }
+
+    #[test]
+    fn test_deadline_order() {
+        use core::time::Duration;
+
+        let executor = Executor::new();
+        let order = Arc::new(spin::Mutex::new(alloc::vec::Vec::new()));
+        let now = axhal::time::monotonic_time();
+        let high = order.clone();
+        let _handle =
+            executor.spawn_with_priority(async move { high.lock().push(0) }, Priority::High);
+        for secs in [30, 10, 20] {
+            let order = order.clone();
+            let deadline = now + Duration::from_secs(secs);
+            let _handle =
+                executor.spawn_with_deadline(async move { order.lock().push(secs) }, deadline);
+        }
+        // Already missed.
+        let _handle = executor.spawn_with_deadline(async {}, Duration::ZERO);
+
+        executor.run();
+        assert_eq!(*order.lock(), [10, 20, 30, 0]);
+        assert_eq!(executor.stats().missed_deadlines, 1);
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn test_deterministic_schedule() {
+        // The indices of the tasks polled, in order.
+        let run = |schedule| {
+            let executor = Executor::new();
+            executor.set_schedule(schedule);
+            let handles: alloc::vec::Vec<_> = [Priority::Low, Priority::Normal, Priority::High]
+                .into_iter()
+                .map(|priority| {
+                    let task = async {
+                        for _ in 0..3 {
+                            yield_now().await;
+                        }
+                    };
+                    executor.spawn_with_priority(task, priority)
+                })
+                .collect();
+            executor.run();
+            let trace = executor.take_poll_trace();
+            trace
+                .into_iter()
+                .map(|id| handles.iter().position(|h| h.id() == id).unwrap())
+                .collect::<alloc::vec::Vec<_>>()
+        };
+
+        // In the order of the spawns, whatever the priorities.
+        assert_eq!(run(Schedule::Fifo)[..3], [0, 1, 2]);
+        let seeded = run(Schedule::Seeded(7));
+        assert_eq!(seeded.len(), 12);
+        assert_eq!(seeded, run(Schedule::Seeded(7)));
+    }
+
+    #[test]
+    fn test_abort() {
+        struct SetOnDrop(Arc<AtomicBool>);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let executor = Executor::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(dropped.clone());
+        let handle = executor.spawn(async move {
+            let _guard = guard;
+            core::future::pending::<()>().await
+        });
+        executor.step();
+
+        handle.abort();
+        assert!(dropped.load(Ordering::SeqCst));
+        // It would wait forever for the parked task otherwise.
+        executor.run();
+        assert_eq!(executor.block_on(handle), Err(JoinError::Cancelled));
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let executor = Executor::new();
+        let ready = executor.spawn(async { 42 });
+        // Parked, not in the run queue.
+        let (_sender, mut receiver) = executor::channel::oneshot::channel::<()>();
+        let parked = executor.spawn(core::future::poll_fn(move |cx| receiver.poll(cx)));
+
+        executor.shutdown(core::time::Duration::ZERO);
+        assert!(executor.try_spawn(async {}).is_err());
+        assert_eq!(executor.block_on(ready), Ok(42));
+        assert_eq!(executor.block_on(parked), Err(JoinError::Cancelled));
+
+        executor.restart();
+        let handle = executor.spawn(async { 1 });
+        assert_eq!(executor.block_on(handle), Ok(1));
+    }
+
+    #[test]
+    fn test_task_states() {
+        let executor = Executor::new();
+        let (_sender, mut receiver) = executor::channel::oneshot::channel::<()>();
+        let parked =
+            executor.spawn_named("parked", core::future::poll_fn(move |cx| receiver.poll(cx)));
+        executor.step();
+        let ready = executor.spawn(async {});
+
+        let state = |id| task_list().into_iter().find(|t| t.id == id).unwrap().state;
+        assert_eq!(state(parked.id()), TaskState::Pending);
+        assert_eq!(state(ready.id()), TaskState::Ready);
+        assert!(dump_tasks().contains("parked"));
+        parked.abort();
+    }
+
+    #[test]
+    fn test_static_task_pool() {
+        use core::sync::atomic::AtomicUsize;
+
+        static POOL: TaskPool<2, 64> = TaskPool::new();
+        static POLLS: AtomicUsize = AtomicUsize::new(0);
+
+        // Yields once, through its waker.
+        let task = || {
+            let mut yielded = false;
+            core::future::poll_fn(move |cx| {
+                POLLS.fetch_add(1, Ordering::Relaxed);
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+        };
+        POOL.spawn(task()).unwrap();
+        POOL.spawn(task()).unwrap();
+        assert_eq!(POOL.spawn(task()), Err(axerrno::AxError::NoMemory));
+
+        run_static();
+        assert_eq!(POLLS.load(Ordering::Relaxed), 4);
+        // The slots are free again.
+        POOL.spawn(task()).unwrap();
+        Executor::new().block_on(static_tasks());
+        assert_eq!(POLLS.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn test_exit_hook() {
+        use alloc::vec::Vec;
+
+        // Other tests exit tasks too.
+        static EXITS: spin::Mutex<Vec<(TaskId, TaskExit)>> = spin::Mutex::new(Vec::new());
+        assert!(register_exit_hook(|id, exit| EXITS.lock().push((id, exit))));
+
+        let executor = Executor::new();
+        let done = executor.spawn(async {});
+        let done_id = done.id();
+        executor.block_on(done).unwrap();
+        let (_sender, mut receiver) = executor::channel::oneshot::channel::<()>();
+        let parked = executor.spawn(core::future::poll_fn(move |cx| receiver.poll(cx)));
+        let parked_id = parked.id();
+        executor.step();
+        parked.abort();
+
+        let exits = EXITS.lock();
+        assert!(exits.contains(&(done_id, TaskExit::Completed)));
+        assert!(exits.contains(&(parked_id, TaskExit::Aborted)));
+    }
+
+    #[test]
+    fn test_scope() {
+        let executor = Executor::new();
+        let mut values = [0; 3];
+        let answer = executor.scope(|s| {
+            for (i, value) in values.iter_mut().enumerate() {
+                // Detached, the scope still waits for it.
+                s.spawn(async move { *value = i + 1 });
+            }
+            executor.block_on(s.spawn(async { 42 }))
+        });
+        assert_eq!(answer, Ok(42));
+        assert_eq!(values, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_scope_panic() {
+        let executor = Executor::new();
+        let mut value = 0;
+        let value_mut = &mut value;
+        let res = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            executor.scope(move |s| {
+                s.spawn(async move {
+                    yield_now().await;
+                    *value_mut = 42;
+                });
+                panic!("scope");
+            })
+        }));
+        assert!(res.is_err());
+        // The task ran before the panic left the scope.
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_scope_abort_unpolled() {
+        /// Checks that it is dropped before the scope returns.
+        struct DropCheck<'a>(&'a AtomicBool);
+
+        impl Drop for DropCheck<'_> {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let executor = Executor::new();
+        let dropped = AtomicBool::new(false);
+        executor.scope(|s| {
+            let check = DropCheck(&dropped);
+            let handle = s.spawn(async move {
+                let _check = check;
+                unreachable!("aborted before its first poll");
+            });
+            handle.abort();
+        });
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "memwatch")]
+    #[test]
+    fn test_memory_pressure() {
+        use mem::{MemoryPressure, pressure_for};
+
+        assert_eq!(pressure_for(50, 100), MemoryPressure::Normal);
+        assert_eq!(pressure_for(10, 100), MemoryPressure::Low);
+        assert_eq!(pressure_for(1, 100), MemoryPressure::Critical);
+        assert_eq!(pressure_for(0, 0), MemoryPressure::Normal);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let executor = Executor::new();
+        let (a, b) = executor.block_on(async { crate::join!(async { 1 }, yield_now()) });
+        assert_eq!((a, b), (1, ()));
+        // The second one completes first, the first one is dropped.
+        let winner = executor.block_on(select2(core::future::pending::<()>(), async { 2 }));
+        assert_eq!(winner, Either::Right(2));
+        let winner = executor.block_on(select3(yield_now(), async { 'b' }, async { 3 }));
+        assert_eq!(winner, Either3::Second('b'));
+    }
+
+    #[test]
+    fn test_lanes() {
+        use sync::lanes::{self, Lane, TrySendError};
+
+        let executor = Executor::new();
+        let (sender, mut receiver) = lanes::channel(2);
+        sender.try_send(1, Lane::Normal).unwrap();
+        sender.try_send(2, Lane::Normal).unwrap();
+        assert_eq!(sender.try_send(3, Lane::Normal), Err(TrySendError::Full(3)));
+        // The high lane is unbounded and overtakes the queued messages.
+        sender.try_send(10, Lane::High).unwrap();
+
+        let blocked = sender.clone();
+        let _handle = executor.spawn(async move { blocked.send(3, Lane::Normal).await });
+        let received = executor.block_on(async move {
+            let mut received = alloc::vec::Vec::new();
+            drop(sender);
+            while let Some(msg) = receiver.recv().await {
+                received.push(msg);
+            }
+            received
+        });
+        assert_eq!(received, [10, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_task_local() {
+        crate::task_local! {
+            static ID: u32;
+        }
+
+        let executor = Executor::new();
+        let seen = Arc::new(spin::Mutex::new(alloc::vec::Vec::new()));
+        for id in 0..2 {
+            let seen = seen.clone();
+            let mut yielded = false;
+            let _handle = executor.spawn(async move {
+                assert_eq!(ID.try_with(|_| ()), Err(AccessError));
+                ID.set(id);
+                // The other task sets its own value meanwhile.
+                core::future::poll_fn(|cx| {
+                    if core::mem::replace(&mut yielded, true) {
+                        Poll::Ready(())
+                    } else {
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+                seen.lock().push(ID.get());
+            });
+        }
+
+        executor.run();
+        assert_eq!(*seen.lock(), [0, 1]);
+        assert_eq!(ID.try_with(|_| ()), Err(AccessError));
+    }
+
+    #[test]
+    fn test_cleanup_on_own_executor() {
+        let executor = Executor::new();
+        let cleaned = Arc::new(AtomicBool::new(false));
+        let flag = cleaned.clone();
+        let _handle = executor.spawn(async move {
+            let _guard = crate::defer(async move {
+                flag.store(true, Ordering::Release);
+            });
+        });
+
+        // The cleanup is spawned on the executor of the task dropping the
+        // guard, not on the global one.
+        executor.run();
+        assert!(cleaned.load(Ordering::Acquire));
+        assert_eq!(executor.stats().polls, 2);
+    }
+
+    #[test]
+    fn test_task_local_nested_poll() {
+        crate::task_local! {
+            static NAME: &'static str;
+        }
+
+        let outer = Executor::new();
+        let _handle = outer.spawn(async {
+            NAME.set("outer");
+            // The poll of the inner task is nested in the one of the outer
+            // task, on the same thread.
+            let inner = Executor::new();
+            let _handle = inner.spawn(async {
+                assert_eq!(NAME.try_with(|_| ()), Err(AccessError));
+                NAME.set("inner");
+                assert_eq!(NAME.get(), "inner");
+            });
+            inner.run();
+            assert_eq!(inner.stats().polls, 1);
+            assert_eq!(NAME.get(), "outer");
+        });
+
+        outer.run();
+        assert_eq!(outer.stats().polls, 1);
+        assert_eq!(NAME.try_with(|_| ()), Err(AccessError));
+    }
+
+    #[test]
+    fn test_wakes_coalesced() {
+        let executor = Executor::new();
+        let mut first = true;
+        let _handle = executor.spawn(core::future::poll_fn(move |cx| {
+            if core::mem::take(&mut first) {
+                for _ in 0..3 {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        }));
+
+        executor.run();
+        let stats = executor.stats();
+        assert_eq!(stats.polls, 2);
+        assert_eq!(stats.coalesced_wakes, 3);
+    }
+
+    #[test]
+    fn test_park_until_woken() {
+        let executor = Executor::new();
+        let stored = Arc::new(spin::Mutex::new(None::<core::task::Waker>));
+        let slot = stored.clone();
+        let _handle = executor.spawn(core::future::poll_fn(move |cx| {
+            let mut slot = slot.lock();
+            if slot.is_some() {
+                return Poll::Ready(());
+            }
+            *slot = Some(cx.waker().clone());
+            Poll::Pending
+        }));
+
+        // Keeps its waker, so it is parked.
+        assert!(!executor.step());
+        assert!(!executor.step());
+        assert_eq!(executor.stats().polls, 1);
+
+        stored.lock().as_ref().unwrap().wake_by_ref();
+        executor.run();
+        assert_eq!(executor.stats().polls, 2);
+    }
+
+    #[test]
+    fn test_defer_wakes() {
+        let executor = Executor::new();
+        let stored = Arc::new(spin::Mutex::new(None::<core::task::Waker>));
+        let slot = stored.clone();
+        let _handle = executor.spawn(core::future::poll_fn(move |cx| {
+            let mut slot = slot.lock();
+            if slot.is_some() {
+                return Poll::Ready(());
+            }
+            *slot = Some(cx.waker().clone());
+            Poll::Pending
+        }));
+        assert!(!executor.step());
+
+        let waker = stored.lock().clone().unwrap();
+        defer_wakes(|| {
+            waker.wake_by_ref();
+            // Not queued until the deferral ends.
+            assert!(!executor.step());
+        });
+        executor.step();
+        assert_eq!(executor.stats().polls, 2);
+    }
+
+    #[test]
+    fn test_defer_wakes_unwind() {
+        let executor = Executor::new();
+        let stored = Arc::new(spin::Mutex::new(None::<core::task::Waker>));
+        let slot = stored.clone();
+        let mut polls = 0;
+        let _handle = executor.spawn(core::future::poll_fn(move |cx| {
+            polls += 1;
+            if polls == 3 {
+                return Poll::Ready(());
+            }
+            *slot.lock() = Some(cx.waker().clone());
+            Poll::Pending
+        }));
+        assert!(!executor.step());
+
+        let waker = stored.lock().clone().unwrap();
+        let res = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            defer_wakes(|| {
+                waker.wake_by_ref();
+                panic!("unwinding out of the deferral");
+            })
+        }));
+        assert!(res.is_err());
+        // Woken as `f` unwound.
+        executor.step();
+        assert_eq!(executor.stats().polls, 2);
+
+        // The deferral is gone, the next wakes are immediate.
+        let waker = stored.lock().clone().unwrap();
+        waker.wake_by_ref();
+        executor.step();
+        assert_eq!(executor.stats().polls, 3);
+    }
+
+    #[test]
+    fn test_requeue_without_waker() {
+        let executor = Executor::new();
+        let order = Arc::new(spin::Mutex::new(alloc::vec::Vec::new()));
+        let mut polls = 0;
+        let first = {
+            let order = order.clone();
+            executor.spawn(core::future::poll_fn(move |_| {
+                polls += 1;
+                order.lock().push(('a', polls));
+                // Nothing can wake it, it is polled again in its turn.
+                if polls < 3 {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(())
+                }
+            }))
+        };
+        let second = {
+            let order = order.clone();
+            executor.spawn(async move {
+                order.lock().push(('b', 1));
+                yield_now().await;
+                order.lock().push(('b', 2));
+            })
+        };
+
+        executor.run();
+        assert_eq!(
+            *order.lock(),
+            [('a', 1), ('b', 1), ('a', 2), ('b', 2), ('a', 3)]
+        );
+        assert_eq!(block_on(first), Ok(()));
+        assert_eq!(block_on(second), Ok(()));
+    }
+
+    #[test]
+    fn test_local_set() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let set = LocalSet::new();
+        // Not `Send`.
+        let order = Rc::new(RefCell::new(alloc::vec::Vec::new()));
+        let handles: alloc::vec::Vec<_> = (0..3)
+            .map(|id| {
+                let order = order.clone();
+                set.spawn_local(async move {
+                    order.borrow_mut().push(id);
+                    id
+                })
+            })
+            .collect();
+        let parked = set.spawn_local(core::future::pending::<()>());
+
+        let sum = set.run_until(async {
+            let mut sum = 0;
+            for handle in handles {
+                sum += handle.await.unwrap();
+            }
+            sum
+        });
+        assert_eq!(sum, 3);
+        assert_eq!(*order.borrow(), [0, 1, 2]);
+        assert_eq!(set.len(), 1);
+
+        drop(set);
+        assert_eq!(block_on(parked), Err(JoinError::Cancelled));
+    }
+
+    #[test]
+    fn test_abort_task_owning_local_set() {
+        let outer = LocalSet::new();
+        let inner = LocalSet::new();
+        let parked = inner.spawn_local(core::future::pending::<()>());
+        let handle = outer.spawn_local(async move {
+            let _inner = inner;
+            core::future::pending::<()>().await;
+        });
+
+        // Dropping the future drops the inner set, which waits for the uses
+        // of its executor, not for the abort in progress.
+        handle.abort();
+        assert_eq!(block_on(handle), Err(JoinError::Cancelled));
+        assert_eq!(block_on(parked), Err(JoinError::Cancelled));
+    }
+
+    #[test]
+    fn test_run_until() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+        let executor = Executor::new();
+        let background = executor.spawn(async {
+            loop {
+                TICKS.fetch_add(1, Ordering::Relaxed);
+                yield_now().await;
+            }
+        });
+        let output = executor.run_until(async {
+            while TICKS.load(Ordering::Relaxed) < 3 {
+                yield_now().await;
+            }
+            42
+        });
+        assert_eq!(output, 42);
+        assert!(TICKS.load(Ordering::Relaxed) >= 3);
+
+        // Left on the executor.
+        background.abort();
+        assert_eq!(executor.block_on(background), Err(JoinError::Cancelled));
+    }
+
+    #[crate::async_test(timeout = "2s")]
+    async fn test_async_test(executor: &Executor) {
+        let handle = executor.spawn(async { 42 });
+        assert_eq!(handle.await, Ok(42));
+    }
+
+    #[crate::async_test(timeout = "10ms")]
+    #[should_panic(expected = "test timed out")]
+    async fn test_async_test_timeout() {
+        core::future::pending::<()>().await;
+    }
 }
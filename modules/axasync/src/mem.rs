@@ -0,0 +1,180 @@
+//! Watching of the free memory, and low-memory events.
+//!
+//! The task spawned with [`watch_memory`] samples the stats of the global
+//! allocator at regular intervals, and publishes the [`MemoryPressure`] when
+//! it crosses the [thresholds](set_thresholds). The other modules read it to
+//! hold back on their allocations, e.g. the network stack shrinks the buffers
+//! of the new sockets and pauses the accepts:
+//!
+//! ```ignore
+//! axasync::spawn_named("mem-watcher", axasync::mem::watch_memory(Duration::from_millis(500)));
+//! let mut pressure = MemoryPressure::Normal;
+//! loop {
+//!     pressure = axasync::mem::pressure_changed(pressure).await;
+//!     warn!("memory pressure: {}", pressure);
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use kspin::SpinNoIrq;
+
+use crate::sleep;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// The default percentage of free memory under which the pressure is
+/// [`Low`](MemoryPressure::Low).
+pub const DEFAULT_LOW_THRESHOLD: u8 = 20;
+/// The default percentage of free memory under which the pressure is
+/// [`Critical`](MemoryPressure::Critical).
+pub const DEFAULT_CRITICAL_THRESHOLD: u8 = 5;
+
+/// The pressure on the memory, from the share of it that is free.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum MemoryPressure {
+    /// Enough memory is free.
+    Normal,
+    /// The free memory is under the low threshold, the caches and the
+    /// buffers should shrink.
+    Low,
+    /// The free memory is under the critical threshold, no new work should
+    /// be taken.
+    Critical,
+}
+
+impl MemoryPressure {
+    const fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Normal,
+            1 => Self::Low,
+            _ => Self::Critical,
+        }
+    }
+}
+
+impl fmt::Display for MemoryPressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Normal => "normal",
+            Self::Low => "low",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+static PRESSURE: AtomicU8 = AtomicU8::new(MemoryPressure::Normal as u8);
+static LOW_THRESHOLD: AtomicU8 = AtomicU8::new(DEFAULT_LOW_THRESHOLD);
+static CRITICAL_THRESHOLD: AtomicU8 = AtomicU8::new(DEFAULT_CRITICAL_THRESHOLD);
+/// The wakers of the tasks waiting for the pressure to change.
+static WAITERS: SpinNoIrq<Vec<Waker>> = SpinNoIrq::new(Vec::new());
+
+/// Sets the percentages of free memory under which the pressure is
+/// [`Low`](MemoryPressure::Low) and [`Critical`](MemoryPressure::Critical).
+///
+/// # Panics
+///
+/// Panics if `critical` is above `low`, or `low` above 100.
+pub fn set_thresholds(low: u8, critical: u8) {
+    assert!(critical <= low && low <= 100, "invalid memory thresholds");
+    LOW_THRESHOLD.store(low, Ordering::Relaxed);
+    CRITICAL_THRESHOLD.store(critical, Ordering::Relaxed);
+}
+
+/// Returns the pressure on the memory at the last sample.
+///
+/// It stays [`Normal`](MemoryPressure::Normal) while no [`watch_memory`]
+/// task runs.
+pub fn pressure() -> MemoryPressure {
+    MemoryPressure::from_u8(PRESSURE.load(Ordering::Acquire))
+}
+
+/// Polls for a change of the pressure from `last`, e.g. to resume an
+/// operation paused under pressure.
+pub fn poll_pressure_changed(last: MemoryPressure, cx: &mut Context<'_>) -> Poll<MemoryPressure> {
+    let current = pressure();
+    if current != last {
+        return Poll::Ready(current);
+    }
+    {
+        // Polled again on other wakes, e.g. of a socket, keep one per task.
+        let mut waiters = WAITERS.lock();
+        if !waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+            waiters.push(cx.waker().clone());
+        }
+    }
+    // Changed while registering, the wake may be missed.
+    match pressure() {
+        current if current != last => Poll::Ready(current),
+        _ => Poll::Pending,
+    }
+}
+
+/// Waits until the pressure is no longer `last`, returns the new one.
+pub async fn pressure_changed(last: MemoryPressure) -> MemoryPressure {
+    poll_fn(|cx| poll_pressure_changed(last, cx)).await
+}
+
+/// Returns the free and the total bytes of the global allocator.
+fn memory_stats() -> (usize, usize) {
+    let allocator = axalloc::global_allocator();
+    let free_pages = allocator.available_pages();
+    let total_pages = allocator.used_pages() + free_pages;
+    // The free bytes of the heap are in pages already counted as used.
+    let free = free_pages * PAGE_SIZE + allocator.available_bytes();
+    (free, total_pages * PAGE_SIZE)
+}
+
+/// Returns the pressure when `free` out of `total` bytes are free.
+pub(crate) fn pressure_for(free: usize, total: usize) -> MemoryPressure {
+    if total == 0 {
+        return MemoryPressure::Normal;
+    }
+    let percent = (free as u128 * 100 / total as u128) as usize;
+    if percent < CRITICAL_THRESHOLD.load(Ordering::Relaxed) as usize {
+        MemoryPressure::Critical
+    } else if percent < LOW_THRESHOLD.load(Ordering::Relaxed) as usize {
+        MemoryPressure::Low
+    } else {
+        MemoryPressure::Normal
+    }
+}
+
+/// Publishes `pressure`, and wakes the tasks waiting for it to change.
+fn publish(pressure: MemoryPressure) {
+    let last = PRESSURE.swap(pressure as u8, Ordering::AcqRel);
+    if last == pressure as u8 {
+        return;
+    }
+    let (free, total) = memory_stats();
+    if pressure > MemoryPressure::from_u8(last) {
+        warn!(
+            "memory pressure {}: {} of {} KiB free",
+            pressure,
+            free / 1024,
+            total / 1024
+        );
+    } else {
+        info!("memory pressure {}", pressure);
+    }
+    let waiters = core::mem::take(&mut *WAITERS.lock());
+    for waker in waiters {
+        waker.wake();
+    }
+}
+
+/// Samples the stats of the global allocator every `interval`, and publishes
+/// the [`pressure`] when it changes. Spawn it once, it runs forever.
+pub async fn watch_memory(interval: Duration) {
+    loop {
+        let (free, total) = memory_stats();
+        publish(pressure_for(free, total));
+        sleep(interval).await;
+    }
+}
@@ -1,5 +1,6 @@
 //! Async MMIO (Memory-Mapped I/O) operations.
 
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::future::Future;
@@ -16,7 +17,8 @@ static MMIO_EVENT_COUNTER: AtomicU64 = AtomicU64::new(1);
 /// The handler for an MMIO device event.
 ///
 /// This trait must be implemented by device drivers that want to support async operations
-/// over MMIO interrupts.
+/// over MMIO interrupts. The interrupt handler of the device delivers the payload of the
+/// events, e.g. the status it read from the device, usually through a [`MmioWakerSet`].
 pub trait MmioEventHandler: Send + Sync {
     /// The type of data passed to the event handler when the MMIO event is triggered.
     type Data: Send + Sync + Clone + 'static;
@@ -24,14 +26,21 @@ pub trait MmioEventHandler: Send + Sync {
     /// Register an MMIO event.
     ///
     /// This method should register the device's IRQ and provide a way to notify
-    /// when the event occurs.
+    /// when the event occurs. It is called on each poll of the [`MmioEvent`], with its
+    /// current waker, and must keep the payload of an event that already occurred.
     fn register_event(&self, event_id: MmioEventId, waker: Waker) -> bool;
 
-    /// Cancel a previously registered MMIO event.
+    /// Cancel a previously registered MMIO event, its payload is dropped if it occurred.
     fn cancel_event(&self, event_id: MmioEventId) -> bool;
+
+    /// Takes the payload of a registered MMIO event, if it has occurred.
+    fn take_event(&self, event_id: MmioEventId) -> Option<Self::Data>;
 }
 
-/// A future that waits for an MMIO event to occur.
+/// A future that waits for an MMIO event to occur, and resolves to its payload.
+///
+/// It resolves to `None` if the event cannot be registered. Dropping it before it
+/// resolves cancels the event.
 pub struct MmioEvent<H: MmioEventHandler> {
     event_handler: Arc<H>,
     event_id: MmioEventId,
@@ -58,28 +67,32 @@ impl<H: MmioEventHandler> MmioEvent<H> {
 }
 
 impl<H: MmioEventHandler> Future for MmioEvent<H> {
-    type Output = MmioEventId;
+    type Output = Option<H::Data>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.completed {
-            return Poll::Ready(self.event_id);
+        assert!(!self.completed, "MmioEvent polled after completion");
+
+        let registered = self
+            .event_handler
+            .register_event(self.event_id, cx.waker().clone());
+        if !registered {
+            warn!("Failed to register MMIO event: {}", self.event_id);
+            if self.registered {
+                self.event_handler.cancel_event(self.event_id);
+            }
+            self.completed = true;
+            return Poll::Ready(None);
         }
+        self.registered = true;
 
-        if !self.registered {
-            let registered = self
-                .event_handler
-                .register_event(self.event_id, cx.waker().clone());
-            if !registered {
-                // If registration failed, we consider the event as completed
-                // This prevents infinite polling loop
-                warn!("Failed to register MMIO event: {}", self.event_id);
+        // Registered first, so that an event occurring now wakes the new waker.
+        match self.event_handler.take_event(self.event_id) {
+            Some(data) => {
                 self.completed = true;
-                return Poll::Ready(self.event_id);
+                Poll::Ready(Some(data))
             }
-            self.registered = true;
+            None => Poll::Pending,
         }
-
-        Poll::Pending
     }
 }
 
@@ -91,89 +104,157 @@ impl<H: MmioEventHandler> Drop for MmioEvent<H> {
     }
 }
 
+/// The state of an event of a [`MmioWakerSet`].
+enum Slot<T> {
+    /// Waiting, with the waker of its future.
+    Waiting(Waker),
+    /// Occurred with the payload, not taken yet.
+    Occurred(T),
+}
+
+/// Keeps the latest payload of an event that occurred again.
+fn replace<T>(old: &mut T, new: T) {
+    *old = new;
+}
+
 /// Utility struct to manage a collection of MMIO wakers.
 ///
 /// This provides a convenient way for device drivers to manage multiple wakers
-/// for different types of events.
-pub struct MmioWakerSet {
-    wakers: SpinNoIrq<alloc::collections::BTreeMap<MmioEventId, Waker>>,
+/// for different types of events, and to deliver the payloads `T` of the events from
+/// the interrupt handler. An event that occurs again before its task takes the payload
+/// wakes it once: the payloads are coalesced, see [`with_coalesce`](Self::with_coalesce).
+pub struct MmioWakerSet<T = ()> {
+    slots: SpinNoIrq<BTreeMap<MmioEventId, Slot<T>>>,
+    coalesce: fn(&mut T, T),
 }
 
-impl MmioWakerSet {
-    /// Creates a new empty waker set.
-    pub fn new() -> Self {
+impl<T> MmioWakerSet<T> {
+    /// Creates a new empty waker set, that keeps the latest payload of an event that
+    /// occurred several times.
+    pub const fn new() -> Self {
+        Self::with_coalesce(replace)
+    }
+
+    /// Creates a new empty waker set, that merges the payload of an event that occurred
+    /// again into the pending one with `coalesce`, e.g. to OR status bits or to count
+    /// the occurrences.
+    pub const fn with_coalesce(coalesce: fn(&mut T, T)) -> Self {
         Self {
-            wakers: SpinNoIrq::new(alloc::collections::BTreeMap::new()),
+            slots: SpinNoIrq::new(BTreeMap::new()),
+            coalesce,
         }
     }
 
-    /// Registers a waker for the given event ID.
+    /// Registers a waker for the given event ID, or updates it. The payload of an event
+    /// that already occurred is kept.
     ///
     /// Returns true if registration was successful.
     pub fn register(&self, event_id: MmioEventId, waker: Waker) -> bool {
-        let mut wakers = self.wakers.lock();
-        wakers.insert(event_id, waker);
+        let mut slots = self.slots.lock();
+        match slots.get_mut(&event_id) {
+            Some(Slot::Occurred(_)) => {}
+            Some(Slot::Waiting(old)) => old.clone_from(&waker),
+            None => {
+                slots.insert(event_id, Slot::Waiting(waker));
+            }
+        }
         true
     }
 
-    /// Removes a previously registered waker.
+    /// Removes a previously registered waker, and the payload of the event if it
+    /// occurred.
     ///
     /// Returns true if cancellation was successful.
     pub fn cancel(&self, event_id: MmioEventId) -> bool {
-        let mut wakers = self.wakers.lock();
-        wakers.remove(&event_id).is_some()
+        // Not under the lock, the payload may hold anything.
+        let slot = self.slots.lock().remove(&event_id);
+        slot.is_some()
+    }
+
+    /// Takes the payload of the given event, and forgets it, if it has occurred.
+    pub fn take(&self, event_id: MmioEventId) -> Option<T> {
+        let mut slots = self.slots.lock();
+        if !matches!(slots.get(&event_id), Some(Slot::Occurred(_))) {
+            return None;
+        }
+        match slots.remove(&event_id) {
+            Some(Slot::Occurred(data)) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of registered events that have not occurred.
+    pub fn waiting(&self) -> usize {
+        let slots = self.slots.lock();
+        slots
+            .values()
+            .filter(|slot| matches!(slot, Slot::Waiting(_)))
+            .count()
+    }
+
+    /// Records the occurrence of the event with `data`, coalesced with a pending
+    /// payload, and returns the waker to wake if it was waiting.
+    fn occur(&self, slot: &mut Slot<T>, data: T) -> Option<Waker> {
+        match slot {
+            Slot::Occurred(pending) => {
+                (self.coalesce)(pending, data);
+                None
+            }
+            Slot::Waiting(_) => match core::mem::replace(slot, Slot::Occurred(data)) {
+                Slot::Waiting(waker) => Some(waker),
+                Slot::Occurred(_) => unreachable!(),
+            },
+        }
     }
 
-    /// Wake all wakers that match the given predicate.
+    /// Delivers `data` to all the registered events that match the given predicate,
+    /// and wakes them.
     ///
     /// The predicate takes an event ID and should return true if the event
     /// should be woken.
-    pub fn wake_matching<F>(&self, predicate: F)
+    pub fn wake_matching<F>(&self, predicate: F, data: T)
     where
         F: Fn(MmioEventId) -> bool,
+        T: Clone,
     {
+        // Wake the tasks outside of the lock
         let mut wakers_to_wake = Vec::new();
-        let mut event_ids_to_remove = Vec::new();
-
-        // First, collect all wakers that match the predicate
         {
-            let wakers = self.wakers.lock();
-            for (&event_id, waker) in wakers.iter() {
+            let mut slots = self.slots.lock();
+            for (&event_id, slot) in slots.iter_mut() {
                 if predicate(event_id) {
-                    wakers_to_wake.push(waker.clone());
-                    event_ids_to_remove.push(event_id);
+                    wakers_to_wake.extend(self.occur(slot, data.clone()));
                 }
             }
         }
 
-        // Then, remove the matched event IDs
-        {
-            let mut wakers = self.wakers.lock();
-            for event_id in event_ids_to_remove {
-                wakers.remove(&event_id);
-            }
-        }
-
-        // Finally, wake all collected wakers
         for waker in wakers_to_wake {
             waker.wake();
         }
     }
 
-    /// Wake a specific event.
+    /// Delivers `data` to a specific event, and wakes it.
     ///
-    /// Returns true if the event was found and woken.
-    pub fn wake_event(&self, event_id: MmioEventId) -> bool {
+    /// Returns true if the event was registered. Its payload is coalesced with the
+    /// pending one if it had already occurred.
+    pub fn wake_event(&self, event_id: MmioEventId, data: T) -> bool {
         let waker = {
-            let mut wakers = self.wakers.lock();
-            wakers.remove(&event_id)
+            let mut slots = self.slots.lock();
+            let Some(slot) = slots.get_mut(&event_id) else {
+                return false;
+            };
+            self.occur(slot, data)
         };
 
         if let Some(waker) = waker {
             waker.wake();
-            true
-        } else {
-            false
         }
+        true
+    }
+}
+
+impl<T> Default for MmioWakerSet<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,94 @@
+//! Parking of an idle executor until one of its tasks is woken.
+//!
+//! With the `irq` feature, an executor that has no ready task parks instead
+//! of spinning. With `multitask`, it blocks its axtask, so that the other
+//! axtasks run meanwhile. Otherwise it halts the CPU until the next
+//! interrupt: the timer interrupt ends the wait at every tick, after it has
+//! expired the timers, and the device interrupts as their events come.
+//!
+//! With `multitask` but without `irq`, the axtask blocks only while there is
+//! no event to poll, i.e. no armed timer and no poll hook, since nothing
+//! else would see them. It yields to the other axtasks otherwise.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+/// Longest time an executor stays parked in its axtask, it bounds the latency
+/// of the events that do not unpark it. Without
+/// `multitask`, the timer interrupt bounds it to a tick.
+pub const MAX_PARK: Duration = Duration::from_millis(10);
+
+/// Parks the workers of an executor until a task is queued, or until a
+/// condition of their own holds, e.g. the future of a `block_on` is woken.
+///
+/// All the parked workers are woken up by an unpark, each checks whether it
+/// was for it: a wakeup meant for one of them is never taken by another.
+pub(crate) struct Parker {
+    /// Number of unparks so far, a worker parks until it changes.
+    unparks: AtomicUsize,
+    #[cfg(feature = "multitask")]
+    wait_queue: axtask::WaitQueue,
+}
+
+impl Parker {
+    pub(crate) const fn new() -> Self {
+        Self {
+            unparks: AtomicUsize::new(0),
+            #[cfg(feature = "multitask")]
+            wait_queue: axtask::WaitQueue::new(),
+        }
+    }
+
+    /// Returns the number of unparks so far, for a worker to [`park`] until
+    /// the next one.
+    ///
+    /// [`park`]: Self::park
+    pub(crate) fn unparks(&self) -> usize {
+        self.unparks.load(Ordering::Acquire)
+    }
+
+    /// Wakes up the parked workers for a task queued, and makes the next park
+    /// of the others return at once.
+    ///
+    /// It may be called from an interrupt handler: the wait queue and the
+    /// run queues it locks are `SpinNoIrq`, they are never held with the
+    /// IRQs on.
+    pub(crate) fn unpark(&self) {
+        self.unparks.fetch_add(1, Ordering::AcqRel);
+        self.notify();
+    }
+
+    /// Wakes up the parked workers to check their condition, after it was
+    /// set.
+    pub(crate) fn notify(&self) {
+        #[cfg(feature = "multitask")]
+        self.wait_queue.notify_all(false);
+    }
+
+    /// Waits until the next unpark after the `seen` ones, or until `woken`
+    /// returns `true`, and updates `seen`.
+    pub(crate) fn park(&self, seen: &mut usize, woken: impl Fn() -> bool) {
+        let last = *seen;
+        let ready = || self.unparks() != last || woken();
+
+        #[cfg(all(feature = "multitask", feature = "irq"))]
+        self.wait_queue.wait_timeout_until(MAX_PARK, ready);
+
+        #[cfg(all(feature = "multitask", not(feature = "irq")))]
+        if crate::polling::has_polled_events() {
+            axtask::yield_now();
+        } else {
+            self.wait_queue.wait_until(ready);
+        }
+
+        // An unpark by an interrupt between the check and the wait is only
+        // seen at the next interrupt, a tick away at most.
+        #[cfg(not(feature = "multitask"))]
+        if !ready() && axhal::arch::irqs_enabled() {
+            axhal::arch::wait_for_irqs();
+        }
+
+        // The caller polls again whatever woke it up.
+        *seen = self.unparks();
+    }
+}
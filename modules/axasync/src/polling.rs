@@ -0,0 +1,116 @@
+//! Time-sliced executor loop for platforms without interrupts.
+//!
+//! Without the `irq` feature, nothing wakes the tasks asynchronously: there
+//! is no timer interrupt to expire the timers, and no NIC interrupt to poll
+//! the network stack. [`Executor::run`](crate::Executor::run) then drives
+//! them from its own loop instead. It runs the ready tasks for at most a
+//! time slice, then expires the timers and calls the hooks registered with
+//! [`register_poll_hook`] (e.g. the one of `axnet`, which polls smoltcp), and
+//! busy-waits for a bounded time when no task is ready.
+
+use core::time::Duration;
+
+use kspin::SpinNoIrq;
+
+#[cfg(not(feature = "irq"))]
+use axhal::time::monotonic_time;
+
+#[cfg(not(feature = "irq"))]
+use crate::Executor;
+
+/// Maximum number of poll hooks.
+const MAX_POLL_HOOKS: usize = 8;
+
+/// Longest time the ready tasks run before the timers and the hooks are
+/// polled again.
+pub const TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// Longest busy-wait when no task is ready, it bounds the latency of the
+/// events that can only be seen by polling.
+pub const MAX_IDLE_WAIT: Duration = Duration::from_millis(1);
+
+struct PollHooks {
+    hooks: [Option<fn()>; MAX_POLL_HOOKS],
+    len: usize,
+}
+
+static HOOKS: SpinNoIrq<PollHooks> = SpinNoIrq::new(PollHooks {
+    hooks: [None; MAX_POLL_HOOKS],
+    len: 0,
+});
+
+/// Registers a hook that polls an event source, e.g. a device, between the
+/// time slices of the executor.
+///
+/// The hooks are only called without the `irq` feature, the event sources
+/// register them either way. Returns `false` if there are too many hooks.
+pub fn register_poll_hook(hook: fn()) -> bool {
+    let mut hooks = HOOKS.lock();
+    if hooks.len >= MAX_POLL_HOOKS {
+        warn!("too many poll hooks, ignoring {:#x}", hook as usize);
+        return false;
+    }
+    let len = hooks.len;
+    hooks.hooks[len] = Some(hook);
+    hooks.len += 1;
+    true
+}
+
+/// Expires the timers and calls the poll hooks, which wake the tasks whose
+/// events happened.
+#[cfg(not(feature = "irq"))]
+pub(crate) fn poll_events() {
+    #[cfg(feature = "timer")]
+    crate::check_timer_events();
+
+    // Not under the lock, a hook may take a while.
+    let (hooks, len) = {
+        let hooks = HOOKS.lock();
+        (hooks.hooks, hooks.len)
+    };
+    for hook in hooks[..len].iter().flatten() {
+        hook();
+    }
+}
+
+/// Returns whether there are events that are only seen by polling them: an
+/// armed timer or a poll hook. An idle executor must not block meanwhile.
+#[cfg(all(feature = "multitask", not(feature = "irq")))]
+pub(crate) fn has_polled_events() -> bool {
+    #[cfg(feature = "timer")]
+    if crate::waker::next_timer_deadline().is_some() {
+        return true;
+    }
+    HOOKS.lock().len > 0
+}
+
+/// Runs `executor` until all its tasks are complete, polling the events
+/// between its time slices.
+#[cfg(not(feature = "irq"))]
+pub(crate) fn run(executor: &Executor) {
+    loop {
+        let slice_end = monotonic_time() + TIME_SLICE;
+        while executor.step() && monotonic_time() < slice_end {}
+
+        poll_events();
+        if executor.has_ready_tasks() {
+            continue;
+        }
+        if executor.is_idle() {
+            break;
+        }
+        idle_wait();
+    }
+}
+
+/// Waits for an event for at most [`MAX_IDLE_WAIT`].
+#[cfg(not(feature = "irq"))]
+fn idle_wait() {
+    let deadline = monotonic_time() + MAX_IDLE_WAIT;
+    while monotonic_time() < deadline {
+        #[cfg(feature = "multitask")]
+        axtask::yield_now();
+        #[cfg(not(feature = "multitask"))]
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,208 @@
+//! Scoped tasks, that may borrow from the stack of their spawner.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::future::{Future, poll_fn};
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker, ready};
+
+use spin::Mutex;
+
+use crate::executor::channel::oneshot;
+use crate::{BoxFuture, Executor, JoinError, JoinHandle, TaskId, executor};
+
+/// Runs `f` with a [`Scope`] on the global executor, see
+/// [`Executor::scope`].
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    executor().scope(f)
+}
+
+/// A scope to spawn tasks in, created by [`Executor::scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    executor: &'env Executor,
+    state: Arc<ScopeState>,
+    // Invariant over both lifetimes, as in `std::thread::Scope`.
+    _scope: PhantomData<&'scope mut &'scope ()>,
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+/// The state shared by a scope and its tasks.
+struct ScopeState {
+    /// Number of scoped tasks whose future is not dropped yet.
+    running: AtomicUsize,
+    /// The waiter of the scope, woken when the last task is dropped.
+    waker: Mutex<Option<Waker>>,
+}
+
+/// Decrements the count of running tasks when the future of a scoped task
+/// is dropped, whether it completed or it was aborted.
+struct RunningGuard(Arc<ScopeState>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::Release) == 1 {
+            let waker = self.0.waker.lock().take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The future of a scoped task, with the guard that counts it as running.
+///
+/// The future is dropped before the guard, whether it completed or it was
+/// aborted, even before its first poll: once the scope sees the count drop,
+/// nothing borrowed by the future is used anymore.
+struct ScopedFuture<F> {
+    future: ManuallyDrop<F>,
+    _guard: RunningGuard,
+}
+
+impl<F: Future> Future for ScopedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: The future is never moved out, it is dropped in place.
+        unsafe { self.map_unchecked_mut(|this| &mut *this.future) }.poll(cx)
+    }
+}
+
+impl<F> Drop for ScopedFuture<F> {
+    fn drop(&mut self) {
+        // SAFETY: Dropped only once, here. The guard is dropped after.
+        unsafe { ManuallyDrop::drop(&mut self.future) };
+    }
+}
+
+impl Executor {
+    /// Runs `f` with a [`Scope`], in which tasks can be spawned that borrow
+    /// from the stack, and waits for all of them before returning.
+    ///
+    /// The caller is blocked until then, running the tasks of the executor
+    /// meanwhile as [`block_on`](Self::block_on) does. A scoped task whose
+    /// handle is dropped still runs to completion. If `f` panics, the tasks
+    /// are waited for before the panic goes on.
+    ///
+    /// ```ignore
+    /// let mut lens = [0; 2];
+    /// let names = ["a", "bc"];
+    /// executor.scope(|s| {
+    ///     for (len, name) in lens.iter_mut().zip(&names) {
+    ///         s.spawn(async move { *len = name.len() });
+    ///     }
+    /// });
+    /// assert_eq!(lens, [1, 2]);
+    /// ```
+    pub fn scope<'env, F, T>(&'env self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let scope = Scope {
+            executor: self,
+            state: Arc::new(ScopeState {
+                running: AtomicUsize::new(0),
+                waker: Mutex::new(None),
+            }),
+            _scope: PhantomData,
+            _env: PhantomData,
+        };
+        // Waits in a guard, so that the tasks are also waited for if `f`
+        // panics, before the stack they borrow from is unwound.
+        let scope = WaitOnDrop(scope);
+        f(&scope.0)
+    }
+}
+
+/// Waits for the tasks of the scope when dropped.
+struct WaitOnDrop<'scope, 'env>(Scope<'scope, 'env>);
+
+impl Drop for WaitOnDrop<'_, '_> {
+    fn drop(&mut self) {
+        let state = &self.0.state;
+        self.0.executor.block_on(poll_fn(|cx| {
+            // Registered before the check, so that the last task wakes it.
+            *state.waker.lock() = Some(cx.waker().clone());
+            if state.running.load(Ordering::Acquire) == 0 {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+    }
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Spawns a task in the scope, whose future may borrow anything that
+    /// outlives the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the run queue is full or the executor has too many tasks,
+    /// and its [`OverflowPolicy`](crate::OverflowPolicy) rejects the task,
+    /// see [`Executor::spawn`].
+    pub fn spawn<F>(&'scope self, future: F) -> ScopedJoinHandle<'scope, F::Output>
+    where
+        F: Future + Send + 'scope,
+        F::Output: Send + 'scope,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.state.running.fetch_add(1, Ordering::Relaxed);
+        let future: Pin<Box<dyn Future<Output = ()> + Send + 'scope>> = Box::pin(ScopedFuture {
+            future: ManuallyDrop::new(async move {
+                let _ = sender.send(future.await);
+            }),
+            _guard: RunningGuard(self.state.clone()),
+        });
+        // SAFETY: The scope waits until the future is dropped, which is
+        // counted by its guard, so it never outlives what it borrows.
+        let future: BoxFuture<()> = unsafe { core::mem::transmute(future) };
+        ScopedJoinHandle {
+            handle: self.executor.spawn(future),
+            receiver,
+            _scope: PhantomData,
+        }
+    }
+}
+
+/// A handle to a scoped task, that resolves to its output.
+///
+/// Dropping the handle does not stop the task, the scope still waits for it.
+pub struct ScopedJoinHandle<'scope, T> {
+    handle: JoinHandle<()>,
+    receiver: oneshot::Receiver<T>,
+    _scope: PhantomData<&'scope ()>,
+}
+
+impl<T> ScopedJoinHandle<'_, T> {
+    /// Returns the ID of the task.
+    pub fn id(&self) -> TaskId {
+        self.handle.id()
+    }
+
+    /// Aborts the task, see [`AbortHandle::abort`](crate::AbortHandle::abort).
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+impl<T> Future for ScopedJoinHandle<'_, T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match ready!(self.receiver.poll(cx)) {
+            Ok(output) => Poll::Ready(Ok(output)),
+            // Dropped unsent, the task tells whether it was aborted or
+            // panicked.
+            Err(()) => Pin::new(&mut self.handle)
+                .poll(cx)
+                .map(|res| Err(res.err().unwrap_or(JoinError::Cancelled))),
+        }
+    }
+}
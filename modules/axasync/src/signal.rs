@@ -0,0 +1,274 @@
+//! Signal-like delivery of kernel events to async tasks.
+//!
+//! [`signal`] marks a signal pending on a task. While an unmasked signal is
+//! pending, every [`Interruptible`] future awaited by the task resolves with
+//! [`AxError::Interrupted`], so that blocking operations can be cancelled
+//! (e.g. on Ctrl-C). The signal stays pending until the task takes it with
+//! [`take_signal`].
+
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use axerrno::{AxError, AxResult, ax_err};
+use spin::Mutex;
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Signal states of all live tasks.
+static SIGNALS: Mutex<BTreeMap<TaskId, SignalState>> = Mutex::new(BTreeMap::new());
+
+/// The unique ID of a task spawned on an [`Executor`](crate::Executor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    /// Returns the ID as a number.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// Formats the ID, followed by the name of the task if it has one, e.g.
+/// `42 (http-conn-42)`.
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::tasks::with_task_name(*self, |name| match name {
+            Some(name) => write!(f, "{} ({})", self.0, name),
+            None => write!(f, "{}", self.0),
+        })
+    }
+}
+
+/// A signal, numbered as on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Sig {
+    /// Hangup.
+    Hup = 1,
+    /// Interrupt from keyboard (Ctrl-C).
+    Int = 2,
+    /// Quit from keyboard.
+    Quit = 3,
+    /// Kill, cannot be masked.
+    Kill = 9,
+    /// User-defined signal 1.
+    Usr1 = 10,
+    /// User-defined signal 2.
+    Usr2 = 12,
+    /// Termination.
+    Term = 15,
+}
+
+impl Sig {
+    const ALL: [Sig; 7] = [
+        Sig::Hup,
+        Sig::Int,
+        Sig::Quit,
+        Sig::Kill,
+        Sig::Usr1,
+        Sig::Usr2,
+        Sig::Term,
+    ];
+
+    const fn bit(self) -> u32 {
+        1 << self as u8
+    }
+}
+
+/// A set of signals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SigSet(u32);
+
+impl SigSet {
+    /// The empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The set of all signals.
+    pub const fn all() -> Self {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < Sig::ALL.len() {
+            bits |= Sig::ALL[i].bit();
+            i += 1;
+        }
+        Self(bits)
+    }
+
+    /// Returns the set with `sig` added.
+    pub const fn with(self, sig: Sig) -> Self {
+        Self(self.0 | sig.bit())
+    }
+
+    /// Returns whether `sig` is in the set.
+    pub const fn contains(self, sig: Sig) -> bool {
+        self.0 & sig.bit() != 0
+    }
+
+    /// Returns whether the set is empty.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Adds `sig` to the set.
+    pub fn insert(&mut self, sig: Sig) {
+        self.0 |= sig.bit();
+    }
+
+    /// Removes `sig` from the set.
+    pub fn remove(&mut self, sig: Sig) {
+        self.0 &= !sig.bit();
+    }
+}
+
+#[derive(Default)]
+struct SignalState {
+    pending: SigSet,
+    mask: SigSet,
+    /// The [`Interruptible`] future the task is waiting on.
+    waker: Option<Waker>,
+}
+
+impl SignalState {
+    /// Returns the pending signals that are not masked.
+    fn deliverable(&self) -> SigSet {
+        let mut mask = self.mask;
+        mask.remove(Sig::Kill);
+        SigSet(self.pending.0 & !mask.0)
+    }
+}
+
+/// Allocates the ID of a new task, and prepares its signal state.
+pub(crate) fn register_task() -> TaskId {
+    let id = TaskId(NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed));
+    SIGNALS.lock().insert(id, SignalState::default());
+    id
+}
+
+/// Discards the signal state of an exited task.
+pub(crate) fn unregister_task(id: TaskId) {
+    SIGNALS.lock().remove(&id);
+}
+
+/// Returns the ID of the task being polled by the current axtask, [`None`]
+/// outside of a task.
+pub fn current_task_id() -> Option<TaskId> {
+    crate::executor::poll_context().map(|context| context.task)
+}
+
+/// Sends `sig` to the task `id`.
+///
+/// If the signal is not masked, the [`Interruptible`] future the task waits
+/// on is woken and resolves with [`AxError::Interrupted`].
+pub fn signal(id: TaskId, sig: Sig) -> AxResult {
+    let mut signals = SIGNALS.lock();
+    let Some(state) = signals.get_mut(&id) else {
+        return ax_err!(NoProcess, "signal(): no such task");
+    };
+    state.pending.insert(sig);
+    let waker = if state.deliverable().is_empty() {
+        None
+    } else {
+        state.waker.take()
+    };
+    drop(signals);
+    debug!("task {}: signal {:?}", id, sig);
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+    Ok(())
+}
+
+/// Sets the signal mask of the current task, and returns the previous one.
+///
+/// Masked signals stay pending but do not interrupt the task. [`Sig::Kill`]
+/// cannot be masked. Outside of a task, it does nothing and returns an empty
+/// set.
+pub fn set_signal_mask(mask: SigSet) -> SigSet {
+    let Some(id) = current_task_id() else {
+        return SigSet::empty();
+    };
+    let mut signals = SIGNALS.lock();
+    match signals.get_mut(&id) {
+        Some(state) => core::mem::replace(&mut state.mask, mask),
+        None => SigSet::empty(),
+    }
+}
+
+/// Returns the pending signals of the current task.
+pub fn pending_signals() -> SigSet {
+    current_task_id()
+        .and_then(|id| SIGNALS.lock().get(&id).map(|state| state.pending))
+        .unwrap_or_default()
+}
+
+/// Takes the lowest-numbered unmasked pending signal of the current task.
+pub fn take_signal() -> Option<Sig> {
+    let id = current_task_id()?;
+    let mut signals = SIGNALS.lock();
+    let state = signals.get_mut(&id)?;
+    let deliverable = state.deliverable();
+    let sig = Sig::ALL
+        .into_iter()
+        .find(|&sig| deliverable.contains(sig))?;
+    state.pending.remove(sig);
+    Some(sig)
+}
+
+/// A future that resolves with [`AxError::Interrupted`] when the current task
+/// has an unmasked pending signal, created by [`interruptible`].
+pub struct Interruptible<F> {
+    future: F,
+    task: Option<TaskId>,
+}
+
+/// Makes `future` interruptible by signals to the task awaiting it.
+///
+/// Outside of a task, the future cannot be interrupted.
+pub fn interruptible<F: Future>(future: F) -> Interruptible<F> {
+    Interruptible { future, task: None }
+}
+
+impl<F: Future> Future for Interruptible<F> {
+    type Output = AxResult<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out.
+        let this = unsafe { self.get_unchecked_mut() };
+        let Some(id) = current_task_id() else {
+            let future = unsafe { Pin::new_unchecked(&mut this.future) };
+            return future.poll(cx).map(Ok);
+        };
+
+        {
+            let mut signals = SIGNALS.lock();
+            if let Some(state) = signals.get_mut(&id) {
+                if !state.deliverable().is_empty() {
+                    state.waker = None;
+                    return Poll::Ready(Err(AxError::Interrupted));
+                }
+                state.waker = Some(cx.waker().clone());
+                this.task = Some(id);
+            }
+        }
+
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        future.poll(cx).map(Ok)
+    }
+}
+
+impl<F> Drop for Interruptible<F> {
+    fn drop(&mut self) {
+        // Do not leave a waker of a future that is gone.
+        if let Some(id) = self.task {
+            if let Some(state) = SIGNALS.lock().get_mut(&id) {
+                state.waker = None;
+            }
+        }
+    }
+}
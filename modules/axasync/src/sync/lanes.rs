@@ -0,0 +1,270 @@
+//! A multi-producer, single-consumer channel with two priority lanes.
+//!
+//! The messages of the [high](Lane::High) lane are received before all the
+//! queued messages of the [normal](Lane::Normal) lane, e.g. so that the
+//! error events of a device are handled at once, not behind a backlog of
+//! received packets:
+//!
+//! ```ignore
+//! let (sender, mut receiver) = lanes::channel(64);
+//! sender.try_send(Event::Packet(buf), Lane::Normal)?;
+//! sender.try_send(Event::LinkDown, Lane::High)?;
+//! assert!(matches!(receiver.recv().await, Some(Event::LinkDown)));
+//! ```
+//!
+//! It can be sent to from interrupt handlers, with [`Sender::try_send`].
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::fmt;
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+
+use kspin::SpinNoIrq;
+
+/// The lane of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Received before the normal lane. It is unbounded, for the rare events
+    /// that must not be dropped or delayed.
+    High,
+    /// Received in order, bounded by the capacity of the channel.
+    Normal,
+}
+
+/// The error of [`Sender::try_send`], with the message that was not sent.
+#[derive(PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The normal lane is full.
+    Full(T),
+    /// The receiver is dropped.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the message that was not sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full(msg) | Self::Closed(msg) => msg,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(_) => f.write_str("Full(..)"),
+            Self::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(_) => f.write_str("channel full"),
+            Self::Closed(_) => f.write_str("channel closed"),
+        }
+    }
+}
+
+struct State<T> {
+    high: VecDeque<T>,
+    normal: VecDeque<T>,
+    /// The bound of the normal lane.
+    capacity: usize,
+    senders: usize,
+    receiver_closed: bool,
+    /// The waker of the receiver.
+    recv_waker: Option<Waker>,
+    /// The wakers of the senders waiting for room in the normal lane.
+    send_wakers: VecDeque<Waker>,
+}
+
+/// Creates a channel whose normal lane holds up to `capacity` messages.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "lanes::channel: zero capacity");
+    let state = Arc::new(SpinNoIrq::new(State {
+        high: VecDeque::new(),
+        normal: VecDeque::new(),
+        capacity,
+        senders: 1,
+        receiver_closed: false,
+        recv_waker: None,
+        send_wakers: VecDeque::new(),
+    }));
+    (
+        Sender {
+            state: state.clone(),
+        },
+        Receiver { state },
+    )
+}
+
+/// The sending side of a [`channel`], cloned for each producer.
+pub struct Sender<T> {
+    state: Arc<SpinNoIrq<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `msg` on `lane` if it has room, without waiting.
+    pub fn try_send(&self, msg: T, lane: Lane) -> Result<(), TrySendError<T>> {
+        let waker = {
+            let mut state = self.state.lock();
+            if state.receiver_closed {
+                return Err(TrySendError::Closed(msg));
+            }
+            match lane {
+                Lane::High => state.high.push_back(msg),
+                Lane::Normal if state.normal.len() == state.capacity => {
+                    return Err(TrySendError::Full(msg));
+                }
+                Lane::Normal => state.normal.push_back(msg),
+            }
+            state.recv_waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Sends `msg` on `lane`, waiting for room in the normal lane. Fails with
+    /// the message if the receiver is dropped.
+    pub async fn send(&self, msg: T, lane: Lane) -> Result<(), T> {
+        let mut msg = Some(msg);
+        poll_fn(|cx| {
+            let taken = msg.take().expect("polled after completion");
+            match self.try_send(taken, lane) {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(TrySendError::Closed(taken)) => Poll::Ready(Err(taken)),
+                Err(TrySendError::Full(taken)) => {
+                    let mut state = self.state.lock();
+                    // Received from meanwhile, try again at once.
+                    if state.normal.len() < state.capacity {
+                        cx.waker().wake_by_ref();
+                    } else {
+                        state.send_wakers.push_back(cx.waker().clone());
+                    }
+                    msg = Some(taken);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Returns whether the receiver is dropped.
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().receiver_closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.state.lock().senders += 1;
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            state.recv_waker.take()
+        };
+        // The last sender, the receiver sees the end of the messages.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The receiving side of a [`channel`].
+pub struct Receiver<T> {
+    state: Arc<SpinNoIrq<State<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next message, of the high lane first, or returns `None`
+    /// once all the senders are dropped and the lanes are empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for the next message, see [`recv`](Self::recv).
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let (msg, waker) = {
+            let mut state = self.state.lock();
+            match state.high.pop_front() {
+                Some(msg) => (msg, None),
+                None => match state.normal.pop_front() {
+                    Some(msg) => (msg, state.send_wakers.pop_front()),
+                    None if state.senders == 0 => return Poll::Ready(None),
+                    None => {
+                        state.recv_waker = Some(cx.waker().clone());
+                        return Poll::Pending;
+                    }
+                },
+            }
+        };
+        // Room for a waiting sender.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Some(msg))
+    }
+
+    /// Receives the next message if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let (msg, waker) = {
+            let mut state = self.state.lock();
+            match state.high.pop_front() {
+                Some(msg) => (msg, None),
+                None => {
+                    let msg = state.normal.pop_front()?;
+                    (msg, state.send_wakers.pop_front())
+                }
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Some(msg)
+    }
+
+    /// Returns the number of queued messages, of both lanes.
+    pub fn len(&self) -> usize {
+        let state = self.state.lock();
+        state.high.len() + state.normal.len()
+    }
+
+    /// Returns whether both lanes are empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.state.lock();
+            state.receiver_closed = true;
+            core::mem::take(&mut state.send_wakers)
+        };
+        // The waiting senders fail.
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Synchronization primitives for async tasks.
 
+pub mod lanes;
 mod mutex;
 mod rwlock;
 mod semaphore;
@@ -9,7 +9,7 @@ use core::future::Future;
 use core::ops::{Deref, DerefMut};
 use core::pin::Pin;
 use core::sync::atomic::{AtomicBool, Ordering};
-use core::task::{Context, Poll, Waker};
+use core::task::{Context, Poll, Waker, ready};
 use spin::Mutex as SpinMutex;
 
 /// An asynchronous mutual exclusion primitive useful for protecting shared data.
@@ -109,6 +109,7 @@ impl<'a, T: ?Sized> Future for MutexLockFuture<'a, T> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         trace!("mutex lock poll");
+        ready!(crate::coop::poll_proceed(cx));
         // Fast path: try to acquire the lock without going to sleep
         if let Some(guard) = self.mutex.try_lock() {
             return Poll::Ready(guard);
@@ -9,7 +9,7 @@ use core::future::Future;
 use core::ops::{Deref, DerefMut};
 use core::pin::Pin;
 use core::sync::atomic::{AtomicUsize, Ordering};
-use core::task::{Context, Poll, Waker};
+use core::task::{Context, Poll, Waker, ready};
 use spin::Mutex as SpinMutex;
 
 // Constants for the state field in RwLockInner
@@ -159,6 +159,7 @@ impl<'a, T: ?Sized> Future for RwLockReadFuture<'a, T> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         trace!("rwlock read poll");
+        ready!(crate::coop::poll_proceed(cx));
         // Fast path: try to acquire the read lock
         if let Some(guard) = self.lock.try_read() {
             return Poll::Ready(guard);
@@ -198,6 +199,7 @@ impl<'a, T: ?Sized> Future for RwLockWriteFuture<'a, T> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         trace!("rwlock write poll");
+        ready!(crate::coop::poll_proceed(cx));
         // Fast path: try to acquire the write lock
         if let Some(guard) = self.lock.try_write() {
             return Poll::Ready(guard);
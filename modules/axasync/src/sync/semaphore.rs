@@ -6,7 +6,7 @@ use core::fmt;
 use core::future::Future;
 use core::pin::Pin;
 use core::sync::atomic::{AtomicUsize, Ordering};
-use core::task::{Context, Poll, Waker};
+use core::task::{Context, Poll, Waker, ready};
 use spin::Mutex as SpinMutex;
 
 /// An asynchronous semaphore.
@@ -105,6 +105,7 @@ impl<'a> Future for SemaphoreAcquireFuture<'a> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         trace!("semaphore acquire poll");
+        ready!(crate::coop::poll_proceed(cx));
         // Fast path: try to acquire a permit immediately
         if let Some(permit) = self.semaphore.try_acquire() {
             return Poll::Ready(permit);
@@ -0,0 +1,171 @@
+//! Values local to a task, see [`task_local!`](crate::task_local!).
+//!
+//! The values are stored per [`TaskId`], so they follow the task across its
+//! await points, whichever CPU polls it, and are dropped when it completes
+//! or is aborted. The current task is the one the current axtask polls, an
+//! axtask preempting it on the same CPU does not see its values.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use spin::Mutex;
+
+use crate::{TaskId, current_task_id};
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+/// The values of the live tasks, by task and by key.
+static LOCALS: Mutex<BTreeMap<TaskId, BTreeMap<usize, Value>>> = Mutex::new(BTreeMap::new());
+
+/// The ID of the next key to be used, `0` is not a key.
+static NEXT_KEY: AtomicUsize = AtomicUsize::new(1);
+
+/// Declares keys of task-local values, of type [`LocalKey`].
+///
+/// ```ignore
+/// axasync::task_local! {
+///     /// The connection the task serves.
+///     static CONNECTION_ID: u64;
+/// }
+///
+/// CONNECTION_ID.set(42);
+/// sleep(Duration::from_millis(10)).await;
+/// assert_eq!(CONNECTION_ID.get(), 42);
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::LocalKey<$t> = $crate::LocalKey::new(stringify!($name));
+        $crate::task_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::task_local!($(#[$attr])* $vis static $name: $t;);
+    };
+}
+
+/// The error of [`LocalKey::try_with`], outside of a task or if the value
+/// is not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError;
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("task-local value not set")
+    }
+}
+
+/// The key of a value local to each task, declared with
+/// [`task_local!`](crate::task_local!).
+///
+/// Each task has its own value, set with [`set`](Self::set) and read with
+/// [`with`](Self::with) from the task itself. A task starts without a value.
+pub struct LocalKey<T: 'static> {
+    name: &'static str,
+    /// Assigned on first use.
+    id: AtomicUsize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            id: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    fn id(&self) -> usize {
+        let id = self.id.load(Ordering::Acquire);
+        if id != 0 {
+            return id;
+        }
+        let new = NEXT_KEY.fetch_add(1, Ordering::Relaxed);
+        match self
+            .id
+            .compare_exchange(0, new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => new,
+            Err(id) => id,
+        }
+    }
+
+    /// Sets the value of the current task, and drops the previous one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is called outside of a task.
+    pub fn set(&'static self, value: T) {
+        let Some(task) = current_task_id() else {
+            panic!("task-local {} set outside of a task", self.name);
+        };
+        let prev = LOCALS
+            .lock()
+            .entry(task)
+            .or_default()
+            .insert(self.id(), Arc::new(value));
+        // Not under the lock, its destructor may use task-locals.
+        drop(prev);
+    }
+
+    /// Calls `f` with the value of the current task.
+    ///
+    /// The value is not borrowed from the storage, `f` may use other
+    /// task-locals.
+    pub fn try_with<R>(&'static self, f: impl FnOnce(&T) -> R) -> Result<R, AccessError> {
+        let task = current_task_id().ok_or(AccessError)?;
+        let value = LOCALS
+            .lock()
+            .get(&task)
+            .and_then(|locals| locals.get(&self.id()))
+            .cloned()
+            .ok_or(AccessError)?;
+        // The key is only set with values of type `T`.
+        let value = value.downcast_ref::<T>().ok_or(AccessError)?;
+        Ok(f(value))
+    }
+
+    /// Calls `f` with the value of the current task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is called outside of a task, or if the value is not set,
+    /// see [`try_with`](Self::try_with).
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        self.try_with(f)
+            .unwrap_or_else(|_| panic!("task-local {} is not set", self.name))
+    }
+
+    /// Returns a copy of the value of the current task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is called outside of a task, or if the value is not set.
+    pub fn get(&'static self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKey")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Drops the values of the task `id`, once it has completed.
+pub(crate) fn clear(id: TaskId) {
+    let locals = LOCALS.lock().remove(&id);
+    drop(locals);
+}
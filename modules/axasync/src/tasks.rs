@@ -0,0 +1,222 @@
+//! Names, states and statistics of the live tasks, for debugging.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::time::Duration;
+
+use kspin::SpinNoIrq;
+use spin::Mutex;
+
+use crate::TaskId;
+use crate::executor::Task;
+
+/// The live tasks of all executors.
+static TASKS: Mutex<BTreeMap<TaskId, Entry>> = Mutex::new(BTreeMap::new());
+
+struct Entry {
+    info: TaskInfo,
+    /// Weak, so that it is not counted as a waker of the task.
+    task: Weak<Task>,
+    /// When the last poll of the task returned, in monotonic nanoseconds.
+    polled_at: u64,
+}
+
+/// Maximum number of exit hooks.
+const MAX_EXIT_HOOKS: usize = 4;
+
+/// The hooks called when a task exits, see [`register_exit_hook`].
+static EXIT_HOOKS: SpinNoIrq<([Option<fn(TaskId, TaskExit)>; MAX_EXIT_HOOKS], usize)> =
+    SpinNoIrq::new(([None; MAX_EXIT_HOOKS], 0));
+
+/// How a task exited, see [`register_exit_hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskExit {
+    /// Its future completed.
+    Completed,
+    /// It panicked, with the `unwind` feature.
+    Panicked,
+    /// It was aborted, or the executor shut down.
+    Aborted,
+}
+
+impl fmt::Display for TaskExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Completed => "completed",
+            Self::Panicked => "panicked",
+            Self::Aborted => "aborted",
+        })
+    }
+}
+
+/// Where a task is in its life, see [`TaskInfo::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// In the run queue, waiting to be polled.
+    Ready,
+    /// Being polled.
+    Running,
+    /// Waiting for a wake, e.g. of a timer or a socket.
+    Pending,
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Ready => "ready",
+            Self::Running => "running",
+            Self::Pending => "pending",
+        })
+    }
+}
+
+/// A snapshot of a live task, returned by [`task_list`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// The ID of the task.
+    pub id: TaskId,
+    /// The name given to [`spawn_named`](crate::spawn_named), if any.
+    pub name: Option<String>,
+    /// Where the task is.
+    pub state: TaskState,
+    /// How long the task has been [pending](TaskState::Pending), zero in
+    /// the other states.
+    pub pending: Duration,
+    /// Number of times the task has been polled.
+    pub polls: u64,
+    /// Total time spent polling the task, in nanoseconds.
+    pub poll_nanos: u64,
+    /// Time spent in the longest poll of the task, in nanoseconds.
+    pub max_poll_nanos: u64,
+}
+
+pub(crate) fn register(id: TaskId, name: Option<String>, task: Weak<Task>) {
+    let info = TaskInfo {
+        id,
+        name,
+        state: TaskState::Ready,
+        pending: Duration::ZERO,
+        polls: 0,
+        poll_nanos: 0,
+        max_poll_nanos: 0,
+    };
+    let entry = Entry {
+        info,
+        task,
+        polled_at: 0,
+    };
+    TASKS.lock().insert(id, entry);
+}
+
+/// Calls the exit hooks for the task `id`, then unregisters it. Its future
+/// has been dropped, with the resources it owned.
+pub(crate) fn exit(id: TaskId, exit: TaskExit) {
+    // Not under the lock, a hook may take a while.
+    let (hooks, len) = *EXIT_HOOKS.lock();
+    for hook in hooks[..len].iter().flatten() {
+        hook(id, exit);
+    }
+    TASKS.lock().remove(&id);
+}
+
+/// Registers a hook called when a task exits, however it does, e.g. to
+/// report the resources it left behind.
+///
+/// The hook is called by the executor, after the future of the task has been
+/// dropped, and while the name of the task is still known. Returns `false`
+/// if there are too many hooks.
+pub fn register_exit_hook(hook: fn(TaskId, TaskExit)) -> bool {
+    let mut hooks = EXIT_HOOKS.lock();
+    let (slots, len) = &mut *hooks;
+    if *len >= MAX_EXIT_HOOKS {
+        warn!("too many exit hooks, ignoring {:#x}", hook as usize);
+        return false;
+    }
+    slots[*len] = Some(hook);
+    *len += 1;
+    true
+}
+
+pub(crate) fn record_poll(id: TaskId, nanos: u64) {
+    if let Some(entry) = TASKS.lock().get_mut(&id) {
+        let info = &mut entry.info;
+        info.polls += 1;
+        info.poll_nanos += nanos;
+        info.max_poll_nanos = info.max_poll_nanos.max(nanos);
+        entry.polled_at = axhal::time::monotonic_time_nanos();
+    }
+}
+
+/// Returns the name of the task `id`, if it is alive and has one.
+pub fn task_name(id: TaskId) -> Option<String> {
+    TASKS.lock().get(&id)?.info.name.clone()
+}
+
+/// Calls `f` with the name of the task `id`, without waiting for the
+/// registry, e.g. to format the ID in a log message.
+pub(crate) fn with_task_name<R>(id: TaskId, f: impl FnOnce(Option<&str>) -> R) -> R {
+    match TASKS.try_lock() {
+        Some(tasks) => f(tasks.get(&id).and_then(|entry| entry.info.name.as_deref())),
+        None => f(None),
+    }
+}
+
+/// Returns a snapshot of all the live tasks, ordered by ID.
+pub fn task_list() -> Vec<TaskInfo> {
+    let now = axhal::time::monotonic_time_nanos();
+    // Dropped after the lock, a task dropped with them may unregister.
+    let mut alive = Vec::new();
+    let tasks = TASKS.lock();
+    let list = tasks
+        .values()
+        .filter_map(|entry| {
+            // Gone if it is being dropped.
+            let task = entry.task.upgrade()?;
+            let state = task.state();
+            alive.push(task);
+            let pending = match state {
+                TaskState::Pending => Duration::from_nanos(now.saturating_sub(entry.polled_at)),
+                _ => Duration::ZERO,
+            };
+            Some(TaskInfo {
+                state,
+                pending,
+                ..entry.info.clone()
+            })
+        })
+        .collect();
+    drop(tasks);
+    list
+}
+
+/// Formats the live tasks of all executors into a table, one per line, with
+/// their names, states and how long they have been pending, e.g. to find
+/// the future that hangs:
+///
+/// ```text
+///     ID  STATE       PENDING(ms)      POLLS  NAME
+///      3  pending           12034          2  http-conn-1
+/// ```
+pub fn dump_tasks() -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:>6}  {:<8} {:>14} {:>10}  NAME",
+        "ID", "STATE", "PENDING(ms)", "POLLS"
+    );
+    for task in task_list() {
+        let _ = writeln!(
+            out,
+            "{:>6}  {:<8} {:>14} {:>10}  {}",
+            task.id.as_u64(),
+            task.state,
+            task.pending.as_millis(),
+            task.polls,
+            task.name.as_deref().unwrap_or("-")
+        );
+    }
+    out
+}
@@ -2,21 +2,31 @@
 
 use core::future::Future;
 use core::pin::Pin;
-use core::task::{Context, Poll, Waker};
+use core::task::{Context, Poll};
 use core::time::Duration;
 
 use axhal::time::{TimeValue, monotonic_time as current_time};
 
+#[cfg(feature = "timer")]
+mod wheel;
+
+#[cfg(feature = "timer")]
+pub(crate) use self::wheel::{expire_one as expire_timer, next_deadline as next_timer_deadline};
+
 /// A future that completes after a specified duration of time.
+///
+/// With the `timer` feature, its timer is stored inline, so sleeping does not
+/// allocate.
 pub struct Sleep {
     deadline: TimeValue,
-    registered_waker: Option<Waker>,
+    #[cfg(feature = "timer")]
+    entry: wheel::TimerEntry,
 }
 
 impl Sleep {
     /// Creates a new future that completes after the specified duration.
     pub fn new(duration: Duration) -> Self {
-        let deadline = current_time() + duration;
+        let deadline = current_time().saturating_add(duration);
         debug!("Sleeping until {:?}", deadline);
         Self::until(deadline)
     }
@@ -25,7 +35,8 @@ impl Sleep {
     pub fn until(deadline: TimeValue) -> Self {
         Self {
             deadline,
-            registered_waker: None,
+            #[cfg(feature = "timer")]
+            entry: wheel::TimerEntry::new(),
         }
     }
 
@@ -35,13 +46,20 @@ impl Sleep {
     }
 
     /// Resets the sleep to complete after the specified duration.
-    pub fn reset(&mut self, duration: Duration) {
-        self.deadline = current_time() + duration;
+    pub fn reset(self: Pin<&mut Self>, duration: Duration) {
+        self.reset_until(current_time().saturating_add(duration));
     }
 
     /// Resets the sleep to complete at the specified deadline.
-    pub fn reset_until(&mut self, deadline: TimeValue) {
-        self.deadline = deadline;
+    pub fn reset_until(self: Pin<&mut Self>, deadline: TimeValue) {
+        // Safety: The deadline is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.deadline = deadline;
+        #[cfg(feature = "timer")]
+        {
+            let entry = unsafe { Pin::new_unchecked(&this.entry) };
+            entry.reset(deadline);
+        }
     }
 }
 
@@ -52,21 +70,18 @@ impl Future for Sleep {
         trace!("sleep poll");
         let now = current_time();
         if now >= self.deadline {
+            #[cfg(feature = "timer")]
+            self.entry.cancel();
             Poll::Ready(())
         } else {
             #[cfg(feature = "timer")]
             {
-                let this = self.get_mut();
-                if let Some(ref waker) = this.registered_waker {
-                    if !waker.will_wake(cx.waker()) {
-                        this.registered_waker = Some(cx.waker().clone());
-                        crate::waker::wake_at(this.deadline, cx.waker().clone());
-                    }
-                } else {
-                    this.registered_waker = Some(cx.waker().clone());
-                    crate::waker::wake_at(this.deadline, cx.waker().clone());
-                }
+                // Safety: The entry is pinned with the sleep.
+                let entry = unsafe { self.as_ref().map_unchecked(|this| &this.entry) };
+                entry.arm(self.deadline, cx.waker());
             }
+            #[cfg(not(feature = "timer"))]
+            let _ = cx;
             // info!("Sleeping for {:?}", self.deadline - now);
             Poll::Pending
         }
@@ -0,0 +1,245 @@
+//! A hashed timing wheel of intrusive timer entries.
+//!
+//! A [`Sleep`](super::Sleep) keeps its [`TimerEntry`] inline, and the wheel
+//! links the entries of each slot in a list through pointers into them, so
+//! arming and cancelling a timer never allocates.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomPinned;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::Waker;
+
+use axhal::time::TimeValue;
+use kspin::SpinNoIrq;
+
+/// Number of slots of the wheel.
+const SLOTS: usize = 256;
+/// Duration covered by a slot, in nanoseconds.
+const TICK_NANOS: u64 = 1_000_000;
+
+static WHEEL: SpinNoIrq<Wheel> = SpinNoIrq::new(Wheel {
+    heads: [None; SLOTS],
+    next_tick: 0,
+});
+
+fn tick_of(time: TimeValue) -> u64 {
+    axhal::time::saturating_nanos(time) / TICK_NANOS
+}
+
+struct Wheel {
+    /// The first entry of each slot.
+    heads: [Option<NonNull<Node>>; SLOTS],
+    /// The first tick whose slot may still hold expired entries.
+    next_tick: u64,
+}
+
+// SAFETY: The entries are only accessed with the wheel locked.
+unsafe impl Send for Wheel {}
+
+impl Wheel {
+    /// Links `node` in the slot of its deadline.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be pinned, not linked, and unlinked before it is dropped.
+    unsafe fn link(&mut self, node: NonNull<Node>) {
+        let n = node.as_ptr();
+        unsafe {
+            // A deadline whose slot is already processed goes to the next one.
+            let tick = tick_of((*n).deadline).max(self.next_tick);
+            let slot = (tick % SLOTS as u64) as usize;
+            (*n).slot = slot;
+            (*n).prev = None;
+            (*n).next = self.heads[slot];
+            if let Some(next) = self.heads[slot] {
+                (*next.as_ptr()).prev = Some(node);
+            }
+            self.heads[slot] = Some(node);
+            (*n).linked = true;
+        }
+    }
+
+    /// Unlinks `node` from its slot.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be linked in this wheel.
+    unsafe fn unlink(&mut self, node: NonNull<Node>) {
+        let n = node.as_ptr();
+        unsafe {
+            match (*n).prev {
+                Some(prev) => (*prev.as_ptr()).next = (*n).next,
+                None => self.heads[(*n).slot] = (*n).next,
+            }
+            if let Some(next) = (*n).next {
+                (*next.as_ptr()).prev = (*n).prev;
+            }
+            (*n).prev = None;
+            (*n).next = None;
+            (*n).linked = false;
+        }
+    }
+
+    /// Unlinks an entry expired at `now`, and returns its waker.
+    fn expire_one(&mut self, now: TimeValue) -> Option<Waker> {
+        let now_tick = tick_of(now);
+        // Past one turn, every slot has been covered.
+        self.next_tick = self
+            .next_tick
+            .max((now_tick + 1).saturating_sub(SLOTS as u64));
+        while self.next_tick <= now_tick {
+            let mut cursor = self.heads[(self.next_tick % SLOTS as u64) as usize];
+            while let Some(node) = cursor {
+                let n = node.as_ptr();
+                // SAFETY: Linked entries are alive, and only accessed with the
+                // wheel locked.
+                unsafe {
+                    if (*n).deadline <= now {
+                        self.unlink(node);
+                        return (*n).waker.take();
+                    }
+                    cursor = (*n).next;
+                }
+            }
+            // Entries may still be armed in the slot of the current tick.
+            if self.next_tick == now_tick {
+                break;
+            }
+            self.next_tick += 1;
+        }
+        None
+    }
+
+    /// Returns the earliest deadline of the armed entries.
+    ///
+    /// The entries of a slot span several turns, so all of them are scanned.
+    fn next_deadline(&self) -> Option<TimeValue> {
+        let mut earliest = None;
+        for head in self.heads {
+            let mut cursor = head;
+            while let Some(node) = cursor {
+                // SAFETY: Linked entries are alive, and only accessed with the
+                // wheel locked.
+                unsafe {
+                    let deadline = (*node.as_ptr()).deadline;
+                    earliest = Some(earliest.map_or(deadline, |e: TimeValue| e.min(deadline)));
+                    cursor = (*node.as_ptr()).next;
+                }
+            }
+        }
+        earliest
+    }
+}
+
+struct Node {
+    deadline: TimeValue,
+    waker: Option<Waker>,
+    slot: usize,
+    prev: Option<NonNull<Node>>,
+    next: Option<NonNull<Node>>,
+    linked: bool,
+}
+
+/// A timer that can be linked in the wheel, to wake a task at a deadline.
+///
+/// It must be pinned to be armed, and is cancelled when dropped.
+pub(crate) struct TimerEntry {
+    node: UnsafeCell<Node>,
+    _pin: PhantomPinned,
+}
+
+// SAFETY: The node is only accessed with the wheel locked.
+unsafe impl Send for TimerEntry {}
+unsafe impl Sync for TimerEntry {}
+
+impl TimerEntry {
+    pub(crate) const fn new() -> Self {
+        Self {
+            node: UnsafeCell::new(Node {
+                deadline: TimeValue::ZERO,
+                waker: None,
+                slot: 0,
+                prev: None,
+                next: None,
+                linked: false,
+            }),
+            _pin: PhantomPinned,
+        }
+    }
+
+    fn node(&self) -> NonNull<Node> {
+        // SAFETY: An `UnsafeCell` is never null.
+        unsafe { NonNull::new_unchecked(self.node.get()) }
+    }
+
+    /// Arms the timer to wake `waker` at `deadline`.
+    pub(crate) fn arm(self: Pin<&Self>, deadline: TimeValue, waker: &Waker) {
+        let mut wheel = WHEEL.lock();
+        let node = self.node();
+        let n = node.as_ptr();
+        // SAFETY: The wheel is locked, the entry is pinned and unlinked when
+        // dropped.
+        unsafe {
+            if !(*n).waker.as_ref().is_some_and(|w| w.will_wake(waker)) {
+                (*n).waker = Some(waker.clone());
+            }
+            if (*n).linked {
+                if (*n).deadline == deadline {
+                    return;
+                }
+                wheel.unlink(node);
+            }
+            (*n).deadline = deadline;
+            wheel.link(node);
+        }
+    }
+
+    /// Moves the timer to `deadline`, if it is armed.
+    pub(crate) fn reset(self: Pin<&Self>, deadline: TimeValue) {
+        let mut wheel = WHEEL.lock();
+        let node = self.node();
+        let n = node.as_ptr();
+        // SAFETY: The wheel is locked, the entry is pinned and unlinked when
+        // dropped.
+        unsafe {
+            if (*n).linked {
+                wheel.unlink(node);
+                (*n).deadline = deadline;
+                wheel.link(node);
+            }
+        }
+    }
+
+    /// Disarms the timer.
+    pub(crate) fn cancel(&self) {
+        let mut wheel = WHEEL.lock();
+        let node = self.node();
+        let n = node.as_ptr();
+        // SAFETY: The wheel is locked.
+        unsafe {
+            if (*n).linked {
+                wheel.unlink(node);
+            }
+            (*n).waker = None;
+        }
+    }
+}
+
+impl Drop for TimerEntry {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Unlinks a timer expired at `now`, and returns its waker.
+///
+/// It returns `None` if the wheel is locked, e.g. by the interrupted code.
+pub(crate) fn expire_one(now: TimeValue) -> Option<Waker> {
+    WHEEL.try_lock()?.expire_one(now)
+}
+
+/// Returns the earliest deadline of the armed timers.
+pub(crate) fn next_deadline() -> Option<TimeValue> {
+    WHEEL.lock().next_deadline()
+}
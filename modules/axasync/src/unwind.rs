@@ -0,0 +1,54 @@
+//! Isolation of the panics of the tasks.
+//!
+//! A panic in the poll of a task unwinds up to its executor instead of
+//! stopping the kernel: the future of the task is dropped, its
+//! [`JoinHandle`](crate::JoinHandle) resolves to
+//! [`JoinError::Panicked`](crate::JoinError::Panicked), and the other tasks
+//! keep running.
+//!
+//! The kernel must be built with `-C panic=unwind`, and its panic handler
+//! must start the unwinding, see the `unwind` feature of `axruntime`. A panic
+//! outside of the polls of the tasks still stops the kernel.
+
+use core::future::Future;
+use core::panic::AssertUnwindSafe;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// A future that resolves to the output of its inner future, or to `Err(())`
+/// once a poll of it panics. The inner future is dropped at once then.
+pub(crate) struct CatchUnwind<F> {
+    future: Option<F>,
+}
+
+impl<F> CatchUnwind<F> {
+    pub(crate) const fn new(future: F) -> Self {
+        Self {
+            future: Some(future),
+        }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, ()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: The inner future is never moved, only dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = this
+            .future
+            .as_mut()
+            .expect("`CatchUnwind` polled after a panic");
+        // SAFETY: See above.
+        let future = unsafe { Pin::new_unchecked(future) };
+        // The state of the future is not observed after a panic, it is
+        // dropped.
+        match unwinding::panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(_) => {
+                this.future = None;
+                Poll::Ready(Err(()))
+            }
+        }
+    }
+}
@@ -98,7 +98,9 @@ mod timer_waker {
 
     /// Sets a waker to be woken at the specified deadline.
     ///
-    /// Only one timer can be active for each waker at a time.
+    /// Only one timer can be active for each waker at a time. Each call
+    /// allocates an entry, frequent timers should use a
+    /// [`Sleep`](crate::time::Sleep), whose timer is stored inline.
     pub fn wake_at(deadline: TimeValue, waker: Waker) {
         // trace!("Setting waker to wake at {:?}", deadline);
         let ticket_id = TIMER_TICKET_ID.fetch_add(1, Ordering::AcqRel);
@@ -111,10 +113,21 @@ mod timer_waker {
 
     /// Processes pending timer events.
     ///
-    /// This should be called periodically, e.g., from the timer interrupt handler.
+    /// It is called from the timer interrupt handler, the executor also
+    /// expires the timers at each iteration of its loop.
     pub fn check_timer_events() {
-        // trace!("check_timer_events");
-        let now = axhal::time::monotonic_time();
+        expire_timers(axhal::time::monotonic_time());
+    }
+
+    /// Wakes the tasks whose timers expired at `now`, returns whether there
+    /// were any.
+    pub(crate) fn expire_timers(now: TimeValue) -> bool {
+        // The sleeps first, they do not allocate.
+        let mut woken = false;
+        while let Some(waker) = crate::time::expire_timer(now) {
+            waker.wake();
+            woken = true;
+        }
 
         // Process all pending events
         loop {
@@ -122,7 +135,7 @@ mod timer_waker {
             let event_to_process = {
                 let Some(mut timer_list_guard) = TIMER_LIST.try_lock() else {
                     trace!("Another timer event is being processed");
-                    return;
+                    return woken;
                 };
                 if let Some(timer_list) = timer_list_guard.as_mut() {
                     timer_list.expire_one(now)
@@ -135,11 +148,27 @@ mod timer_waker {
             match event_to_process {
                 Some((_deadline, event)) => {
                     // debug!("Waking waker with ticket id {}", event.ticket_id);
-                    event.callback(now)
+                    event.callback(now);
+                    woken = true;
                 }
                 None => break,
             }
         }
+        woken
+    }
+
+    /// Returns the earliest deadline of the armed timers, of the sleeps and
+    /// of [`wake_at`].
+    pub(crate) fn next_timer_deadline() -> Option<TimeValue> {
+        let sleeps = crate::time::next_timer_deadline();
+        let wakers = TIMER_LIST
+            .lock()
+            .as_ref()
+            .and_then(|timer_list| timer_list.next_deadline());
+        match (sleeps, wakers) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
     }
 }
 
@@ -0,0 +1,148 @@
+//! Procedural macros of [`axasync`](https://github.com/arceos-org/arceos/tree/main/modules/axasync),
+//! re-exported by it.
+
+use core::time::Duration;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Error, FnArg, ItemFn, LitStr, PatType, parse_macro_input};
+
+/// Runs an async test on a fresh executor, in the hosted test harness.
+///
+/// The body is polled by a new [`Executor`] with `run_until`, on a thread of
+/// its own. It may take a `&Executor` argument to spawn tasks on it. With
+/// `timeout`, the test fails once it has run for longer, e.g. when a task is
+/// never woken, rather than hanging the whole suite:
+///
+/// ```ignore
+/// #[axasync::async_test(timeout = "2s")]
+/// async fn test_spawn(executor: &Executor) {
+///     let handle = executor.spawn(async { 42 });
+///     assert_eq!(handle.await, Ok(42));
+/// }
+/// ```
+///
+/// The timeout is a number followed by one of `ns`, `us`, `ms`, `s` or `m`.
+/// Only timeouts on the clock of the host are supported: there is no mock
+/// clock, and the time of the executor does not advance on the dummy
+/// platform, so a test awaiting an axasync timer never completes. The thread
+/// of a test that times out is left running until the end of the suite.
+///
+/// It is not named `test`, so that importing `axasync::*` does not shadow the
+/// built-in `#[test]`.
+///
+/// [`Executor`]: https://arceos-org.github.io/arceos/axasync/struct.Executor.html
+#[proc_macro_attribute]
+pub fn async_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut timeout = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("timeout") {
+            let lit: LitStr = meta.value()?.parse()?;
+            timeout = Some(parse_duration(&lit)?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported attribute, expected `timeout`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let func = parse_macro_input!(item as ItemFn);
+    expand_test(func, timeout)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_test(func: ItemFn, timeout: Option<Duration>) -> syn::Result<proc_macro2::TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+    if sig.asyncness.is_none() {
+        return Err(Error::new_spanned(sig.fn_token, "the test must be `async`"));
+    }
+    let body = match sig.inputs.len() {
+        0 => quote!(async move #block),
+        1 => match &sig.inputs[0] {
+            FnArg::Typed(arg) => {
+                let PatType { pat, ty, .. } = arg;
+                quote!(async move {
+                    let #pat: #ty = executor;
+                    #block
+                })
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new_spanned(receiver, "the test is not a method"));
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &sig.inputs,
+                "the test takes at most an `&Executor` argument",
+            ));
+        }
+    };
+    let name = &sig.ident;
+    let output = &sig.output;
+    let timeout = match timeout {
+        Some(timeout) => {
+            let nanos = timeout.as_nanos() as u64;
+            quote!(::core::option::Option::Some(::core::time::Duration::from_nanos(#nanos)))
+        }
+        None => quote!(::core::option::Option::None),
+    };
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            let timeout: ::core::option::Option<::core::time::Duration> = #timeout;
+            let (done_tx, done_rx) = ::std::sync::mpsc::channel::<()>();
+            let runner = ::std::thread::Builder::new()
+                .name(::std::string::String::from(stringify!(#name)))
+                .spawn(move || {
+                    let executor = &::axasync::Executor::new();
+                    let output = executor.run_until(#body);
+                    let _ = done_tx.send(());
+                    output
+                })
+                .expect("failed to spawn the thread of the test");
+            if let ::core::option::Option::Some(timeout) = timeout {
+                // Disconnected if the test panicked, the join reports it.
+                if let ::core::result::Result::Err(::std::sync::mpsc::RecvTimeoutError::Timeout) =
+                    done_rx.recv_timeout(timeout)
+                {
+                    panic!("test timed out after {:?}", timeout);
+                }
+            }
+            match runner.join() {
+                ::core::result::Result::Ok(output) => output,
+                ::core::result::Result::Err(panic) => ::std::panic::resume_unwind(panic),
+            }
+        }
+    })
+}
+
+/// Parses a duration such as `"2s"` or `"500ms"`.
+fn parse_duration(lit: &LitStr) -> syn::Result<Duration> {
+    let value = lit.value();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let error = |msg| Error::new(lit.span(), msg);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| error("expected a number followed by a unit, e.g. \"2s\""))?;
+    let duration = match unit.trim() {
+        "ns" => Duration::from_nanos(number),
+        "us" => Duration::from_micros(number),
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.saturating_mul(60)),
+        _ => return Err(error("unknown unit, expected `ns`, `us`, `ms`, `s` or `m`")),
+    };
+    if duration.is_zero() {
+        return Err(error("the timeout must not be zero"));
+    }
+    Ok(duration)
+}
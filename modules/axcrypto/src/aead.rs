@@ -0,0 +1,53 @@
+//! Authenticated encryption with associated data.
+
+use crate::Error;
+
+/// The length of a nonce, in bytes.
+pub const NONCE_LEN: usize = 12;
+/// The length of an authentication tag, in bytes.
+pub const TAG_LEN: usize = 16;
+
+/// An AEAD algorithm with a key, as used by TLS 1.2 and 1.3.
+///
+/// Messages are encrypted in place, and the tag is kept apart, so that no
+/// buffer needs to be allocated. A nonce must never be reused with the same
+/// key.
+pub trait Aead: Sized {
+    /// The length of a key, in bytes.
+    const KEY_LEN: usize;
+
+    /// Creates an instance with `key`, or fails with
+    /// [`InvalidLength`](Error::InvalidLength) if it is not
+    /// [`KEY_LEN`](Self::KEY_LEN) bytes long.
+    fn new_from_slice(key: &[u8]) -> Result<Self, Error>;
+
+    /// Creates an instance with a key from [`fill_random`], for ephemeral
+    /// sessions.
+    ///
+    /// [`fill_random`]: crate::rng::fill_random
+    fn generate() -> Result<Self, Error> {
+        let mut key = [0; 32];
+        let key = &mut key[..Self::KEY_LEN];
+        crate::rng::fill_random(key)?;
+        let aead = Self::new_from_slice(key);
+        key.fill(0);
+        aead
+    }
+
+    /// Encrypts `buf` in place, and returns the tag that authenticates it
+    /// with `aad`.
+    fn seal_in_place(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], buf: &mut [u8]) -> [u8; TAG_LEN];
+
+    /// Checks that `tag` authenticates `buf` with `aad`, and decrypts `buf` in
+    /// place.
+    ///
+    /// Fails with [`AuthFailed`](Error::AuthFailed), leaving `buf` untouched,
+    /// if it does not.
+    fn open_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), Error>;
+}
@@ -0,0 +1,299 @@
+//! The AES block cipher, as specified in FIPS 197.
+//!
+//! The S-box is not looked up in a table, whose access pattern would leak the
+//! key through the cache: it is computed with the circuit of Boyar and
+//! Peralta, on the 16 bytes of the state at once, with a bit of each byte in
+//! each lane of a `u16`.
+
+pub(crate) const BLOCK_LEN: usize = 16;
+
+/// An expanded AES-128, AES-192 or AES-256 key.
+#[derive(Clone)]
+pub(crate) struct Aes {
+    round_keys: [[u8; BLOCK_LEN]; 15],
+    rounds: usize,
+}
+
+impl Aes {
+    /// Expands `key`, which must be 16, 24 or 32 bytes long.
+    pub fn new(key: &[u8]) -> Self {
+        assert!(matches!(key.len(), 16 | 24 | 32));
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+
+        let mut w = [[0u8; 4]; 60];
+        for (w, word) in w.iter_mut().zip(key.chunks_exact(4)) {
+            w.copy_from_slice(word);
+        }
+        let mut rcon = 1;
+        for i in nk..4 * (rounds + 1) {
+            let mut t = w[i - 1];
+            if i % nk == 0 {
+                t.rotate_left(1);
+                t = sub_word(t);
+                t[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                t = sub_word(t);
+            }
+            for j in 0..4 {
+                w[i][j] = w[i - nk][j] ^ t[j];
+            }
+        }
+
+        let mut round_keys = [[0; BLOCK_LEN]; 15];
+        for (round_key, words) in round_keys.iter_mut().zip(w.chunks_exact(4)) {
+            for (dst, word) in round_key.chunks_exact_mut(4).zip(words) {
+                dst.copy_from_slice(word);
+            }
+        }
+        Self { round_keys, rounds }
+    }
+
+    /// Encrypts `block` in place.
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round_key in &self.round_keys[1..self.rounds] {
+            sub_bytes(block);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, round_key);
+        }
+        sub_bytes(block);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[self.rounds]);
+    }
+}
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ (0x1b & 0u8.wrapping_sub(b >> 7))
+}
+
+fn add_round_key(state: &mut [u8; BLOCK_LEN], round_key: &[u8; BLOCK_LEN]) {
+    for (s, k) in state.iter_mut().zip(round_key) {
+        *s ^= k;
+    }
+}
+
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut bytes = [0; BLOCK_LEN];
+    bytes[..4].copy_from_slice(&word);
+    sub_bytes(&mut bytes);
+    [bytes[0], bytes[1], bytes[2], bytes[3]]
+}
+
+fn sub_bytes(state: &mut [u8; BLOCK_LEN]) {
+    // `q[j]` holds the bit `j` of every byte.
+    let mut q = [0u16; 8];
+    for (i, b) in state.iter().enumerate() {
+        for (j, q) in q.iter_mut().enumerate() {
+            *q |= (((b >> j) & 1) as u16) << i;
+        }
+    }
+    sbox(&mut q);
+    for (i, b) in state.iter_mut().enumerate() {
+        *b = 0;
+        for (j, q) in q.iter().enumerate() {
+            *b |= (((q >> i) & 1) as u8) << j;
+        }
+    }
+}
+
+/// The S-box circuit of Boyar and Peralta, "A depth-16 circuit for the AES
+/// S-box" (2011), on bitsliced bytes, `q[0]` being the least significant bits.
+fn sbox(q: &mut [u16; 8]) {
+    let [x7, x6, x5, x4, x3, x2, x1, x0] = *q;
+
+    // Top linear transformation.
+    let y14 = x3 ^ x5;
+    let y13 = x0 ^ x6;
+    let y9 = x0 ^ x3;
+    let y8 = x0 ^ x5;
+    let t0 = x1 ^ x2;
+    let y1 = t0 ^ x7;
+    let y4 = y1 ^ x3;
+    let y12 = y13 ^ y14;
+    let y2 = y1 ^ x0;
+    let y5 = y1 ^ x6;
+    let y3 = y5 ^ y8;
+    let t1 = x4 ^ y12;
+    let y15 = t1 ^ x5;
+    let y20 = t1 ^ x1;
+    let y6 = y15 ^ x7;
+    let y10 = y15 ^ t0;
+    let y11 = y20 ^ y9;
+    let y7 = x7 ^ y11;
+    let y17 = y10 ^ y11;
+    let y19 = y10 ^ y8;
+    let y16 = t0 ^ y11;
+    let y21 = y13 ^ y16;
+    let y18 = x0 ^ y16;
+
+    // Non-linear section.
+    let t2 = y12 & y15;
+    let t3 = y3 & y6;
+    let t4 = t3 ^ t2;
+    let t5 = y4 & x7;
+    let t6 = t5 ^ t2;
+    let t7 = y13 & y16;
+    let t8 = y5 & y1;
+    let t9 = t8 ^ t7;
+    let t10 = y2 & y7;
+    let t11 = t10 ^ t7;
+    let t12 = y9 & y11;
+    let t13 = y14 & y17;
+    let t14 = t13 ^ t12;
+    let t15 = y8 & y10;
+    let t16 = t15 ^ t12;
+    let t17 = t4 ^ t14;
+    let t18 = t6 ^ t16;
+    let t19 = t9 ^ t14;
+    let t20 = t11 ^ t16;
+    let t21 = t17 ^ y20;
+    let t22 = t18 ^ y19;
+    let t23 = t19 ^ y21;
+    let t24 = t20 ^ y18;
+
+    let t25 = t21 ^ t22;
+    let t26 = t21 & t23;
+    let t27 = t24 ^ t26;
+    let t28 = t25 & t27;
+    let t29 = t28 ^ t22;
+    let t30 = t23 ^ t24;
+    let t31 = t22 ^ t26;
+    let t32 = t31 & t30;
+    let t33 = t32 ^ t24;
+    let t34 = t23 ^ t33;
+    let t35 = t27 ^ t33;
+    let t36 = t24 & t35;
+    let t37 = t36 ^ t34;
+    let t38 = t27 ^ t36;
+    let t39 = t29 & t38;
+    let t40 = t25 ^ t39;
+
+    let t41 = t40 ^ t37;
+    let t42 = t29 ^ t33;
+    let t43 = t29 ^ t40;
+    let t44 = t33 ^ t37;
+    let t45 = t42 ^ t41;
+    let z0 = t44 & y15;
+    let z1 = t37 & y6;
+    let z2 = t33 & x7;
+    let z3 = t43 & y16;
+    let z4 = t40 & y1;
+    let z5 = t29 & y7;
+    let z6 = t42 & y11;
+    let z7 = t45 & y17;
+    let z8 = t41 & y10;
+    let z9 = t44 & y12;
+    let z10 = t37 & y3;
+    let z11 = t33 & y4;
+    let z12 = t43 & y13;
+    let z13 = t40 & y5;
+    let z14 = t29 & y2;
+    let z15 = t42 & y9;
+    let z16 = t45 & y14;
+    let z17 = t41 & y8;
+
+    // Bottom linear transformation.
+    let t46 = z15 ^ z16;
+    let t47 = z10 ^ z11;
+    let t48 = z5 ^ z13;
+    let t49 = z9 ^ z10;
+    let t50 = z2 ^ z12;
+    let t51 = z2 ^ z5;
+    let t52 = z7 ^ z8;
+    let t53 = z0 ^ z3;
+    let t54 = z6 ^ z7;
+    let t55 = z16 ^ z17;
+    let t56 = z12 ^ t48;
+    let t57 = t50 ^ t53;
+    let t58 = z4 ^ t46;
+    let t59 = z3 ^ t54;
+    let t60 = t46 ^ t57;
+    let t61 = z14 ^ t57;
+    let t62 = t52 ^ t58;
+    let t63 = t49 ^ t58;
+    let t64 = z4 ^ t59;
+    let t65 = t61 ^ t62;
+    let t66 = z1 ^ t63;
+    let s0 = t59 ^ t63;
+    let s6 = t56 ^ !t62;
+    let s7 = t48 ^ !t60;
+    let t67 = t64 ^ t65;
+    let s3 = t53 ^ t66;
+    let s4 = t51 ^ t66;
+    let s5 = t47 ^ t65;
+    let s1 = t64 ^ !s3;
+    let s2 = t55 ^ !t67;
+
+    *q = [s7, s6, s5, s4, s3, s2, s1, s0];
+}
+
+fn shift_rows(state: &mut [u8; BLOCK_LEN]) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[4 * c + r] = old[4 * ((c + r) % 4) + r];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; BLOCK_LEN]) {
+    for col in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [col[0], col[1], col[2], col[3]];
+        let t = a0 ^ a1 ^ a2 ^ a3;
+        col[0] = a0 ^ t ^ xtime(a0 ^ a1);
+        col[1] = a1 ^ t ^ xtime(a1 ^ a2);
+        col[2] = a2 ^ t ^ xtime(a2 ^ a3);
+        col[3] = a3 ^ t ^ xtime(a3 ^ a0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unhex;
+
+    #[test]
+    fn test_sbox() {
+        fn gmul(a: u8, b: u8) -> u8 {
+            (0..8).fold(0, |p, i| {
+                if b >> i & 1 != 0 {
+                    p ^ (0..i).fold(a, |a, _| xtime(a))
+                } else {
+                    p
+                }
+            })
+        }
+        for x in 0..=255u8 {
+            // x^254 is the inverse of x, or 0.
+            let inv = (0..253).fold(x, |acc, _| gmul(acc, x));
+            let expected = inv
+                ^ inv.rotate_left(1)
+                ^ inv.rotate_left(2)
+                ^ inv.rotate_left(3)
+                ^ inv.rotate_left(4)
+                ^ 0x63;
+            assert_eq!(sub_word([x; 4]), [expected; 4], "S-box of {:#x}", x);
+        }
+    }
+
+    #[test]
+    fn test_aes() {
+        // FIPS 197, appendix C.
+        let plaintext = unhex("00112233445566778899aabbccddeeff");
+        let key: [u8; 32] =
+            unhex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        let vectors = [
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+        for (key_len, ciphertext) in vectors {
+            let mut block = plaintext;
+            Aes::new(&key[..key_len]).encrypt_block(&mut block);
+            assert_eq!(block, unhex(ciphertext));
+        }
+    }
+}
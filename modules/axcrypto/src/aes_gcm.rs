@@ -0,0 +1,153 @@
+//! AES-GCM, as specified in NIST SP 800-38D, with 96-bit nonces.
+
+use crate::aead::{Aead, NONCE_LEN, TAG_LEN};
+use crate::aes::{Aes, BLOCK_LEN};
+use crate::{Error, ct_eq};
+
+/// The AES-GCM AEAD, with a key of `KEY_LEN` bytes: 16 for AES-128, 24 for
+/// AES-192 or 32 for AES-256.
+///
+/// Without AES instructions, it is several times slower than
+/// [`ChaCha20Poly1305`](crate::ChaCha20Poly1305): both AES and GHASH are
+/// computed bit by bit to run in constant time.
+#[derive(Clone)]
+pub struct AesGcm<const KEY_LEN: usize> {
+    aes: Aes,
+    /// The hash key, `E(K, 0)`.
+    h: u128,
+}
+
+/// AES-128-GCM.
+pub type Aes128Gcm = AesGcm<16>;
+/// AES-256-GCM.
+pub type Aes256Gcm = AesGcm<32>;
+
+impl<const KEY_LEN: usize> AesGcm<KEY_LEN> {
+    /// Creates an instance with `key`.
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        const { assert!(matches!(KEY_LEN, 16 | 24 | 32)) };
+        let aes = Aes::new(key);
+        let mut h = [0; BLOCK_LEN];
+        aes.encrypt_block(&mut h);
+        Self {
+            aes,
+            h: u128::from_be_bytes(h),
+        }
+    }
+
+    /// XORs `buf` with the keystream of `nonce`, from the block `counter`.
+    fn ctr(&self, nonce: &[u8; NONCE_LEN], mut counter: u32, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(BLOCK_LEN) {
+            let mut block = [0; BLOCK_LEN];
+            block[..NONCE_LEN].copy_from_slice(nonce);
+            block[NONCE_LEN..].copy_from_slice(&counter.to_be_bytes());
+            self.aes.encrypt_block(&mut block);
+            for (b, k) in chunk.iter_mut().zip(block) {
+                *b ^= k;
+            }
+            counter = counter.wrapping_add(1);
+        }
+    }
+
+    fn tag(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let mut y = 0;
+        for data in [aad, ciphertext] {
+            for chunk in data.chunks(BLOCK_LEN) {
+                let mut block = [0; BLOCK_LEN];
+                block[..chunk.len()].copy_from_slice(chunk);
+                y = gf_mul(y ^ u128::from_be_bytes(block), self.h);
+            }
+        }
+        let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        y = gf_mul(y ^ lengths, self.h);
+
+        let mut tag = y.to_be_bytes();
+        self.ctr(nonce, 1, &mut tag);
+        tag
+    }
+}
+
+impl<const KEY_LEN: usize> Aead for AesGcm<KEY_LEN> {
+    const KEY_LEN: usize = KEY_LEN;
+
+    fn new_from_slice(key: &[u8]) -> Result<Self, Error> {
+        let key = key.try_into().map_err(|_| Error::InvalidLength)?;
+        Ok(Self::new(key))
+    }
+
+    fn seal_in_place(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], buf: &mut [u8]) -> [u8; TAG_LEN] {
+        self.ctr(nonce, 2, buf);
+        self.tag(nonce, aad, buf)
+    }
+
+    fn open_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), Error> {
+        if !ct_eq(&self.tag(nonce, aad, buf), tag) {
+            return Err(Error::AuthFailed);
+        }
+        self.ctr(nonce, 2, buf);
+        Ok(())
+    }
+}
+
+/// Multiplies in GF(2^128), with the bit order of GCM, in constant time.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        z ^= v & 0u128.wrapping_sub((x >> i) & 1);
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1));
+    }
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unhex;
+
+    #[test]
+    fn test_aes_gcm() {
+        // The test cases 2, 4 and 14 of the GCM specification.
+        let mut buf = [0; 16];
+        let tag = Aes128Gcm::new(&[0; 16]).seal_in_place(&[0; 12], &[], &mut buf);
+        assert_eq!(buf, unhex("0388dace60b6a392f328c2b971b2fe78"));
+        assert_eq!(tag, unhex("ab6e47d42cec13bdf53a67b21257bddf"));
+
+        let mut buf = [0; 16];
+        let tag = Aes256Gcm::new(&[0; 32]).seal_in_place(&[0; 12], &[], &mut buf);
+        assert_eq!(buf, unhex("cea7403d4d606b6e074ec5d3baf39d18"));
+        assert_eq!(tag, unhex("d0d1c8a799996bf0265b98b5d48ab919"));
+
+        let aead = Aes128Gcm::new(&unhex("feffe9928665731c6d6a8f9467308308"));
+        let nonce = unhex("cafebabefacedbaddecaf888");
+        let aad: [u8; 20] = unhex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext: [u8; 60] = unhex(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        );
+        let ciphertext: [u8; 60] = unhex(
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+             21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+        );
+        let tag = unhex("5bc94fbc3221a5db94fae95ae7121a47");
+
+        let mut buf = plaintext;
+        assert_eq!(aead.seal_in_place(&nonce, &aad, &mut buf), tag);
+        assert_eq!(buf, ciphertext);
+        let mut bad_tag = tag;
+        bad_tag[15] ^= 1;
+        assert_eq!(
+            aead.open_in_place(&nonce, &aad, &mut buf, &bad_tag),
+            Err(Error::AuthFailed)
+        );
+        assert_eq!(aead.open_in_place(&nonce, &aad, &mut buf, &tag), Ok(()));
+        assert_eq!(buf, plaintext);
+    }
+}
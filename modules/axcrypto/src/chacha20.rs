@@ -0,0 +1,65 @@
+//! The ChaCha20 stream cipher, as specified in RFC 8439.
+
+pub(crate) const BLOCK_LEN: usize = 64;
+
+/// A ChaCha20 keystream, at a block counter.
+pub(crate) struct ChaCha20 {
+    state: [u32; 16],
+}
+
+impl ChaCha20 {
+    /// Starts the keystream of `key` and `nonce` at the block `counter`.
+    pub fn new(key: &[u8; 32], nonce: &[u8; 12], counter: u32) -> Self {
+        let mut state = [0; 16];
+        state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+        for (s, word) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+            *s = u32::from_le_bytes(word.try_into().unwrap());
+        }
+        state[12] = counter;
+        for (s, word) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+            *s = u32::from_le_bytes(word.try_into().unwrap());
+        }
+        Self { state }
+    }
+
+    /// Returns the next block of the keystream.
+    pub fn next_block(&mut self) -> [u8; BLOCK_LEN] {
+        let mut x = self.state;
+        for _ in 0..10 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+        let mut block = [0; BLOCK_LEN];
+        for ((dst, x), s) in block.chunks_exact_mut(4).zip(x).zip(self.state) {
+            dst.copy_from_slice(&x.wrapping_add(s).to_le_bytes());
+        }
+        self.state[12] = self.state[12].wrapping_add(1);
+        block
+    }
+
+    /// XORs `buf` with the keystream.
+    pub fn apply_keystream(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(BLOCK_LEN) {
+            for (b, k) in chunk.iter_mut().zip(self.next_block()) {
+                *b ^= k;
+            }
+        }
+    }
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
@@ -0,0 +1,93 @@
+//! ChaCha20-Poly1305, as specified in RFC 8439.
+
+use crate::aead::{Aead, NONCE_LEN, TAG_LEN};
+use crate::chacha20::ChaCha20;
+use crate::poly1305::Poly1305;
+use crate::{Error, ct_eq};
+
+/// The ChaCha20-Poly1305 AEAD, fast in software on any CPU.
+#[derive(Clone)]
+pub struct ChaCha20Poly1305 {
+    key: [u8; 32],
+}
+
+impl ChaCha20Poly1305 {
+    /// Creates an instance with `key`.
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self { key: *key }
+    }
+
+    fn tag(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let block = ChaCha20::new(&self.key, nonce, 0).next_block();
+        let mut mac = Poly1305::new(block[..32].try_into().unwrap());
+        mac.update_padded(aad);
+        mac.update_padded(ciphertext);
+        mac.update(&(aad.len() as u64).to_le_bytes());
+        mac.update(&(ciphertext.len() as u64).to_le_bytes());
+        mac.finalize()
+    }
+}
+
+impl Aead for ChaCha20Poly1305 {
+    const KEY_LEN: usize = 32;
+
+    fn new_from_slice(key: &[u8]) -> Result<Self, Error> {
+        let key = key.try_into().map_err(|_| Error::InvalidLength)?;
+        Ok(Self::new(key))
+    }
+
+    fn seal_in_place(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], buf: &mut [u8]) -> [u8; TAG_LEN] {
+        ChaCha20::new(&self.key, nonce, 1).apply_keystream(buf);
+        self.tag(nonce, aad, buf)
+    }
+
+    fn open_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), Error> {
+        if !ct_eq(&self.tag(nonce, aad, buf), tag) {
+            return Err(Error::AuthFailed);
+        }
+        ChaCha20::new(&self.key, nonce, 1).apply_keystream(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unhex;
+
+    #[test]
+    fn test_chacha20_poly1305() {
+        // RFC 8439, section 2.8.2.
+        const PLAINTEXT: &[u8; 114] = b"Ladies and Gentlemen of the class of '99: \
+            If I could offer you only one tip for the future, sunscreen would be it.";
+        let aead = ChaCha20Poly1305::new(&unhex(
+            "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f",
+        ));
+        let nonce = unhex("070000004041424344454647");
+        let aad: [u8; 12] = unhex("50515253c0c1c2c3c4c5c6c7");
+        let ciphertext: [u8; 114] = unhex(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+             3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+             92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+             3ff4def08e4b7a9de576d26586cec64b6116",
+        );
+        let tag = unhex("1ae10b594f09e26a7e902ecbd0600691");
+
+        let mut buf = *PLAINTEXT;
+        assert_eq!(aead.seal_in_place(&nonce, &aad, &mut buf), tag);
+        assert_eq!(buf, ciphertext);
+        assert_eq!(
+            aead.open_in_place(&nonce, &aad[1..], &mut buf, &tag),
+            Err(Error::AuthFailed)
+        );
+        assert_eq!(buf, ciphertext);
+        assert_eq!(aead.open_in_place(&nonce, &aad, &mut buf, &tag), Ok(()));
+        assert_eq!(&buf, PLAINTEXT);
+    }
+}
@@ -0,0 +1,152 @@
+//! CRC-32/ISO-HDLC, the CRC of Ethernet, zlib, gzip and PNG.
+//!
+//! The polynomial is `0x04c11db7`, reflected, with the register initialized
+//! to and the result XORed with `0xffffffff`.
+//!
+//! On AArch64 targets with the `crc` feature, it is computed by the `CRC32X`
+//! family of instructions. Elsewhere, it is computed 8 bytes at a time with
+//! lookup tables ("slicing-by-8").
+
+/// The reflected polynomial.
+#[cfg(not(all(target_arch = "aarch64", target_feature = "crc")))]
+const POLY: u32 = 0xedb8_8320;
+
+/// `TABLES[0][b]` is the CRC of the byte `b`, `TABLES[k][b]` the CRC of `b`
+/// followed by `k` zero bytes.
+#[cfg(not(all(target_arch = "aarch64", target_feature = "crc")))]
+static TABLES: [[u32; 256]; 8] = {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev >> 8) ^ tables[0][(prev & 0xff) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+};
+
+/// An incremental CRC32 calculator.
+///
+/// # Examples
+///
+/// ```
+/// let mut crc = axcrypto::Crc32::new();
+/// crc.update(b"1234");
+/// crc.update(b"56789");
+/// assert_eq!(crc.finalize(), 0xcbf43926);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    /// Creates a new calculator.
+    pub const fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    /// Adds `data` to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        self.state = update(self.state, data);
+    }
+
+    /// Returns the checksum of the data.
+    pub const fn finalize(self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the CRC32 of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "crc"))]
+fn update(mut crc: u32, data: &[u8]) -> u32 {
+    use core::arch::aarch64::{__crc32b, __crc32d};
+
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        // SAFETY: the `crc` target feature is enabled.
+        crc = unsafe { __crc32d(crc, u64::from_le_bytes(word.try_into().unwrap())) };
+    }
+    for &b in words.remainder() {
+        // SAFETY: the `crc` target feature is enabled.
+        crc = unsafe { __crc32b(crc, b) };
+    }
+    crc
+}
+
+#[cfg(not(all(target_arch = "aarch64", target_feature = "crc")))]
+fn update(mut crc: u32, data: &[u8]) -> u32 {
+    let t = &TABLES;
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        let lo = crc ^ u32::from_le_bytes(word[..4].try_into().unwrap());
+        let hi = u32::from_le_bytes(word[4..].try_into().unwrap());
+        crc = t[7][(lo & 0xff) as usize]
+            ^ t[6][((lo >> 8) & 0xff) as usize]
+            ^ t[5][((lo >> 16) & 0xff) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][(hi & 0xff) as usize]
+            ^ t[2][((hi >> 8) & 0xff) as usize]
+            ^ t[1][((hi >> 16) & 0xff) as usize]
+            ^ t[0][(hi >> 24) as usize];
+    }
+    for &b in words.remainder() {
+        crc = (crc >> 8) ^ t[0][((crc ^ b as u32) & 0xff) as usize];
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+
+        // Any split gives the same result as a single update.
+        let data = b"The quick brown fox jumps over the lazy dog";
+        for split in 0..data.len() {
+            let mut crc = Crc32::new();
+            crc.update(&data[..split]);
+            crc.update(&data[split..]);
+            assert_eq!(crc.finalize(), 0x414f_a339);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! HMAC, as specified in RFC 2104, with SHA-256.
+
+use crate::Sha256;
+
+/// An incremental HMAC-SHA-256 authenticator.
+///
+/// # Examples
+///
+/// ```
+/// let mut mac = axcrypto::HmacSha256::new(b"key");
+/// mac.update(b"message");
+/// assert!(mac.verify(&axcrypto::hmac_sha256(b"key", b"message")));
+/// ```
+#[derive(Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    /// Creates an authenticator with `key`, of any length.
+    pub fn new(key: &[u8]) -> Self {
+        let mut block = [0; Sha256::BLOCK_LEN];
+        if key.len() > Sha256::BLOCK_LEN {
+            block[..Sha256::DIGEST_LEN].copy_from_slice(&crate::sha256(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha256::new();
+        let mut outer = Sha256::new();
+        inner.update(&block.map(|b| b ^ 0x36));
+        outer.update(&block.map(|b| b ^ 0x5c));
+        Self { inner, outer }
+    }
+
+    /// Authenticates `data`.
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Returns the MAC of the authenticated data.
+    pub fn finalize(self) -> [u8; 32] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+
+    /// Checks in constant time that `mac` is the MAC of the authenticated
+    /// data.
+    pub fn verify(self, mac: &[u8]) -> bool {
+        crate::ct_eq(&self.finalize(), mac)
+    }
+}
+
+/// Returns the HMAC-SHA-256 of `data` with `key`.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new(key);
+    mac.update(data);
+    mac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test cases 2 and 6.
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43,
+            ]
+        );
+        let mut mac = HmacSha256::new(&[0xaa; 131]);
+        mac.update(b"Test Using Larger Than Block-Size Key - Hash Key First");
+        let expected = [
+            0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+            0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+            0x0e, 0xe3, 0x7f, 0x54,
+        ];
+        assert!(mac.clone().verify(&expected));
+        assert!(!mac.verify(&expected[..31]));
+    }
+}
@@ -0,0 +1,78 @@
+//! Cryptographic primitives for [ArceOS](https://github.com/arceos-org/arceos).
+//!
+//! All algorithms are implemented in software, without allocation, so they
+//! can be used from any module (TLS, firmware verification, network
+//! checksums). Those that handle secrets run in constant time.
+//! [CRC32](mod@crc32) uses the CRC instructions of the CPU when the target
+//! enables them.
+//!
+//! - [`sha256`]: SHA-256 ([`Sha256`]).
+//! - [`hmac_sha256`]: HMAC-SHA-256 ([`HmacSha256`]).
+//! - [`crc32()`]: CRC-32/ISO-HDLC, as used by Ethernet, zlib and PNG ([`Crc32`]).
+//! - [`Aead`]: authenticated encryption, with [`ChaCha20Poly1305`] and
+//!   [`AesGcm`].
+//! - [`rng`]: a random number generator, to generate keys and nonces.
+
+#![no_std]
+
+mod aead;
+mod aes;
+mod aes_gcm;
+mod chacha20;
+mod chacha20_poly1305;
+pub mod crc32;
+mod hmac;
+mod poly1305;
+pub mod rng;
+mod sha256;
+
+use core::fmt;
+
+pub use self::aead::{Aead, NONCE_LEN, TAG_LEN};
+pub use self::aes_gcm::{Aes128Gcm, Aes256Gcm, AesGcm};
+pub use self::chacha20_poly1305::ChaCha20Poly1305;
+pub use self::crc32::{Crc32, crc32};
+pub use self::hmac::{HmacSha256, hmac_sha256};
+pub use self::sha256::{Sha256, sha256};
+
+/// Errors of the cryptographic operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A key has an invalid length.
+    InvalidLength,
+    /// A tag does not authenticate the data.
+    AuthFailed,
+    /// The random number generator has not been seeded.
+    NoEntropy,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::InvalidLength => "invalid key length",
+            Error::AuthFailed => "authentication failed",
+            Error::NoEntropy => "random number generator not seeded",
+        })
+    }
+}
+
+/// Compares two byte strings in constant time for equal lengths, e.g. to check
+/// a MAC without leaking where it differs.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    // Keep the compiler from short-circuiting the fold.
+    core::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+fn unhex<const N: usize>(hex: &str) -> [u8; N] {
+    assert_eq!(hex.len(), 2 * N);
+    let mut out = [0; N];
+    for (b, pair) in out.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *b = u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap();
+    }
+    out
+}
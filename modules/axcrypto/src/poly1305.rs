@@ -0,0 +1,168 @@
+//! The Poly1305 one-time authenticator, as specified in RFC 8439.
+//!
+//! The accumulator is kept in five 26-bit limbs, and reduced without
+//! branches on secret data.
+
+const MASK: u32 = 0x3ff_ffff;
+
+/// A Poly1305 authenticator, for a single message.
+pub(crate) struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+    block: [u8; 16],
+    /// The number of bytes in `block`.
+    block_len: usize,
+}
+
+impl Poly1305 {
+    /// Creates an authenticator with the one-time `key`.
+    pub fn new(key: &[u8; 32]) -> Self {
+        let le32 = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+        let r = [
+            le32(0) & 0x3ff_ffff,
+            (le32(3) >> 2) & 0x3ff_ff03,
+            (le32(6) >> 4) & 0x3ff_c0ff,
+            (le32(9) >> 6) & 0x3f0_3fff,
+            (le32(12) >> 8) & 0x00f_ffff,
+        ];
+        Self {
+            r,
+            h: [0; 5],
+            pad: [le32(16), le32(20), le32(24), le32(28)],
+            block: [0; 16],
+            block_len: 0,
+        }
+    }
+
+    /// Authenticates `data`.
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.block_len > 0 {
+            let n = data.len().min(16 - self.block_len);
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            if self.block_len < 16 {
+                return;
+            }
+            let block = self.block;
+            self.compress(&block, 1 << 24);
+            self.block_len = 0;
+        }
+        let mut blocks = data.chunks_exact(16);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap(), 1 << 24);
+        }
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.block_len = rest.len();
+    }
+
+    /// Authenticates `data`, followed by zeros up to a multiple of 16 bytes,
+    /// as the AEAD construction does.
+    pub fn update_padded(&mut self, data: &[u8]) {
+        self.update(data);
+        let rest = data.len() % 16;
+        if rest != 0 {
+            self.update(&[0; 16][rest..]);
+        }
+    }
+
+    fn compress(&mut self, block: &[u8; 16], hibit: u32) {
+        let le32 = |i: usize| u32::from_le_bytes(block[i..i + 4].try_into().unwrap());
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+
+        let h = &mut self.h;
+        h[0] += le32(0) & MASK;
+        h[1] += (le32(3) >> 2) & MASK;
+        h[2] += (le32(6) >> 4) & MASK;
+        h[3] += (le32(9) >> 6) & MASK;
+        h[4] += (le32(12) >> 8) | hibit;
+        let [h0, h1, h2, h3, h4] = h.map(u64::from);
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let mut h0 = (d0 as u32 & MASK) + (d4 >> 26) as u32 * 5;
+        let h1 = (d1 as u32 & MASK) + (h0 >> 26);
+        h0 &= MASK;
+        *h = [h0, h1, d2 as u32 & MASK, d3 as u32 & MASK, d4 as u32 & MASK];
+    }
+
+    /// Returns the tag of the authenticated data.
+    pub fn finalize(mut self) -> [u8; 16] {
+        if self.block_len > 0 {
+            let mut block = [0; 16];
+            block[..self.block_len].copy_from_slice(&self.block[..self.block_len]);
+            block[self.block_len] = 1;
+            self.compress(&block, 0);
+        }
+
+        // Fully carry h.
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+        h2 += h1 >> 26;
+        h1 &= MASK;
+        h3 += h2 >> 26;
+        h2 &= MASK;
+        h4 += h3 >> 26;
+        h3 &= MASK;
+        h0 += (h4 >> 26) * 5;
+        h4 &= MASK;
+        h1 += h0 >> 26;
+        h0 &= MASK;
+
+        // Compute g = h + 5 - 2^130, and select it if it does not underflow.
+        let g0 = h0 + 5;
+        let g1 = h1 + (g0 >> 26);
+        let g2 = h2 + (g1 >> 26);
+        let g3 = h3 + (g2 >> 26);
+        let g4 = (h4 + (g3 >> 26)).wrapping_sub(1 << 26);
+        let mask = (g4 >> 31).wrapping_sub(1);
+        let select = |h: u32, g: u32| (h & !mask) | (g & MASK & mask);
+        let h0 = select(h0, g0);
+        let h1 = select(h1, g1);
+        let h2 = select(h2, g2);
+        let h3 = select(h3, g3);
+        let h4 = select(h4, g4);
+
+        // h mod 2^128, plus pad.
+        let words = [
+            h0 | (h1 << 26),
+            (h1 >> 6) | (h2 << 20),
+            (h2 >> 12) | (h3 << 14),
+            (h3 >> 18) | (h4 << 8),
+        ];
+        let mut tag = [0; 16];
+        let mut carry = 0;
+        for ((dst, word), pad) in tag.chunks_exact_mut(4).zip(words).zip(self.pad) {
+            let f = word as u64 + pad as u64 + carry;
+            dst.copy_from_slice(&(f as u32).to_le_bytes());
+            carry = f >> 32;
+        }
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unhex;
+
+    #[test]
+    fn test_poly1305() {
+        // RFC 8439, section 2.5.2.
+        let key = unhex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let mut mac = Poly1305::new(&key);
+        mac.update(b"Cryptographic Forum ");
+        mac.update(b"Research Group");
+        assert_eq!(mac.finalize(), unhex("a8061dc1305136c6c22b8baf0c0127a9"));
+    }
+}
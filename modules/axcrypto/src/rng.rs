@@ -0,0 +1,67 @@
+//! A cryptographically secure random number generator.
+//!
+//! The kernel has no entropy source of its own: the platform, or the user,
+//! must feed it with [`add_entropy`] (from a hardware RNG, the jitter of
+//! timers, a seed file...) before keys can be generated with
+//! [`fill_random`].
+
+use spin::Mutex;
+
+use crate::Error;
+use crate::chacha20::ChaCha20;
+
+static RNG: Mutex<Option<ChaCha20Rng>> = Mutex::new(None);
+
+/// A random number generator that expands a 256-bit seed with ChaCha20.
+///
+/// The key is replaced after each request ("fast key erasure"), so that a
+/// later compromise of the state does not reveal earlier outputs.
+pub struct ChaCha20Rng {
+    key: [u8; 32],
+}
+
+impl ChaCha20Rng {
+    /// Creates a generator from `seed`, which must be secret and uniformly
+    /// random.
+    pub const fn from_seed(seed: [u8; 32]) -> Self {
+        Self { key: seed }
+    }
+
+    /// Fills `buf` with random bytes.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        let mut stream = ChaCha20::new(&self.key, &[0; 12], 0);
+        let mut next = stream.next_block();
+        self.key.copy_from_slice(&next[..32]);
+        next.fill(0);
+        buf.fill(0);
+        stream.apply_keystream(buf);
+    }
+}
+
+/// Mixes `data` into the state of the global generator.
+///
+/// The first call seeds it, so it must carry at least 256 bits of entropy.
+/// Later calls only add to the entropy of the state.
+pub fn add_entropy(data: &[u8]) {
+    let mut rng = RNG.lock();
+    let mut hasher = crate::Sha256::new();
+    if let Some(rng) = rng.as_ref() {
+        hasher.update(&rng.key);
+    }
+    hasher.update(data);
+    *rng = Some(ChaCha20Rng::from_seed(hasher.finalize()));
+}
+
+/// Returns whether the global generator has been seeded.
+pub fn is_seeded() -> bool {
+    RNG.lock().is_some()
+}
+
+/// Fills `buf` with random bytes from the global generator.
+///
+/// Fails with [`NoEntropy`](Error::NoEntropy) if [`add_entropy`] has never
+/// been called.
+pub fn fill_random(buf: &mut [u8]) -> Result<(), Error> {
+    RNG.lock().as_mut().ok_or(Error::NoEntropy)?.fill_bytes(buf);
+    Ok(())
+}
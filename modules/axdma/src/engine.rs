@@ -0,0 +1,208 @@
+//! Memory copies offloaded to a DMA controller.
+//!
+//! A platform with a memcpy DMA controller registers its driver with
+//! [`register_copy_engine`]. [`dma_copy`] then hands it the copies of at
+//! least [`DMA_COPY_THRESHOLD`] bytes, and the task waits for the interrupt
+//! of the controller instead of copying:
+//!
+//! ```ignore
+//! axdma::dma_copy(&block, &mut page, PAGE_SIZE).await;
+//! ```
+//!
+//! The CPU copies the smaller buffers, the buffers out of the linear mapping
+//! of the physical memory, and everything when no engine is registered or
+//! when it fails.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::{Context, Poll, ready};
+
+use axconfig::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use axerrno::AxResult;
+use axhal::cache::CACHE_LINE_SIZE;
+use kspin::SpinNoIrq;
+use log::{debug, warn};
+
+use crate::{BusAddr, DmaDirection, DmaMapping, map_single, unmap_single};
+
+/// The smallest copy handed to the DMA engine, in bytes: the smaller ones are
+/// faster on the CPU than the cache maintenance and the interrupt.
+pub const DMA_COPY_THRESHOLD: usize = 4096;
+
+/// The driver of a DMA controller that copies memory.
+pub trait CopyEngine: Send + Sync {
+    /// Starts copying `len` bytes from the bus address `src` to `dst`, and
+    /// returns an identifier of the transfer.
+    ///
+    /// Fails, e.g. with [`WouldBlock`](axerrno::AxError::WouldBlock) if all
+    /// the channels are busy, to have the CPU copy instead.
+    fn start(&self, src: BusAddr, dst: BusAddr, len: usize) -> AxResult<usize>;
+
+    /// Returns the result of the transfer `id` once it is complete, or
+    /// registers the waker of `cx` to be woken by the interrupt of the
+    /// controller.
+    fn poll_complete(&self, id: usize, cx: &mut Context<'_>) -> Poll<AxResult>;
+
+    /// Stops the transfer `id`, which is not polled anymore. It returns once
+    /// the controller no longer accesses the buffers.
+    fn abort(&self, id: usize);
+}
+
+static ENGINE: SpinNoIrq<Option<&'static dyn CopyEngine>> = SpinNoIrq::new(None);
+
+/// Registers the DMA engine that [`dma_copy`] offloads to, in place of the
+/// previous one if any.
+pub fn register_copy_engine(engine: &'static dyn CopyEngine) {
+    ENGINE.lock().replace(engine);
+}
+
+/// Returns whether [`dma_copy`] has a DMA engine to offload to.
+pub fn has_copy_engine() -> bool {
+    ENGINE.lock().is_some()
+}
+
+/// Copies the first `len` bytes of `src` to `dst`, with the DMA engine if
+/// the copy is large enough, see the [module documentation](self).
+///
+/// # Panics
+///
+/// Panics if `src` or `dst` is shorter than `len`.
+pub fn dma_copy<'a>(src: &'a [u8], dst: &'a mut [u8], len: usize) -> DmaCopy<'a> {
+    assert!(
+        len <= src.len() && len <= dst.len(),
+        "dma_copy: buffers shorter than the copy"
+    );
+    DmaCopy {
+        src: &src[..len],
+        dst: &mut dst[..len],
+        transfer: None,
+    }
+}
+
+/// A copy in progress on the DMA engine.
+struct Transfer {
+    engine: &'static dyn CopyEngine,
+    id: usize,
+    src: DmaMapping,
+    dst: DmaMapping,
+    /// The part of the buffers copied by the engine.
+    start: usize,
+    end: usize,
+}
+
+// SAFETY: The mappings are of the buffers borrowed by the `DmaCopy`.
+unsafe impl Send for Transfer {}
+
+impl Transfer {
+    /// Gives the buffers back to the CPU.
+    fn unmap(self) {
+        unmap_single(self.src);
+        unmap_single(self.dst);
+    }
+}
+
+/// The future of [`dma_copy`].
+///
+/// Dropping it before completion aborts the transfer, and leaves the content
+/// of the destination unspecified.
+pub struct DmaCopy<'a> {
+    src: &'a [u8],
+    dst: &'a mut [u8],
+    transfer: Option<Transfer>,
+}
+
+/// Returns whether `buf` is in the linear mapping of the physical memory,
+/// where it is physically contiguous.
+fn is_linear(buf: &[u8]) -> bool {
+    let start = PHYS_VIRT_OFFSET + PHYS_MEMORY_BASE;
+    let addr = buf.as_ptr() as usize;
+    addr >= start && addr + buf.len() <= start + PHYS_MEMORY_SIZE
+}
+
+impl DmaCopy<'_> {
+    /// Starts the transfer on the engine, or returns `false` if the CPU
+    /// copies.
+    fn start(&mut self) -> bool {
+        if self.src.len() < DMA_COPY_THRESHOLD || !is_linear(self.src) || !is_linear(self.dst) {
+            return false;
+        }
+        let Some(engine) = *ENGINE.lock() else {
+            return false;
+        };
+        // Invalidating the cache lines shared with other data would discard
+        // what the CPU writes to it meanwhile: the CPU copies the ends.
+        let addr = self.dst.as_ptr() as usize;
+        let start = addr.next_multiple_of(CACHE_LINE_SIZE) - addr;
+        let end = (addr + self.dst.len()) / CACHE_LINE_SIZE * CACHE_LINE_SIZE - addr;
+        let len = end.saturating_sub(start);
+        if len < DMA_COPY_THRESHOLD {
+            return false;
+        }
+        let src = NonNull::from(&self.src[start]);
+        let dst = NonNull::from(&mut self.dst[start]);
+        // SAFETY: Both buffers are linearly mapped, and borrowed until the
+        // transfer is completed or aborted.
+        let (src, dst) = unsafe {
+            (
+                map_single(src, len, DmaDirection::ToDevice),
+                map_single(dst, len, DmaDirection::FromDevice),
+            )
+        };
+        match engine.start(src.bus_addr(), dst.bus_addr(), len) {
+            Ok(id) => {
+                self.transfer = Some(Transfer {
+                    engine,
+                    id,
+                    src,
+                    dst,
+                    start,
+                    end: start + len,
+                });
+                true
+            }
+            Err(err) => {
+                debug!("dma_copy: {} bytes on the CPU: {:?}", len, err);
+                unmap_single(src);
+                unmap_single(dst);
+                false
+            }
+        }
+    }
+}
+
+impl Future for DmaCopy<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.transfer.is_none() && !this.start() {
+            this.dst.copy_from_slice(this.src);
+            return Poll::Ready(());
+        }
+        let transfer = this.transfer.as_ref().unwrap();
+        let res = ready!(transfer.engine.poll_complete(transfer.id, cx));
+        let transfer = this.transfer.take().unwrap();
+        let (start, end) = (transfer.start, transfer.end);
+        transfer.unmap();
+        let (start, end) = match res {
+            Ok(()) => (start, end),
+            Err(err) => {
+                warn!("dma_copy: transfer failed, copying on the CPU: {:?}", err);
+                (0, 0)
+            }
+        };
+        this.dst[..start].copy_from_slice(&this.src[..start]);
+        this.dst[end..].copy_from_slice(&this.src[end..]);
+        Poll::Ready(())
+    }
+}
+
+impl Drop for DmaCopy<'_> {
+    fn drop(&mut self) {
+        if let Some(transfer) = self.transfer.take() {
+            transfer.engine.abort(transfer.id);
+            transfer.unmap();
+        }
+    }
+}
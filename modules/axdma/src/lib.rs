@@ -5,6 +5,8 @@
 extern crate alloc;
 
 mod dma;
+mod engine;
+mod streaming;
 
 use core::{alloc::Layout, ptr::NonNull};
 
@@ -13,6 +15,11 @@ use memory_addr::PhysAddr;
 
 use self::dma::ALLOCATOR;
 
+pub use self::engine::{
+    CopyEngine, DMA_COPY_THRESHOLD, DmaCopy, dma_copy, has_copy_engine, register_copy_engine,
+};
+pub use self::streaming::{DmaDirection, DmaMapping, map_single, unmap_single};
+
 /// Converts a physical address to a bus address.
 ///
 /// It assumes that there is a linear mapping with the offset
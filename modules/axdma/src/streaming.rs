@@ -0,0 +1,152 @@
+//! Streaming DMA mappings.
+//!
+//! Unlike coherent memory, a streaming mapping makes an existing (cacheable)
+//! buffer accessible to a device. Ownership of the buffer moves between the
+//! CPU and the device, and each transition must perform the right cache
+//! maintenance:
+//!
+//! - [`map_single`] / [`DmaMapping::sync_for_device`]: hand the buffer to the
+//!   device. Dirty cache lines are written back, so the device sees the data
+//!   written by the CPU.
+//! - [`unmap_single`] / [`DmaMapping::sync_for_cpu`]: hand the buffer back to
+//!   the CPU. Stale cache lines are invalidated, so the CPU sees the data
+//!   written by the device.
+
+use core::ptr::NonNull;
+
+use axhal::mem::{VirtAddr, virt_to_phys};
+
+use crate::{BusAddr, phys_to_bus};
+
+/// The direction of a DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device reads from the buffer (e.g. a TX packet).
+    ToDevice,
+    /// The device writes to the buffer (e.g. an RX packet).
+    FromDevice,
+    /// The device both reads and writes the buffer.
+    Bidirectional,
+}
+
+/// A buffer mapped for streaming DMA.
+///
+/// It is created by [`map_single`] and must be released by [`unmap_single`].
+/// While mapped, the buffer is owned by the device and must not be accessed
+/// by the CPU, unless [`sync_for_cpu`](Self::sync_for_cpu) is called first.
+#[derive(Debug)]
+#[must_use = "a DMA mapping must be released by `unmap_single`"]
+pub struct DmaMapping {
+    cpu_addr: NonNull<u8>,
+    bus_addr: BusAddr,
+    size: usize,
+    dir: DmaDirection,
+}
+
+impl DmaMapping {
+    /// Rebuilds a mapping returned by [`map_single`] from its parts, for HALs
+    /// that only keep the addresses between mapping and unmapping (e.g. the
+    /// `share` and `unshare` of virtio).
+    ///
+    /// # Safety
+    ///
+    /// The parts must be those of a mapping that has not been released yet.
+    pub const unsafe fn from_raw_parts(
+        cpu_addr: NonNull<u8>,
+        bus_addr: BusAddr,
+        size: usize,
+        dir: DmaDirection,
+    ) -> Self {
+        Self {
+            cpu_addr,
+            bus_addr,
+            size,
+            dir,
+        }
+    }
+
+    /// The address at which the CPU accesses the buffer.
+    pub const fn cpu_addr(&self) -> NonNull<u8> {
+        self.cpu_addr
+    }
+
+    /// The address at which the device accesses the buffer.
+    pub const fn bus_addr(&self) -> BusAddr {
+        self.bus_addr
+    }
+
+    /// The size in bytes of the buffer.
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The direction of the transfer.
+    pub const fn direction(&self) -> DmaDirection {
+        self.dir
+    }
+
+    /// Gives the buffer back to the CPU, so it can read what the device wrote.
+    pub fn sync_for_cpu(&self) {
+        if self.dir != DmaDirection::ToDevice {
+            cache::invalidate(self.cpu_addr, self.size);
+        }
+    }
+
+    /// Gives the buffer to the device, so it can read what the CPU wrote.
+    pub fn sync_for_device(&self) {
+        match self.dir {
+            DmaDirection::ToDevice => cache::clean(self.cpu_addr, self.size),
+            // Write back dirty lines so they are not evicted over the data
+            // written by the device later.
+            DmaDirection::FromDevice | DmaDirection::Bidirectional => {
+                cache::flush(self.cpu_addr, self.size)
+            }
+        }
+    }
+}
+
+/// Maps an existing buffer for streaming DMA, and gives it to the device.
+///
+/// # Safety
+///
+/// The buffer must lie in the linearly mapped physical memory (e.g. allocated
+/// from the global allocator), be physically contiguous, and stay valid and
+/// untouched by the CPU until [`unmap_single`] is called.
+pub unsafe fn map_single(buf: NonNull<u8>, size: usize, dir: DmaDirection) -> DmaMapping {
+    let paddr = virt_to_phys(VirtAddr::from(buf.as_ptr() as usize));
+    let mapping = DmaMapping {
+        cpu_addr: buf,
+        bus_addr: phys_to_bus(paddr),
+        size,
+        dir,
+    };
+    mapping.sync_for_device();
+    mapping
+}
+
+/// Releases a streaming DMA mapping, and gives the buffer back to the CPU.
+pub fn unmap_single(mapping: DmaMapping) {
+    mapping.sync_for_cpu();
+}
+
+/// Cache maintenance on the buffer of a streaming mapping.
+mod cache {
+    use core::ptr::NonNull;
+
+    use axhal::cache::{clean_dcache_range, flush_dcache_range, invalidate_dcache_range};
+
+    /// Writes back dirty cache lines.
+    pub fn clean(start: NonNull<u8>, size: usize) {
+        clean_dcache_range((start.as_ptr() as usize).into(), size);
+    }
+
+    /// Discards cache lines.
+    pub fn invalidate(start: NonNull<u8>, size: usize) {
+        invalidate_dcache_range((start.as_ptr() as usize).into(), size);
+    }
+
+    /// Writes back and discards cache lines.
+    pub fn flush(start: NonNull<u8>, size: usize) {
+        flush_dcache_range((start.as_ptr() as usize).into(), size);
+    }
+}
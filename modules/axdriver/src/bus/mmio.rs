@@ -1,5 +1,22 @@
 #[allow(unused_imports)]
-use crate::{AllDevices, AxDeviceEnum, prelude::*};
+use crate::{
+    AllDevices, AxDeviceEnum,
+    prelude::*,
+    registry::{DRIVERS, DriverDesc, drivers_compatible_with},
+};
+
+/// Returns the drivers to try for the MMIO device at `base`: those compatible
+/// with its device-tree node, most specific first, or all of them if it is
+/// not in the device tree.
+fn mmio_drivers(base: usize) -> impl Iterator<Item = &'static DriverDesc> {
+    let compatible = axhal::dt_compatible(base);
+    let all = compatible.is_none().then(|| DRIVERS.iter());
+    compatible
+        .into_iter()
+        .flatten()
+        .flat_map(drivers_compatible_with)
+        .chain(all.into_iter().flatten())
+}
 
 impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
@@ -10,52 +27,74 @@ impl AllDevices {
                 info!("skipping GPIO MMIO region");
                 continue;
             }
-            for_each_drivers!(type Driver, {
-                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
-                    // TODO: hardcode for tutorial
-                    if reg.0 == 0x16030000 {
-                        info!(
-                            "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
-                            dev.device_type(),
-                            reg.0, reg.0 + reg.1,
-                            dev.device_name(),
-                        );
-                        self.add_device(dev, 7); // GMAC0 IRQ
-                    } else if reg.0 == 0x16040000 {
-                        info!(
-                            "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
-                            dev.device_type(),
-                            reg.0, reg.0 + reg.1,
-                            dev.device_name(),
-                        );
-                        self.add_device(dev, 78); // GMAC1 IRQ
-                    } else {
-                        unimplemented!("unknown device");
-                    }
-
-                    continue; // skip to the next device
+            for driver in mmio_drivers(reg.0) {
+                // Do not bring up a device whose interrupts would be lost.
+                let Some(irq) = driver.mmio_irq(reg.0) else {
+                    debug!(
+                        "{}: no IRQ for the device at [PA:{:#x}, PA:{:#x}), skipped",
+                        driver.name,
+                        reg.0,
+                        reg.0 + reg.1,
+                    );
+                    continue;
+                };
+                if self.probe_mmio_device(driver, reg.0, reg.1, irq) {
+                    break; // skip to the next device
                 }
-            });
+            }
         }
 
         let mut irq = 0;
-        // TODO: parse device tree
         #[cfg(feature = "virtio")]
         for reg in axconfig::devices::VIRTIO_MMIO_REGIONS {
             irq += 1;
-            for_each_drivers!(type Driver, {
-                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
-                    info!(
-                        "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
-                        dev.device_type(),
-                        reg.0, reg.0 + reg.1,
-                        dev.device_name(),
-                    );
-
-                    self.add_device(dev, irq);
-                    continue; // skip to the next device
+            for driver in mmio_drivers(reg.0) {
+                let irq = driver.mmio_irq(reg.0).unwrap_or(irq);
+                if self.probe_mmio_device(driver, reg.0, reg.1, irq) {
+                    break; // skip to the next device
                 }
-            });
+            }
         }
     }
+
+    /// Probes the MMIO device at `base` with `driver`, and adds it with `irq`
+    /// if the driver takes it.
+    fn probe_mmio_device(
+        &mut self,
+        driver: &DriverDesc,
+        base: usize,
+        size: usize,
+        irq: u32,
+    ) -> bool {
+        let Some(dev) = (driver.probe_mmio)(base, size) else {
+            return false;
+        };
+        info!(
+            "registered a new {:?} device with {} at [PA:{:#x}, PA:{:#x}): {:?}",
+            dev.device_type(),
+            driver.name,
+            base,
+            base + size,
+            dev.device_name(),
+        );
+        #[cfg(feature = "mmio")]
+        register_mmio_device(driver.name, base, size, irq);
+        self.add_device(dev, irq);
+        true
+    }
+}
+
+/// Registers a probed device in [`axhal::mmio`], so that its IRQ events and
+/// page faults (e.g., when traced) are attributed to it.
+#[cfg(feature = "mmio")]
+fn register_mmio_device(name: &'static str, paddr: usize, size: usize, irq: u32) {
+    let dev = axhal::mmio::MmioDevice {
+        name,
+        paddr: paddr.into(),
+        size,
+        irq: Some(irq as usize),
+    };
+    if let Err(e) = axhal::mmio::register_device(dev) {
+        warn!("{}: failed to register the MMIO region: {:?}", name, e);
+    }
 }
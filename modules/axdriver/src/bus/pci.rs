@@ -1,4 +1,4 @@
-use crate::{AllDevices, prelude::*};
+use crate::{AllDevices, prelude::*, registry::DRIVERS};
 use axdriver_pci::{
     BarInfo, Cam, Command, DeviceFunction, HeaderType, MemoryBarType, PciRangeAllocator, PciRoot,
 };
@@ -99,18 +99,21 @@ impl AllDevices {
                     continue;
                 }
                 match config_pci_device(&mut root, bdf, &mut allocator) {
-                    Ok(_) => for_each_drivers!(type Driver, {
-                        if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info) {
-                            info!(
-                                "registered a new {:?} device at {}: {:?}",
-                                dev.device_type(),
-                                bdf,
-                                dev.device_name(),
-                            );
-                            self.add_device(dev, 0);
-                            continue; // skip to the next device
+                    Ok(_) => {
+                        for driver in DRIVERS {
+                            if let Some(dev) = (driver.probe_pci)(&mut root, bdf, &dev_info) {
+                                info!(
+                                    "registered a new {:?} device with {} at {}: {:?}",
+                                    dev.device_type(),
+                                    driver.name,
+                                    bdf,
+                                    dev.device_name(),
+                                );
+                                self.add_device(dev, 0);
+                                break; // skip to the next device
+                            }
                         }
-                    }),
+                    }
                     Err(e) => warn!(
                         "failed to enable PCI device at {}({}): {:?}",
                         bdf, dev_info, e
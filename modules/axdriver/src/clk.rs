@@ -0,0 +1,126 @@
+//! Programming of clock registers, verified by reading them back.
+//!
+//! A clock controller may take a while to apply a write, e.g. a gate that
+//! only opens on the next edge of its parent, and a write to a clock whose
+//! parent is off is lost. Each step of a [`ClockProgram`] writes a register,
+//! then reads it back until it holds the expected bits, and fails with a
+//! [`ClockError`] at the timeout rather than going on with a dead clock.
+
+use core::fmt;
+use core::time::Duration;
+
+use axhal::time::{busy_wait, monotonic_time};
+
+/// The gate bit of the clock registers (ICG, integrated clock gating).
+pub const CLK_ICG: u32 = 1 << 31;
+
+/// How long a step waits for a clock register to read back by default.
+pub const DEFAULT_CLOCK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The interval between two readbacks of a clock register.
+const POLL_INTERVAL: Duration = Duration::from_micros(10);
+
+/// A clock register that does not hold the expected bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockError {
+    /// The name of the clock.
+    pub name: &'static str,
+    /// The bits checked.
+    pub mask: u32,
+    /// The value of the checked bits expected.
+    pub expected: u32,
+    /// The value of the register last read.
+    pub readback: u32,
+}
+
+impl fmt::Display for ClockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "clock {}: {:#x} read back, expected {:#x} (mask {:#x})",
+            self.name, self.readback, self.expected, self.mask
+        )
+    }
+}
+
+/// A sequence of verified writes to clock registers.
+///
+/// ```ignore
+/// let clocks = ClockProgram::new(DEFAULT_CLOCK_TIMEOUT);
+/// let axi = syscrg.clk_gmac5_axi64_axi();
+/// clocks.enable("gmac1_axi", || axi.write(|w| w.clk_icg().set_bit()), || axi.read().bits())?;
+/// ```
+pub struct ClockProgram {
+    timeout: Duration,
+}
+
+impl ClockProgram {
+    /// Creates a program whose steps wait at most `timeout` for each
+    /// register to read back.
+    pub const fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+
+    /// Writes the register of clock `name` with `write`, then reads it back
+    /// with `read` until it equals `value`.
+    pub fn set(
+        &self,
+        name: &'static str,
+        write: impl FnOnce(),
+        read: impl Fn() -> u32,
+        value: u32,
+    ) -> Result<(), ClockError> {
+        write();
+        self.wait(name, read, u32::MAX, value)
+    }
+
+    /// Opens the gate of clock `name` with `write`, then reads the register
+    /// back with `read` until its gate bit is set. The other bits are left
+    /// unchecked.
+    pub fn enable(
+        &self,
+        name: &'static str,
+        write: impl FnOnce(),
+        read: impl Fn() -> u32,
+    ) -> Result<(), ClockError> {
+        write();
+        self.wait(name, read, CLK_ICG, CLK_ICG)
+    }
+
+    /// Checks that the register of clock `name`, read with `read`, equals
+    /// `value`, e.g. as set by the bootloader.
+    pub fn check(
+        &self,
+        name: &'static str,
+        read: impl Fn() -> u32,
+        value: u32,
+    ) -> Result<(), ClockError> {
+        self.wait(name, read, u32::MAX, value)
+    }
+
+    fn wait(
+        &self,
+        name: &'static str,
+        read: impl Fn() -> u32,
+        mask: u32,
+        expected: u32,
+    ) -> Result<(), ClockError> {
+        let deadline = monotonic_time() + self.timeout;
+        loop {
+            let readback = read();
+            if readback & mask == expected & mask {
+                log::debug!("clock {}: {:#x}", name, readback);
+                return Ok(());
+            }
+            if monotonic_time() >= deadline {
+                return Err(ClockError {
+                    name,
+                    mask,
+                    expected,
+                    readback,
+                });
+            }
+            busy_wait(POLL_INTERVAL);
+        }
+    }
+}
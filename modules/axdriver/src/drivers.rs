@@ -38,23 +38,42 @@ register_net_driver!(
     <virtio::VirtIoNet as VirtIoDevMeta>::Driver,
     <virtio::VirtIoNet as VirtIoDevMeta>::Device
 );
+#[cfg(net_dev = "virtio-net")]
+register_driver!(
+    <virtio::VirtIoNet as VirtIoDevMeta>::Driver,
+    "virtio-net",
+    compatible: ["virtio,mmio"],
+);
 
 #[cfg(block_dev = "virtio-blk")]
 register_block_driver!(
     <virtio::VirtIoBlk as VirtIoDevMeta>::Driver,
     <virtio::VirtIoBlk as VirtIoDevMeta>::Device
 );
+#[cfg(block_dev = "virtio-blk")]
+register_driver!(
+    <virtio::VirtIoBlk as VirtIoDevMeta>::Driver,
+    "virtio-blk",
+    compatible: ["virtio,mmio"],
+);
 
 #[cfg(display_dev = "virtio-gpu")]
 register_display_driver!(
     <virtio::VirtIoGpu as VirtIoDevMeta>::Driver,
     <virtio::VirtIoGpu as VirtIoDevMeta>::Device
 );
+#[cfg(display_dev = "virtio-gpu")]
+register_driver!(
+    <virtio::VirtIoGpu as VirtIoDevMeta>::Driver,
+    "virtio-gpu",
+    compatible: ["virtio,mmio"],
+);
 
 cfg_if::cfg_if! {
     if #[cfg(block_dev = "ramdisk")] {
         pub struct RamDiskDriver;
         register_block_driver!(RamDiskDriver, axdriver_block::ramdisk::RamDisk);
+        register_driver!(RamDiskDriver, "ramdisk");
 
         impl DriverProbe for RamDiskDriver {
             fn probe_global() -> Option<AxDeviceEnum> {
@@ -71,6 +90,7 @@ cfg_if::cfg_if! {
     if #[cfg(block_dev = "bcm2835-sdhci")]{
         pub struct BcmSdhciDriver;
         register_block_driver!(MmckDriver, axdriver_block::bcm2835sdhci::SDHCIDriver);
+        register_driver!(BcmSdhciDriver, "bcm2835-sdhci", compatible: ["brcm,bcm2835-sdhci"]);
 
         impl DriverProbe for BcmSdhciDriver {
             fn probe_global() -> Option<AxDeviceEnum> {
@@ -87,6 +107,7 @@ cfg_if::cfg_if! {
         use axhal::mem::phys_to_virt;
         pub struct IxgbeDriver;
         register_net_driver!(IxgbeDriver, axdriver_net::ixgbe::IxgbeNic<IxgbeHalImpl, 1024, 1>);
+        register_driver!(IxgbeDriver, "ixgbe");
         impl DriverProbe for IxgbeDriver {
             #[cfg(bus = "pci")]
             fn probe_pci(
@@ -164,6 +185,7 @@ cfg_if::cfg_if! {
         }
 
         register_net_driver!(FXmacDriver, axdriver_net::fxmac::FXmacNic);
+        register_driver!(FXmacDriver, "fxmac");
 
         pub struct FXmacDriver;
         impl DriverProbe for FXmacDriver {
@@ -183,6 +205,14 @@ cfg_if::cfg_if! {
 
         pub struct DwmacDriver;
         register_net_driver!(DwmacDriver, axdriver_net::dwmac::DwmacNic<DwmacHalImpl>);
+        register_driver!(
+            DwmacDriver,
+            "dwmac",
+            // The VisionFive 2 device tree uses `starfive,dwmac`.
+            compatible: ["starfive,jh7110-dwmac", "starfive,dwmac"],
+            // GMAC0 and GMAC1 of the JH7110.
+            mmio_irqs: [(0x1603_0000, 7), (0x1604_0000, 78)],
+        );
 
         impl DriverProbe for DwmacDriver {
             #[cfg(bus = "mmio")]
@@ -6,7 +6,9 @@
 use axdma::{BusAddr, DMAInfo, alloc_coherent, dealloc_coherent};
 use axdriver_net::dwmac::{DwmacHal, PhysAddr as DwmacPhysAddr};
 use axdriver_virtio::PhysAddr;
-use axhal::mem::{MemoryAddr, phys_to_virt, virt_to_phys};
+use axhal::mem::{mb, phys_to_virt, virt_to_phys};
+
+use crate::clk::{ClockError, ClockProgram, DEFAULT_CLOCK_TIMEOUT};
 use core::sync::atomic::Ordering;
 use core::{alloc::Layout, ptr::NonNull, sync::atomic::AtomicBool};
 use jh7110_vf2_13b_pac::{self as pac, aon_pinctrl::gmac0_mdio::GMAC0_MDIO_SPEC};
@@ -16,29 +18,12 @@ pub struct DwmacHalImpl;
 
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
-fn mb() {
-    unsafe { core::arch::asm!("fence iorw, iorw") };
-}
-
 impl DwmacHal for DwmacHalImpl {
     fn cache_flush_range(start: NonNull<u8>, end: NonNull<u8>) {
-        const CCACHE_BASE: usize = 0x0201_0000;
-        const FLUSH64_OFFSET: usize = 0x200;
-        const LINE_SIZE: usize = 64;
-
-        let mut addr = start.as_ptr() as usize & !(LINE_SIZE - 1);
-
-        let flush_addr = phys_to_virt(CCACHE_BASE.into())
-            .add(FLUSH64_OFFSET)
-            .as_mut_ptr() as *mut u32;
-        let end_addr = end.as_ptr() as usize;
-        mb();
-        while addr < end_addr {
-            unsafe {
-                core::ptr::write_volatile(flush_addr, addr as u32);
-                addr += LINE_SIZE;
-            }
-        }
+        let size = end.as_ptr() as usize - start.as_ptr() as usize;
+        // The driver flushes a buffer both before giving it to the device and
+        // before reading what the device wrote, a flush covers both ways.
+        axhal::cache::flush_dcache_range((start.as_ptr() as usize).into(), size);
     }
 
     fn dma_alloc(size: usize, align: usize) -> (DwmacPhysAddr, NonNull<u8>) {
@@ -93,7 +78,11 @@ impl DwmacHal for DwmacHalImpl {
             return Ok(());
         }
 
-        Self::set_clocks_uboot();
+        if let Err(err) = Self::set_clocks_uboot() {
+            log::error!("🔧 {}", err);
+            INITIALIZED.store(false, Ordering::SeqCst);
+            return Err("GMAC clocks not enabled");
+        }
         // Just do a quick status check without changing anything
         Self::print_preserved_status();
 
@@ -105,19 +94,7 @@ impl DwmacHal for DwmacHalImpl {
 }
 
 impl DwmacHalImpl {
-    fn check_diff(name: &str, readback: u32, expected: u32) {
-        if readback != expected {
-            log::error!(
-                "🔧 Clock {} is not set correctly: {:#x} != {:#x}",
-                name,
-                readback,
-                expected
-            );
-        } else {
-            log::debug!("🔧 Clock {} is set correctly: {:#x}", name, readback);
-        }
-    }
-    fn set_clocks_uboot() {
+    fn set_clocks_uboot() -> Result<(), ClockError> {
         // Use PAC for available registers
         let aoncrg: &pac::aoncrg::RegisterBlock = unsafe {
             &*(<Self as DwmacHal>::mmio_phys_to_virt(pac::AONCRG::ptr() as usize, 0x1000).as_ptr()
@@ -129,201 +106,191 @@ impl DwmacHalImpl {
                 as *const pac::syscrg::RegisterBlock)
         };
 
+        let clocks = ClockProgram::new(DEFAULT_CLOCK_TIMEOUT);
+
         // gmac0 clocks
-        unsafe {
-            // clk_enable(clk=00000000ff742940 name=gmac0_rmii_rtx)
-            // clk_enable(clk=00000000ff724390 name=gmac0-rmii-refin-clock)
-            // clk_mux_set_parent: set clock gmac0_tx to parent gmac0_rmii_rtx (reg=0x0000000017000014, val=0x01000000)
-            // md 0x81000000
-            aoncrg.clk_gmac5_axi64_tx().write(|w| w.bits(0x81000000));
-            Self::check_diff(
-                "GMAC5 AXI64 TX",
-                aoncrg.clk_gmac5_axi64_tx().read().bits(),
-                0x81000000,
-            );
-            // clk_enable(clk=00000000ff745340 name=clock-controller@17000000)
-            // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
-            // clk_gate_endisable: enabling clock gmac0_axi (reg=0x000000001700000c, bit=31, set=1)
-            // md 0x80000000
-            aoncrg.clk_axi_gmac5().write(|w| w.clk_icg().set_bit());
-            Self::check_diff(
-                "GMAC5 AXI",
-                aoncrg.clk_axi_gmac5().read().bits(),
-                0x80000000,
-            );
-            // clk_enable(clk=00000000ff745368 name=clock-controller@17000000)
-            // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
-            // clk_gate_endisable: enabling clock gmac0_ahb (reg=0x0000000017000008, bit=31, set=1)
-            // md 0x80000000
-            aoncrg.clk_ahb_gmac5().write(|w| w.clk_icg().set_bit());
-            Self::check_diff(
-                "GMAC5 AHB",
-                aoncrg.clk_ahb_gmac5().read().bits(),
-                0x80000000,
-            );
-            // clk_enable(clk=00000000ff745390 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72bf40 name=gmac_src)
-            // clk_enable(clk=00000000ff7295c0 name=pll0_out)
-            // clk_gate_endisable: enabling clock gmac0_ptp (reg=0x00000000130201b4, bit=31, set=1)
-            // md 0x8000000a
-            // syscrg.clk_gmac0_ptp().write(|w| w.clk_icg().set_bit());
-            syscrg.clk_gmac0_ptp().write(|w| w.bits(0x8000000a));
-            Self::check_diff(
-                "GMAC0 PTP",
-                syscrg.clk_gmac0_ptp().read().bits(),
-                0x8000000a,
-            );
-            // clk_enable(clk=00000000ff7453b8 name=clock-controller@17000000)
-            // clk_enable(clk=00000000ff742bc0 name=gmac0_tx)
-            // clk_enable(clk=00000000ff742940 name=gmac0_rmii_rtx)
-            // clk_gate_endisable: enabling clock gmac0_tx (reg=0x0000000017000014, bit=31, set=1)
-            // md 0x81000000
-            aoncrg.clk_gmac5_axi64_tx().write(|w| w.bits(0x81000000));
-            Self::check_diff(
-                "GMAC5 AXI64 TX",
-                aoncrg.clk_gmac5_axi64_tx().read().bits(),
-                0x81000000,
-            );
-            // clk_gate_endisable: enabling clock gmac0_tx_inv (reg=0x0000000017000018, bit=30, set=1)
-            // md 0x40000000
-            aoncrg.clk_gmac5_axi64_txi().write(|w| w.bits(1 << 30));
-            Self::check_diff(
-                "GMAC5 AXI64 TXI",
-                aoncrg.clk_gmac5_axi64_txi().read().bits(),
-                0x40000000,
-            );
-            // clk_enable(clk=00000000ff7453e0 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72cdc0 name=gmac0_gtxclk)
-            // clk_enable(clk=00000000ff7295c0 name=pll0_out)
-            // clk_gate_endisable: enabling clock gmac0_gtxclk (reg=0x00000000130201b0, bit=31, set=1)
-            // md 0x80000008
-            // syscrg.clk_gmac0_gtx().write(|w| w.clk_icg().set_bit());
-            syscrg.clk_gmac0_gtx().write(|w| w.bits(0x80000008));
-            Self::check_diff(
-                "GMAC0 GTX",
-                syscrg.clk_gmac0_gtx().read().bits(),
-                0x80000008,
-            );
-            // clk_gate_endisable: enabling clock gmac0_gtxc (reg=0x00000000130201bc, bit=31, set=1)
-            // md 0x80000020
-            // syscrg.clk_gmac0_gtxclk().write(|w| w.bits(1 << 31));
-            syscrg.clk_gmac0_gtxclk().write(|w| w.bits(0x80000020));
-            Self::check_diff(
-                "GMAC0 GTXCLK",
-                syscrg.clk_gmac0_gtxclk().read().bits(),
-                0x80000020,
-            );
-        }
+        // clk_enable(clk=00000000ff742940 name=gmac0_rmii_rtx)
+        // clk_enable(clk=00000000ff724390 name=gmac0-rmii-refin-clock)
+        // clk_mux_set_parent: set clock gmac0_tx to parent gmac0_rmii_rtx (reg=0x0000000017000014, val=0x01000000)
+        // clk_gate_endisable: enabling clock gmac0_tx (reg=0x0000000017000014, bit=31, set=1)
+        // md 0x81000000
+        let reg = aoncrg.clk_gmac5_axi64_tx();
+        clocks.set(
+            "gmac0_tx",
+            || reg.write(|w| unsafe { w.bits(0x81000000) }),
+            || reg.read().bits(),
+            0x81000000,
+        )?;
+        // clk_enable(clk=00000000ff745340 name=clock-controller@17000000)
+        // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
+        // clk_gate_endisable: enabling clock gmac0_axi (reg=0x000000001700000c, bit=31, set=1)
+        // md 0x80000000
+        let reg = aoncrg.clk_axi_gmac5();
+        clocks.enable(
+            "gmac0_axi",
+            || reg.write(|w| w.clk_icg().set_bit()),
+            || reg.read().bits(),
+        )?;
+        // clk_enable(clk=00000000ff745368 name=clock-controller@17000000)
+        // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
+        // clk_gate_endisable: enabling clock gmac0_ahb (reg=0x0000000017000008, bit=31, set=1)
+        // md 0x80000000
+        let reg = aoncrg.clk_ahb_gmac5();
+        clocks.enable(
+            "gmac0_ahb",
+            || reg.write(|w| w.clk_icg().set_bit()),
+            || reg.read().bits(),
+        )?;
+        // clk_enable(clk=00000000ff745390 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72bf40 name=gmac_src)
+        // clk_enable(clk=00000000ff7295c0 name=pll0_out)
+        // clk_gate_endisable: enabling clock gmac0_ptp (reg=0x00000000130201b4, bit=31, set=1)
+        // md 0x8000000a
+        let reg = syscrg.clk_gmac0_ptp();
+        clocks.set(
+            "gmac0_ptp",
+            || reg.write(|w| unsafe { w.bits(0x8000000a) }),
+            || reg.read().bits(),
+            0x8000000a,
+        )?;
+        // clk_gate_endisable: enabling clock gmac0_tx_inv (reg=0x0000000017000018, bit=30, set=1)
+        // md 0x40000000
+        let reg = aoncrg.clk_gmac5_axi64_txi();
+        clocks.set(
+            "gmac0_tx_inv",
+            || reg.write(|w| unsafe { w.bits(1 << 30) }),
+            || reg.read().bits(),
+            0x40000000,
+        )?;
+        // clk_enable(clk=00000000ff7453e0 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72cdc0 name=gmac0_gtxclk)
+        // clk_enable(clk=00000000ff7295c0 name=pll0_out)
+        // clk_gate_endisable: enabling clock gmac0_gtxclk (reg=0x00000000130201b0, bit=31, set=1)
+        // md 0x80000008
+        let reg = syscrg.clk_gmac0_gtx();
+        clocks.set(
+            "gmac0_gtxclk",
+            || reg.write(|w| unsafe { w.bits(0x80000008) }),
+            || reg.read().bits(),
+            0x80000008,
+        )?;
+        // clk_gate_endisable: enabling clock gmac0_gtxc (reg=0x00000000130201bc, bit=31, set=1)
+        // md 0x80000020
+        let reg = syscrg.clk_gmac0_gtxclk();
+        clocks.set(
+            "gmac0_gtxc",
+            || reg.write(|w| unsafe { w.bits(0x80000020) }),
+            || reg.read().bits(),
+            0x80000020,
+        )?;
 
         // gmac1 clocks
-        unsafe {
-            // clk_get_by_name_nodev(node=00000000ff71d794, name=gtx, clk=00000000ff7456e0)
-            // clk_request(dev=00000000ff725cf0, clk=00000000ff7456e0)
-            // clk_enable(clk=00000000ff747b40 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
-            // syscrg.clk_stg_axiahb().write(|w| w.clk_icg().set_bit());
-            // clk_gate_endisable: enabling clock gmac1_axi (reg=0x0000000013020188, bit=31, set=1)
-            // md 0x80000000
-            syscrg
-                .clk_gmac5_axi64_axi()
-                .write(|w| w.clk_icg().set_bit());
-            Self::check_diff(
-                "GMAC5 AXI64 AXI",
-                syscrg.clk_gmac5_axi64_axi().read().bits(),
-                0x80000000,
-            );
-            // clk_enable(clk=00000000ff747b68 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72a680 name=ahb0)
-            // clk_gate_endisable: enabling clock gmac1_ahb (reg=0x0000000013020184, bit=31, set=1)
-            // md 0x80000000
-            syscrg
-                .clk_gmac5_axi64_ahb()
-                .write(|w| w.clk_icg().set_bit());
-            Self::check_diff(
-                "GMAC5 AXI64 AHB",
-                syscrg.clk_gmac5_axi64_ahb().read().bits(),
-                0x80000000,
-            );
-            // clk_enable(clk=00000000ff747b90 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72bf40 name=gmac_src)
-            // clk_gate_endisable: enabling clock gmac1_ptp (reg=0x0000000013020198, bit=31, set=1)
-            // md 0x8000000a
-            syscrg
-                .clk_gmac5_axi64_ptp()
-                // .write(|w| w.clk_icg().set_bit());
-                .write(|w| w.bits(0x8000000a));
-            Self::check_diff(
-                "GMAC5 AXI64 PTP",
-                syscrg.clk_gmac5_axi64_ptp().read().bits(),
-                0x8000000a,
-            );
-            // clk_enable(clk=00000000ff747bb8 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72c9c0 name=gmac1_tx)
-            // clk_enable(clk=00000000ff72c300 name=gmac1_rmii_rtx)
-            // md 0x00000002
-            // syscrg.clk_gmac1_rmii_rtx().write(|w| w.bits(2));
-            Self::check_diff(
-                "GMAC1 RMII RTX",
-                syscrg.clk_gmac1_rmii_rtx().read().bits(),
-                0x00000002,
-            );
-            // clk_gate_endisable: enabling clock gmac1_tx (reg=0x00000000130201a4, bit=31, set=1)
-            // md 0x81000000
-            // syscrg.clk_gmac5_axi64_tx().write(|w| w.clk_icg().set_bit());
-            syscrg.clk_gmac5_axi64_tx().write(|w| w.bits(0x81000000));
-            Self::check_diff(
-                "GMAC5 AXI64 TX",
-                syscrg.clk_gmac5_axi64_tx().read().bits(),
-                0x81000000,
-            );
-            // clk_gate_endisable: enabling clock gmac1_tx_inv (reg=0x00000000130201a8, bit=30, set=1)
-            // md 0x40000000
-            syscrg.clk_gmac5_axi64_txi().write(|w| w.bits(1 << 30));
-            Self::check_diff(
-                "GMAC5 AXI64 TXI",
-                syscrg.clk_gmac5_axi64_txi().read().bits(),
-                0x40000000,
-            );
-            // clk_enable(clk=00000000ff747be0 name=clock-controller@13020000)
-            // clk_enable(clk=00000000ff72c080 name=gmac1_gtxclk)
-            // clk_enable(clk=00000000ff7295c0 name=pll0_out)
-            // clk_gate_endisable: enabling clock gmac1_gtxc (reg=0x00000000130201ac, bit=31, set=1)
-            // md 0x80000020
-            // syscrg.clk_gmac1_gtxclk().write(|w| w.bits(1 << 31));
-            syscrg.clk_gmac1_gtxclk().write(|w| w.bits(0x80000020));
-            Self::check_diff(
-                "GMAC1 GTXCLK",
-                syscrg.clk_gmac1_gtxclk().read().bits(),
-                0x80000020,
-            );
-            // clk_set_defaults(ethernet-phy@1)
-            // clk_set_default_parents: could not read assigned-clock-parents for 00000000ff728410
-        }
+        // clk_get_by_name_nodev(node=00000000ff71d794, name=gtx, clk=00000000ff7456e0)
+        // clk_request(dev=00000000ff725cf0, clk=00000000ff7456e0)
+        // clk_enable(clk=00000000ff747b40 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72a540 name=stg_axiahb)
+        // clk_gate_endisable: enabling clock gmac1_axi (reg=0x0000000013020188, bit=31, set=1)
+        // md 0x80000000
+        let reg = syscrg.clk_gmac5_axi64_axi();
+        clocks.enable(
+            "gmac1_axi",
+            || reg.write(|w| w.clk_icg().set_bit()),
+            || reg.read().bits(),
+        )?;
+        // clk_enable(clk=00000000ff747b68 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72a680 name=ahb0)
+        // clk_gate_endisable: enabling clock gmac1_ahb (reg=0x0000000013020184, bit=31, set=1)
+        // md 0x80000000
+        let reg = syscrg.clk_gmac5_axi64_ahb();
+        clocks.enable(
+            "gmac1_ahb",
+            || reg.write(|w| w.clk_icg().set_bit()),
+            || reg.read().bits(),
+        )?;
+        // clk_enable(clk=00000000ff747b90 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72bf40 name=gmac_src)
+        // clk_gate_endisable: enabling clock gmac1_ptp (reg=0x0000000013020198, bit=31, set=1)
+        // md 0x8000000a
+        let reg = syscrg.clk_gmac5_axi64_ptp();
+        clocks.set(
+            "gmac1_ptp",
+            || reg.write(|w| unsafe { w.bits(0x8000000a) }),
+            || reg.read().bits(),
+            0x8000000a,
+        )?;
+        // clk_enable(clk=00000000ff747bb8 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72c9c0 name=gmac1_tx)
+        // clk_enable(clk=00000000ff72c300 name=gmac1_rmii_rtx)
+        // md 0x00000002, the divider set by U-Boot.
+        clocks.check(
+            "gmac1_rmii_rtx",
+            || syscrg.clk_gmac1_rmii_rtx().read().bits(),
+            0x00000002,
+        )?;
+        // clk_gate_endisable: enabling clock gmac1_tx (reg=0x00000000130201a4, bit=31, set=1)
+        // md 0x81000000
+        let reg = syscrg.clk_gmac5_axi64_tx();
+        clocks.set(
+            "gmac1_tx",
+            || reg.write(|w| unsafe { w.bits(0x81000000) }),
+            || reg.read().bits(),
+            0x81000000,
+        )?;
+        // clk_gate_endisable: enabling clock gmac1_tx_inv (reg=0x00000000130201a8, bit=30, set=1)
+        // md 0x40000000
+        let reg = syscrg.clk_gmac5_axi64_txi();
+        clocks.set(
+            "gmac1_tx_inv",
+            || reg.write(|w| unsafe { w.bits(1 << 30) }),
+            || reg.read().bits(),
+            0x40000000,
+        )?;
+        // clk_enable(clk=00000000ff747be0 name=clock-controller@13020000)
+        // clk_enable(clk=00000000ff72c080 name=gmac1_gtxclk)
+        // clk_enable(clk=00000000ff7295c0 name=pll0_out)
+        // clk_gate_endisable: enabling clock gmac1_gtxc (reg=0x00000000130201ac, bit=31, set=1)
+        // md 0x80000020
+        let reg = syscrg.clk_gmac1_gtxclk();
+        clocks.set(
+            "gmac1_gtxc",
+            || reg.write(|w| unsafe { w.bits(0x80000020) }),
+            || reg.read().bits(),
+            0x80000020,
+        )?;
+        // clk_set_defaults(ethernet-phy@1)
+        // clk_set_default_parents: could not read assigned-clock-parents for 00000000ff728410
 
         // reset
+        // Each step must reach the CRG before its delay starts.
+        let settle = || {
+            mb();
+            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+        };
         unsafe {
             aoncrg.soft_rst_addr_sel().write(|w| w.bits(0xe1));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             aoncrg.soft_rst_addr_sel().write(|w| w.bits(0xe3));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             // jh7110_reset_trigger: deasserting reset 0 (reg=0x17000038, value=0xe2)
             // jh7110_reset_trigger: deasserting reset 1 (reg=0x17000038, value=0xe0)
             aoncrg.soft_rst_addr_sel().write(|w| w.bits(0xe2));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             aoncrg.soft_rst_addr_sel().write(|w| w.bits(0xe0));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
 
             syscrg.soft_rst_addr_sel_2().write(|w| w.bits(0xffe5efc4));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             syscrg.soft_rst_addr_sel_2().write(|w| w.bits(0xffe5efcc));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             // jh7110_reset_trigger: deasserting reset 66 (reg=0x13020300, value=0xffe5efc8)
             // jh7110_reset_trigger: deasserting reset 67 (reg=0x13020300, value=0xffe5efc0)
             syscrg.soft_rst_addr_sel_2().write(|w| w.bits(0xffe5efc8));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
             syscrg.soft_rst_addr_sel_2().write(|w| w.bits(0xffe5efc0));
-            let _ = DwmacHalImpl::wait_until(core::time::Duration::from_millis(100));
+            settle();
         }
+
+        Ok(())
     }
 
     fn write_reg(paddr: PhysAddr, val: u32) {
@@ -49,6 +49,13 @@
 //! - `block`: use block storage devices. Similar to the `net` feature.
 //! - `display`: use graphics display devices. Similar to the `net` feature.
 //!
+//! # Registry
+//!
+//! Each driver registers a [`DriverDesc`](registry::DriverDesc) in the
+//! [`registry`] at link time, with its probe functions, the device-tree
+//! `compatible` strings of its devices and their IRQs. The buses probe every
+//! device with all the registered drivers.
+//!
 //! [`VirtioNetDev`]: axdriver_virtio::VirtIoNetDev
 //! [`Box<dyn NetDriverOps>`]: axdriver_net::NetDriverOps
 //! [trait objects]: https://doc.rust-lang.org/book/ch17-02-trait-objects.html
@@ -70,6 +77,7 @@ mod macros;
 mod bus;
 mod drivers;
 mod dummy;
+pub mod registry;
 mod structs;
 
 #[cfg(feature = "virtio")]
@@ -78,6 +86,8 @@ mod virtio;
 #[cfg(feature = "ixgbe")]
 mod ixgbe;
 
+#[cfg(feature = "dwmac")]
+mod clk;
 #[cfg(feature = "dwmac")]
 mod dwmac;
 
@@ -122,16 +132,17 @@ impl AllDevices {
 
     /// Probes all supported devices.
     fn probe(&mut self) {
-        for_each_drivers!(type Driver, {
-            if let Some(dev) = Driver::probe_global() {
+        for driver in registry::DRIVERS {
+            if let Some(dev) = (driver.probe_global)() {
                 info!(
-                    "registered a new {:?} device: {:?}",
+                    "registered a new {:?} device with {}: {:?}",
                     dev.device_type(),
+                    driver.name,
                     dev.device_name(),
                 );
                 self.add_device(dev, 0); // TODO: 0 is the invalid IRQ number
             }
-        });
+        }
 
         self.probe_bus_devices();
     }
@@ -158,6 +169,11 @@ pub fn init_drivers() -> AllDevices {
     let mut all_devs = AllDevices::default();
     all_devs.probe();
 
+    // Registered before the hooks of the subsystems using the devices, so
+    // that it runs after them.
+    #[cfg(feature = "irq")]
+    axhal::misc::on_shutdown(mask_device_irqs);
+
     #[cfg(feature = "net")]
     {
         debug!("number of NICs: {}", all_devs.net.len());
@@ -202,3 +218,14 @@ pub use axdriver_virtio as virtio_drivers;
 pub use self::drivers::*;
 pub use self::structs::*;
 pub use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+/// Masks the IRQs of the MMIO devices at shutdown, so that no handler runs
+/// once the subsystems have torn their devices down.
+#[cfg(feature = "irq")]
+fn mask_device_irqs() {
+    for driver in registry::DRIVERS {
+        for &(_, irq) in driver.mmio_irqs {
+            axhal::irq::set_enable(irq as usize, false);
+        }
+    }
+}
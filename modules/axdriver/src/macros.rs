@@ -1,4 +1,4 @@
-//! TODO: generate registered drivers in `for_each_drivers!` automatically.
+//! Macros to register the drivers.
 
 #![allow(unused_macros)]
 
@@ -26,53 +26,24 @@ macro_rules! register_display_driver {
     };
 }
 
-macro_rules! for_each_drivers {
-    (type $drv_type:ident, $code:block) => {{
-        #[allow(unused_imports)]
-        use crate::drivers::DriverProbe;
-        #[cfg(feature = "virtio")]
-        #[allow(unused_imports)]
-        use crate::virtio::{self, VirtIoDevMeta};
-
-        #[cfg(net_dev = "virtio-net")]
-        {
-            type $drv_type = <virtio::VirtIoNet as VirtIoDevMeta>::Driver;
-            $code
-        }
-        #[cfg(block_dev = "virtio-blk")]
-        {
-            type $drv_type = <virtio::VirtIoBlk as VirtIoDevMeta>::Driver;
-            $code
-        }
-        #[cfg(display_dev = "virtio-gpu")]
-        {
-            type $drv_type = <virtio::VirtIoGpu as VirtIoDevMeta>::Driver;
-            $code
-        }
-        #[cfg(block_dev = "ramdisk")]
-        {
-            type $drv_type = crate::drivers::RamDiskDriver;
-            $code
-        }
-        #[cfg(block_dev = "bcm2835-sdhci")]
-        {
-            type $drv_type = crate::drivers::BcmSdhciDriver;
-            $code
-        }
-        #[cfg(net_dev = "ixgbe")]
-        {
-            type $drv_type = crate::drivers::IxgbeDriver;
-            $code
-        }
-        #[cfg(net_dev = "fxmac")]
-        {
-            type $drv_type = crate::drivers::FXmacDriver;
-            $code
-        }
-        #[cfg(net_dev = "dwmac")]
-        {
-            type $drv_type = crate::drivers::DwmacDriver;
-            $code
-        }
-    }};
+/// Registers the descriptor of a driver in [`DRIVERS`](crate::registry::DRIVERS),
+/// with the device-tree `compatible` strings of its devices, and the IRQs of
+/// its MMIO devices by base address.
+macro_rules! register_driver {
+    (
+        $driver_type:ty, $name:expr
+        $(, compatible: [$($compat:expr),* $(,)?])?
+        $(, mmio_irqs: [$(($base:expr, $irq:expr)),* $(,)?])?
+        $(,)?
+    ) => {
+        const _: () = {
+            #[linkme::distributed_slice(crate::registry::DRIVERS)]
+            static DRIVER: crate::registry::DriverDesc =
+                crate::registry::DriverDesc::new::<$driver_type>(
+                    $name,
+                    &[$($($compat),*)?],
+                    &[$($(($base, $irq)),*)?],
+                );
+        };
+    };
 }
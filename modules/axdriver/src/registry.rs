@@ -0,0 +1,83 @@
+//! The registry of the drivers, collected at link time.
+//!
+//! Each driver registers a [`DriverDesc`] next to its definition, with
+//! `register_driver!`, and the buses probe the devices with the drivers of
+//! the [`DRIVERS`] slice. The MMIO bus only tries the drivers compatible with
+//! the device-tree node of a device, if it has one. Adding a driver does not
+//! touch the bus code.
+
+use linkme::distributed_slice;
+
+use crate::AxDeviceEnum;
+use crate::drivers::DriverProbe;
+
+#[cfg(bus = "pci")]
+use axdriver_pci::{DeviceFunction, DeviceFunctionInfo, PciRoot};
+
+/// The descriptors of all the drivers linked in.
+#[distributed_slice]
+pub static DRIVERS: [DriverDesc];
+
+/// The descriptor of a driver: how to probe its devices, and how they are
+/// wired.
+pub struct DriverDesc {
+    /// The name of the driver, for the logs.
+    pub name: &'static str,
+    /// The device-tree `compatible` strings of the devices it drives.
+    pub compatible: &'static [&'static str],
+    /// The IRQs of its MMIO devices, by base address. The devices not listed
+    /// get the IRQ assigned by the bus.
+    pub mmio_irqs: &'static [(usize, u32)],
+    /// Probes a device that is not on a bus, e.g. a RAM disk.
+    pub probe_global: fn() -> Option<AxDeviceEnum>,
+    /// Probes the MMIO region at the given base and size.
+    #[cfg(bus = "mmio")]
+    pub probe_mmio: fn(usize, usize) -> Option<AxDeviceEnum>,
+    /// Probes a function of the PCI bus.
+    #[cfg(bus = "pci")]
+    pub probe_pci: fn(&mut PciRoot, DeviceFunction, &DeviceFunctionInfo) -> Option<AxDeviceEnum>,
+}
+
+impl DriverDesc {
+    /// Creates the descriptor of the driver `D`, that probes with its
+    /// [`DriverProbe`] methods.
+    pub(crate) const fn new<D: DriverProbe>(
+        name: &'static str,
+        compatible: &'static [&'static str],
+        mmio_irqs: &'static [(usize, u32)],
+    ) -> Self {
+        Self {
+            name,
+            compatible,
+            mmio_irqs,
+            probe_global: D::probe_global,
+            #[cfg(bus = "mmio")]
+            probe_mmio: D::probe_mmio,
+            #[cfg(bus = "pci")]
+            probe_pci: D::probe_pci,
+        }
+    }
+
+    /// Returns the IRQ of the MMIO device at `base`, if it is wired to a
+    /// known one.
+    pub fn mmio_irq(&self, base: usize) -> Option<u32> {
+        self.mmio_irqs
+            .iter()
+            .find(|(reg, _)| *reg == base)
+            .map(|(_, irq)| *irq)
+    }
+
+    /// Returns whether the driver drives the devices with the device-tree
+    /// `compatible` string `compat`.
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.compatible.contains(&compat)
+    }
+}
+
+/// Returns the drivers of the devices with the device-tree `compatible`
+/// string `compat`.
+pub fn drivers_compatible_with(compat: &str) -> impl Iterator<Item = &'static DriverDesc> + '_ {
+    DRIVERS
+        .iter()
+        .filter(move |desc| desc.is_compatible(compat))
+}
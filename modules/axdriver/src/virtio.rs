@@ -2,6 +2,7 @@ use core::marker::PhantomData;
 use core::ptr::NonNull;
 
 use axalloc::global_allocator;
+use axdma::{BusAddr, DmaDirection, DmaMapping, map_single, unmap_single};
 use axdriver_base::{BaseDriverOps, DevResult, DeviceType};
 use axdriver_virtio::{BufferDirection, PhysAddr, VirtIoHal};
 use axhal::mem::{phys_to_virt, virt_to_phys};
@@ -162,11 +163,29 @@ unsafe impl VirtIoHal for VirtIoHalImpl {
     }
 
     #[inline]
-    unsafe fn share(buffer: NonNull<[u8]>, _direction: BufferDirection) -> PhysAddr {
-        let vaddr = buffer.as_ptr() as *mut u8 as usize;
-        virt_to_phys(vaddr.into()).into()
+    unsafe fn share(buffer: NonNull<[u8]>, direction: BufferDirection) -> PhysAddr {
+        let mapping = unsafe { map_single(buffer.cast(), buffer.len(), dma_direction(direction)) };
+        mapping.bus_addr().as_u64() as PhysAddr
     }
 
     #[inline]
-    unsafe fn unshare(_paddr: PhysAddr, _buffer: NonNull<[u8]>, _direction: BufferDirection) {}
+    unsafe fn unshare(paddr: PhysAddr, buffer: NonNull<[u8]>, direction: BufferDirection) {
+        let mapping = unsafe {
+            DmaMapping::from_raw_parts(
+                buffer.cast(),
+                BusAddr::from(paddr as u64),
+                buffer.len(),
+                dma_direction(direction),
+            )
+        };
+        unmap_single(mapping);
+    }
+}
+
+const fn dma_direction(direction: BufferDirection) -> DmaDirection {
+    match direction {
+        BufferDirection::DriverToDevice => DmaDirection::ToDevice,
+        BufferDirection::DeviceToDriver => DmaDirection::FromDevice,
+        BufferDirection::Both => DmaDirection::Bidirectional,
+    }
 }
@@ -0,0 +1,278 @@
+//! Firmware blob loader for [ArceOS](https://github.com/arceos-org/arceos).
+//!
+//! Drivers that need a blob at run time (e.g. the firmware of a PHY, or in the
+//! future a dynamically-loaded driver) ask for it by name with [`load`]. The
+//! blob is read from a [`Source`], checked against its expected SHA-256
+//! digest, and kept in memory until [`unload`]ed, so that the next
+//! [`load`] or [`get`] of the same name shares it.
+//!
+//! Subsystems can observe the lifecycle of firmware with [`on_event`], e.g.
+//! to reset a device when its firmware is replaced.
+//!
+//! # Cargo Features
+//!
+//! - `fs`: Load firmware from files, with [axfs].
+//! - `net`: Load firmware over HTTP or TFTP, with [`axnet::fetch`].
+//!
+//! [axfs]: https://arceos-org.github.io/arceos/axfs/index.html
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use axerrno::{AxResult, ax_err, ax_err_type};
+use spin::Mutex;
+
+/// A SHA-256 digest.
+pub type Sha256Digest = [u8; 32];
+
+/// Firmware loaded by name, shared by all users.
+static LOADED: Mutex<BTreeMap<String, Arc<Firmware>>> = Mutex::new(BTreeMap::new());
+
+static HOOKS: Mutex<Vec<fn(&FirmwareEvent)>> = Mutex::new(Vec::new());
+
+/// Where to load firmware from.
+#[derive(Debug, Clone, Copy)]
+pub enum Source<'a> {
+    /// A blob already in memory, e.g. embedded with `include_bytes!`.
+    Memory(&'a [u8]),
+    /// A file, at the given path.
+    #[cfg(feature = "fs")]
+    File(&'a str),
+    /// A `http://` or `tftp://` URL.
+    #[cfg(feature = "net")]
+    Url(&'a str),
+}
+
+/// A firmware blob in memory.
+pub struct Firmware {
+    name: String,
+    data: Vec<u8>,
+    sha256: Sha256Digest,
+}
+
+impl Firmware {
+    /// Returns the name the firmware was loaded with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the content of the firmware.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the SHA-256 digest of the content.
+    pub fn sha256(&self) -> &Sha256Digest {
+        &self.sha256
+    }
+}
+
+impl fmt::Debug for Firmware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Firmware")
+            .field("name", &self.name)
+            .field("len", &self.data.len())
+            .field("sha256", &HexDigest(&self.sha256))
+            .finish()
+    }
+}
+
+/// A change in the lifecycle of firmware, reported to [`on_event`] hooks.
+#[derive(Debug)]
+pub enum FirmwareEvent<'a> {
+    /// The firmware has been loaded and verified.
+    Loaded(&'a Firmware),
+    /// The firmware did not match its expected digest, and was discarded.
+    Rejected {
+        /// The name of the firmware.
+        name: &'a str,
+        /// The digest of the content that was read.
+        sha256: &'a Sha256Digest,
+    },
+    /// The firmware has been unloaded. It is freed once its last user drops
+    /// it.
+    Unloaded(&'a Firmware),
+}
+
+/// Registers a hook called on every [`FirmwareEvent`].
+///
+/// Hooks are called in the order of registration, by the task that loads or
+/// unloads the firmware.
+pub fn on_event(hook: fn(&FirmwareEvent)) {
+    HOOKS.lock().push(hook);
+}
+
+fn notify(event: FirmwareEvent) {
+    // Do not hold the lock while running the hooks, they may register more.
+    let hooks = HOOKS.lock().clone();
+    for hook in hooks {
+        hook(&event);
+    }
+}
+
+/// Formats a digest in hexadecimal.
+struct HexDigest<'a>(&'a Sha256Digest);
+
+impl fmt::Debug for HexDigest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a SHA-256 digest written in hexadecimal, e.g. from a configuration
+/// file or a manifest.
+pub fn parse_sha256(hex: &str) -> AxResult<Sha256Digest> {
+    let hex = hex.trim().as_bytes();
+    if hex.len() != 64 {
+        return ax_err!(InvalidInput, "SHA-256 digest must be 64 hex digits");
+    }
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.chunks(2)) {
+        let pair = core::str::from_utf8(pair).unwrap_or_default();
+        *byte = u8::from_str_radix(pair, 16)
+            .map_err(|_| ax_err_type!(InvalidInput, "invalid hex digit in SHA-256 digest"))?;
+    }
+    Ok(digest)
+}
+
+async fn read_source(source: Source<'_>) -> AxResult<Vec<u8>> {
+    match source {
+        Source::Memory(data) => Ok(data.to_vec()),
+        #[cfg(feature = "fs")]
+        Source::File(path) => axfs::api::read(path),
+        #[cfg(feature = "net")]
+        Source::Url(url) => {
+            let mut data = Vec::new();
+            axnet::fetch(url, &mut data, |progress| {
+                trace!(
+                    "firmware: {} of {:?} bytes",
+                    progress.received, progress.total
+                )
+            })
+            .await?;
+            Ok(data)
+        }
+    }
+}
+
+/// Loads the firmware `name` from `source`, and checks that its SHA-256
+/// digest is `expected`, if given.
+///
+/// If firmware of the same name is already loaded, it is returned without
+/// reading `source`, after checking its digest. Fails with
+/// [`InvalidData`](axerrno::AxError::InvalidData) if the digest does not
+/// match.
+pub async fn load(
+    name: &str,
+    source: Source<'_>,
+    expected: Option<&Sha256Digest>,
+) -> AxResult<Arc<Firmware>> {
+    if let Some(fw) = get(name) {
+        return check_loaded(fw, expected);
+    }
+
+    let data = read_source(source).await?;
+    let sha256 = axcrypto::sha256(&data);
+    if expected.is_some_and(|expected| *expected != sha256) {
+        warn!(
+            "firmware {}: digest mismatch, got {:?}",
+            name,
+            HexDigest(&sha256)
+        );
+        notify(FirmwareEvent::Rejected {
+            name,
+            sha256: &sha256,
+        });
+        return ax_err!(InvalidData, "firmware digest mismatch");
+    }
+
+    let fw = Arc::new(Firmware {
+        name: name.to_string(),
+        data,
+        sha256,
+    });
+    {
+        let mut loaded = LOADED.lock();
+        // Another task may have loaded it meanwhile, keep the first one.
+        if let Some(first) = loaded.get(name) {
+            let first = first.clone();
+            drop(loaded);
+            return check_loaded(first, expected);
+        }
+        loaded.insert(name.to_string(), fw.clone());
+    }
+    info!("firmware loaded: {:?}", fw);
+    notify(FirmwareEvent::Loaded(&fw));
+    Ok(fw)
+}
+
+fn check_loaded(fw: Arc<Firmware>, expected: Option<&Sha256Digest>) -> AxResult<Arc<Firmware>> {
+    if expected.is_some_and(|expected| expected != fw.sha256()) {
+        return ax_err!(InvalidData, "firmware loaded with another digest");
+    }
+    Ok(fw)
+}
+
+/// Returns the firmware `name`, if it is loaded.
+pub fn get(name: &str) -> Option<Arc<Firmware>> {
+    LOADED.lock().get(name).cloned()
+}
+
+/// Unloads the firmware `name`.
+///
+/// Its memory is freed once its last user drops it.
+pub fn unload(name: &str) -> AxResult {
+    let Some(fw) = LOADED.lock().remove(name) else {
+        return ax_err!(NotFound, "firmware not loaded");
+    };
+    info!("firmware unloaded: {}", name);
+    notify(FirmwareEvent::Unloaded(&fw));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_verify() {
+        const BLOB: &[u8] = b"abc";
+        let digest =
+            parse_sha256("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                .unwrap();
+        let mut wrong = digest;
+        wrong[0] ^= 1;
+
+        futures_executor::block_on(async {
+            let res = load("test", Source::Memory(BLOB), Some(&wrong)).await;
+            assert_eq!(res.err(), Some(axerrno::AxError::InvalidData));
+            assert!(get("test").is_none());
+
+            let fw = load("test", Source::Memory(BLOB), Some(&digest))
+                .await
+                .unwrap();
+            assert_eq!(fw.data(), BLOB);
+            // Already loaded, the source is not read again.
+            let again = load("test", Source::Memory(b""), None).await.unwrap();
+            assert!(Arc::ptr_eq(&fw, &again));
+            let res = load("test", Source::Memory(BLOB), Some(&wrong)).await;
+            assert_eq!(res.err(), Some(axerrno::AxError::InvalidData));
+        });
+
+        assert_eq!(unload("test"), Ok(()));
+        assert_eq!(unload("test"), Err(axerrno::AxError::NotFound));
+        assert!(get("test").is_none());
+    }
+}
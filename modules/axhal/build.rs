@@ -22,6 +22,16 @@ const BUILTIN_PLATFORM_FAMILIES: &[&str] = &[
     "x86-pc",
 ];
 
+/// The `.eh_frame` section, and the symbols the unwinder finds it with.
+const EH_FRAME_SECTION: &str = r#"
+    .eh_frame : ALIGN(8) {
+        __executable_start = _skernel;
+        __etext = _etext;
+        __eh_frame = .;
+        KEEP(*(.eh_frame .eh_frame.*))
+    }
+"#;
+
 fn make_cfg_values(str_list: &[&str]) -> String {
     str_list
         .iter()
@@ -69,6 +79,16 @@ fn gen_linker_script(arch: &str, platform: &str) -> Result<()> {
     );
     let ld_content = ld_content.replace("%SMP%", &format!("{}", axconfig::SMP));
 
+    // The unwinder looks up the frames of the kernel in `.eh_frame`, which is
+    // discarded otherwise.
+    let (eh_frame, discard_eh_frame) = if std::env::var("CARGO_FEATURE_UNWIND").is_ok() {
+        (EH_FRAME_SECTION, "")
+    } else {
+        ("", "*(.eh_frame*)")
+    };
+    let ld_content = ld_content.replace("%EH_FRAME%", eh_frame);
+    let ld_content = ld_content.replace("%DISCARD_EH_FRAME%", discard_eh_frame);
+
     // target/<target_triple>/<mode>/build/axhal-xxxx/out
     let out_dir = std::env::var("OUT_DIR").unwrap();
     // target/<target_triple>/<mode>/linker_xxxx.lds
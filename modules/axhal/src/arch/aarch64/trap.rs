@@ -39,6 +39,10 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
 #[unsafe(no_mangle)]
 fn handle_irq_exception(_tf: &TrapFrame) {
     handle_trap!(IRQ, 0);
+    #[cfg(feature = "irq")]
+    if crate::irq::nesting_depth() == 0 {
+        crate::trap::resched_on_irq_return();
+    }
 }
 
 fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
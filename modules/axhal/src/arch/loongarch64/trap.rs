@@ -59,6 +59,10 @@ fn loongarch64_trap_handler(tf: &mut TrapFrame, from_user: bool) {
         Trap::Interrupt(_) => {
             let irq_num: usize = estat.is().trailing_zeros() as usize;
             handle_trap!(IRQ, irq_num);
+            #[cfg(feature = "irq")]
+            if crate::irq::nesting_depth() == 0 {
+                crate::trap::resched_on_irq_return();
+            }
         }
         _ => {
             panic!(
@@ -0,0 +1,108 @@
+//! Emulation of the kernel loads and stores that fault on a traced page, see
+//! [`MMIO_ACCESS`](crate::trap::MMIO_ACCESS).
+
+use memory_addr::VirtAddr;
+
+use super::{GeneralRegisters, TrapFrame};
+use crate::trap::{MMIO_ACCESS, MmioAccess};
+
+/// A decoded load or store instruction.
+struct LoadStore {
+    /// The register loaded, or stored.
+    reg: usize,
+    /// The width of the access in bytes.
+    width: usize,
+    /// Whether the loaded value is sign-extended.
+    signed: bool,
+    store: bool,
+    /// The length of the instruction in bytes.
+    len: usize,
+}
+
+/// Decodes the integer load or store at `pc`, including the compressed ones
+/// of quadrant 0. The stack-relative compressed ones never access MMIO.
+fn decode(pc: usize) -> Option<LoadStore> {
+    // Safety: the faulting instruction is mapped, and 2-byte aligned.
+    let low = unsafe { (pc as *const u16).read() } as u32;
+    if low & 0b11 != 0b11 {
+        let reg = ((low >> 2) & 0b111) as usize + 8;
+        let (width, store) = match (low & 0b11, low >> 13) {
+            (0b00, 0b010) => (4, false), // C.LW
+            (0b00, 0b011) => (8, false), // C.LD
+            (0b00, 0b110) => (4, true),  // C.SW
+            (0b00, 0b111) => (8, true),  // C.SD
+            _ => return None,
+        };
+        let signed = width == 4;
+        return Some(LoadStore {
+            reg,
+            width,
+            signed,
+            store,
+            len: 2,
+        });
+    }
+    // Safety: as above, a 32-bit instruction continues in the next halfword.
+    let insn = low | ((unsafe { (pc as *const u16).add(1).read() } as u32) << 16);
+    let funct3 = (insn >> 12) & 0b111;
+    let (reg, store) = match insn & 0x7f {
+        0x03 => ((insn >> 7) & 0x1f, false), // LOAD
+        0x23 => ((insn >> 20) & 0x1f, true), // STORE
+        _ => return None,
+    };
+    if (store && funct3 > 0b011) || funct3 == 0b111 {
+        return None;
+    }
+    Some(LoadStore {
+        reg: reg as usize,
+        width: 1 << (funct3 & 0b11),
+        signed: funct3 < 0b100 && funct3 != 0b011,
+        store,
+        len: 4,
+    })
+}
+
+/// Returns the general registers `x1` to `x31`, in order.
+fn regs(regs: &mut GeneralRegisters) -> &mut [usize; 31] {
+    // Safety: `GeneralRegisters` is `repr(C)`, with 31 `usize` fields in
+    // the order of the registers.
+    unsafe { &mut *(regs as *mut GeneralRegisters as *mut [usize; 31]) }
+}
+
+/// Performs the kernel load or store of `tf` that faulted at `vaddr` with a
+/// handler of [`MMIO_ACCESS`], and skips it. Returns `false` if it is not an
+/// integer load or store, or if no handler traces the page.
+pub(super) fn emulate_access(tf: &mut TrapFrame, vaddr: VirtAddr) -> bool {
+    let Some(insn) = decode(tf.sepc) else {
+        return false;
+    };
+    let bits = insn.width * 8;
+    let mask = u64::MAX >> (64 - bits);
+    let regs = regs(&mut tf.regs);
+    let access = if insn.store {
+        let value = match insn.reg {
+            0 => 0,
+            reg => regs[reg - 1] as u64,
+        };
+        MmioAccess::Write(insn.width, value & mask)
+    } else {
+        MmioAccess::Read(insn.width)
+    };
+    let Some(value) = MMIO_ACCESS
+        .iter()
+        .find_map(|handler| handler(vaddr, access))
+    else {
+        return false;
+    };
+    if !insn.store && insn.reg != 0 {
+        let value = value & mask;
+        let shift = 64 - bits;
+        regs[insn.reg - 1] = if insn.signed {
+            ((value << shift) as i64 >> shift) as usize
+        } else {
+            value as usize
+        };
+    }
+    tf.sepc += insn.len;
+    true
+}
@@ -2,6 +2,10 @@
 mod macros;
 
 mod context;
+#[cfg(feature = "mmio")]
+mod mmio;
+mod pmu;
+mod sta;
 mod trap;
 
 use memory_addr::{PhysAddr, VirtAddr};
@@ -11,6 +15,28 @@ use riscv::register::{satp, sstatus, stvec};
 #[cfg(feature = "uspace")]
 pub use self::context::UspaceContext;
 pub use self::context::{GeneralRegisters, TaskContext, TrapFrame};
+pub(crate) use self::pmu::init_percpu as init_pmu;
+pub use self::pmu::{read_cache_misses, read_cycles, read_instructions};
+pub(crate) use self::sta::init_percpu as init_steal_time;
+pub use self::sta::steal_time_nanos;
+
+/// Makes an SBI call, returns the `(error, value)` pair.
+fn sbi_call(eid: usize, fid: usize, args: [usize; 5]) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    (error, value)
+}
 
 /// Allows the current CPU to respond to interrupts.
 #[inline]
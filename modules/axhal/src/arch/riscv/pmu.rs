@@ -0,0 +1,113 @@
+//! Hardware performance counters through the `cycle`/`instret` CSRs and the
+//! SBI PMU extension.
+//!
+//! `cycle` and `instret` are fixed counters. The cache miss counter is one of
+//! the programmable `hpmcounter`s, allocated per CPU by the SBI
+//! implementation.
+//!
+//! Reference: RISC-V SBI specification, chapter "Performance Monitoring Unit
+//! Extension".
+
+use super::sbi_call;
+
+/// The SBI extension ID of PMU ("PMU" in ASCII).
+const EID_PMU: usize = 0x0050_4D55;
+/// `sbi_pmu_num_counters`.
+const FID_NUM_COUNTERS: usize = 0;
+/// `sbi_pmu_counter_get_info`.
+const FID_COUNTER_GET_INFO: usize = 1;
+/// `sbi_pmu_counter_config_matching`.
+const FID_COUNTER_CONFIG_MATCHING: usize = 2;
+
+/// Hardware general event `SBI_PMU_HW_CACHE_MISSES`.
+const EVENT_HW_CACHE_MISSES: usize = 4;
+
+const CFG_FLAG_CLEAR_VALUE: usize = 1 << 1;
+const CFG_FLAG_AUTO_START: usize = 1 << 2;
+/// Do not count events in M-mode (i.e. inside the SBI implementation).
+const CFG_FLAG_SET_MINH: usize = 1 << 7;
+
+/// The first `hpmcounter` CSR number.
+const CSR_HPMCOUNTER3: usize = 0xc03;
+
+/// The CSR number of the cache miss counter of the current CPU, `0` if not
+/// available.
+#[percpu::def_percpu]
+static CACHE_MISS_CSR: usize = 0;
+
+/// Asks the SBI implementation for a counter of cache misses on the current
+/// CPU, and starts it.
+///
+/// It does nothing if the SBI implementation does not support PMU or there is
+/// no counter able to count cache misses.
+pub(crate) fn init_percpu() {
+    let (ret, num_counters) = sbi_call(EID_PMU, FID_NUM_COUNTERS, [0; 5]);
+    if ret != 0 || num_counters == 0 {
+        debug!("SBI PMU is not available: error {}", ret);
+        return;
+    }
+
+    let mask = (1usize << num_counters.min(usize::BITS as usize - 1)) - 1;
+    let flags = CFG_FLAG_CLEAR_VALUE | CFG_FLAG_AUTO_START | CFG_FLAG_SET_MINH;
+    let (ret, idx) = sbi_call(
+        EID_PMU,
+        FID_COUNTER_CONFIG_MATCHING,
+        [0, mask, flags, EVENT_HW_CACHE_MISSES, 0],
+    );
+    if ret != 0 {
+        debug!("no PMU counter for cache misses: error {}", ret);
+        return;
+    }
+
+    let (ret, info) = sbi_call(EID_PMU, FID_COUNTER_GET_INFO, [idx, 0, 0, 0, 0]);
+    let is_firmware = info >> (usize::BITS - 1) != 0;
+    let csr = info & 0xfff;
+    if ret != 0 || is_firmware || !(CSR_HPMCOUNTER3..CSR_HPMCOUNTER3 + 29).contains(&csr) {
+        debug!("unsupported PMU counter {}: info {:#x}", idx, info);
+        return;
+    }
+    unsafe { CACHE_MISS_CSR.write_current_raw(csr) };
+}
+
+/// Returns the number of cycles executed by the current CPU.
+#[inline]
+pub fn read_cycles() -> u64 {
+    let value: usize;
+    unsafe { core::arch::asm!("rdcycle {}", out(reg) value) };
+    value as u64
+}
+
+/// Returns the number of instructions retired by the current CPU.
+#[inline]
+pub fn read_instructions() -> u64 {
+    let value: usize;
+    unsafe { core::arch::asm!("rdinstret {}", out(reg) value) };
+    value as u64
+}
+
+/// Returns the number of cache misses of the current CPU, or [`None`] if it
+/// cannot be counted.
+pub fn read_cache_misses() -> Option<u64> {
+    macro_rules! read_hpmcounter {
+        ($csr:expr, $($n:literal),+) => {
+            match $csr {
+                $(
+                    n if n == CSR_HPMCOUNTER3 + $n - 3 => {
+                        let value: usize;
+                        unsafe {
+                            core::arch::asm!(concat!("csrr {}, hpmcounter", $n), out(reg) value)
+                        };
+                        Some(value as u64)
+                    }
+                )+
+                _ => None,
+            }
+        };
+    }
+
+    let csr = unsafe { CACHE_MISS_CSR.read_current_raw() };
+    read_hpmcounter!(
+        csr, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+        26, 27, 28, 29, 30, 31
+    )
+}
@@ -0,0 +1,77 @@
+//! Steal-time accounting through the SBI STA extension.
+//!
+//! When running as a guest, the hypervisor reports in a per-CPU shared memory
+//! how long the vCPU was ready to run but not scheduled. The `time` CSR keeps
+//! counting during that period, so monotonic time and timers stay accurate;
+//! the steal time only explains why a CPU made less progress than expected.
+//!
+//! Reference: RISC-V SBI specification, chapter "Steal-time Accounting Extension".
+
+use core::sync::atomic::{AtomicBool, Ordering, fence};
+
+use super::sbi_call;
+use crate::mem::virt_to_phys;
+
+/// The SBI extension ID of STA ("STA" in ASCII).
+const EID_STA: usize = 0x0053_5441;
+/// `sbi_steal_time_set_shmem`.
+const FID_SET_SHMEM: usize = 0;
+
+/// The shared memory layout defined by the SBI specification.
+#[repr(C, align(64))]
+struct StealTimeShmem {
+    sequence: u32,
+    flags: u32,
+    steal: u64,
+    preempted: u8,
+    _pad: [u8; 47],
+}
+
+#[percpu::def_percpu]
+static STEAL_TIME: StealTimeShmem = StealTimeShmem {
+    sequence: 0,
+    flags: 0,
+    steal: 0,
+    preempted: 0,
+    _pad: [0; 47],
+};
+
+static STA_AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// Registers the steal-time shared memory of the current CPU to the SBI
+/// implementation.
+///
+/// It does nothing if the SBI implementation does not support STA (e.g. when
+/// running on bare metal).
+pub(crate) fn init_percpu() {
+    let vaddr = va!(unsafe { STEAL_TIME.current_ptr() } as usize);
+    let paddr = virt_to_phys(vaddr).as_usize();
+    let (ret, _) = sbi_call(EID_STA, FID_SET_SHMEM, [paddr, 0, 0, 0, 0]);
+    if ret == 0 {
+        STA_AVAILABLE.store(true, Ordering::Release);
+    } else {
+        debug!("SBI STA is not available: error {}", ret);
+    }
+}
+
+/// Returns the total time in nanoseconds that the current CPU was stolen by
+/// the hypervisor, or `0` if steal-time accounting is not available.
+pub fn steal_time_nanos() -> u64 {
+    if !STA_AVAILABLE.load(Ordering::Acquire) {
+        return 0;
+    }
+    let shmem = unsafe { STEAL_TIME.current_ptr() };
+    loop {
+        // The hypervisor increments `sequence` before and after each update,
+        // so an odd or changed value means the read raced with an update.
+        let seq = unsafe { (&raw const (*shmem).sequence).read_volatile() };
+        fence(Ordering::Acquire);
+        let steal = unsafe { (&raw const (*shmem).steal).read_volatile() };
+        fence(Ordering::Acquire);
+        let seq_after = unsafe { (&raw const (*shmem).sequence).read_volatile() };
+        if seq % 2 == 0 && seq == seq_after {
+            return steal;
+        }
+        core::hint::spin_loop();
+    }
+}
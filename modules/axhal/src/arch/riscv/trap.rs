@@ -16,11 +16,15 @@ fn handle_breakpoint(sepc: &mut usize) {
     *sepc += 2
 }
 
-fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
+fn handle_page_fault(tf: &mut TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
     if is_user {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(stval::read());
+    #[cfg(feature = "mmio")]
+    if !is_user && super::mmio::emulate_access(tf, vaddr) {
+        return;
+    }
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
         panic!(
             "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
@@ -55,6 +59,10 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
             Trap::Interrupt(_) => {
                 handle_trap!(IRQ, scause.bits());
+                #[cfg(feature = "irq")]
+                if crate::irq::nesting_depth() == 0 {
+                    crate::trap::resched_on_irq_return();
+                }
             }
             _ => {
                 panic!("Unhandled trap {:?} @ {:#x}:\n{:#x?}", cause, tf.sepc, tf);
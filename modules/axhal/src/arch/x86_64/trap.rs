@@ -44,6 +44,10 @@ fn x86_trap_handler(tf: &mut TrapFrame) {
         LEGACY_SYSCALL_VECTOR => super::syscall::x86_syscall_handler(tf),
         IRQ_VECTOR_START..=IRQ_VECTOR_END => {
             handle_trap!(IRQ, tf.vector as _);
+            #[cfg(feature = "irq")]
+            if crate::irq::nesting_depth() == 0 {
+                crate::trap::resched_on_irq_return();
+            }
         }
         _ => {
             panic!(
@@ -0,0 +1,205 @@
+//! Kernel command line passed by the bootloader.
+//!
+//! The command line is read from the `bootargs` property of the `/chosen`
+//! node of the device tree, and is split into whitespace-separated arguments
+//! of the form `key=value` or `flag`. Values containing spaces can be quoted:
+//! `key="a b"`.
+
+use lazyinit::LazyInit;
+
+use crate::dt::{FDT_BEGIN_NODE, FDT_END_NODE, FDT_NOP, FDT_PROP, be32, cstr, fdt_at};
+
+/// The maximum length of the command line, longer ones are truncated.
+pub const MAX_BOOTARGS_LEN: usize = 1024;
+
+static BOOTARGS: LazyInit<BootArgs> = LazyInit::new();
+
+/// The parsed kernel command line.
+pub struct BootArgs {
+    buf: [u8; MAX_BOOTARGS_LEN],
+    len: usize,
+}
+
+impl BootArgs {
+    const fn empty() -> Self {
+        Self {
+            buf: [0; MAX_BOOTARGS_LEN],
+            len: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut args = Self::empty();
+        let mut len = bytes.len().min(MAX_BOOTARGS_LEN);
+        // Do not cut a multi-byte UTF-8 character in half.
+        while core::str::from_utf8(&bytes[..len]).is_err() {
+            len -= 1;
+        }
+        args.buf[..len].copy_from_slice(&bytes[..len]);
+        args.len = len;
+        args
+    }
+
+    /// Returns the raw command line.
+    pub fn as_str(&self) -> &str {
+        // Validated in `from_bytes`.
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    /// Returns an iterator over the `(key, value)` pairs, `value` is [`None`]
+    /// for a flag without `=`.
+    pub fn iter(&self) -> BootArgsIter<'_> {
+        BootArgsIter {
+            rest: self.as_str(),
+        }
+    }
+
+    /// Returns the value of the last argument named `key`.
+    ///
+    /// A flag without `=` has an empty value.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .filter(|(k, _)| *k == key)
+            .last()
+            .map(|(_, v)| v.unwrap_or(""))
+    }
+
+    /// Returns whether an argument named `key` is present.
+    pub fn contains(&self, key: &str) -> bool {
+        self.iter().any(|(k, _)| k == key)
+    }
+
+    /// Returns the value of `key` parsed as `T`, or [`None`] if it is absent
+    /// or malformed.
+    pub fn get_parsed<T: core::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Returns the value of `key` as a boolean.
+    ///
+    /// A flag without `=`, `1`, `y`, `yes`, `on` and `true` are `true`;
+    /// `0`, `n`, `no`, `off` and `false` are `false`.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            "" | "1" | "y" | "yes" | "on" | "true" => Some(true),
+            "0" | "n" | "no" | "off" | "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of `key` as an integer, accepting a `0x` prefix for
+    /// hexadecimal.
+    pub fn get_usize(&self, key: &str) -> Option<usize> {
+        let value = self.get(key)?;
+        match value.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+}
+
+/// An iterator over the arguments of [`BootArgs`].
+pub struct BootArgsIter<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for BootArgsIter<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let s = self.rest.trim_start();
+        if s.is_empty() {
+            self.rest = s;
+            return None;
+        }
+
+        // Find the end of the argument, skipping spaces inside quotes.
+        let mut in_quotes = false;
+        let end = s
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                c.is_whitespace() && !in_quotes
+            })
+            .map_or(s.len(), |(i, _)| i);
+        let (arg, rest) = s.split_at(end);
+        self.rest = rest;
+
+        Some(match arg.split_once('=') {
+            Some((key, value)) => {
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                (key, Some(value))
+            }
+            None => (arg, None),
+        })
+    }
+}
+
+/// Finds the `bootargs` property of the `/chosen` node in the flattened
+/// device tree.
+fn find_chosen_bootargs(fdt: &[u8]) -> Option<&[u8]> {
+    let struct_off = be32(fdt, 8)? as usize;
+    let strings_off = be32(fdt, 12)? as usize;
+    let mut off = struct_off;
+    let mut depth = 0;
+    let mut in_chosen = false;
+    loop {
+        let token = be32(fdt, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(fdt, off)?;
+                off = (off + name.len() + 1).next_multiple_of(4);
+                depth += 1;
+                in_chosen = depth == 2 && (name == b"chosen" || name.starts_with(b"chosen@"));
+            }
+            FDT_END_NODE => {
+                if in_chosen {
+                    return None;
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(fdt, off)? as usize;
+                let name_off = be32(fdt, off + 4)? as usize;
+                let value = fdt.get(off + 8..off + 8 + len)?;
+                off = (off + 8 + len).next_multiple_of(4);
+                if in_chosen && cstr(fdt, strings_off + name_off)? == b"bootargs" {
+                    // Drop the trailing NUL.
+                    let len = value.iter().position(|&b| b == 0).unwrap_or(len);
+                    return Some(&value[..len]);
+                }
+            }
+            FDT_NOP => {}
+            _ => return None,
+        }
+    }
+}
+
+fn read_bootargs(dtb: usize) -> Option<BootArgs> {
+    find_chosen_bootargs(fdt_at(dtb)?).map(BootArgs::from_bytes)
+}
+
+/// Reads the kernel command line from the device tree at physical address
+/// `dtb`.
+///
+/// It should be called once by the primary CPU, before the memory holding the
+/// device tree is reused. If `dtb` does not point to a valid device tree (e.g.
+/// on x86), the command line is empty.
+pub fn init_bootargs(dtb: usize) {
+    BOOTARGS.init_once(read_bootargs(dtb).unwrap_or_else(BootArgs::empty));
+}
+
+/// Returns the kernel command line.
+///
+/// It is empty if [`init_bootargs`] has not been called or the bootloader did
+/// not provide one.
+pub fn bootargs() -> &'static BootArgs {
+    static EMPTY: BootArgs = BootArgs::empty();
+    BOOTARGS.get().unwrap_or(&EMPTY)
+}
@@ -0,0 +1,134 @@
+//! Data cache maintenance, for DMA with non-coherent devices.
+//!
+//! On DMA-coherent platforms, these operations only enforce the memory
+//! ordering between the CPU and the devices.
+
+use memory_addr::VirtAddr;
+
+use crate::mem::mb;
+
+/// The data cache line size assumed by the maintenance operations.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Whether the devices snoop the CPU caches, e.g. on QEMU virt, where the
+/// maintenance operations are skipped.
+#[cfg(target_arch = "aarch64")]
+const DMA_COHERENT: bool = axconfig::devices::DMA_COHERENT;
+
+/// Writes back dirty cache lines in the given range to memory.
+///
+/// It must be called before a device reads the memory written by the CPU.
+pub fn clean_dcache_range(start: VirtAddr, size: usize) {
+    cfg_if::cfg_if! {
+        if #[cfg(platform_family = "riscv64-starfive")] {
+            // The CCACHE only supports flushing (write back and invalidate).
+            mb();
+            crate::platform::ccache::flush_range(start, size);
+        } else if #[cfg(target_arch = "aarch64")] {
+            if !DMA_COHERENT {
+                for_each_line(start, size, |line| unsafe {
+                    core::arch::asm!("dc cvac, {}", in(reg) line)
+                });
+            }
+        } else if #[cfg(all(target_arch = "riscv64", target_feature = "zicbom"))] {
+            for_each_line(start, size, |line| unsafe {
+                core::arch::asm!("cbo.clean ({})", in(reg) line)
+            });
+        } else {
+            let _ = (start, size);
+        }
+    }
+    mb();
+}
+
+/// Discards cache lines in the given range, without writing them back.
+///
+/// It must be called before the CPU reads the memory written by a device.
+/// Dirty data in the range is lost. The lines the range only partly covers
+/// are written back first, like Linux `dcache_inval_poc` does, so that the
+/// data sharing them outside the range is kept.
+pub fn invalidate_dcache_range(start: VirtAddr, size: usize) {
+    mb();
+    cfg_if::cfg_if! {
+        if #[cfg(platform_family = "riscv64-starfive")] {
+            crate::platform::ccache::flush_range(start, size);
+        } else if #[cfg(target_arch = "aarch64")] {
+            if !DMA_COHERENT {
+                for_each_line_inval(
+                    start,
+                    size,
+                    |line| unsafe { core::arch::asm!("dc civac, {}", in(reg) line) },
+                    |line| unsafe { core::arch::asm!("dc ivac, {}", in(reg) line) },
+                );
+            }
+        } else if #[cfg(all(target_arch = "riscv64", target_feature = "zicbom"))] {
+            for_each_line_inval(
+                start,
+                size,
+                |line| unsafe { core::arch::asm!("cbo.flush ({})", in(reg) line) },
+                |line| unsafe { core::arch::asm!("cbo.inval ({})", in(reg) line) },
+            );
+        } else {
+            let _ = (start, size);
+        }
+    }
+    mb();
+}
+
+/// Writes back and discards cache lines in the given range.
+pub fn flush_dcache_range(start: VirtAddr, size: usize) {
+    mb();
+    cfg_if::cfg_if! {
+        if #[cfg(platform_family = "riscv64-starfive")] {
+            crate::platform::ccache::flush_range(start, size);
+        } else if #[cfg(target_arch = "aarch64")] {
+            if !DMA_COHERENT {
+                for_each_line(start, size, |line| unsafe {
+                    core::arch::asm!("dc civac, {}", in(reg) line)
+                });
+            }
+        } else if #[cfg(all(target_arch = "riscv64", target_feature = "zicbom"))] {
+            for_each_line(start, size, |line| unsafe {
+                core::arch::asm!("cbo.flush ({})", in(reg) line)
+            });
+        } else {
+            let _ = (start, size);
+        }
+    }
+    mb();
+}
+
+#[allow(dead_code)]
+#[inline]
+fn for_each_line(start: VirtAddr, size: usize, f: impl Fn(usize)) {
+    let end = start.as_usize() + size;
+    let mut line = start.as_usize() & !(CACHE_LINE_SIZE - 1);
+    while line < end {
+        f(line);
+        line += CACHE_LINE_SIZE;
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("dsb sy")
+    };
+}
+
+/// Like [`for_each_line`], but calls `partial` on the lines that the range
+/// only partly covers, and `full` on the others.
+#[allow(dead_code)]
+#[inline]
+fn for_each_line_inval(
+    start: VirtAddr,
+    size: usize,
+    partial: impl Fn(usize),
+    full: impl Fn(usize),
+) {
+    let (range_start, range_end) = (start.as_usize(), start.as_usize() + size);
+    for_each_line(start, size, |line| {
+        if line < range_start || line + CACHE_LINE_SIZE > range_end {
+            partial(line);
+        } else {
+            full(line);
+        }
+    });
+}
@@ -0,0 +1,210 @@
+//! Devices described by the device tree passed by the bootloader.
+//!
+//! Only what the drivers need to be matched is kept: the base address (the
+//! first `reg` entry) and the `compatible` strings of each node. They are
+//! copied at boot, as the memory holding the device tree may be reused later.
+//! The `ranges` of the buses are not applied, the addresses are expected to
+//! be identity-mapped (as on QEMU `virt` and the StarFive JH7110).
+
+use lazyinit::LazyInit;
+use memory_addr::pa;
+
+use crate::mem::phys_to_virt;
+
+/// The maximum number of devices that are kept.
+pub const MAX_DT_DEVICES: usize = 128;
+/// The maximum length of the `compatible` property of a device, longer ones
+/// are truncated to their first strings.
+pub const MAX_COMPATIBLE_LEN: usize = 64;
+
+pub(crate) const FDT_MAGIC: u32 = 0xd00d_feed;
+pub(crate) const FDT_BEGIN_NODE: u32 = 1;
+pub(crate) const FDT_END_NODE: u32 = 2;
+pub(crate) const FDT_PROP: u32 = 3;
+pub(crate) const FDT_NOP: u32 = 4;
+
+/// The deepest node that is parsed.
+const MAX_DEPTH: usize = 16;
+/// The `#address-cells` and `#size-cells` when a node does not set them.
+const DEFAULT_CELLS: (usize, usize) = (2, 1);
+
+static DT_DEVICES: LazyInit<DtDevices> = LazyInit::new();
+
+#[derive(Clone, Copy)]
+struct DtDevice {
+    base: usize,
+    compatible: [u8; MAX_COMPATIBLE_LEN],
+    len: usize,
+}
+
+impl DtDevice {
+    const EMPTY: Self = Self {
+        base: 0,
+        compatible: [0; MAX_COMPATIBLE_LEN],
+        len: 0,
+    };
+
+    fn new(base: usize, compatible: &[u8]) -> Self {
+        let mut len = compatible.len().min(MAX_COMPATIBLE_LEN);
+        // Only keep whole strings.
+        if len < compatible.len() {
+            len = compatible[..len].iter().rposition(|&b| b == 0).unwrap_or(0);
+        }
+        let mut dev = Self::EMPTY;
+        dev.base = base;
+        dev.compatible[..len].copy_from_slice(&compatible[..len]);
+        dev.len = len;
+        dev
+    }
+
+    fn compatible(&self) -> impl Iterator<Item = &str> {
+        self.compatible[..self.len]
+            .split(|&b| b == 0)
+            .filter_map(|s| core::str::from_utf8(s).ok())
+            .filter(|s| !s.is_empty())
+    }
+}
+
+struct DtDevices {
+    devices: [DtDevice; MAX_DT_DEVICES],
+    len: usize,
+}
+
+impl DtDevices {
+    const fn empty() -> Self {
+        Self {
+            devices: [DtDevice::EMPTY; MAX_DT_DEVICES],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, base: usize, compatible: &[u8]) {
+        if self.len == MAX_DT_DEVICES {
+            warn!("too many device tree devices, {:#x} ignored", base);
+            return;
+        }
+        self.devices[self.len] = DtDevice::new(base, compatible);
+        self.len += 1;
+    }
+}
+
+pub(crate) fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn cstr(bytes: &[u8], offset: usize) -> Option<&[u8]> {
+    let s = bytes.get(offset..)?;
+    let len = s.iter().position(|&b| b == 0)?;
+    Some(&s[..len])
+}
+
+/// Returns the flattened device tree at physical address `dtb`, if it is a
+/// valid one.
+pub(crate) fn fdt_at(dtb: usize) -> Option<&'static [u8]> {
+    if dtb == 0 {
+        return None;
+    }
+    let ptr = phys_to_virt(pa!(dtb)).as_ptr();
+    let header = unsafe { core::slice::from_raw_parts(ptr, 8) };
+    if be32(header, 0)? != FDT_MAGIC {
+        return None;
+    }
+    let total_size = be32(header, 4)? as usize;
+    Some(unsafe { core::slice::from_raw_parts(ptr, total_size) })
+}
+
+/// Reads the first address of a `reg` property with `cells` address cells.
+fn reg_base(reg: &[u8], cells: usize) -> Option<usize> {
+    match cells {
+        1 => Some(be32(reg, 0)? as usize),
+        2 => Some((((be32(reg, 0)? as u64) << 32) | be32(reg, 4)? as u64) as usize),
+        _ => None,
+    }
+}
+
+/// Collects the nodes with both a `reg` and a `compatible` property.
+fn collect_devices(fdt: &[u8], devices: &mut DtDevices) -> Option<()> {
+    let struct_off = be32(fdt, 8)? as usize;
+    let strings_off = be32(fdt, 12)? as usize;
+    // Per depth: the cells that the node sets for its children, and its own
+    // `reg` and `compatible`.
+    let mut cells = [DEFAULT_CELLS; MAX_DEPTH];
+    let mut regs: [Option<&[u8]>; MAX_DEPTH] = [None; MAX_DEPTH];
+    let mut compats: [Option<&[u8]>; MAX_DEPTH] = [None; MAX_DEPTH];
+    let mut off = struct_off;
+    let mut depth = 0;
+    loop {
+        let token = be32(fdt, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(fdt, off)?;
+                off = (off + name.len() + 1).next_multiple_of(4);
+                depth += 1;
+                if depth == MAX_DEPTH {
+                    return None;
+                }
+                cells[depth] = DEFAULT_CELLS;
+                regs[depth] = None;
+                compats[depth] = None;
+            }
+            FDT_END_NODE => {
+                if depth == 0 {
+                    return None;
+                }
+                if let (Some(reg), Some(compat)) = (regs[depth], compats[depth])
+                    && let Some(base) = reg_base(reg, cells[depth - 1].0)
+                {
+                    devices.push(base, compat);
+                }
+                depth -= 1;
+                if depth == 0 {
+                    return Some(());
+                }
+            }
+            FDT_PROP => {
+                let len = be32(fdt, off)? as usize;
+                let name_off = be32(fdt, off + 4)? as usize;
+                let value = fdt.get(off + 8..off + 8 + len)?;
+                off = (off + 8 + len).next_multiple_of(4);
+                match cstr(fdt, strings_off + name_off)? {
+                    b"#address-cells" => cells[depth].0 = be32(value, 0)? as usize,
+                    b"#size-cells" => cells[depth].1 = be32(value, 0)? as usize,
+                    b"reg" => regs[depth] = Some(value),
+                    b"compatible" => compats[depth] = Some(value),
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            _ => return None,
+        }
+    }
+}
+
+/// Records the devices of the device tree at physical address `dtb`.
+///
+/// It should be called once by the primary CPU, before the memory holding the
+/// device tree is reused. If `dtb` does not point to a valid device tree (e.g.
+/// on x86), no device is recorded.
+pub fn init_dt_devices(dtb: usize) {
+    let mut devices = DtDevices::empty();
+    if let Some(fdt) = fdt_at(dtb)
+        && collect_devices(fdt, &mut devices).is_none()
+    {
+        warn!("malformed device tree, {} devices found", devices.len);
+    }
+    DT_DEVICES.init_once(devices);
+}
+
+/// Returns the `compatible` strings of the device tree node whose registers
+/// start at `base`, most specific first.
+///
+/// It returns [`None`] if there is no such node, e.g. without a device tree.
+pub fn dt_compatible(base: usize) -> Option<impl Iterator<Item = &'static str>> {
+    let devices = DT_DEVICES.get()?;
+    devices.devices[..devices.len]
+        .iter()
+        .find(|dev| dev.base == base)
+        .map(DtDevice::compatible)
+}
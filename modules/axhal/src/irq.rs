@@ -1,22 +1,271 @@
 //! Interrupt management.
 
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
 use handler_table::HandlerTable;
+use kspin::SpinNoIrq;
 
 use crate::platform::irq::{MAX_IRQ_COUNT, dispatch_irq};
 use crate::trap::{IRQ, register_trap_handler};
 
 pub use crate::platform::irq::{register_handler, set_enable};
 
+#[cfg(any(
+    platform_family = "riscv64-qemu-virt",
+    platform_family = "riscv64-starfive"
+))]
+pub use crate::platform::irq::set_priority;
+
 /// The type if an IRQ handler.
 pub type IrqHandler = handler_table::Handler;
 
+/// The lowest priority of an IRQ, that of the IRQs not given one. Only
+/// the platforms with IRQ priorities (the PLIC of RISC-V) use them.
+pub const LOWEST_IRQ_PRIORITY: u32 = 1;
+
+/// The highest priority of an IRQ.
+pub const HIGHEST_IRQ_PRIORITY: u32 = 7;
+
+/// Registers an IRQ handler like [`register_handler`], with the given
+/// priority, from [`LOWEST_IRQ_PRIORITY`] to [`HIGHEST_IRQ_PRIORITY`].
+///
+/// While its handler runs, only the IRQs of a strictly higher priority and
+/// the timer interrupt may preempt it, up to [`MAX_NESTING_DEPTH`]. The
+/// priority is ignored on the platforms without IRQ priorities, where
+/// handlers are not preempted.
+pub fn register_handler_with_priority(irq_num: usize, priority: u32, handler: IrqHandler) -> bool {
+    #[cfg(any(
+        platform_family = "riscv64-qemu-virt",
+        platform_family = "riscv64-starfive"
+    ))]
+    set_priority(
+        irq_num,
+        priority.clamp(LOWEST_IRQ_PRIORITY, HIGHEST_IRQ_PRIORITY),
+    );
+    #[cfg(not(any(
+        platform_family = "riscv64-qemu-virt",
+        platform_family = "riscv64-starfive"
+    )))]
+    let _ = priority;
+    register_handler(irq_num, handler)
+}
+
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The maximum depth of nested IRQ handling on a single CPU.
+///
+/// Once this depth is reached, interrupts stay disabled while the handler
+/// runs, so the trap stack cannot grow without bound.
+pub const MAX_NESTING_DEPTH: usize = 4;
+
+#[percpu::def_percpu]
+static IRQ_NESTING_DEPTH: usize = 0;
+
+/// Returns how many nested IRQ handlers are currently running on this CPU.
+///
+/// It returns `0` when called outside of any IRQ handler.
+pub fn nesting_depth() -> usize {
+    // Safety: the value is only modified by IRQ handlers on the same CPU, and
+    // always restored before they return.
+    unsafe { IRQ_NESTING_DEPTH.read_current_raw() }
+}
+
+#[percpu::def_percpu]
+static IRQ_HANDLERS: usize = 0;
+
+/// Returns whether the current CPU is running an IRQ handler.
+pub fn in_irq() -> bool {
+    // Safety: like `IRQ_NESTING_DEPTH`.
+    unsafe { IRQ_HANDLERS.read_current_raw() != 0 }
+}
+
+/// A guard that marks one more level of nested IRQ handling on this CPU.
+///
+/// While the guard is alive, the platform may re-enable interrupts so that
+/// higher-priority IRQs can preempt the current handler. The depth is
+/// restored when the guard is dropped.
+#[allow(dead_code)]
+pub(crate) struct NestingGuard(usize);
+
+impl NestingGuard {
+    /// Enters a new nesting level, or returns [`None`] if the maximum depth
+    /// ([`MAX_NESTING_DEPTH`]) has been reached.
+    #[allow(dead_code)]
+    pub fn try_enter() -> Option<Self> {
+        let depth = nesting_depth();
+        if depth >= MAX_NESTING_DEPTH {
+            return None;
+        }
+        unsafe { IRQ_NESTING_DEPTH.write_current_raw(depth + 1) };
+        Some(Self(depth))
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        unsafe { IRQ_NESTING_DEPTH.write_current_raw(self.0) };
+    }
+}
+
+/// Per-IRQ state for [`wait_for_irq`].
+struct IrqEventSlot {
+    /// Whether the IRQ is waited by [`IrqFuture`]s.
+    armed: AtomicBool,
+    /// Whether the IRQ is masked until it is awaited again.
+    masked: AtomicBool,
+    /// Number of IRQs that occurred but have not been consumed yet.
+    pending: AtomicUsize,
+    waker: SpinNoIrq<Option<Waker>>,
+}
+
+impl IrqEventSlot {
+    const fn new() -> Self {
+        Self {
+            armed: AtomicBool::new(false),
+            masked: AtomicBool::new(false),
+            pending: AtomicUsize::new(0),
+            waker: SpinNoIrq::new(None),
+        }
+    }
+
+    /// Records one occurrence of the IRQ and wakes its waiter, returns
+    /// `false` if not armed. The IRQ must then be masked by the caller, and
+    /// is unmasked once awaited again, see [`unmask`](Self::unmask).
+    fn signal(&self) -> bool {
+        if !self.armed.load(Ordering::Acquire) {
+            return false;
+        }
+        self.masked.store(true, Ordering::Release);
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        // Not woken under the lock, the waker may take it, e.g. to poll.
+        let waker = self.waker.lock().take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+
+    /// Returns whether the IRQ was masked by [`signal`](Self::signal), and
+    /// must be unmasked by the caller.
+    fn unmask(&self) -> bool {
+        self.masked.swap(false, Ordering::AcqRel)
+    }
+
+    /// Consumes one pending occurrence of the IRQ, if any.
+    fn try_consume(&self) -> bool {
+        self.pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Consumes one pending occurrence of the IRQ, or registers `waker` to
+    /// be woken by the next one. Returns whether one was consumed, and
+    /// whether the IRQ must be unmasked meanwhile, as it is awaited again.
+    fn poll(&self, waker: &Waker) -> (bool, bool) {
+        if self.try_consume() {
+            return (true, false);
+        }
+        *self.waker.lock() = Some(waker.clone());
+        let unmask = self.unmask();
+        // Check again, the IRQ may have occurred before the waker was stored.
+        if self.try_consume() {
+            self.waker.lock().take();
+            return (true, unmask);
+        }
+        (false, unmask)
+    }
+}
+
+static IRQ_EVENTS: [IrqEventSlot; MAX_IRQ_COUNT] = [const { IrqEventSlot::new() }; MAX_IRQ_COUNT];
+
+/// A future that resolves when the given IRQ occurs.
+///
+/// Created by [`wait_for_irq`].
+pub struct IrqFuture {
+    irq_num: usize,
+}
+
+impl Future for IrqFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let (ready, unmask) = IRQ_EVENTS[self.irq_num].poll(cx.waker());
+        if unmask {
+            set_enable(self.irq_num, true);
+        }
+        if ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Returns a future that resolves when the given IRQ occurs.
+///
+/// The IRQ is enabled on the first call. Occurrences are counted from then
+/// on, so an IRQ that fires while no one is awaiting is not lost: the next
+/// awaited future resolves immediately. Each occurrence resolves exactly one
+/// future, and only one task should wait for a given IRQ at a time.
+///
+/// Like a oneshot IRQ, it is masked when it occurs, and only unmasked when
+/// the task awaits it again, with no occurrence pending. The task clears the
+/// interrupt condition of the device in between, a level-triggered IRQ does
+/// not fire again meanwhile.
+///
+/// No handler needs to be registered for the IRQ. If a handler is also
+/// registered, it runs before the future is woken, and is not called again
+/// until the IRQ is awaited again.
+///
+/// # Panics
+///
+/// Panics if `irq_num` is not less than the maximum number of IRQs.
+pub fn wait_for_irq(irq_num: usize) -> IrqFuture {
+    assert!(irq_num < MAX_IRQ_COUNT, "invalid IRQ number {}", irq_num);
+    if !IRQ_EVENTS[irq_num].armed.swap(true, Ordering::AcqRel) {
+        set_enable(irq_num, true);
+    }
+    IrqFuture { irq_num }
+}
+
+/// The number of occurrences of each IRQ.
+static IRQ_COUNTS: [AtomicU64; MAX_IRQ_COUNT] = [const { AtomicU64::new(0) }; MAX_IRQ_COUNT];
+
+/// Returns the number of times the IRQ `irq_num` occurred since boot, on all
+/// the CPUs.
+pub fn irq_count(irq_num: usize) -> u64 {
+    IRQ_COUNTS
+        .get(irq_num)
+        .map_or(0, |count| count.load(Ordering::Relaxed))
+}
+
+/// Returns the IRQs that occurred since boot, with their number of
+/// occurrences, e.g. to export them as metrics.
+pub fn irq_counts() -> impl Iterator<Item = (usize, u64)> {
+    IRQ_COUNTS
+        .iter()
+        .map(|count| count.load(Ordering::Relaxed))
+        .enumerate()
+        .filter(|&(_, count)| count != 0)
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
-    if !IRQ_HANDLER_TABLE.handle(irq_num) {
+    if let Some(count) = IRQ_COUNTS.get(irq_num) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+    let handled = IRQ_HANDLER_TABLE.handle(irq_num);
+    let waited = irq_num < MAX_IRQ_COUNT && IRQ_EVENTS[irq_num].signal();
+    if waited {
+        // Until its waiter has cleared the interrupt condition.
+        set_enable(irq_num, false);
+    }
+    if !handled && !waited {
         warn!("Unhandled IRQ {}", irq_num);
     }
 }
@@ -38,7 +287,72 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
 #[register_trap_handler(IRQ)]
 fn handler_irq(irq_num: usize) -> bool {
     let guard = kernel_guard::NoPreempt::new();
+    let handlers = unsafe { IRQ_HANDLERS.read_current_raw() };
+    unsafe { IRQ_HANDLERS.write_current_raw(handlers + 1) };
     dispatch_irq(irq_num);
+    unsafe { IRQ_HANDLERS.write_current_raw(handlers) };
     drop(guard); // rescheduling may occur when preemption is re-enabled.
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::IrqEventSlot;
+
+    static SLOT: IrqEventSlot = IrqEventSlot::new();
+    /// The pending count seen by each wake, `usize::MAX` if the waker lock
+    /// was held meanwhile.
+    static WOKEN_WITH: AtomicUsize = AtomicUsize::new(0);
+
+    fn test_waker() -> Waker {
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(
+            |_| RawWaker::new(core::ptr::null(), &VTABLE),
+            wake,
+            wake,
+            |_| {},
+        );
+        fn wake(_: *const ()) {
+            let seen = match SLOT.waker.try_lock() {
+                Some(_) => SLOT.pending.load(Ordering::Acquire),
+                None => usize::MAX,
+            };
+            WOKEN_WITH.store(seen, Ordering::Release);
+        }
+        unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) }
+    }
+
+    #[test]
+    fn test_irq_event_slot() {
+        let slot = IrqEventSlot::new();
+        let waker = Waker::noop();
+        // Not armed, the IRQ is neither counted nor masked.
+        assert!(!slot.signal());
+        assert_eq!(slot.poll(waker), (false, false));
+
+        slot.armed.store(true, Ordering::Release);
+        assert!(slot.signal());
+        assert!(slot.signal());
+        // Each occurrence resolves one wait, the IRQ stays masked while
+        // occurrences are pending.
+        assert_eq!(slot.poll(waker), (true, false));
+        assert_eq!(slot.poll(waker), (true, false));
+        // Awaited again with none pending, it is unmasked once.
+        assert_eq!(slot.poll(waker), (false, true));
+        assert_eq!(slot.poll(waker), (false, false));
+    }
+
+    #[test]
+    fn test_irq_event_wake_order() {
+        SLOT.armed.store(true, Ordering::Release);
+        assert_eq!(SLOT.poll(&test_waker()), (false, false));
+        assert!(SLOT.signal());
+        // Woken once the occurrence is counted, and the lock released.
+        assert_eq!(WOKEN_WITH.load(Ordering::Acquire), 1);
+        assert!(SLOT.waker.lock().is_none());
+        assert_eq!(SLOT.poll(&test_waker()), (true, false));
+        assert_eq!(SLOT.poll(&test_waker()), (false, true));
+    }
+}
@@ -20,6 +20,9 @@
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
+//! - `mmio`: Enable the MMIO device registry.
+//! - `busy-wait-audit`: Report the long busy waits with interrupts enabled,
+//!   see [`time::set_busy_wait_warn_threshold`].
 //!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -33,18 +36,26 @@
 #[macro_use]
 extern crate log;
 
+#[cfg(test)]
+extern crate std;
+
 #[allow(unused_imports)]
 #[macro_use]
 extern crate memory_addr;
 
+mod bootargs;
+mod dt;
 mod platform;
+mod shutdown;
 
 #[macro_use]
 pub mod trap;
 
 pub mod arch;
+pub mod cache;
 pub mod cpu;
 pub mod mem;
+pub mod perf;
 pub mod time;
 
 #[cfg(feature = "tls")]
@@ -53,6 +64,9 @@ pub mod tls;
 #[cfg(feature = "irq")]
 pub mod irq;
 
+#[cfg(feature = "mmio")]
+pub mod mmio;
+
 #[cfg(feature = "paging")]
 pub mod paging;
 
@@ -64,6 +78,79 @@ pub mod console {
 /// Miscellaneous operation, e.g. terminate the system.
 pub mod misc {
     pub use super::platform::misc::*;
+    pub use super::shutdown::{
+        MAX_SHUTDOWN_HOOKS, on_panic_shutdown, on_shutdown, run_shutdown_hooks, set_panicking,
+    };
+
+    /// Shutdown the whole system, including all CPUs.
+    ///
+    /// All hooks registered by [`on_shutdown`] are executed first.
+    pub fn terminate() -> ! {
+        run_shutdown_hooks();
+        super::platform::misc::terminate()
+    }
+
+    /// Shutdown the whole system, reporting `code` as the exit status of the
+    /// machine, 0 meaning success.
+    ///
+    /// The status is only reported by QEMU, with the `sifive_test` device on
+    /// RISC-V or the `isa-debug-exit` device on x86_64. Elsewhere, it is the
+    /// same as [`terminate`]. All hooks registered by [`on_shutdown`] are
+    /// executed first.
+    pub fn exit(code: u32) -> ! {
+        run_shutdown_hooks();
+        platform_exit(code)
+    }
+
+    /// Why the system is rebooted, see [`reboot`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ResetReason {
+        /// Requested, e.g. by the user.
+        Requested,
+        /// A failure the system cannot recover from without a reboot.
+        SystemFailure,
+    }
+
+    /// Reboots the whole system, including all CPUs.
+    ///
+    /// The `reason` is told to the firmware with the SBI on RISC-V, and only
+    /// logged elsewhere. Rebooting is supported on RISC-V, x86_64, and the
+    /// QEMU virt machines of aarch64 and LoongArch, elsewhere it is the same
+    /// as [`terminate`]. All hooks registered by [`on_shutdown`] are executed
+    /// first.
+    pub fn reboot(reason: ResetReason) -> ! {
+        run_shutdown_hooks();
+        platform_reboot(reason)
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(any(
+            target_arch = "riscv64",
+            all(target_arch = "x86_64", platform_family = "x86-pc"),
+            all(target_arch = "aarch64", platform_family = "aarch64-qemu-virt"),
+            all(target_arch = "loongarch64", platform_family = "loongarch64-qemu-virt")
+        ))] {
+            use super::platform::misc::reboot as platform_reboot;
+        } else {
+            fn platform_reboot(reason: ResetReason) -> ! {
+                warn!("reboot ({:?}) not supported, shutting down", reason);
+                super::platform::misc::terminate()
+            }
+        }
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(any(
+            all(target_arch = "riscv64", platform_family = "riscv64-qemu-virt"),
+            all(target_arch = "x86_64", platform_family = "x86-pc")
+        ))] {
+            use super::platform::misc::exit as platform_exit;
+        } else {
+            fn platform_exit(_code: u32) -> ! {
+                super::platform::misc::terminate()
+            }
+        }
+    }
 }
 
 /// Multi-core operations.
@@ -72,6 +159,8 @@ pub mod mp {
     pub use super::platform::mp::*;
 }
 
+pub use self::bootargs::{BootArgs, BootArgsIter, MAX_BOOTARGS_LEN, bootargs, init_bootargs};
+pub use self::dt::{MAX_COMPATIBLE_LEN, MAX_DT_DEVICES, dt_compatible, init_dt_devices};
 pub use self::platform::platform_init;
 
 #[cfg(feature = "smp")]
@@ -68,9 +68,114 @@ pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
+macro_rules! def_barrier {
+    ($(#[$attr:meta])* $name:ident {
+        riscv64: $riscv:literal,
+        aarch64: $aarch64:literal,
+        x86_64: $x86:literal,
+        loongarch64: $loongarch:literal $(,)?
+    }) => {
+        $(#[$attr])*
+        #[inline(always)]
+        pub fn $name() {
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "riscv64")] {
+                    unsafe { core::arch::asm!($riscv, options(nostack, preserves_flags)) }
+                } else if #[cfg(target_arch = "aarch64")] {
+                    unsafe { core::arch::asm!($aarch64, options(nostack, preserves_flags)) }
+                } else if #[cfg(target_arch = "x86_64")] {
+                    unsafe { core::arch::asm!($x86, options(nostack, preserves_flags)) }
+                } else if #[cfg(target_arch = "loongarch64")] {
+                    unsafe { core::arch::asm!($loongarch, options(nostack, preserves_flags)) }
+                } else {
+                    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst)
+                }
+            }
+        }
+    };
+}
+
+def_barrier! {
+    /// Full memory barrier, ordering all prior memory and MMIO accesses
+    /// before all subsequent ones.
+    mb {
+        riscv64: "fence iorw, iorw",
+        aarch64: "dsb sy",
+        x86_64: "mfence",
+        loongarch64: "dbar 0",
+    }
+}
+
+def_barrier! {
+    /// Read memory barrier, ordering all prior reads (including MMIO reads)
+    /// before all subsequent reads.
+    rmb {
+        riscv64: "fence ir, ir",
+        aarch64: "dsb ld",
+        x86_64: "lfence",
+        loongarch64: "dbar 0",
+    }
+}
+
+def_barrier! {
+    /// Write memory barrier, ordering all prior writes (including MMIO
+    /// writes) before all subsequent writes.
+    wmb {
+        riscv64: "fence ow, ow",
+        aarch64: "dsb st",
+        x86_64: "sfence",
+        loongarch64: "dbar 0",
+    }
+}
+
+def_barrier! {
+    /// Orders reads of DMA-coherent memory, e.g. reading a descriptor's
+    /// ownership bit before reading the rest of the descriptor.
+    dma_rmb {
+        riscv64: "fence r, r",
+        aarch64: "dmb oshld",
+        x86_64: "",
+        loongarch64: "dbar 0",
+    }
+}
+
+def_barrier! {
+    /// Orders writes to DMA-coherent memory, e.g. filling a descriptor before
+    /// handing its ownership bit to the device.
+    dma_wmb {
+        riscv64: "fence w, w",
+        aarch64: "dmb oshst",
+        x86_64: "",
+        loongarch64: "dbar 0",
+    }
+}
+
+/// The size of the region kept across warm reboots for the log, in bytes.
+#[cfg(feature = "pstore")]
+pub const PSTORE_SIZE: usize = 0x10000;
+
+/// Returns the region kept across warm reboots for the log, at the end of the
+/// physical memory, out of the free memory.
+#[cfg(feature = "pstore")]
+pub fn pstore_region() -> (PhysAddr, usize) {
+    let end = pa!(PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE).align_down_4k();
+    (end - PSTORE_SIZE, PSTORE_SIZE)
+}
+
 /// Returns an iterator over all physical memory regions.
 pub fn memory_regions() -> impl Iterator<Item = MemRegion> {
-    kernel_image_regions().chain(crate::platform::mem::platform_regions())
+    let regions = kernel_image_regions().chain(crate::platform::mem::platform_regions());
+    #[cfg(feature = "pstore")]
+    let regions = regions.chain(core::iter::once({
+        let (paddr, size) = pstore_region();
+        MemRegion {
+            paddr,
+            size,
+            flags: MemRegionFlags::RESERVED | MemRegionFlags::READ | MemRegionFlags::WRITE,
+            name: "pstore",
+        }
+    }));
+    regions
 }
 
 /// Returns the memory regions of the kernel image (code and data sections).
@@ -128,7 +233,10 @@ pub(crate) fn default_mmio_regions() -> impl Iterator<Item = MemRegion> {
 #[allow(dead_code)]
 pub(crate) fn default_free_regions() -> impl Iterator<Item = MemRegion> {
     let start = virt_to_phys((_ekernel as usize).into()).align_up_4k();
+    #[cfg(not(feature = "pstore"))]
     let end = pa!(PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE).align_down_4k();
+    #[cfg(feature = "pstore")]
+    let end = pstore_region().0;
     core::iter::once(MemRegion {
         paddr: start,
         size: end.as_usize() - start.as_usize(),
@@ -0,0 +1,115 @@
+//! Registry of memory-mapped I/O devices.
+//!
+//! Drivers register the physical address range (and optionally the IRQ) of
+//! their devices here, so that other subsystems can find which device owns
+//! an address or an IRQ, e.g. for debugging unexpected accesses.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use kspin::SpinNoIrq;
+use memory_addr::PhysAddr;
+
+/// A registered MMIO device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmioDevice {
+    /// The device name, used for identification.
+    pub name: &'static str,
+    /// The start physical address of the register region.
+    pub paddr: PhysAddr,
+    /// The size in bytes of the register region.
+    pub size: usize,
+    /// The IRQ number of the device, if any.
+    pub irq: Option<usize>,
+}
+
+impl MmioDevice {
+    /// Returns the end physical address (exclusive) of the register region.
+    pub fn end(&self) -> PhysAddr {
+        self.paddr + self.size
+    }
+
+    /// Whether the register region contains the given physical address.
+    pub fn contains(&self, paddr: PhysAddr) -> bool {
+        self.paddr <= paddr && paddr < self.end()
+    }
+
+    /// Whether the register regions of the two devices overlap.
+    pub fn overlaps(&self, other: &MmioDevice) -> bool {
+        self.paddr < other.end() && other.paddr < self.end()
+    }
+}
+
+/// Errors returned by [`register_device`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmioRegisterError {
+    /// The region is empty.
+    EmptyRegion,
+    /// The region overlaps with an already registered device.
+    Overlap(MmioDevice),
+}
+
+/// Registered devices, sorted by start address.
+static DEVICES: SpinNoIrq<Vec<MmioDevice>> = SpinNoIrq::new(Vec::new());
+
+/// Registers an MMIO device.
+///
+/// It fails if the register region is empty or overlaps with any registered
+/// device.
+pub fn register_device(dev: MmioDevice) -> Result<(), MmioRegisterError> {
+    if dev.size == 0 {
+        return Err(MmioRegisterError::EmptyRegion);
+    }
+    let mut devices = DEVICES.lock();
+    let idx = devices.partition_point(|d| d.paddr < dev.paddr);
+    // Only the neighbours can overlap since the regions are disjoint.
+    for neighbour in devices[idx.saturating_sub(1)..].iter().take(2) {
+        if neighbour.overlaps(&dev) {
+            warn!("MMIO device {:?} overlaps with {:?}", dev, neighbour);
+            return Err(MmioRegisterError::Overlap(*neighbour));
+        }
+    }
+    debug!(
+        "register MMIO device {}: [{:#x}, {:#x})",
+        dev.name,
+        dev.paddr,
+        dev.end()
+    );
+    devices.insert(idx, dev);
+    Ok(())
+}
+
+/// Unregisters the MMIO device that starts at the given physical address.
+///
+/// Returns the removed device, or [`None`] if there is no such device.
+pub fn unregister_device(paddr: PhysAddr) -> Option<MmioDevice> {
+    let mut devices = DEVICES.lock();
+    let idx = devices.iter().position(|d| d.paddr == paddr)?;
+    Some(devices.remove(idx))
+}
+
+/// Finds the device whose register region contains the given physical
+/// address.
+pub fn find_device_by_addr(paddr: PhysAddr) -> Option<MmioDevice> {
+    let devices = DEVICES.lock();
+    let idx = devices.partition_point(|d| d.paddr <= paddr);
+    devices[..idx].last().filter(|d| d.contains(paddr)).copied()
+}
+
+/// Finds the device that uses the given IRQ.
+pub fn find_device_by_irq(irq_num: usize) -> Option<MmioDevice> {
+    DEVICES
+        .lock()
+        .iter()
+        .find(|d| d.irq == Some(irq_num))
+        .copied()
+}
+
+/// Calls `f` on each registered device, in order of start address.
+///
+/// The registry is locked during the iteration, so `f` must not register or
+/// unregister devices.
+pub fn for_each_device(mut f: impl FnMut(&MmioDevice)) {
+    DEVICES.lock().iter().for_each(|d| f(d));
+}
@@ -0,0 +1,130 @@
+//! Hardware performance counters of the current CPU.
+//!
+//! Counters are free-running, so a measurement is the difference of two
+//! [`PerfCounters`] snapshots taken on the same CPU. A counter that is not
+//! supported by the platform always reads `0`.
+
+use core::ops::Sub;
+
+/// A snapshot of the performance counters of the current CPU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PerfCounters {
+    /// CPU cycles.
+    pub cycles: u64,
+    /// Retired instructions.
+    pub instructions: u64,
+    /// Cache misses.
+    pub cache_misses: u64,
+}
+
+impl PerfCounters {
+    /// Reads the counters of the current CPU.
+    pub fn read() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "riscv64")] {
+                Self {
+                    cycles: crate::arch::read_cycles(),
+                    instructions: crate::arch::read_instructions(),
+                    cache_misses: crate::arch::read_cache_misses().unwrap_or(0),
+                }
+            } else if #[cfg(target_arch = "x86_64")] {
+                Self {
+                    cycles: unsafe { core::arch::x86_64::_rdtsc() },
+                    ..Default::default()
+                }
+            } else {
+                Self::default()
+            }
+        }
+    }
+}
+
+impl Sub for PerfCounters {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            cycles: self.cycles.wrapping_sub(rhs.cycles),
+            instructions: self.instructions.wrapping_sub(rhs.instructions),
+            cache_misses: self.cache_misses.wrapping_sub(rhs.cache_misses),
+        }
+    }
+}
+
+/// Returns whether the current CPU can count cache misses.
+pub fn cache_misses_available() -> bool {
+    #[cfg(target_arch = "riscv64")]
+    {
+        crate::arch::read_cache_misses().is_some()
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    false
+}
+
+/// A measurement in progress, started by [`PerfMeasurement::start`].
+///
+/// The measured code should not migrate to another CPU.
+pub struct PerfMeasurement {
+    start: PerfCounters,
+}
+
+impl PerfMeasurement {
+    /// Starts a measurement.
+    pub fn start() -> Self {
+        Self {
+            start: PerfCounters::read(),
+        }
+    }
+
+    /// Returns the counters elapsed since the measurement started.
+    pub fn elapsed(&self) -> PerfCounters {
+        PerfCounters::read() - self.start
+    }
+
+    /// Stops the measurement, returns the elapsed counters.
+    pub fn stop(self) -> PerfCounters {
+        self.elapsed()
+    }
+}
+
+/// Runs `f` and returns its result together with the counters it consumed.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, PerfCounters) {
+    let m = PerfMeasurement::start();
+    let ret = f();
+    (ret, m.stop())
+}
+
+/// A measurement that logs the elapsed counters when dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// {
+///     let _perf = axhal::perf::ScopedPerf::new("poll");
+///     poll_interfaces();
+/// } // logs "perf[poll]: cycles=.. instructions=.. cache_misses=.."
+/// ```
+pub struct ScopedPerf {
+    name: &'static str,
+    measurement: PerfMeasurement,
+}
+
+impl ScopedPerf {
+    /// Starts a measurement named `name`.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            measurement: PerfMeasurement::start(),
+        }
+    }
+}
+
+impl Drop for ScopedPerf {
+    fn drop(&mut self) {
+        let c = self.measurement.elapsed();
+        info!(
+            "perf[{}]: cycles={} instructions={} cache_misses={}",
+            self.name, c.cycles, c.instructions, c.cache_misses
+        );
+    }
+}
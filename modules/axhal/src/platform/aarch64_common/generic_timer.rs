@@ -1,6 +1,6 @@
 #![allow(unused_imports)]
 
-use aarch64_cpu::registers::{CNTFRQ_EL0, CNTP_CTL_EL0, CNTP_TVAL_EL0, CNTPCT_EL0};
+use aarch64_cpu::registers::{CNTFRQ_EL0, CNTP_CTL_EL0, CNTP_CVAL_EL0, CNTP_TVAL_EL0, CNTPCT_EL0};
 use int_ratio::Ratio;
 use tock_registers::interfaces::{Readable, Writeable};
 
@@ -37,15 +37,10 @@ pub fn epochoffset_nanos() -> u64 {
 /// A timer interrupt will be triggered at the specified monotonic time deadline (in nanoseconds).
 #[cfg(feature = "irq")]
 pub fn set_oneshot_timer(deadline_ns: u64) {
-    let cnptct = CNTPCT_EL0.get();
-    let cnptct_deadline = nanos_to_ticks(deadline_ns);
-    if cnptct < cnptct_deadline {
-        let interval = cnptct_deadline - cnptct;
-        debug_assert!(interval <= u32::MAX as u64);
-        CNTP_TVAL_EL0.set(interval);
-    } else {
-        CNTP_TVAL_EL0.set(0);
-    }
+    // Program the 64-bit absolute comparator instead of the 32-bit `TVAL`, so
+    // far-future deadlines (e.g. long async sleeps) do not overflow. A past
+    // deadline fires immediately.
+    CNTP_CVAL_EL0.set(nanos_to_ticks(deadline_ns));
 }
 
 /// Early stage initialization: stores the timer frequency.
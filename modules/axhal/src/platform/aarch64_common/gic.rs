@@ -51,6 +51,16 @@ pub(crate) fn init_primary() {
     info!("Initialize GICv2...");
     GICD.lock().init();
     GICC.init();
+
+    #[cfg(feature = "mmio")]
+    for (name, paddr, size) in [("gicd", GICD_BASE, 0x1000), ("gicc", GICC_BASE, 0x2000)] {
+        let _ = crate::mmio::register_device(crate::mmio::MmioDevice {
+            name,
+            paddr,
+            size,
+            irq: None,
+        });
+    }
 }
 
 /// Initializes GICC on secondary CPUs.
@@ -103,6 +103,17 @@ pub fn system_off() -> ! {
     }
 }
 
+/// Reboots the whole system, including all CPUs. PSCI has no reason, the
+/// `reason` is only logged.
+pub fn system_reset(reason: crate::misc::ResetReason) -> ! {
+    info!("Rebooting ({:?})...", reason);
+    psci_call(PSCI_0_2_FN_SYSTEM_RESET, 0, 0, 0).ok();
+    warn!("It should reboot!");
+    loop {
+        crate::arch::halt();
+    }
+}
+
 /// Power up a core. This call is used to power up cores that either:
 ///
 /// * Have not yet been booted into the calling supervisory software.
@@ -18,6 +18,7 @@ pub mod time {
 
 pub mod misc {
     pub use crate::platform::aarch64_common::psci::system_off as terminate;
+    pub use crate::platform::aarch64_common::psci::system_reset as reboot;
 }
 
 unsafe extern "C" {
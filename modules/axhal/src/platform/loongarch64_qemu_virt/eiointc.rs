@@ -0,0 +1,114 @@
+//! Extended I/O interrupt controller (EIOINTC) and the PCH-PIC in front of it.
+//!
+//! Device interrupts go through the PCH-PIC, which forwards input `n` to the
+//! EIOINTC vector `n`. All vectors are routed to the `HWI0` line of CPU 0:
+//! there is no IRQ affinity yet, the device IRQ handlers and the wakes of
+//! `wait_for_irq` all run on the boot CPU, the other CPUs only take their
+//! timer IRQs.
+//!
+//! Reference: <https://loongson.github.io/LoongArch-Documentation/Loongson-3A5000-usermanual-EN.html>
+
+use core::arch::asm;
+
+use loongArch64::register::ecfg::{self, LineBasedInterrupt};
+use memory_addr::pa;
+
+use crate::mem::phys_to_virt;
+
+/// The number of EIOINTC vectors.
+pub const VECTOR_COUNT: usize = 256;
+
+/// The number of PCH-PIC inputs.
+const PCH_PIC_INPUTS: usize = 64;
+
+const IOCSR_EXTIOI_IPMAP: usize = 0x14c0;
+const IOCSR_EXTIOI_EN: usize = 0x1600;
+const IOCSR_EXTIOI_ISR: usize = 0x1800;
+const IOCSR_EXTIOI_ROUTE: usize = 0x1c00;
+
+const PCH_PIC_PADDR: usize = 0x1000_0000;
+const PCH_PIC_INT_MASK: usize = 0x20;
+const PCH_PIC_HTMSI_EN: usize = 0x40;
+const PCH_PIC_INT_EDGE: usize = 0x60;
+const PCH_PIC_ROUTE_ENTRY: usize = 0x100;
+const PCH_PIC_HTMSI_VEC: usize = 0x200;
+const PCH_PIC_INT_POL: usize = 0x3e0;
+
+#[inline]
+fn iocsr_read_w(reg: usize) -> u32 {
+    let value: u32;
+    unsafe { asm!("iocsrrd.w {}, {}", out(reg) value, in(reg) reg) };
+    value
+}
+
+#[inline]
+fn iocsr_write_w(reg: usize, value: u32) {
+    unsafe { asm!("iocsrwr.w {}, {}", in(reg) value, in(reg) reg) };
+}
+
+#[inline]
+fn iocsr_write_d(reg: usize, value: u64) {
+    unsafe { asm!("iocsrwr.d {}, {}", in(reg) value, in(reg) reg) };
+}
+
+fn pch_pic_reg<T>(offset: usize) -> *mut T {
+    phys_to_virt(pa!(PCH_PIC_PADDR + offset)).as_mut_ptr() as *mut T
+}
+
+/// Enables or disables the given EIOINTC vector.
+pub fn set_enable(vector: usize, enabled: bool) {
+    let reg = IOCSR_EXTIOI_EN + (vector / 32) * 4;
+    let bit = 1 << (vector % 32);
+    let old = iocsr_read_w(reg);
+    iocsr_write_w(reg, if enabled { old | bit } else { old & !bit });
+
+    if vector < PCH_PIC_INPUTS {
+        let mask = pch_pic_reg::<u64>(PCH_PIC_INT_MASK);
+        unsafe {
+            let old = mask.read_volatile();
+            let bit = 1 << vector;
+            mask.write_volatile(if enabled { old & !bit } else { old | bit });
+        }
+    }
+}
+
+/// Claims the lowest pending vector on the current CPU and acknowledges it.
+///
+/// Returns [`None`] if there is no pending vector.
+pub fn claim() -> Option<usize> {
+    for i in 0..VECTOR_COUNT / 32 {
+        let reg = IOCSR_EXTIOI_ISR + i * 4;
+        let pending = iocsr_read_w(reg);
+        if pending != 0 {
+            let bit = pending.trailing_zeros() as usize;
+            // The ISR register is write-1-to-clear.
+            iocsr_write_w(reg, 1 << bit);
+            return Some(i * 32 + bit);
+        }
+    }
+    None
+}
+
+/// Initializes the PCH-PIC and the EIOINTC, and enables the `HWI0` line.
+pub fn init() {
+    unsafe {
+        // Mask all inputs, level-triggered, active-high, no HT message.
+        pch_pic_reg::<u64>(PCH_PIC_INT_MASK).write_volatile(u64::MAX);
+        pch_pic_reg::<u64>(PCH_PIC_HTMSI_EN).write_volatile(0);
+        pch_pic_reg::<u64>(PCH_PIC_INT_EDGE).write_volatile(0);
+        pch_pic_reg::<u64>(PCH_PIC_INT_POL).write_volatile(0);
+        for i in 0..PCH_PIC_INPUTS {
+            pch_pic_reg::<u8>(PCH_PIC_ROUTE_ENTRY + i).write_volatile(1);
+            pch_pic_reg::<u8>(PCH_PIC_HTMSI_VEC + i).write_volatile(i as u8);
+        }
+    }
+
+    // Map all vector groups to HWI0, and route all vectors to CPU 0, the
+    // only one with the `HWI0` line enabled.
+    iocsr_write_d(IOCSR_EXTIOI_IPMAP, 0x0101_0101_0101_0101);
+    for i in 0..VECTOR_COUNT / 8 {
+        iocsr_write_d(IOCSR_EXTIOI_ROUTE + i * 8, 0x0101_0101_0101_0101);
+    }
+
+    ecfg::set_lie(ecfg::read().lie() | LineBasedInterrupt::HWI0);
+}
@@ -3,12 +3,24 @@ use loongArch64::register::{
     estat, ticlr,
 };
 
+use super::eiointc;
+
+/// The IRQ number of the first external interrupt.
+///
+/// IRQ numbers below it are CPU-local interrupt lines (see
+/// [`estat::Interrupt`]). External IRQ `n` (the PCH-PIC input or EIOINTC
+/// vector `n`) is numbered `EXT_IRQ_BASE + n`.
+pub const EXT_IRQ_BASE: usize = 16;
+
 /// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 12;
+pub const MAX_IRQ_COUNT: usize = EXT_IRQ_BASE + eiointc::VECTOR_COUNT;
 
 /// The timer IRQ number.
 pub const TIMER_IRQ_NUM: usize = estat::Interrupt::Timer as usize;
 
+/// The local interrupt line the EIOINTC is connected to.
+const EIOINTC_IRQ_NUM: usize = estat::Interrupt::HWI0 as usize;
+
 /// Enables or disables the given IRQ.
 pub fn set_enable(irq_num: usize, enabled: bool) {
     if irq_num == TIMER_IRQ_NUM {
@@ -18,6 +30,8 @@ pub fn set_enable(irq_num: usize, enabled: bool) {
             false => old_value & !LineBasedInterrupt::TIMER,
         };
         ecfg::set_lie(new_value);
+    } else if (EXT_IRQ_BASE..MAX_IRQ_COUNT).contains(&irq_num) {
+        eiointc::set_enable(irq_num - EXT_IRQ_BASE, enabled);
     }
 }
 
@@ -32,8 +46,20 @@ pub fn register_handler(irq_num: usize, handler: crate::irq::IrqHandler) -> bool
 /// up in the IRQ handler table and calls the corresponding handler. If
 /// necessary, it also acknowledges the interrupt controller after handling.
 pub fn dispatch_irq(irq_num: usize) {
-    if irq_num == TIMER_IRQ_NUM {
-        ticlr::clear_timer_interrupt();
+    match irq_num {
+        TIMER_IRQ_NUM => {
+            ticlr::clear_timer_interrupt();
+            crate::irq::dispatch_irq_common(irq_num)
+        }
+        EIOINTC_IRQ_NUM => {
+            while let Some(vector) = eiointc::claim() {
+                crate::irq::dispatch_irq_common(EXT_IRQ_BASE + vector);
+            }
+        }
+        _ => crate::irq::dispatch_irq_common(irq_num),
     }
-    crate::irq::dispatch_irq_common(irq_num)
+}
+
+pub(super) fn init_primary() {
+    eiointc::init();
 }
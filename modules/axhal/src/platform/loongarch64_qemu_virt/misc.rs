@@ -2,6 +2,9 @@ use crate::mem::phys_to_virt;
 use memory_addr::pa;
 
 const HALT_ADDR: *mut u8 = phys_to_virt(pa!(axconfig::devices::GED_PADDR)).as_mut_ptr();
+/// The reset register of the generic event device.
+const RESET_ADDR: *mut u8 = phys_to_virt(pa!(axconfig::devices::GED_PADDR + 2)).as_mut_ptr();
+const RESET_VALUE: u8 = 0x42;
 
 /// Shutdown the whole system, including all CPUs.
 pub fn terminate() -> ! {
@@ -13,3 +16,14 @@ pub fn terminate() -> ! {
         crate::arch::halt();
     }
 }
+
+/// Reboots the whole system. The `reason` is only logged.
+pub fn reboot(reason: crate::misc::ResetReason) -> ! {
+    info!("Rebooting ({:?})...", reason);
+    unsafe { RESET_ADDR.write_volatile(RESET_VALUE) };
+    crate::arch::halt();
+    warn!("It should reboot!");
+    loop {
+        crate::arch::halt();
+    }
+}
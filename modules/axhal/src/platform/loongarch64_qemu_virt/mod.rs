@@ -2,6 +2,8 @@ mod boot;
 
 pub mod console;
 #[cfg(feature = "irq")]
+mod eiointc;
+#[cfg(feature = "irq")]
 pub mod irq;
 pub mod mem;
 pub mod misc;
@@ -10,7 +12,12 @@ pub mod mp;
 pub mod time;
 
 /// Initializes the platform devices for the primary CPU.
-pub fn platform_init() {}
+///
+/// For example, the interrupt controller.
+pub fn platform_init() {
+    #[cfg(feature = "irq")]
+    super::irq::init_primary();
+}
 
 /// Initializes the platform devices for secondary CPUs.
 #[cfg(feature = "smp")]
@@ -41,7 +41,9 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
 
     let ticks_now = current_ticks();
     let ticks_deadline = nanos_to_ticks(deadline_ns);
-    let init_value = ticks_deadline - ticks_now;
+    // The lowest 2 bits of the initial value are ignored by hardware, so a
+    // deadline that has already passed still needs a non-zero countdown.
+    let init_value = ticks_deadline.saturating_sub(ticks_now).max(4);
     tcfg::set_init_val(init_value as _);
     tcfg::set_en(true);
 }
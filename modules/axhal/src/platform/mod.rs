@@ -3,6 +3,8 @@
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "aarch64")]{
         mod aarch64_common;
+    } else if #[cfg(target_arch = "riscv64")] {
+        mod riscv64_common;
     }
 }
 
@@ -0,0 +1,119 @@
+//! Wrappers of the SBI extensions for system reset (SRST) and hart state
+//! management (HSM), that report the errors of the SBI.
+
+#![allow(dead_code)]
+
+use sbi_rt::SbiRet;
+
+use crate::misc::ResetReason;
+
+/// An error returned by the SBI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbiError {
+    Failed,
+    NotSupported,
+    InvalidParam,
+    Denied,
+    InvalidAddress,
+    AlreadyAvailable,
+    AlreadyStarted,
+    AlreadyStopped,
+    /// An error code not defined by the specification.
+    Unknown(isize),
+}
+
+impl SbiError {
+    fn from_code(code: isize) -> Self {
+        use SbiError::*;
+        match code {
+            -1 => Failed,
+            -2 => NotSupported,
+            -3 => InvalidParam,
+            -4 => Denied,
+            -5 => InvalidAddress,
+            -6 => AlreadyAvailable,
+            -7 => AlreadyStarted,
+            -8 => AlreadyStopped,
+            code => Unknown(code),
+        }
+    }
+}
+
+/// Returns the value of `ret`, or its error.
+fn result(ret: SbiRet) -> Result<usize, SbiError> {
+    match ret.error as isize {
+        0 => Ok(ret.value),
+        code => Err(SbiError::from_code(code)),
+    }
+}
+
+/// The kinds of system reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetType {
+    Shutdown,
+    ColdReboot,
+    WarmReboot,
+}
+
+fn reset_with<T: sbi_rt::ResetType>(ty: T, reason: ResetReason) -> SbiRet {
+    match reason {
+        ResetReason::Requested => sbi_rt::system_reset(ty, sbi_rt::NoReason),
+        ResetReason::SystemFailure => sbi_rt::system_reset(ty, sbi_rt::SystemFailure),
+    }
+}
+
+/// Resets the system, telling the firmware `reason`. It only returns if the
+/// reset failed, e.g. if the SRST extension is not supported.
+pub fn system_reset(ty: ResetType, reason: ResetReason) -> SbiError {
+    let ret = match ty {
+        ResetType::Shutdown => reset_with(sbi_rt::Shutdown, reason),
+        ResetType::ColdReboot => reset_with(sbi_rt::ColdReboot, reason),
+        ResetType::WarmReboot => reset_with(sbi_rt::WarmReboot, reason),
+    };
+    result(ret).err().unwrap_or(SbiError::Failed)
+}
+
+/// The state of a hart, see [`hart_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HartState {
+    Started,
+    Stopped,
+    StartPending,
+    StopPending,
+    Suspended,
+    SuspendPending,
+    ResumePending,
+}
+
+/// Returns whether the HSM extension is supported.
+pub fn has_hsm() -> bool {
+    !sbi_rt::probe_extension(sbi_rt::Hsm).is_unavailable()
+}
+
+/// Starts the hart `hartid` at the physical address `start_addr`, with
+/// `opaque` in its `a1` register.
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> Result<(), SbiError> {
+    result(sbi_rt::hart_start(hartid, start_addr, opaque)).map(|_| ())
+}
+
+/// Stops the calling hart. It only returns if it failed.
+pub fn hart_stop() -> SbiError {
+    result(sbi_rt::hart_stop())
+        .err()
+        .unwrap_or(SbiError::Failed)
+}
+
+/// Returns the state of the hart `hartid`.
+pub fn hart_state(hartid: usize) -> Result<HartState, SbiError> {
+    use HartState::*;
+    match result(sbi_rt::hart_get_status(hartid))? {
+        0 => Ok(Started),
+        1 => Ok(Stopped),
+        2 => Ok(StartPending),
+        3 => Ok(StopPending),
+        4 => Ok(Suspended),
+        5 => Ok(SuspendPending),
+        6 => Ok(ResumePending),
+        _ => Err(SbiError::Failed),
+    }
+}
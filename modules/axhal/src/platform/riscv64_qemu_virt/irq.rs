@@ -64,14 +64,25 @@ pub fn set_enable(irq_num: usize, enabled: bool) {
     let hart_ctx = HartCtx::this_hart_supervisor();
     let irq = Irq(irq_num as u32);
     if enabled {
-        // For other IRQs, enable/disable in PLIC
+        // For other IRQs, enable/disable in PLIC. Keeps the priority given
+        // with `set_priority`, if any.
+        if PLIC.get_priority(irq) == 0 {
+            PLIC.set_priority(irq, crate::irq::LOWEST_IRQ_PRIORITY);
+        }
         PLIC.enable(irq, hart_ctx);
-        PLIC.set_priority(irq, 1);
     } else {
         PLIC.disable(irq, hart_ctx);
     }
 }
 
+/// Sets the priority of the given IRQ.
+///
+/// While an IRQ is being handled, only IRQs with a strictly higher priority
+/// (and the local timer interrupt) may preempt its handler.
+pub fn set_priority(irq_num: usize, priority: u32) {
+    PLIC.set_priority(Irq(irq_num as u32), priority);
+}
+
 /// Registers an IRQ handler for the given IRQ.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
@@ -110,7 +121,7 @@ pub fn dispatch_irq(scause: usize) {
                 error!("IRQ: external claim failed");
                 return;
             };
-            crate::irq::dispatch_irq_common(irq.get() as usize);
+            dispatch_nested(irq.get());
             PLIC.complete(hart_ctx, Irq(irq.get()));
         }
         _ => {
@@ -119,6 +130,25 @@ pub fn dispatch_irq(scause: usize) {
     }
 }
 
+/// Runs the handler of an external IRQ with nested interrupts allowed.
+///
+/// The PLIC threshold is raised to the priority of the claimed IRQ, so that
+/// only higher-priority sources can preempt the handler, then interrupts are
+/// re-enabled on this CPU. Nesting stops at [`crate::irq::MAX_NESTING_DEPTH`].
+fn dispatch_nested(irq: u32) {
+    let Some(_nesting) = crate::irq::NestingGuard::try_enter() else {
+        crate::irq::dispatch_irq_common(irq as usize);
+        return;
+    };
+    let threshold_ctx = HartCtx::this_hart_supervisor();
+    let prev_threshold = PLIC.get_threshold(threshold_ctx);
+    PLIC.set_threshold(threshold_ctx, PLIC.get_priority(Irq(irq)));
+    crate::arch::enable_irqs();
+    crate::irq::dispatch_irq_common(irq as usize);
+    crate::arch::disable_irqs();
+    PLIC.set_threshold(threshold_ctx, prev_threshold);
+}
+
 const PLIC_BASE: usize = 0x0c00_0000;
 
 fn init_plic() {
@@ -1,9 +1,43 @@
+use crate::mem::phys_to_virt;
+use crate::misc::ResetReason;
+use crate::platform::riscv64_common::sbi::{self, ResetType};
+use memory_addr::pa;
+
+/// Makes QEMU exit with status 0.
+const FINISHER_PASS: u32 = 0x5555;
+/// Makes QEMU exit with the status in the upper 16 bits.
+const FINISHER_FAIL: u32 = 0x3333;
+
 /// Shutdown the whole system, including all CPUs.
 pub fn terminate() -> ! {
     info!("Shutting down...");
-    sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
-    warn!("It should shutdown!");
+    let err = sbi::system_reset(ResetType::Shutdown, ResetReason::Requested);
+    warn!("It should shutdown! ({:?})", err);
     loop {
         crate::arch::halt();
     }
 }
+
+/// Reboots the whole system, telling the firmware `reason`.
+pub fn reboot(reason: ResetReason) -> ! {
+    info!("Rebooting ({:?})...", reason);
+    let err = sbi::system_reset(ResetType::ColdReboot, reason);
+    warn!("It should reboot! ({:?})", err);
+    loop {
+        crate::arch::halt();
+    }
+}
+
+/// Shutdown the whole system, and makes QEMU exit with the status `code`,
+/// through the `sifive_test` device.
+pub fn exit(code: u32) -> ! {
+    info!("Shutting down with exit code {}...", code);
+    let value = match code {
+        0 => FINISHER_PASS,
+        code => (code << 16) | FINISHER_FAIL,
+    };
+    let test_dev = phys_to_virt(pa!(axconfig::devices::TEST_PADDR)).as_mut_ptr() as *mut u32;
+    unsafe { test_dev.write_volatile(value) };
+    // Not in QEMU, or without the device.
+    terminate()
+}
@@ -37,6 +37,8 @@ unsafe extern "C" fn rust_entry_secondary(cpu_id: usize) {
 ///
 /// For example, the interrupt controller and the timer.
 pub fn platform_init() {
+    crate::arch::init_steal_time();
+    crate::arch::init_pmu();
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
     self::time::init_percpu();
@@ -45,6 +47,8 @@ pub fn platform_init() {
 /// Initializes the platform devices for secondary CPUs.
 #[cfg(feature = "smp")]
 pub fn platform_init_secondary() {
+    crate::arch::init_steal_time();
+    crate::arch::init_pmu();
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
     self::time::init_percpu();
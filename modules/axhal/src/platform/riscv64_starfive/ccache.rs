@@ -0,0 +1,26 @@
+//! SiFive composable cache (CCACHE) controller of the JH7110.
+//!
+//! The JH7110 peripherals are not coherent with the L2 cache, so DMA buffers
+//! must be flushed line by line through the `FLUSH64` register.
+
+use memory_addr::{PhysAddr, VirtAddr};
+
+use crate::mem::{phys_to_virt, virt_to_phys};
+
+const CCACHE_BASE: PhysAddr = pa!(0x0201_0000);
+const FLUSH64_OFFSET: usize = 0x200;
+
+/// The cache line size of the L2 cache.
+pub const LINE_SIZE: usize = 64;
+
+/// Writes back and invalidates all L2 cache lines in the given range.
+pub fn flush_range(start: VirtAddr, size: usize) {
+    let flush64 = (phys_to_virt(CCACHE_BASE).as_usize() + FLUSH64_OFFSET) as *mut u64;
+    let end = start.as_usize() + size;
+    let mut addr = start.as_usize() & !(LINE_SIZE - 1);
+    while addr < end {
+        let paddr = virt_to_phys(addr.into()).as_usize();
+        unsafe { flush64.write_volatile(paddr as u64) };
+        addr += LINE_SIZE;
+    }
+}
@@ -1,4 +1,4 @@
-//! IRQ handling using PLIC for QEMU virt machine
+//! IRQ handling using PLIC for the StarFive JH7110.
 
 use crate::irq::IrqHandler;
 use crate::mem::{PhysAddr, phys_to_virt};
@@ -35,11 +35,14 @@ pub static PLIC: LazyInit<Plic> = LazyInit::new();
 struct HartCtx(usize);
 
 impl HartCtx {
+    /// The S-mode context of this hart, the one the kernel takes its
+    /// external interrupts from: its enables, threshold and claim/complete
+    /// registers must all be this context's.
+    ///
+    /// On the JH7110, hart 0 (the S7 monitor core) only has an M-mode
+    /// context (0), and each U74 hart `h` has its M-mode context at `2h - 1`
+    /// and its S-mode context at `2h`. The CPU ID is the hart ID.
     fn this_hart_supervisor() -> Self {
-        Self(crate::cpu::this_cpu_id() * 2 + 1)
-    }
-
-    fn this_hart_machine() -> Self {
         Self(crate::cpu::this_cpu_id() * 2)
     }
 }
@@ -61,18 +64,29 @@ impl InterruptSource for Irq {
 
 /// Enables or disables the given IRQ.
 pub fn set_enable(irq_num: usize, enabled: bool) {
-    let m_hart_ctx = HartCtx::this_hart_machine();
+    let hart_ctx = HartCtx::this_hart_supervisor();
     let irq = Irq(irq_num as u32);
     if enabled {
-        // For other IRQs, enable/disable in PLIC
-        PLIC.disable(irq, m_hart_ctx);
-        PLIC.set_priority(irq, 7);
-        PLIC.enable(irq, m_hart_ctx);
+        // For other IRQs, enable/disable in PLIC. Keeps the priority given
+        // with `set_priority`, if any.
+        PLIC.disable(irq, hart_ctx);
+        if PLIC.get_priority(irq) == 0 {
+            PLIC.set_priority(irq, crate::irq::LOWEST_IRQ_PRIORITY);
+        }
+        PLIC.enable(irq, hart_ctx);
     } else {
-        PLIC.disable(irq, m_hart_ctx);
+        PLIC.disable(irq, hart_ctx);
     }
 }
 
+/// Sets the priority of the given IRQ.
+///
+/// While an IRQ is being handled, only IRQs with a strictly higher priority
+/// (and the local timer interrupt) may preempt its handler.
+pub fn set_priority(irq_num: usize, priority: u32) {
+    PLIC.set_priority(Irq(irq_num as u32), priority);
+}
+
 /// Registers an IRQ handler for the given IRQ.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
@@ -110,7 +124,7 @@ pub fn dispatch_irq(scause: usize) {
                 error!("IRQ: external claim failed");
                 return;
             };
-            crate::irq::dispatch_irq_common(irq.get() as usize);
+            dispatch_nested(irq.get());
             PLIC.complete(hart_ctx, Irq(irq.get()));
         }
         _ => {
@@ -119,6 +133,26 @@ pub fn dispatch_irq(scause: usize) {
     }
 }
 
+/// Runs the handler of an external IRQ with nested interrupts allowed.
+///
+/// The PLIC threshold is raised to the priority of the claimed IRQ, so that
+/// only higher-priority sources can preempt the handler, then interrupts are
+/// re-enabled on this CPU. Nesting stops at [`crate::irq::MAX_NESTING_DEPTH`].
+fn dispatch_nested(irq: u32) {
+    let Some(_nesting) = crate::irq::NestingGuard::try_enter() else {
+        crate::irq::dispatch_irq_common(irq as usize);
+        return;
+    };
+    // The context the IRQ was claimed from, see `HartCtx`.
+    let threshold_ctx = HartCtx::this_hart_supervisor();
+    let prev_threshold = PLIC.get_threshold(threshold_ctx);
+    PLIC.set_threshold(threshold_ctx, PLIC.get_priority(Irq(irq)));
+    crate::arch::enable_irqs();
+    crate::irq::dispatch_irq_common(irq as usize);
+    crate::arch::disable_irqs();
+    PLIC.set_threshold(threshold_ctx, prev_threshold);
+}
+
 const PLIC_BASE: usize = 0x0c00_0000;
 
 fn init_plic() {
@@ -134,10 +168,8 @@ pub(super) fn init_primary() {
 
 pub(super) fn init_percpu() {
     // PLIC is already initialized by primary CPU, just configure per-CPU settings
-    let hart_ctx_machine = HartCtx::this_hart_machine();
-    PLIC.set_threshold(hart_ctx_machine, 0);
-    // let hart_ctx_supervisor = HartCtx::this_hart_supervisor();
-    // PLIC.set_threshold(hart_ctx_supervisor, 0);
+    let hart_ctx = HartCtx::this_hart_supervisor();
+    PLIC.set_threshold(hart_ctx, 0);
 
     // Enable all types of interrupts
     unsafe {
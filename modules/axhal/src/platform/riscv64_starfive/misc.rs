@@ -1,8 +1,21 @@
+use crate::misc::ResetReason;
+use crate::platform::riscv64_common::sbi::{self, ResetType};
+
 /// Shutdown the whole system, including all CPUs.
 pub fn terminate() -> ! {
     info!("Shutting down...");
-    sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
-    warn!("It should shutdown!");
+    let err = sbi::system_reset(ResetType::Shutdown, ResetReason::Requested);
+    warn!("It should shutdown! ({:?})", err);
+    loop {
+        crate::arch::halt();
+    }
+}
+
+/// Reboots the whole system, telling the firmware `reason`.
+pub fn reboot(reason: ResetReason) -> ! {
+    info!("Rebooting ({:?})...", reason);
+    let err = sbi::system_reset(ResetType::ColdReboot, reason);
+    warn!("It should reboot! ({:?})", err);
     loop {
         crate::arch::halt();
     }
@@ -1,5 +1,7 @@
 mod boot;
 
+pub(crate) mod ccache;
+
 pub mod console;
 pub mod mem;
 pub mod misc;
@@ -34,6 +36,8 @@ unsafe extern "C" fn rust_entry_secondary(cpu_id: usize) {
 ///
 /// For example, the interrupt controller and the timer.
 pub fn platform_init() {
+    crate::arch::init_steal_time();
+    crate::arch::init_pmu();
     #[cfg(feature = "irq")]
     {
         self::irq::init_primary();
@@ -45,6 +49,8 @@ pub fn platform_init() {
 /// Initializes the platform devices for secondary CPUs.
 #[cfg(feature = "smp")]
 pub fn platform_init_secondary() {
+    crate::arch::init_steal_time();
+    crate::arch::init_pmu();
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
     // self::time::init_percpu();
@@ -119,6 +119,37 @@ pub(super) fn init_primary() {
     info!("Initialize IO APIC...");
     let io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
     IO_APIC.init_once(SpinNoIrq::new(io_apic));
+
+    #[cfg(feature = "mmio")]
+    register_mmio_devices();
+}
+
+/// Registers the interrupt controllers and the HPET in the MMIO registry.
+#[cfg(feature = "mmio")]
+fn register_mmio_devices() {
+    use crate::mmio::{MmioDevice, register_device};
+
+    let devices = [
+        ("ioapic", IO_APIC_BASE, 0x1000),
+        ("hpet", super::hpet::HPET_BASE, super::hpet::HPET_SIZE),
+    ];
+    for (name, paddr, size) in devices {
+        let _ = register_device(MmioDevice {
+            name,
+            paddr,
+            size,
+            irq: None,
+        });
+    }
+    // The x2APIC is accessed with MSRs.
+    if !unsafe { IS_X2APIC } {
+        let _ = register_device(MmioDevice {
+            name: "lapic",
+            paddr: pa!(unsafe { xapic_base() } as usize),
+            size: 0x1000,
+            irq: None,
+        });
+    }
 }
 
 #[cfg(feature = "smp")]
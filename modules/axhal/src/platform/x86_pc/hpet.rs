@@ -0,0 +1,80 @@
+//! HPET (High Precision Event Timer), only used as a reference clock to
+//! calibrate the local APIC timer.
+
+use core::hint::spin_loop;
+
+use memory_addr::PhysAddr;
+
+use crate::mem::phys_to_virt;
+
+pub(super) const HPET_BASE: PhysAddr = pa!(0xFED0_0000);
+
+/// The size of the register region.
+pub(super) const HPET_SIZE: usize = 0x1000;
+
+/// General Capabilities and ID Register.
+const GCAP_ID: usize = 0x00;
+/// General Configuration Register.
+const GEN_CONF: usize = 0x10;
+/// Main Counter Value Register.
+const MAIN_CNT: usize = 0xf0;
+
+/// The main counter is 64-bit wide, in `GCAP_ID`.
+const COUNT_SIZE_CAP: u64 = 1 << 13;
+/// Starts the main counter, in `GEN_CONF`.
+const ENABLE_CNF: u64 = 1 << 0;
+
+/// The longest valid period of the main counter (100 ns).
+const MAX_PERIOD_FEMTOS: u64 = 100_000_000;
+const FEMTOS_PER_NANO: u64 = 1_000_000;
+
+/// The running main counter of the HPET.
+pub(super) struct Hpet {
+    /// The period of the counter in femtoseconds.
+    period_femtos: u64,
+    /// The mask of the valid bits of the counter.
+    mask: u64,
+}
+
+fn read(offset: usize) -> u64 {
+    let reg = phys_to_virt(HPET_BASE + offset).as_ptr() as *const u64;
+    // Safety: the register region is mapped as MMIO.
+    unsafe { reg.read_volatile() }
+}
+
+fn write(offset: usize, value: u64) {
+    let reg = phys_to_virt(HPET_BASE + offset).as_mut_ptr() as *mut u64;
+    // Safety: the register region is mapped as MMIO.
+    unsafe { reg.write_volatile(value) }
+}
+
+impl Hpet {
+    /// Starts the main counter, returns [`None`] if there is no valid HPET.
+    pub fn init() -> Option<Self> {
+        let caps = read(GCAP_ID);
+        let period_femtos = caps >> 32;
+        if period_femtos == 0 || period_femtos > MAX_PERIOD_FEMTOS {
+            warn!("no valid HPET, period: {} fs", period_femtos);
+            return None;
+        }
+        write(GEN_CONF, read(GEN_CONF) | ENABLE_CNF);
+        let mask = if caps & COUNT_SIZE_CAP != 0 {
+            u64::MAX
+        } else {
+            u32::MAX as u64
+        };
+        Some(Self {
+            period_femtos,
+            mask,
+        })
+    }
+
+    /// Busy waits for `nanos` nanoseconds.
+    pub fn busy_wait(&self, nanos: u64) {
+        let ticks = nanos * FEMTOS_PER_NANO / self.period_femtos;
+        let start = read(MAIN_CNT);
+        while read(MAIN_CNT).wrapping_sub(start) & self.mask < ticks {
+            spin_loop();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! KVM paravirt clock (`kvmclock`), only used to get the TSC frequency.
+//!
+//! Guests often have no CPUID leaf `0x16`, and calibrating the TSC is skewed
+//! when the vCPU is preempted. The hypervisor knows the exact rate, and
+//! reports it as the scale it uses to convert TSC ticks to nanoseconds.
+//!
+//! Reference: Linux `Documentation/virt/kvm/x86/msr.rst`.
+
+use core::arch::x86_64::__cpuid;
+
+use crate::mem::virt_to_phys;
+
+/// The CPUID leaf with the hypervisor signature.
+const CPUID_SIGNATURE: u32 = 0x4000_0000;
+/// The CPUID leaf with the KVM paravirt features.
+const CPUID_FEATURES: u32 = 0x4000_0001;
+/// `KVM_FEATURE_CLOCKSOURCE2`.
+const FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+/// `MSR_KVM_SYSTEM_TIME_NEW`.
+const MSR_SYSTEM_TIME: u32 = 0x4b56_4d01;
+/// Enables the updates of the time info, in `MSR_SYSTEM_TIME`.
+const SYSTEM_TIME_ENABLE: u64 = 1;
+
+/// `struct pvclock_vcpu_time_info`.
+#[repr(C, align(32))]
+struct PvclockTimeInfo {
+    version: u32,
+    _pad0: u32,
+    tsc_timestamp: u64,
+    system_time: u64,
+    tsc_to_system_mul: u32,
+    tsc_shift: i8,
+    flags: u8,
+    _pad: [u8; 2],
+}
+
+static mut TIME_INFO: PvclockTimeInfo = PvclockTimeInfo {
+    version: 0,
+    _pad0: 0,
+    tsc_timestamp: 0,
+    system_time: 0,
+    tsc_to_system_mul: 0,
+    tsc_shift: 0,
+    flags: 0,
+    _pad: [0; 2],
+};
+
+fn is_kvm() -> bool {
+    let sig = unsafe { __cpuid(CPUID_SIGNATURE) };
+    let mut name = [0; 12];
+    name[..4].copy_from_slice(&sig.ebx.to_le_bytes());
+    name[4..8].copy_from_slice(&sig.ecx.to_le_bytes());
+    name[8..].copy_from_slice(&sig.edx.to_le_bytes());
+    &name == b"KVMKVMKVM\0\0\0" && sig.eax >= CPUID_FEATURES
+}
+
+/// Returns the TSC frequency in Hz reported by KVM, or [`None`] if not
+/// running on KVM.
+pub(super) fn tsc_frequency() -> Option<u64> {
+    if !is_kvm() || unsafe { __cpuid(CPUID_FEATURES) }.eax & FEATURE_CLOCKSOURCE2 == 0 {
+        return None;
+    }
+    let info = &raw const TIME_INFO;
+    let paddr = virt_to_phys(va!(info as usize)).as_usize() as u64;
+    unsafe { x86::msr::wrmsr(MSR_SYSTEM_TIME, paddr | SYSTEM_TIME_ENABLE) };
+    let (mul, shift) = loop {
+        // The hypervisor makes `version` odd while it updates the info.
+        let version = unsafe { (&raw const (*info).version).read_volatile() };
+        let mul = unsafe { (&raw const (*info).tsc_to_system_mul).read_volatile() };
+        let shift = unsafe { (&raw const (*info).tsc_shift).read_volatile() };
+        let version_after = unsafe { (&raw const (*info).version).read_volatile() };
+        if version % 2 == 0 && version == version_after {
+            break (mul, shift);
+        }
+        core::hint::spin_loop();
+    };
+    // Only the scale is used, stop the updates.
+    unsafe { x86::msr::wrmsr(MSR_SYSTEM_TIME, 0) };
+    if mul == 0 {
+        return None;
+    }
+
+    // nanos = (ticks << shift) * mul >> 32, or ticks >> -shift if negative.
+    let hz = (crate::time::NANOS_PER_SEC as u128) << 32;
+    let hz = if shift >= 0 {
+        hz >> shift
+    } else {
+        hz << -shift
+    };
+    Some((hz / mul as u128) as u64)
+}
@@ -26,3 +26,30 @@ pub fn terminate() -> ! {
         crate::arch::halt();
     }
 }
+
+/// Reboots the whole system, through the reset line of the keyboard
+/// controller. The `reason` is only logged.
+pub fn reboot(reason: crate::misc::ResetReason) -> ! {
+    info!("Rebooting ({:?})...", reason);
+    unsafe { PortWriteOnly::new(0x64).write(0xfeu8) };
+    crate::arch::halt();
+    warn!("It should reboot!");
+    loop {
+        crate::arch::halt();
+    }
+}
+
+/// Shutdown the whole system, and makes QEMU exit with a status that reports
+/// `code`, through the `isa-debug-exit` device at port `0xf4`.
+///
+/// The device can only report odd statuses, `(code << 1) | 1`, so a `code` of
+/// 0 shuts down normally, with status 0.
+pub fn exit(code: u32) -> ! {
+    info!("Shutting down with exit code {}...", code);
+    #[cfg(platform = "x86_64-qemu-q35")]
+    if code != 0 {
+        unsafe { PortWriteOnly::new(0xf4).write(code) };
+    }
+    // Not in QEMU, or without the device.
+    terminate()
+}
@@ -1,5 +1,8 @@
 mod apic;
 mod boot;
+#[cfg(feature = "irq")]
+mod hpet;
+mod kvmclock;
 mod uart16550;
 
 pub mod mem;
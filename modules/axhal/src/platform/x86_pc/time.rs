@@ -3,8 +3,13 @@ use raw_cpuid::CpuId;
 #[cfg(feature = "irq")]
 use int_ratio::Ratio;
 
+/// The frequency of the local APIC timer, if it cannot be calibrated.
 #[cfg(feature = "irq")]
-const LAPIC_TICKS_PER_SEC: u64 = 1_000_000_000; // TODO: need to calibrate
+const LAPIC_TICKS_PER_SEC: u64 = 1_000_000_000;
+
+/// How long the local APIC timer is counted against the HPET.
+#[cfg(feature = "irq")]
+const CALIBRATION_NANOS: u64 = 10_000_000;
 
 #[cfg(feature = "irq")]
 static mut NANOS_TO_LAPIC_TICKS_RATIO: Ratio = Ratio::zero();
@@ -44,9 +49,10 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     let now_ns = crate::time::monotonic_time_nanos();
     unsafe {
         if now_ns < deadline_ns {
+            // Deadlines beyond the 32-bit counter range fire early; the
+            // timer users re-arm the timer for the remaining time.
             let apic_ticks = NANOS_TO_LAPIC_TICKS_RATIO.mul_trunc(deadline_ns - now_ns);
-            assert!(apic_ticks <= u32::MAX as u64);
-            lapic.set_timer_initial(apic_ticks.max(1) as u32);
+            lapic.set_timer_initial(apic_ticks.clamp(1, u32::MAX as u64) as u32);
         } else {
             lapic.set_timer_initial(1);
         }
@@ -63,6 +69,11 @@ pub(super) fn init_early() {
             unsafe { CPU_FREQ_MHZ = freq as u64 }
         }
     }
+    // The hypervisor knows the TSC frequency better than the CPUID leaf.
+    if let Some(freq) = super::kvmclock::tsc_frequency() {
+        axlog::ax_println!("Got TSC frequency by kvmclock: {} Hz", freq);
+        unsafe { CPU_FREQ_MHZ = freq / 1_000_000 }
+    }
 
     unsafe {
         INIT_TICK = core::arch::x86_64::_rdtsc();
@@ -90,14 +101,31 @@ pub(super) fn init_primary() {
         lapic.set_timer_divide(TimerDivide::Div256); // indeed it is Div1, the name is confusing.
         lapic.enable_timer();
 
-        // TODO: calibrate with HPET
+        let lapic_ticks_per_sec = calibrate_lapic_timer().unwrap_or(LAPIC_TICKS_PER_SEC);
+        info!("Local APIC timer: {} Hz", lapic_ticks_per_sec);
         NANOS_TO_LAPIC_TICKS_RATIO = Ratio::new(
-            LAPIC_TICKS_PER_SEC as u32,
+            lapic_ticks_per_sec.min(u32::MAX as u64) as u32,
             crate::time::NANOS_PER_SEC as u32,
         );
     }
 }
 
+/// Counts the ticks of the local APIC timer over [`CALIBRATION_NANOS`] of
+/// the HPET, returns its frequency, or [`None`] if there is no HPET.
+#[cfg(feature = "irq")]
+fn calibrate_lapic_timer() -> Option<u64> {
+    let hpet = super::hpet::Hpet::init()?;
+    let lapic = super::apic::local_apic();
+    unsafe {
+        lapic.set_timer_initial(u32::MAX);
+        hpet.busy_wait(CALIBRATION_NANOS);
+        let ticks = u32::MAX - lapic.timer_current();
+        // Stops the timer, it is armed by `set_oneshot_timer`.
+        lapic.set_timer_initial(0);
+        Some(ticks as u64 * crate::time::NANOS_PER_SEC / CALIBRATION_NANOS)
+    }
+}
+
 #[cfg(feature = "smp")]
 pub(super) fn init_secondary() {
     #[cfg(feature = "irq")]
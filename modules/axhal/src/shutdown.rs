@@ -0,0 +1,91 @@
+//! Teardown callbacks executed before the system powers off.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// The maximum number of shutdown hooks that can be registered.
+pub const MAX_SHUTDOWN_HOOKS: usize = 32;
+
+struct ShutdownHooks {
+    /// The hooks, with whether they also run after a panic.
+    hooks: [Option<(fn(), bool)>; MAX_SHUTDOWN_HOOKS],
+    len: usize,
+}
+
+static HOOKS: SpinNoIrq<ShutdownHooks> = SpinNoIrq::new(ShutdownHooks {
+    hooks: [None; MAX_SHUTDOWN_HOOKS],
+    len: 0,
+});
+
+/// Whether the system is stopping on a panic, see [`set_panicking`].
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Registers a callback to be executed when the system shuts down.
+///
+/// Callbacks are executed in the reverse order of registration, so a
+/// subsystem is torn down before the subsystems it depends on (e.g. sockets
+/// are closed before the NIC driver quiesces its DMA).
+///
+/// The callback is skipped when the system stops on a panic, as it may need
+/// the locks or the state of the code that panicked, see
+/// [`on_panic_shutdown`].
+///
+/// Returns `false` if [`MAX_SHUTDOWN_HOOKS`] callbacks are already registered.
+pub fn on_shutdown(hook: fn()) -> bool {
+    register(hook, false)
+}
+
+/// Registers a callback like [`on_shutdown`], that also runs when the system
+/// stops on a panic.
+///
+/// The callback must then not take locks nor rely on the state of the other
+/// subsystems, e.g. it only cleans the cache over a memory region.
+pub fn on_panic_shutdown(hook: fn()) -> bool {
+    register(hook, true)
+}
+
+/// Tells that the system is stopping on a panic: from then on, the hooks
+/// registered by [`on_shutdown`] are skipped.
+///
+/// It is called by the panic handler before it shuts the system down, and
+/// also covers a panic of a hook itself.
+pub fn set_panicking() {
+    PANICKING.store(true, Ordering::Release);
+}
+
+fn register(hook: fn(), panic_safe: bool) -> bool {
+    let mut hooks = HOOKS.lock();
+    if hooks.len >= MAX_SHUTDOWN_HOOKS {
+        warn!("too many shutdown hooks, ignoring {:#x}", hook as usize);
+        return false;
+    }
+    let len = hooks.len;
+    hooks.hooks[len] = Some((hook, panic_safe));
+    hooks.len += 1;
+    true
+}
+
+/// Executes all registered shutdown hooks in reverse order of registration.
+///
+/// Each hook is executed at most once, even if this function is called
+/// multiple times. Hooks registered by other hooks are also executed. After
+/// [`set_panicking`], only the hooks registered by [`on_panic_shutdown`] are.
+pub fn run_shutdown_hooks() {
+    loop {
+        // Do not hold the lock while running the hook, it may register more.
+        let hook = {
+            let mut hooks = HOOKS.lock();
+            if hooks.len == 0 {
+                break;
+            }
+            hooks.len -= 1;
+            let len = hooks.len;
+            hooks.hooks[len].take()
+        };
+        match hook {
+            Some((hook, panic_safe)) if panic_safe || !PANICKING.load(Ordering::Acquire) => hook(),
+            _ => {}
+        }
+    }
+}
@@ -10,8 +10,6 @@ pub type TimeValue = Duration;
 
 #[cfg(feature = "irq")]
 pub use crate::platform::irq::TIMER_IRQ_NUM;
-#[cfg(feature = "irq")]
-pub use crate::platform::time::set_oneshot_timer;
 pub use crate::platform::time::{current_ticks, epochoffset_nanos, nanos_to_ticks, ticks_to_nanos};
 
 /// Number of milliseconds in a second.
@@ -25,6 +23,32 @@ pub const NANOS_PER_MILLIS: u64 = 1_000_000;
 /// Number of nanoseconds in a microsecond.
 pub const NANOS_PER_MICROS: u64 = 1_000;
 
+/// The furthest ahead of the current time that [`set_oneshot_timer`]
+/// programs the hardware timer.
+///
+/// It is within the range of the timer of every platform (the x86 local APIC
+/// counts down from 32 bits). A later deadline fires early at the horizon,
+/// and the timer interrupt handler re-arms the timer, so a far-future
+/// deadline is reached in several steps instead of silently never firing.
+pub const MAX_TIMER_HORIZON: Duration = Duration::from_secs(1);
+
+/// Converts a clock time to nanoseconds, saturating at [`u64::MAX`] (about
+/// 584 years) instead of truncating.
+pub fn saturating_nanos(time: TimeValue) -> u64 {
+    u64::try_from(time.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Sets a one-shot timer.
+///
+/// A timer interrupt will be triggered at the specified monotonic time
+/// deadline (in nanoseconds), or at [`MAX_TIMER_HORIZON`] from now if it is
+/// later.
+#[cfg(feature = "irq")]
+pub fn set_oneshot_timer(deadline_ns: u64) {
+    let horizon = monotonic_time_nanos().saturating_add(MAX_TIMER_HORIZON.as_nanos() as u64);
+    crate::platform::time::set_oneshot_timer(deadline_ns.min(horizon));
+}
+
 /// Returns nanoseconds elapsed since system boot.
 pub fn monotonic_time_nanos() -> u64 {
     ticks_to_nanos(current_ticks())
@@ -45,14 +69,115 @@ pub fn wall_time() -> TimeValue {
     TimeValue::from_nanos(monotonic_time_nanos() + epochoffset_nanos())
 }
 
+/// Returns the total time in nanoseconds that the current CPU was stolen by
+/// the hypervisor (ready to run but not scheduled).
+///
+/// The monotonic clock keeps advancing during steal, so timers are not
+/// affected. It returns `0` on bare metal or if the hypervisor does not
+/// report steal time.
+pub fn steal_time_nanos() -> u64 {
+    #[cfg(target_arch = "riscv64")]
+    {
+        crate::arch::steal_time_nanos()
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    0
+}
+
+/// The default longest busy wait with interrupts enabled that is not
+/// reported, see [`set_busy_wait_warn_threshold`].
+#[cfg(feature = "busy-wait-audit")]
+pub const DEFAULT_BUSY_WAIT_WARN_THRESHOLD: Duration = Duration::from_millis(1);
+
+#[cfg(feature = "busy-wait-audit")]
+static BUSY_WAIT_WARN_THRESHOLD_NANOS: core::sync::atomic::AtomicU64 =
+    core::sync::atomic::AtomicU64::new(DEFAULT_BUSY_WAIT_WARN_THRESHOLD.as_nanos() as u64);
+
+/// Sets the longest busy wait with interrupts enabled that is not reported.
+/// The longer ones log a warning with their caller, so that they can be
+/// replaced with sleeps. [`Duration::ZERO`] disables the reports.
+///
+/// With interrupts disabled, e.g. during the boot, there is no alternative,
+/// so those are never reported.
+#[cfg(feature = "busy-wait-audit")]
+pub fn set_busy_wait_warn_threshold(threshold: Duration) {
+    BUSY_WAIT_WARN_THRESHOLD_NANOS.store(
+        saturating_nanos(threshold),
+        core::sync::atomic::Ordering::Relaxed,
+    );
+}
+
+/// Reports a busy wait of `dur` if it is past the threshold.
+#[cfg(feature = "busy-wait-audit")]
+#[track_caller]
+fn audit_busy_wait(dur: Duration) {
+    let threshold = BUSY_WAIT_WARN_THRESHOLD_NANOS.load(core::sync::atomic::Ordering::Relaxed);
+    if threshold != 0 && saturating_nanos(dur) > threshold && crate::arch::irqs_enabled() {
+        warn!(
+            "busy wait of {:?} with interrupts enabled at {}, sleep instead",
+            dur,
+            core::panic::Location::caller()
+        );
+    }
+}
+
 /// Busy waiting for the given duration.
+///
+/// It holds the CPU, so in tasks prefer the sleeps of the scheduler, or
+/// [`busy_wait_async`] in async tasks. With the `busy-wait-audit` feature,
+/// the long ones are reported, see [`set_busy_wait_warn_threshold`].
+#[track_caller]
 pub fn busy_wait(dur: Duration) {
     busy_wait_until(wall_time() + dur);
 }
 
 /// Busy waiting until reaching the given deadline.
+///
+/// See [`busy_wait`].
+#[track_caller]
 pub fn busy_wait_until(deadline: TimeValue) {
+    #[cfg(feature = "busy-wait-audit")]
+    audit_busy_wait(deadline.saturating_sub(wall_time()));
     while wall_time() < deadline {
         core::hint::spin_loop();
     }
 }
+
+/// Waits for the given duration in an async task, see
+/// [`busy_wait_until_async`].
+pub fn busy_wait_async(dur: Duration) -> BusyWaitUntil {
+    busy_wait_until_async(wall_time() + dur)
+}
+
+/// Waits until reaching the given deadline in an async task, without holding
+/// the CPU: the future yields to the other tasks until the deadline.
+///
+/// It does not need a timer, so drivers can use it in place of
+/// [`busy_wait_until`], e.g. for the delays of their resets. Where a timer
+/// is available, e.g. the sleeps of the async runtime, it is cheaper.
+pub fn busy_wait_until_async(deadline: TimeValue) -> BusyWaitUntil {
+    BusyWaitUntil { deadline }
+}
+
+/// The future returned by [`busy_wait_until_async`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[derive(Debug)]
+pub struct BusyWaitUntil {
+    deadline: TimeValue,
+}
+
+impl core::future::Future for BusyWaitUntil {
+    type Output = ();
+
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<()> {
+        if wall_time() >= self.deadline {
+            return core::task::Poll::Ready(());
+        }
+        // Polled again after the other ready tasks.
+        cx.waker().wake_by_ref();
+        core::task::Poll::Pending
+    }
+}
@@ -17,6 +17,32 @@ pub static IRQ: [fn(usize) -> bool];
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
+/// A slice of functions called on the IRQ return path, when a reschedule has
+/// been requested by [`set_need_resched`].
+#[def_trap_handler]
+pub static RESCHED: [fn()];
+
+/// A slice of handlers of the kernel loads and stores that fault on a page
+/// they trace, e.g. of an MMIO device, see [`MmioAccess`].
+///
+/// The handler performs the access at the given address and returns the
+/// value loaded (anything for a store), or returns [`None`] if it does not
+/// trace the page. The faulting instruction is then skipped. Only RISC-V
+/// decodes the faulting instructions, elsewhere they go to [`PAGE_FAULT`].
+#[cfg(feature = "mmio")]
+#[def_trap_handler]
+pub static MMIO_ACCESS: [fn(VirtAddr, MmioAccess) -> Option<u64>];
+
+/// A load or a store of a traced page, see [`MMIO_ACCESS`].
+#[cfg(feature = "mmio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmioAccess {
+    /// A load of the given width in bytes.
+    Read(usize),
+    /// A store of the given width in bytes, of the value.
+    Write(usize, u64),
+}
+
 /// A slice of syscall handler functions.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
@@ -38,6 +64,41 @@ macro_rules! handle_trap {
     }}
 }
 
+#[percpu::def_percpu]
+static NEED_RESCHED: bool = false;
+
+/// Requests a reschedule when the current CPU returns from the outermost IRQ.
+///
+/// It is called when an IRQ handler woke up a task (or an async future) of a
+/// higher priority than the interrupted one, so that the woken one runs right
+/// away instead of waiting for the interrupted context to yield.
+///
+/// Outside of an IRQ handler, it does nothing: the caller is the running
+/// task, which yields by itself.
+pub fn set_need_resched() {
+    #[cfg(feature = "irq")]
+    if crate::irq::in_irq() {
+        // Safety: only accessed on the current CPU, by its IRQ handlers and
+        // by the return path of the outermost one. A nested handler may
+        // interrupt the access, but it only sets the flag.
+        unsafe { NEED_RESCHED.write_current_raw(true) };
+    }
+}
+
+/// Runs the [`RESCHED`] hook if a reschedule was requested during the IRQ.
+///
+/// It must be called on the trap return path, after the outermost IRQ
+/// handler has returned.
+#[allow(dead_code)]
+pub(crate) fn resched_on_irq_return() {
+    if unsafe { NEED_RESCHED.read_current_raw() } {
+        unsafe { NEED_RESCHED.write_current_raw(false) };
+        if let Some(func) = RESCHED.iter().next() {
+            func();
+        }
+    }
+}
+
 /// Call the external syscall handler.
 #[cfg(feature = "uspace")]
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
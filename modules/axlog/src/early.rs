@@ -0,0 +1,99 @@
+//! Recording of early console output.
+//!
+//! Before the real console is up (e.g. before the display), the output is
+//! still written through to the early console, so that it is not lost if the
+//! boot hangs, and a copy is kept in a fixed-size ring buffer. The copy is
+//! only for a later replay with [`early_output`], e.g. by a `dmesg`-like
+//! command. [`switch_console`] stops the recording.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// The capacity in bytes of the early output buffer.
+pub const EARLY_BUFFER_SIZE: usize = 16 * 1024;
+
+struct EarlyBuffer {
+    buf: [u8; EARLY_BUFFER_SIZE],
+    /// Total number of bytes ever written.
+    written: usize,
+}
+
+impl EarlyBuffer {
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buf[self.written % EARLY_BUFFER_SIZE] = b;
+            self.written += 1;
+        }
+    }
+
+    /// Returns the two parts (in order) of the retained bytes.
+    fn contents(&self) -> (&[u8], &[u8]) {
+        if self.written <= EARLY_BUFFER_SIZE {
+            (&self.buf[..self.written], &[])
+        } else {
+            let head = self.written % EARLY_BUFFER_SIZE;
+            (&self.buf[head..], &self.buf[..head])
+        }
+    }
+
+    fn dropped(&self) -> usize {
+        self.written.saturating_sub(EARLY_BUFFER_SIZE)
+    }
+}
+
+static EARLY_BUFFER: SpinNoIrq<EarlyBuffer> = SpinNoIrq::new(EarlyBuffer {
+    buf: [0; EARLY_BUFFER_SIZE],
+    written: 0,
+});
+
+static CONSOLE_READY: AtomicBool = AtomicBool::new(false);
+
+/// Records a copy of `s` if the real console is not ready yet. It is still
+/// written to the console by the caller.
+pub(crate) fn record(s: &str) {
+    if CONSOLE_READY.load(Ordering::Acquire) {
+        return;
+    }
+    let mut early = EARLY_BUFFER.lock();
+    // Check again, the console may be switched while we were waiting.
+    if !CONSOLE_READY.load(Ordering::Acquire) {
+        early.push(s.as_bytes());
+    }
+}
+
+/// Calls `f` with the recorded early output, oldest first.
+///
+/// The buffer is kept after [`switch_console`], so it can still be inspected
+/// later (e.g. by a `dmesg`-like command). Only the last
+/// [`EARLY_BUFFER_SIZE`] bytes are kept, see [`early_output_dropped`].
+pub fn early_output(mut f: impl FnMut(&[u8])) {
+    let early = EARLY_BUFFER.lock();
+    let (first, second) = early.contents();
+    f(first);
+    f(second);
+}
+
+/// Returns the number of bytes of early output that did not fit in the
+/// buffer, the oldest ones.
+pub fn early_output_dropped() -> usize {
+    EARLY_BUFFER.lock().dropped()
+}
+
+/// Marks the real console as ready, and stops recording the output.
+///
+/// The early output was already written through to the console, it is not
+/// written again.
+pub fn switch_console() {
+    let _early = EARLY_BUFFER.lock();
+    CONSOLE_READY.store(true, Ordering::Release);
+}
+
+/// Stops recording the output from the panic handler of a fatal panic, the
+/// later output only goes to the console.
+///
+/// Unlike [`switch_console`], it does not wait for the buffer, which the
+/// panicking code may hold.
+pub fn switch_console_on_panic() {
+    CONSOLE_READY.store(true, Ordering::Release);
+}
@@ -18,6 +18,13 @@
 //!
 //! - `std`: Use in the `std` environment. If it is enabled, you can use console
 //!   output without implementing the [`LogIf`] trait. This is disabled by default.
+//! - `early-buffer`: Keep a copy of the console output until
+//!   [`switch_console`] is called, to replay it later with `early_output`.
+//!   The output is still written through to the console meanwhile. It is
+//!   useful when the real console is brought up late in the boot process.
+//! - `pstore`: Also copy the console output to a memory region given with
+//!   [`init_pstore`] that survives warm reboots, and keep the output of the
+//!   previous boot found in it, to diagnose a crash where the console was dead.
 //! - `log-level-off`: Disable all logging. If it is enabled, all log macros
 //!   (e.g. [`info!`]) will be optimized out to a no-op in compilation time.
 //! - `log-level-error`: Set the maximum log level to `error`. Any macro
@@ -58,6 +65,20 @@ use log::{Level, LevelFilter, Log, Metadata, Record};
 #[cfg(not(feature = "std"))]
 use crate_interface::call_interface;
 
+#[cfg(all(feature = "early-buffer", not(feature = "std")))]
+mod early;
+
+#[cfg(all(feature = "early-buffer", not(feature = "std")))]
+pub use self::early::{
+    EARLY_BUFFER_SIZE, early_output, early_output_dropped, switch_console, switch_console_on_panic,
+};
+
+#[cfg(all(feature = "pstore", not(feature = "std")))]
+mod pstore;
+
+#[cfg(all(feature = "pstore", not(feature = "std")))]
+pub use self::pstore::{dump_previous_boot_log, init_pstore, previous_boot_log};
+
 pub use log::{debug, error, info, trace, warn};
 
 /// Prints to the console.
@@ -135,6 +156,10 @@ impl Write for Logger {
             if #[cfg(feature = "std")] {
                 std::print!("{}", s);
             } else {
+                #[cfg(feature = "early-buffer")]
+                early::record(s);
+                #[cfg(feature = "pstore")]
+                pstore::write(s);
                 call_interface!(LogIf::console_write_str, s);
             }
         }
@@ -247,6 +272,20 @@ pub fn init() {
     log::set_max_level(LevelFilter::Warn);
 }
 
+/// Marks the real console as ready.
+///
+/// Without the `early-buffer` feature, the output is never recorded, so it
+/// does nothing.
+#[cfg(any(not(feature = "early-buffer"), feature = "std"))]
+pub fn switch_console() {}
+
+/// Marks the real console as ready from the panic handler of a fatal panic.
+///
+/// Without the `early-buffer` feature, the output is never recorded, so it
+/// does nothing.
+#[cfg(any(not(feature = "early-buffer"), feature = "std"))]
+pub fn switch_console_on_panic() {}
+
 /// Set the maximum log level.
 ///
 /// Unlike the features such as `log-level-error`, setting the logging level in
@@ -0,0 +1,198 @@
+//! A log ring that survives warm reboots, like the pstore of Linux.
+//!
+//! The platform reserves a region of memory at a fixed address, which the
+//! firmware does not clear on a warm reboot, and hands it over with
+//! [`init_pstore`]. It is split into two slots: each boot copies its output
+//! to one of them, and keeps the output of the previous boot in the other
+//! one. A crash in an interrupt handler on real hardware, where the console
+//! may be dead, can then be read at the next boot with
+//! [`previous_boot_log`] or [`dump_previous_boot_log`].
+//!
+//! The ring is written without the heap, from the first log on, with what
+//! reaches the console: with the `early-buffer` feature, the early output
+//! reaches it once replayed. It is in cacheable memory: the caller must write
+//! back its cache lines before rebooting.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// Marks a slot written by a previous boot.
+const MAGIC: u64 = u64::from_le_bytes(*b"AXPSTORE");
+
+/// The header of a slot, followed by its ring.
+#[repr(C)]
+struct SlotHeader {
+    magic: u64,
+    /// The number of the boot that wrote the slot, from 1.
+    boot: u64,
+    /// Total number of bytes ever written to the ring.
+    written: u64,
+}
+
+const HEADER_SIZE: usize = size_of::<SlotHeader>();
+
+/// A slot of the region.
+#[derive(Clone, Copy)]
+struct Slot {
+    header: NonNull<SlotHeader>,
+    /// The size of the ring after the header.
+    size: usize,
+}
+
+impl Slot {
+    fn header(&self) -> SlotHeader {
+        // SAFETY: In the region given to `init_pstore`, read as is since it
+        // may be from another boot.
+        unsafe { ptr::read_volatile(self.header.as_ptr()) }
+    }
+
+    fn ring(&self) -> *mut u8 {
+        // SAFETY: The ring follows the header in the slot.
+        unsafe { self.header.as_ptr().cast::<u8>().add(HEADER_SIZE) }
+    }
+
+    /// Returns whether the slot holds the output of a boot.
+    fn is_valid(&self) -> bool {
+        let header = self.header();
+        header.magic == MAGIC && header.boot != 0
+    }
+
+    /// Returns the two parts (in order) of the retained bytes.
+    fn contents(&self) -> (&[u8], &[u8]) {
+        let written = self.header().written as usize;
+        // SAFETY: The ring is `size` bytes long, and only written under the
+        // lock of `PSTORE`.
+        let ring = unsafe { core::slice::from_raw_parts(self.ring(), self.size) };
+        if written <= self.size {
+            (&ring[..written], &[])
+        } else {
+            let head = written % self.size;
+            (&ring[head..], &ring[..head])
+        }
+    }
+
+    fn dropped(&self) -> usize {
+        (self.header().written as usize).saturating_sub(self.size)
+    }
+}
+
+struct Pstore {
+    current: Slot,
+    previous: Option<Slot>,
+}
+
+// SAFETY: The region is only used under the lock.
+unsafe impl Send for Pstore {}
+
+static PSTORE: SpinNoIrq<Option<Pstore>> = SpinNoIrq::new(None);
+
+/// Whether the output is copied to the ring, it is checked before locking.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Starts copying the output to the persistent region of `size` bytes at
+/// `base`, and keeps the output of the previous boot found in it.
+///
+/// A region smaller than two headers is ignored. It does nothing if called
+/// again.
+///
+/// # Safety
+///
+/// The region must be mapped, 8-byte aligned, and reserved for this use, for
+/// the whole run of the kernel.
+pub unsafe fn init_pstore(base: *mut u8, size: usize) {
+    let mut pstore = PSTORE.lock();
+    let Some(base) = NonNull::new(base) else {
+        return;
+    };
+    let slot_size = size / 2 / 8 * 8;
+    if pstore.is_some() || slot_size <= HEADER_SIZE {
+        return;
+    }
+    let slots = [0, slot_size].map(|offset| Slot {
+        // SAFETY: In the region.
+        header: unsafe { base.add(offset).cast() },
+        size: slot_size - HEADER_SIZE,
+    });
+    let previous = slots
+        .into_iter()
+        .filter(Slot::is_valid)
+        .max_by_key(|slot| slot.header().boot);
+    let (current, boot) = match previous {
+        Some(previous) if previous.header == slots[0].header => {
+            (slots[1], previous.header().boot + 1)
+        }
+        Some(previous) => (slots[0], previous.header().boot + 1),
+        None => (slots[0], 1),
+    };
+    // SAFETY: The slot is in the region.
+    unsafe {
+        ptr::write_volatile(
+            current.header.as_ptr(),
+            SlotHeader {
+                magic: MAGIC,
+                boot,
+                written: 0,
+            },
+        )
+    };
+    *pstore = Some(Pstore { current, previous });
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Copies `s` to the ring of the current boot, if any.
+pub(crate) fn write(s: &str) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    let pstore = PSTORE.lock();
+    let Some(pstore) = pstore.as_ref() else {
+        return;
+    };
+    let slot = &pstore.current;
+    let mut written = slot.header().written;
+    for &b in s.as_bytes() {
+        // SAFETY: In the ring, under the lock.
+        unsafe { slot.ring().add(written as usize % slot.size).write(b) };
+        written += 1;
+    }
+    // SAFETY: In the region, under the lock.
+    unsafe { ptr::write_volatile(&raw mut (*slot.header.as_ptr()).written, written) };
+}
+
+/// Calls `f` with the output of the previous boot, oldest first, and returns
+/// the number of that boot, or returns `None` if there is none.
+///
+/// The bytes that did not fit in the slot are lost.
+pub fn previous_boot_log(mut f: impl FnMut(&[u8])) -> Option<u64> {
+    // The slot is not written anymore, `f` may log.
+    let previous = PSTORE.lock().as_ref()?.previous?;
+    let (first, second) = previous.contents();
+    f(first);
+    f(second);
+    Some(previous.header().boot)
+}
+
+/// Prints the output of the previous boot, if any, e.g. once the console is
+/// up.
+pub fn dump_previous_boot_log() {
+    // The output is copied to the ring of this boot, not to this slot.
+    let Some(previous) = PSTORE.lock().as_ref().and_then(|pstore| pstore.previous) else {
+        return;
+    };
+    let header = previous.header();
+    crate::__print_impl(format_args!(
+        "[--- output of boot {} ({} bytes dropped) ---]\n",
+        header.boot,
+        previous.dropped()
+    ));
+    let (first, second) = previous.contents();
+    for part in [first, second] {
+        // The ring may split a UTF-8 sequence, only print valid parts.
+        for chunk in part.utf8_chunks() {
+            crate::__print_impl(format_args!("{}", chunk.valid()));
+        }
+    }
+    crate::__print_impl(format_args!("[--- end of boot {} ---]\n", header.boot));
+}
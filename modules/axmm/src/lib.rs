@@ -8,9 +8,13 @@ extern crate alloc;
 
 mod aspace;
 mod backend;
+#[cfg(feature = "mmio-trace")]
+mod mmio_trace;
 
 pub use self::aspace::AddrSpace;
 pub use self::backend::Backend;
+#[cfg(feature = "mmio-trace")]
+pub use self::mmio_trace::trace_mmio_device;
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
@@ -0,0 +1,150 @@
+//! Page-fault-based tracing of MMIO accesses, for debugging drivers.
+//!
+//! While a device is traced, its register pages are unmapped from the kernel
+//! address space, so that every access faults and gets logged with the
+//! device name. On RISC-V, the faulting load or store is performed by the
+//! handler of [`MMIO_ACCESS`] through a temporary mapping, and skipped: all
+//! the accesses are traced. Elsewhere, the first access to each page is
+//! logged, then the page is mapped again so the driver can continue. Call
+//! [`trace_mmio_device`] again to re-arm the trace.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use axerrno::{AxResult, ax_err};
+use axhal::mem::{phys_to_virt, virt_to_phys};
+use axhal::mmio::{MmioDevice, find_device_by_addr};
+use axhal::paging::MappingFlags;
+use axhal::trap::{MMIO_ACCESS, MmioAccess, PAGE_FAULT, register_trap_handler};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, PhysAddr, VirtAddr};
+
+/// Pages (kernel virtual addresses) whose accesses are being traced.
+static TRACED_PAGES: SpinNoIrq<Vec<VirtAddr>> = SpinNoIrq::new(Vec::new());
+
+const MMIO_FLAGS: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::DEVICE);
+
+/// Starts tracing the accesses to the registered MMIO device that contains
+/// `paddr`.
+///
+/// The device must have been registered with [`axhal::mmio::register_device`].
+pub fn trace_mmio_device(paddr: PhysAddr) -> AxResult<MmioDevice> {
+    let Some(dev) = find_device_by_addr(paddr) else {
+        return ax_err!(NotFound, "no MMIO device at this address");
+    };
+    let start = phys_to_virt(dev.paddr).align_down_4k();
+    let end = phys_to_virt(dev.end()).align_up_4k();
+
+    let mut pages = TRACED_PAGES.lock();
+    crate::kernel_aspace().lock().unmap(start, end - start)?;
+    for page in (start.as_usize()..end.as_usize()).step_by(PAGE_SIZE_4K) {
+        let page = VirtAddr::from(page);
+        if !pages.contains(&page) {
+            pages.push(page);
+        }
+    }
+    info!("MMIO trace enabled for {} ({:#x?})", dev.name, dev.paddr);
+    Ok(dev)
+}
+
+fn log_access(vaddr: VirtAddr, access: fmt::Arguments) {
+    let paddr = virt_to_phys(vaddr);
+    match find_device_by_addr(paddr) {
+        Some(dev) => warn!(
+            "MMIO trace: {} {} at offset {:#x} (paddr {:#x})",
+            access,
+            dev.name,
+            paddr - dev.paddr,
+            paddr
+        ),
+        None => warn!("MMIO trace: {} unregistered paddr {:#x}", access, paddr),
+    }
+}
+
+/// Maps the traced `page` back.
+fn map_page(page: VirtAddr) -> bool {
+    crate::kernel_aspace()
+        .lock()
+        .map_linear(page, virt_to_phys(page), PAGE_SIZE_4K, MMIO_FLAGS)
+        .is_ok()
+}
+
+#[register_trap_handler(MMIO_ACCESS)]
+fn handle_mmio_trace_access(vaddr: VirtAddr, access: MmioAccess) -> Option<u64> {
+    let page = vaddr.align_down_4k();
+    if !TRACED_PAGES.lock().contains(&page) {
+        return None;
+    }
+    if !map_page(page) {
+        return None;
+    }
+    // Safety: the page is mapped, and the access is the one the driver made.
+    let value = unsafe {
+        match access {
+            MmioAccess::Read(width) => read(vaddr, width),
+            MmioAccess::Write(width, value) => {
+                write(vaddr, width, value);
+                value
+            }
+        }
+    };
+    let _ = crate::kernel_aspace().lock().unmap(page, PAGE_SIZE_4K);
+    match access {
+        MmioAccess::Read(width) => {
+            log_access(vaddr, format_args!("read{} {:#x} from", width * 8, value))
+        }
+        MmioAccess::Write(width, _) => {
+            log_access(vaddr, format_args!("write{} {:#x} to", width * 8, value))
+        }
+    }
+    Some(value)
+}
+
+unsafe fn read(vaddr: VirtAddr, width: usize) -> u64 {
+    let ptr = vaddr.as_ptr();
+    unsafe {
+        match width {
+            1 => ptr.read_volatile() as u64,
+            2 => (ptr as *const u16).read_volatile() as u64,
+            4 => (ptr as *const u32).read_volatile() as u64,
+            _ => (ptr as *const u64).read_volatile(),
+        }
+    }
+}
+
+unsafe fn write(vaddr: VirtAddr, width: usize, value: u64) {
+    let ptr = vaddr.as_mut_ptr();
+    unsafe {
+        match width {
+            1 => ptr.write_volatile(value as u8),
+            2 => (ptr as *mut u16).write_volatile(value as u16),
+            4 => (ptr as *mut u32).write_volatile(value as u32),
+            _ => (ptr as *mut u64).write_volatile(value),
+        }
+    }
+}
+
+/// Handles the faults that were not performed by [`handle_mmio_trace_access`]:
+/// logs the first access to the page, and maps it back.
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_mmio_trace_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    if is_user {
+        return false;
+    }
+    let page = vaddr.align_down_4k();
+    {
+        let mut pages = TRACED_PAGES.lock();
+        let Some(idx) = pages.iter().position(|p| *p == page) else {
+            return false;
+        };
+        pages.swap_remove(idx);
+    }
+    if access_flags.contains(MappingFlags::WRITE) {
+        log_access(vaddr, format_args!("write to"));
+    } else {
+        log_access(vaddr, format_args!("read from"));
+    }
+    map_page(page)
+}
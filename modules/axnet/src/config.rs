@@ -0,0 +1,174 @@
+//! Runtime network configuration.
+//!
+//! The compile-time settings (`AX_IP`, `AX_GW`) are only the defaults. Each
+//! parameter can be overridden at boot, before [`init_network`] is called,
+//! e.g. from the kernel command line or the device tree `/chosen` node:
+//!
+//! ```text
+//! net.ip=10.0.2.15/24 net.gw=10.0.2.2 net.dns=1.1.1.1 net.hostname=arceos net.mac=52:54:00:12:34:56
+//! ```
+//!
+//! [`init_network`]: crate::init_network
+
+use alloc::string::String;
+use core::net::Ipv4Addr;
+
+use axerrno::{AxError, AxResult};
+use spin::Mutex;
+
+macro_rules! env_or_default {
+    ($key:literal) => {
+        match option_env!($key) {
+            Some(val) => val,
+            None => "",
+        }
+    };
+}
+
+const DEFAULT_IP: &str = env_or_default!("AX_IP");
+const DEFAULT_GATEWAY: &str = env_or_default!("AX_GW");
+const DEFAULT_DNS_SERVER: Ipv4Addr = Ipv4Addr::new(8, 8, 8, 8);
+const DEFAULT_PREFIX_LEN: u8 = 24;
+const DEFAULT_HOSTNAME: &str = "arceos";
+
+/// The prefix of the boot arguments recognized by [`set_bootarg`].
+pub const BOOTARG_PREFIX: &str = "net.";
+
+static OVERRIDES: Mutex<NetConfig> = Mutex::new(NetConfig::empty());
+
+/// Network parameters of the primary interface.
+///
+/// A [`None`] field means "not overridden", the compile-time default or the
+/// value reported by the NIC is used instead.
+#[derive(Debug, Clone)]
+pub struct NetConfig {
+    /// The static IPv4 address.
+    pub ip: Option<Ipv4Addr>,
+    /// The network prefix length of [`ip`](Self::ip).
+    pub prefix_len: Option<u8>,
+    /// The default gateway.
+    pub gateway: Option<Ipv4Addr>,
+    /// The DNS server used by [`dns_query`](crate::dns_query).
+    pub dns: Option<Ipv4Addr>,
+    /// The host name.
+    pub hostname: Option<String>,
+    /// The MAC address to use instead of the one burned into the NIC.
+    ///
+    /// It is ignored with a warning if the NIC cannot be programmed with
+    /// it, which is currently the case of all the drivers.
+    pub mac: Option<[u8; 6]>,
+}
+
+impl NetConfig {
+    const fn empty() -> Self {
+        Self {
+            ip: None,
+            prefix_len: None,
+            gateway: None,
+            dns: None,
+            hostname: None,
+            mac: None,
+        }
+    }
+
+    /// Sets one parameter from its textual form.
+    ///
+    /// Recognized keys are `ip` (`a.b.c.d` or `a.b.c.d/len`), `prefix`, `gw`,
+    /// `dns`, `hostname` and `mac` (`xx:xx:xx:xx:xx:xx`).
+    pub fn set(&mut self, key: &str, value: &str) -> AxResult {
+        match key {
+            "ip" => match value.split_once('/') {
+                Some((ip, len)) => {
+                    self.ip = Some(parse_ipv4(ip)?);
+                    self.prefix_len = Some(parse_prefix_len(len)?);
+                }
+                None => self.ip = Some(parse_ipv4(value)?),
+            },
+            "prefix" => self.prefix_len = Some(parse_prefix_len(value)?),
+            "gw" => self.gateway = Some(parse_ipv4(value)?),
+            "dns" => self.dns = Some(parse_ipv4(value)?),
+            "hostname" if !value.is_empty() => self.hostname = Some(value.into()),
+            "mac" => self.mac = Some(parse_mac(value)?),
+            _ => return Err(AxError::InvalidInput),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn ip(&self) -> Ipv4Addr {
+        self.ip
+            .unwrap_or_else(|| DEFAULT_IP.parse().expect("invalid IP address"))
+    }
+
+    pub(crate) fn prefix_len(&self) -> u8 {
+        self.prefix_len.unwrap_or(DEFAULT_PREFIX_LEN)
+    }
+
+    pub(crate) fn gateway(&self) -> Ipv4Addr {
+        self.gateway
+            .unwrap_or_else(|| DEFAULT_GATEWAY.parse().expect("invalid gateway IP address"))
+    }
+
+    pub(crate) fn dns(&self) -> Ipv4Addr {
+        self.dns.unwrap_or(DEFAULT_DNS_SERVER)
+    }
+}
+
+fn parse_ipv4(s: &str) -> AxResult<Ipv4Addr> {
+    s.parse().map_err(|_| AxError::InvalidInput)
+}
+
+fn parse_prefix_len(s: &str) -> AxResult<u8> {
+    match s.parse() {
+        Ok(len) if len <= 32 => Ok(len),
+        _ => Err(AxError::InvalidInput),
+    }
+}
+
+fn parse_mac(s: &str) -> AxResult<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = s.split([':', '-']);
+    for byte in mac.iter_mut() {
+        let part = parts.next().ok_or(AxError::InvalidInput)?;
+        if part.len() != 2 {
+            return Err(AxError::InvalidInput);
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| AxError::InvalidInput)?;
+    }
+    if parts.next().is_some() {
+        return Err(AxError::InvalidInput);
+    }
+    Ok(mac)
+}
+
+/// Overrides one network parameter, see [`NetConfig::set`] for the keys.
+///
+/// It must be called before [`init_network`](crate::init_network) to take
+/// effect.
+pub fn set_config(key: &str, value: &str) -> AxResult {
+    OVERRIDES.lock().set(key, value)
+}
+
+/// Applies one `net.<key>=<value>` boot argument.
+///
+/// Returns `Ok(false)` if the argument does not start with
+/// [`BOOTARG_PREFIX`] and is therefore not for the network subsystem.
+pub fn set_bootarg(key: &str, value: &str) -> AxResult<bool> {
+    match key.strip_prefix(BOOTARG_PREFIX) {
+        Some(key) => set_config(key, value).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Returns the current network configuration overrides.
+pub fn config() -> NetConfig {
+    OVERRIDES.lock().clone()
+}
+
+/// Returns the host name, e.g. for `gethostname`.
+pub fn hostname() -> String {
+    OVERRIDES
+        .lock()
+        .hostname
+        .clone()
+        .unwrap_or_else(|| DEFAULT_HOSTNAME.into())
+}
@@ -0,0 +1,328 @@
+//! A small authoritative DNS responder, e.g. for a captive network.
+//!
+//! It answers the `A`, `AAAA` and `PTR` queries over UDP from a static
+//! [`DnsZone`]. Several responders can share a zone, e.g. one per address:
+//!
+//! ```ignore
+//! let mut zone = DnsZone::new();
+//! zone.add("portal.local", IpAddr::V4(PORTAL))
+//!     .set_catch_all(Some(PORTAL));
+//! let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), DNS_PORT);
+//! let responder = DnsResponder::bind(addr, Arc::new(zone))?;
+//! axasync::spawn_named("dns", responder.run());
+//! ```
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use axerrno::AxResult;
+
+use crate::UdpSocket;
+
+/// The port of the DNS servers.
+pub const DNS_PORT: u16 = 53;
+/// The time to live of the answers, in seconds, unless set with
+/// [`DnsZone::set_ttl`].
+pub const DEFAULT_DNS_TTL: u32 = 60;
+
+/// The longest DNS message over UDP, without EDNS.
+const MAX_MESSAGE_LEN: usize = 512;
+const HEADER_LEN: usize = 12;
+const MAX_LABEL_LEN: usize = 63;
+const MAX_NAME_LEN: usize = 253;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const FLAG_QR: u16 = 1 << 15;
+const OPCODE_MASK: u16 = 0xf << 11;
+const FLAG_AA: u16 = 1 << 10;
+const FLAG_TC: u16 = 1 << 9;
+const FLAG_RD: u16 = 1 << 8;
+
+const RCODE_FORMERR: u16 = 1;
+const RCODE_NXDOMAIN: u16 = 3;
+const RCODE_NOTIMP: u16 = 4;
+
+/// The records a [`DnsResponder`] answers with.
+#[derive(Debug, Clone)]
+pub struct DnsZone {
+    /// The names, in lowercase and without the trailing dot, and their
+    /// addresses.
+    records: Vec<(String, IpAddr)>,
+    catch_all: Option<Ipv4Addr>,
+    ttl: u32,
+}
+
+/// An answer to a question.
+enum Answer<'a> {
+    Addr(IpAddr),
+    Name(&'a str),
+}
+
+impl DnsZone {
+    /// Creates an empty zone.
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            catch_all: None,
+            ttl: DEFAULT_DNS_TTL,
+        }
+    }
+
+    /// Adds the address `addr` to `name`: it answers the `A` or `AAAA`
+    /// queries of the name, and the `PTR` queries of the address. A name may
+    /// have several addresses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid domain name.
+    pub fn add(&mut self, name: &str, addr: IpAddr) -> &mut Self {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        assert!(
+            name.len() <= MAX_NAME_LEN
+                && name
+                    .split('.')
+                    .all(|label| !label.is_empty() && label.len() <= MAX_LABEL_LEN),
+            "invalid domain name"
+        );
+        self.records.push((name, addr));
+        self
+    }
+
+    /// Answers the `A` queries of the names not in the zone with `addr`, as
+    /// a captive portal does. With `None`, the default, they fail with
+    /// `NXDOMAIN`.
+    pub fn set_catch_all(&mut self, addr: Option<Ipv4Addr>) -> &mut Self {
+        self.catch_all = addr;
+        self
+    }
+
+    /// Sets the time to live of the answers, in seconds.
+    pub fn set_ttl(&mut self, ttl: u32) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns the answers of the question of `name` (in lowercase) and
+    /// `qtype`, or `None` if the name does not exist.
+    fn answers(&self, name: &str, qtype: u16) -> Option<Vec<Answer<'_>>> {
+        if name.ends_with(".in-addr.arpa") || name.ends_with(".ip6.arpa") {
+            let answers: Vec<_> = self
+                .records
+                .iter()
+                .filter(|(_, addr)| reverse_name(*addr) == name)
+                .map(|(record, _)| Answer::Name(record))
+                .collect();
+            return match (answers.is_empty(), qtype) {
+                (true, _) => None,
+                (false, TYPE_PTR) => Some(answers),
+                (false, _) => Some(Vec::new()),
+            };
+        }
+
+        let mut known = false;
+        let mut answers = Vec::new();
+        for (_, addr) in self.records.iter().filter(|(record, _)| record == name) {
+            known = true;
+            match (addr, qtype) {
+                (IpAddr::V4(_), TYPE_A) | (IpAddr::V6(_), TYPE_AAAA) => {
+                    answers.push(Answer::Addr(*addr))
+                }
+                _ => {}
+            }
+        }
+        match (known, self.catch_all) {
+            (true, _) => Some(answers),
+            (false, Some(addr)) if qtype == TYPE_A => Some([Answer::Addr(IpAddr::V4(addr))].into()),
+            (false, Some(_)) => Some(Vec::new()),
+            (false, None) => None,
+        }
+    }
+}
+
+impl Default for DnsZone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the name of the `PTR` records of `addr`.
+fn reverse_name(addr: IpAddr) -> String {
+    let mut name = String::new();
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            let _ = write!(name, "{}.{}.{}.{}.in-addr.arpa", d, c, b, a);
+        }
+        IpAddr::V6(addr) => {
+            for byte in addr.octets().iter().rev() {
+                let _ = write!(name, "{:x}.{:x}.", byte & 0xf, byte >> 4);
+            }
+            name.push_str("ip6.arpa");
+        }
+    }
+    name
+}
+
+fn push_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.') {
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(msg.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+/// The question of a query.
+struct Question {
+    /// The name, in lowercase.
+    name: String,
+    qtype: u16,
+    qclass: u16,
+    /// Where the question ends in the query.
+    end: usize,
+}
+
+fn parse_question(msg: &[u8]) -> Option<Question> {
+    let mut pos = HEADER_LEN;
+    let mut name = String::new();
+    loop {
+        let len = *msg.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // There is no name before it to point to.
+        if len > MAX_LABEL_LEN || name.len() + len >= MAX_NAME_LEN {
+            return None;
+        }
+        let label = core::str::from_utf8(msg.get(pos..pos + len)?).ok()?;
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(label);
+        pos += len;
+    }
+    name.make_ascii_lowercase();
+    Some(Question {
+        name,
+        qtype: read_u16(msg, pos)?,
+        qclass: read_u16(msg, pos + 2)?,
+        end: pos + 4,
+    })
+}
+
+/// Builds the response to the query `msg` from `zone`, or returns `None` if
+/// it must be dropped.
+fn respond(zone: &DnsZone, msg: &[u8]) -> Option<Vec<u8>> {
+    let id = read_u16(msg, 0)?;
+    let flags = read_u16(msg, 2)?;
+    if flags & FLAG_QR != 0 {
+        // Not a query, answering it could loop.
+        return None;
+    }
+    let flags = FLAG_QR | FLAG_AA | (flags & (OPCODE_MASK | FLAG_RD));
+    let header = |flags: u16, questions: u16, answers: u16| {
+        let mut out = Vec::with_capacity(MAX_MESSAGE_LEN);
+        for field in [id, flags, questions, answers, 0, 0] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        out
+    };
+
+    if flags & OPCODE_MASK != 0 {
+        return Some(header(flags | RCODE_NOTIMP, 0, 0));
+    }
+    let question = match (read_u16(msg, 4)?, parse_question(msg)) {
+        (1, Some(question)) => question,
+        _ => return Some(header(flags | RCODE_FORMERR, 0, 0)),
+    };
+    if question.qclass != CLASS_IN {
+        return Some(header(flags | RCODE_NOTIMP, 0, 0));
+    }
+    trace!("dns: query of {} type {}", question.name, question.qtype);
+    let Some(answers) = zone.answers(&question.name, question.qtype) else {
+        let mut out = header(flags | RCODE_NXDOMAIN, 1, 0);
+        out.extend_from_slice(&msg[HEADER_LEN..question.end]);
+        return Some(out);
+    };
+
+    let mut out = header(flags, 1, 0);
+    out.extend_from_slice(&msg[HEADER_LEN..question.end]);
+    let mut count: u16 = 0;
+    for answer in answers {
+        let mut record = Vec::new();
+        // A pointer to the name of the question.
+        record.extend_from_slice(&0xc00cu16.to_be_bytes());
+        let (rtype, rdata) = match answer {
+            Answer::Addr(IpAddr::V4(addr)) => (TYPE_A, addr.octets().to_vec()),
+            Answer::Addr(IpAddr::V6(addr)) => (TYPE_AAAA, addr.octets().to_vec()),
+            Answer::Name(name) => {
+                let mut rdata = Vec::new();
+                push_name(&mut rdata, name);
+                (TYPE_PTR, rdata)
+            }
+        };
+        record.extend_from_slice(&rtype.to_be_bytes());
+        record.extend_from_slice(&CLASS_IN.to_be_bytes());
+        record.extend_from_slice(&zone.ttl.to_be_bytes());
+        record.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        record.extend_from_slice(&rdata);
+        if out.len() + record.len() > MAX_MESSAGE_LEN {
+            out[2..4].copy_from_slice(&(flags | FLAG_TC).to_be_bytes());
+            break;
+        }
+        out.extend_from_slice(&record);
+        count += 1;
+    }
+    out[6..8].copy_from_slice(&count.to_be_bytes());
+    Some(out)
+}
+
+/// An authoritative DNS server over UDP, that answers from a [`DnsZone`].
+///
+/// See the [module documentation](self).
+pub struct DnsResponder {
+    socket: UdpSocket,
+    zone: Arc<DnsZone>,
+}
+
+impl DnsResponder {
+    /// Binds a responder to `addr`, usually on [`DNS_PORT`].
+    pub fn bind(addr: SocketAddr, zone: Arc<DnsZone>) -> AxResult<Self> {
+        let socket = UdpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(addr)?;
+        Ok(Self { socket, zone })
+    }
+
+    /// Returns the address the responder is bound to.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Answers the queries until receiving fails. Spawn it in a task of its
+    /// own.
+    pub async fn run(self) -> AxResult {
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        loop {
+            let (len, peer) = self.socket.recv_from_async(&mut buf).await?;
+            let Some(response) = respond(&self.zone, &buf[..len]) else {
+                continue;
+            };
+            // Like a lost datagram, the client retries.
+            if let Err(err) = self.socket.send_to(&response, peer) {
+                debug!("dns: response to {} dropped: {:?}", peer, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Events of the network device, for the tasks that react to them.
+//!
+//! The errors of the device are delivered on the high lane of the channel,
+//! ahead of the backlog of receive events, so that they are handled at once:
+//!
+//! ```ignore
+//! let mut events = axnet::subscribe_events().unwrap();
+//! while let Some(event) = events.recv().await {
+//!     match event {
+//!         NetEvent::DeviceError { op, err } => reset_link(op, err),
+//!         NetEvent::Received => {}
+//!     }
+//! }
+//! ```
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axasync::sync::lanes::{self, Lane, Receiver, Sender, TrySendError};
+use axdriver_net::DevError;
+use lazyinit::LazyInit;
+
+/// The most receive events queued until they are taken, the newer ones are
+/// dropped beyond. The errors are never dropped.
+pub const MAX_PENDING_NET_EVENTS: usize = 256;
+
+/// An event of the network device.
+#[derive(Debug)]
+pub enum NetEvent {
+    /// The device raised a receive interrupt.
+    Received,
+    /// An operation of the device failed.
+    DeviceError {
+        /// The failed operation.
+        op: &'static str,
+        /// The error of the device.
+        err: DevError,
+    },
+}
+
+impl NetEvent {
+    /// Returns the lane the event is delivered on.
+    fn lane(&self) -> Lane {
+        match self {
+            Self::Received => Lane::Normal,
+            Self::DeviceError { .. } => Lane::High,
+        }
+    }
+}
+
+static SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+static EVENTS: LazyInit<Sender<NetEvent>> = LazyInit::new();
+
+/// Subscribes to the events of the network device.
+///
+/// There is a single subscriber, returns `None` if there is already one.
+pub fn subscribe_events() -> Option<Receiver<NetEvent>> {
+    if SUBSCRIBED.swap(true, Ordering::AcqRel) {
+        return None;
+    }
+    let (sender, receiver) = lanes::channel(MAX_PENDING_NET_EVENTS);
+    EVENTS.init_once(sender);
+    Some(receiver)
+}
+
+/// Publishes `event` to the subscriber, if any. Called from the interrupt
+/// handler too, it never waits.
+pub(crate) fn publish(event: NetEvent) {
+    let Some(sender) = EVENTS.get() else {
+        return;
+    };
+    let lane = event.lane();
+    match sender.try_send(event, lane) {
+        Ok(()) | Err(TrySendError::Closed(_)) => {}
+        Err(TrySendError::Full(event)) => trace!("net event dropped: {:?}", event),
+    }
+}
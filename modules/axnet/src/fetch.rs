@@ -0,0 +1,353 @@
+//! Netboot-style download of files over HTTP or TFTP.
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::pin::Pin;
+use core::time::Duration;
+
+use axasync::TimeoutExt;
+use axasync::io::AsyncWrite;
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+
+use crate::{TcpSocket, UdpSocket, dns_query, poll_interfaces};
+
+/// The maximum length of an HTTP response header.
+const HTTP_MAX_HEADER_LEN: usize = 8 * 1024;
+
+const TFTP_PORT: u16 = 69;
+const TFTP_BLOCK_SIZE: usize = 512;
+/// How long to wait for a TFTP packet before retransmitting.
+const TFTP_TIMEOUT: Duration = Duration::from_secs(1);
+const TFTP_MAX_RETRIES: usize = 5;
+
+const TFTP_RRQ: u16 = 1;
+const TFTP_DATA: u16 = 3;
+const TFTP_ACK: u16 = 4;
+const TFTP_ERROR: u16 = 5;
+const TFTP_OACK: u16 = 6;
+
+/// The progress of a [`fetch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// The number of bytes received so far.
+    pub received: u64,
+    /// The size of the file, if the server announced it.
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Http,
+    Tftp,
+}
+
+struct Url<'a> {
+    scheme: Scheme,
+    host: &'a str,
+    port: u16,
+    /// The path, starting with `/`.
+    path: &'a str,
+}
+
+impl<'a> Url<'a> {
+    fn parse(url: &'a str) -> AxResult<Self> {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return ax_err!(InvalidInput, "fetch: URL without a scheme");
+        };
+        let (scheme, default_port) = if scheme.eq_ignore_ascii_case("http") {
+            (Scheme::Http, 80)
+        } else if scheme.eq_ignore_ascii_case("tftp") {
+            (Scheme::Tftp, TFTP_PORT)
+        } else {
+            return ax_err!(Unsupported, "fetch: unsupported URL scheme");
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| ax_err_type!(InvalidInput, "fetch: invalid port"))?,
+            ),
+            None => (authority, default_port),
+        };
+        if host.is_empty() {
+            return ax_err!(InvalidInput, "fetch: URL without a host");
+        }
+        Ok(Self {
+            scheme,
+            host,
+            port,
+            path,
+        })
+    }
+}
+
+fn resolve(host: &str) -> AxResult<IpAddr> {
+    if let Ok(ip) = host.parse() {
+        return Ok(ip);
+    }
+    dns_query(host)?
+        .first()
+        .copied()
+        .ok_or_else(|| ax_err_type!(NotFound, "fetch: host not found"))
+}
+
+async fn write_all<W: AsyncWrite + Unpin>(sink: &mut W, mut buf: &[u8]) -> AxResult {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *sink).poll_write(cx, buf)).await?;
+        if n == 0 {
+            return ax_err!(WriteZero, "fetch: sink is full");
+        }
+        buf = &buf[n..];
+    }
+    Ok(())
+}
+
+/// Downloads the file at `url` into `sink`, and returns its size.
+///
+/// Supported URLs are:
+///
+/// - `http://host[:port]/path`, fetched with an HTTP/1.0 `GET`. Redirects
+///   are not followed.
+/// - `tftp://host[:port]/path`, fetched in octet mode (RFC 1350). The size
+///   of the file is asked for with the `tsize` option (RFC 2349).
+///
+/// `host` is an IPv4 address or a name resolved with [`dns_query`].
+/// `progress` is called each time data is written to `sink`, e.g. to print a
+/// progress bar while pulling a firmware image at boot. The sink is flushed
+/// at the end, but not on failure, when it contains a partial file.
+pub async fn fetch<W, P>(url: &str, mut sink: W, mut progress: P) -> AxResult<u64>
+where
+    W: AsyncWrite + Unpin,
+    P: FnMut(FetchProgress),
+{
+    let url = Url::parse(url)?;
+    let server = SocketAddr::new(resolve(url.host)?, url.port);
+    info!("fetch: {} from {}", url.path, server);
+    let size = match url.scheme {
+        Scheme::Http => http_get(server, &url, &mut sink, &mut progress).await?,
+        Scheme::Tftp => tftp_get(server, &url.path[1..], &mut sink, &mut progress).await?,
+    };
+    poll_fn(|cx| Pin::new(&mut sink).poll_flush(cx)).await?;
+    info!("fetch: {} done, {} bytes", url.path, size);
+    Ok(size)
+}
+
+async fn tcp_recv(socket: &TcpSocket, buf: &mut [u8]) -> AxResult<usize> {
+    poll_interfaces();
+    socket.recv_async(buf).await
+}
+
+async fn http_get<W: AsyncWrite + Unpin>(
+    server: SocketAddr,
+    url: &Url<'_>,
+    sink: &mut W,
+    progress: &mut dyn FnMut(FetchProgress),
+) -> AxResult<u64> {
+    let socket = TcpSocket::new();
+    socket.connect_async(server).await?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: ArceOS\r\nConnection: close\r\n\r\n",
+        url.path, url.host
+    );
+    let mut request = request.as_bytes();
+    while !request.is_empty() {
+        let n = socket.send_async(request).await?;
+        request = &request[n..];
+        poll_interfaces();
+    }
+
+    let mut chunk = [0; 1024];
+    let mut response = Vec::new();
+    let body_start = loop {
+        if let Some(pos) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if response.len() > HTTP_MAX_HEADER_LEN {
+            return ax_err!(InvalidData, "fetch: HTTP header too long");
+        }
+        let n = tcp_recv(&socket, &mut chunk).await?;
+        if n == 0 {
+            return ax_err!(UnexpectedEof, "fetch: HTTP connection closed");
+        }
+        response.extend_from_slice(&chunk[..n]);
+    };
+    let total = parse_http_header(&response[..body_start])?;
+
+    let mut received = 0;
+    let mut data = &response[body_start..];
+    loop {
+        if !data.is_empty() {
+            write_all(sink, data).await?;
+            received += data.len() as u64;
+            progress(FetchProgress { received, total });
+        }
+        let n = tcp_recv(&socket, &mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        data = &chunk[..n];
+    }
+    if total.is_some_and(|total| received < total) {
+        return ax_err!(UnexpectedEof, "fetch: HTTP body truncated");
+    }
+    socket.shutdown().ok();
+    Ok(received)
+}
+
+/// Checks the status of an HTTP response, and returns its `Content-Length`.
+fn parse_http_header(header: &[u8]) -> AxResult<Option<u64>> {
+    let header = core::str::from_utf8(header)
+        .map_err(|_| ax_err_type!(InvalidData, "fetch: invalid HTTP header"))?;
+    let mut lines = header.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let status: u16 = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| ax_err_type!(InvalidData, "fetch: invalid HTTP status line"))?;
+    match status {
+        200..=299 => {}
+        401 | 403 => return ax_err!(PermissionDenied, "fetch: HTTP access denied"),
+        404 | 410 => return ax_err!(NotFound, "fetch: HTTP file not found"),
+        300..=399 => {
+            warn!("fetch: HTTP redirects are not supported: {}", status_line);
+            return Err(AxError::Unsupported);
+        }
+        _ => {
+            warn!("fetch: HTTP request failed: {}", status_line);
+            return Err(AxError::Io);
+        }
+    }
+    Ok(lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok()))
+}
+
+fn tftp_packet(opcode: u16, fields: &[&[u8]]) -> Vec<u8> {
+    let mut packet = opcode.to_be_bytes().to_vec();
+    for field in fields {
+        packet.extend_from_slice(field);
+    }
+    packet
+}
+
+fn tftp_ack(block: u16) -> Vec<u8> {
+    tftp_packet(TFTP_ACK, &[&block.to_be_bytes()])
+}
+
+/// Returns the `tsize` option of an OACK packet.
+fn tftp_tsize(options: &[u8]) -> Option<u64> {
+    let mut fields = options
+        .split(|&b| b == 0)
+        .map(|field| core::str::from_utf8(field).unwrap_or_default());
+    while let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+        if name.eq_ignore_ascii_case("tsize") {
+            return value.parse().ok();
+        }
+    }
+    None
+}
+
+fn tftp_error(code: u16, msg: &[u8]) -> AxError {
+    let msg = msg.split(|&b| b == 0).next().unwrap_or_default();
+    warn!(
+        "fetch: TFTP error {}: {}",
+        code,
+        core::str::from_utf8(msg).unwrap_or_default()
+    );
+    match code {
+        1 => AxError::NotFound,
+        2 => AxError::PermissionDenied,
+        3 => AxError::StorageFull,
+        _ => AxError::Io,
+    }
+}
+
+async fn tftp_get<W: AsyncWrite + Unpin>(
+    server: SocketAddr,
+    path: &str,
+    sink: &mut W,
+    progress: &mut dyn FnMut(FetchProgress),
+) -> AxResult<u64> {
+    let socket = UdpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))?;
+
+    // The server answers from a new port, its transfer ID.
+    let mut peer = None;
+    let mut dest = server;
+    let mut last_sent = tftp_packet(TFTP_RRQ, &[path.as_bytes(), b"\0octet\0tsize\00\0"]);
+    socket.send_to(&last_sent, dest)?;
+    poll_interfaces();
+
+    let mut total = None;
+    let mut received = 0;
+    let mut block: u16 = 1;
+    let mut retries = 0;
+    let mut packet = [0; 4 + TFTP_BLOCK_SIZE];
+    loop {
+        let Ok(res) = socket
+            .recv_from_async(&mut packet)
+            .timeout(TFTP_TIMEOUT)
+            .await
+        else {
+            retries += 1;
+            if retries > TFTP_MAX_RETRIES {
+                return ax_err!(Io, "fetch: TFTP server timed out");
+            }
+            debug!("fetch: TFTP timeout, retransmitting");
+            socket.send_to(&last_sent, dest)?;
+            continue;
+        };
+        let (len, from) = res?;
+        let from_peer = match peer {
+            Some(peer) => from == peer,
+            None => from.ip() == server.ip(),
+        };
+        if !from_peer || len < 4 {
+            continue;
+        }
+        let opcode = u16::from_be_bytes([packet[0], packet[1]]);
+        let arg = u16::from_be_bytes([packet[2], packet[3]]);
+        match opcode {
+            TFTP_DATA if arg == block => {
+                peer = Some(from);
+                dest = from;
+                retries = 0;
+                let data = &packet[4..len];
+                write_all(sink, data).await?;
+                received += data.len() as u64;
+                progress(FetchProgress { received, total });
+                last_sent = tftp_ack(block);
+                socket.send_to(&last_sent, dest)?;
+                poll_interfaces();
+                if data.len() < TFTP_BLOCK_SIZE {
+                    return Ok(received);
+                }
+                block = block.wrapping_add(1);
+            }
+            TFTP_DATA | TFTP_OACK if peer.is_some() => {
+                // A duplicate, our ACK was lost.
+                socket.send_to(&last_sent, dest)?;
+            }
+            TFTP_OACK if peer.is_none() => {
+                peer = Some(from);
+                dest = from;
+                retries = 0;
+                total = tftp_tsize(&packet[2..len]);
+                last_sent = tftp_ack(0);
+                socket.send_to(&last_sent, dest)?;
+                poll_interfaces();
+            }
+            TFTP_ERROR => return Err(tftp_error(arg, &packet[4..len])),
+            _ => return ax_err!(InvalidData, "fetch: unexpected TFTP packet"),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Reaping of the connections that went idle.
+//!
+//! A client that neither sends nor closes would hold its connection, and the
+//! task serving it, forever. An [`IdleReaper`] scans the connections it
+//! tracks at regular intervals, and resets those idle past its timeout:
+//!
+//! ```ignore
+//! let reaper = IdleReaper::new(Duration::from_secs(30));
+//! axasync::spawn(reaper.clone().run());
+//! loop {
+//!     let (socket, _) = listener.accept_async().await?;
+//!     let stream = TcpStream::from(socket);
+//!     reaper.track(&stream);
+//!     axasync::spawn(serve(stream));
+//! }
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::net::SocketAddr;
+use core::pin::pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Poll, Waker};
+use core::time::Duration;
+
+use axasync::time::Sleep;
+use spin::Mutex;
+
+use crate::{TcpSocket, TcpStream};
+
+/// The most reap events kept until they are taken, the oldest are dropped
+/// beyond.
+pub const MAX_PENDING_REAP_EVENTS: usize = 64;
+/// The shortest interval between two scans.
+const MIN_SCAN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A connection reset by an [`IdleReaper`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReapEvent {
+    /// The address of the peer.
+    pub peer: Option<SocketAddr>,
+    /// How long the connection was idle.
+    pub idle: Duration,
+}
+
+/// Resets the tracked connections that are idle past a timeout, see the
+/// [module documentation](self).
+///
+/// The clones share the tracked connections and the events.
+#[derive(Clone)]
+pub struct IdleReaper {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// The idle timeout, in nanoseconds.
+    timeout: AtomicU64,
+    conns: Mutex<Vec<Weak<TcpSocket>>>,
+    events: Mutex<Events>,
+}
+
+struct Events {
+    queue: VecDeque<ReapEvent>,
+    /// The waiter of [`IdleReaper::next_event`].
+    waker: Option<Waker>,
+}
+
+impl IdleReaper {
+    /// Creates a reaper of the connections idle for `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                timeout: AtomicU64::new(timeout.as_nanos() as u64),
+                conns: Mutex::new(Vec::new()),
+                events: Mutex::new(Events {
+                    queue: VecDeque::new(),
+                    waker: None,
+                }),
+            }),
+        }
+    }
+
+    /// Returns the idle timeout.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_nanos(self.inner.timeout.load(Ordering::Relaxed))
+    }
+
+    /// Sets the idle timeout, from the next scan on.
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.inner
+            .timeout
+            .store(timeout.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Tracks the connection of `stream`, until it is closed or all its
+    /// clones are dropped.
+    pub fn track(&self, stream: &TcpStream) {
+        let socket = stream.shared_socket();
+        // A socket connected by the blocking calls has no activity yet.
+        socket.init_activity();
+        self.inner.conns.lock().push(Arc::downgrade(socket));
+    }
+
+    /// Returns the number of connections tracked.
+    pub fn tracked(&self) -> usize {
+        self.inner.conns.lock().len()
+    }
+
+    /// Resets the tracked connections idle past the timeout now, and returns
+    /// how many there were.
+    pub fn reap(&self) -> usize {
+        let timeout = self.timeout();
+        let mut idle_conns = Vec::new();
+        self.inner.conns.lock().retain(|conn| {
+            let Some(socket) = conn.upgrade() else {
+                return false;
+            };
+            if !socket.is_connected() {
+                return false;
+            }
+            let idle = socket.idle_time();
+            if idle < timeout {
+                return true;
+            }
+            idle_conns.push((socket, idle));
+            false
+        });
+
+        let reaped = idle_conns.len();
+        for (socket, idle) in idle_conns {
+            let peer = socket.peer_addr().ok();
+            debug!("reaping TCP connection of {:?}, idle for {:?}", peer, idle);
+            socket.abort().ok();
+            self.publish(ReapEvent { peer, idle });
+        }
+        reaped
+    }
+
+    /// Scans the tracked connections at intervals of a quarter of the
+    /// timeout, and resets the idle ones.
+    ///
+    /// It is meant to be spawned, and returns once all the other clones of
+    /// the reaper are dropped.
+    pub async fn run(self) {
+        let mut sleep = pin!(Sleep::new(self.scan_interval()));
+        while Arc::strong_count(&self.inner) > 1 {
+            sleep.as_mut().await;
+            self.reap();
+            let next = sleep.deadline() + self.scan_interval();
+            sleep.as_mut().reset_until(next);
+        }
+    }
+
+    /// Waits for the next connection reset.
+    pub async fn next_event(&self) -> ReapEvent {
+        poll_fn(|cx| {
+            let mut events = self.inner.events.lock();
+            match events.queue.pop_front() {
+                Some(event) => Poll::Ready(event),
+                None => {
+                    events.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    fn scan_interval(&self) -> Duration {
+        (self.timeout() / 4).max(MIN_SCAN_INTERVAL)
+    }
+
+    fn publish(&self, event: ReapEvent) {
+        let waker = {
+            let mut events = self.inner.events.lock();
+            if events.queue.len() == MAX_PENDING_REAP_EVENTS {
+                events.queue.pop_front();
+            }
+            events.queue.push_back(event);
+            events.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
@@ -7,15 +7,38 @@
 //! # Organization
 //!
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
+//! - [`set_default_handshake_timeout`]: Bound on the time a connection waits
+//!   to be established, or for its first byte once accepted.
+//! - [`TcpStream`]: A cloneable async TCP connection (requires `async`).
+//! - [`IdleReaper`]: Reset of the connections that went idle (requires
+//!   `async`).
+//! - [`subscribe_events`]: The events of the network device, its errors ahead
+//!   of the receive backlog (requires `async`).
+//! - [`set_leak_policy`]: Report, or close, the sockets left open by the
+//!   tasks that exited (requires `leak-detect`).
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`DnsResponder`]: An authoritative DNS server that answers from a static
+//!   [`DnsZone`] (requires `async`).
+//! - [`poll_stats`]: Timing of the polls of the network stack.
+//! - [`listen_stats`]: Occupancy of the SYN queues, and wait of the accepts.
+//! - [`fetch`]: Download of files over HTTP or TFTP (requires `async`).
+//! - [`config`]: Network parameters that can be overridden at boot.
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `async`: Async socket operations, for the [axasync] runtime. On platforms
+//!   without interrupts, the runtime polls the stack between its time slices.
+//! - `memwatch`: Back off under memory pressure, as published by
+//!   `axasync::mem`: the new TCP sockets get smaller buffers when the memory
+//!   is low, and the async accepts pause when it is critical.
+//! - `leak-detect`: Track the task that owns each socket, and report the
+//!   sockets still open when it exits, see [`set_leak_policy`].
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
+//! [axasync]: https://arceos-org.github.io/arceos/axasync/index.html
 
 #![no_std]
 
@@ -23,6 +46,18 @@
 extern crate log;
 extern crate alloc;
 
+pub mod config;
+#[cfg(feature = "async")]
+mod dns_server;
+#[cfg(feature = "async")]
+mod event;
+#[cfg(feature = "async")]
+mod fetch;
+#[cfg(feature = "async")]
+pub mod idle;
+#[cfg(feature = "async")]
+mod stream;
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "smoltcp")] {
         mod smoltcp_impl;
@@ -30,11 +65,29 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+pub use self::net_impl::{
+    ACCEPT_WAIT_BUCKETS_MICROS, ListenStats, PortListenStats, listen_stats, port_listen_stats,
+    reset_listen_stats, set_listen_overflow_handler,
+};
+#[cfg(feature = "leak-detect")]
+pub use self::net_impl::{LeakPolicy, set_leak_policy};
+pub use self::net_impl::{PollStats, poll_stats, reset_poll_stats};
+pub use self::net_impl::{TcpSocket, set_default_handshake_timeout};
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
 
+#[cfg(feature = "async")]
+pub use self::dns_server::{DEFAULT_DNS_TTL, DNS_PORT, DnsResponder, DnsZone};
+#[cfg(feature = "async")]
+pub use self::event::{MAX_PENDING_NET_EVENTS, NetEvent, subscribe_events};
+#[cfg(feature = "async")]
+pub use self::fetch::{FetchProgress, fetch};
+#[cfg(feature = "async")]
+pub use self::idle::{IdleReaper, ReapEvent};
+#[cfg(feature = "async")]
+pub use self::stream::{OwnedReadHalf, OwnedWriteHalf, TcpStream};
+
 use axdriver::{AxDeviceContainer, prelude::*};
 
 /// Initializes the network subsystem by NIC devices.
@@ -1,5 +1,6 @@
 use super::{AxNetRxToken, AxNetTxToken, STANDARD_MTU};
 use super::{DeviceWrapper, InterfaceWrapper};
+use axhal::perf::{PerfCounters, PerfMeasurement};
 use smoltcp::phy::{Device, RxToken, TxToken};
 
 const GB: usize = 1000 * MB;
@@ -13,6 +14,7 @@ impl DeviceWrapper {
         let mut send_bytes: usize = 0;
         let mut past_send_bytes: usize = 0;
         let mut past_time = InterfaceWrapper::current_time();
+        let mut perf = PerfMeasurement::start();
 
         // Send bytes
         while send_bytes < MAX_SEND_BYTES {
@@ -36,6 +38,11 @@ impl DeviceWrapper {
                     "Transmit: {}.{:03}GBytes, Bandwidth: {}.{:03}Gbits/sec.",
                     gib, mib, gb, mb
                 );
+                log_perf(
+                    perf.stop(),
+                    ((send_bytes - past_send_bytes) / STANDARD_MTU).max(1),
+                );
+                perf = PerfMeasurement::start();
                 past_time = current_time;
                 past_send_bytes = send_bytes;
             }
@@ -48,6 +55,7 @@ impl DeviceWrapper {
         let mut receive_bytes: usize = 0;
         let mut past_receive_bytes: usize = 0;
         let mut past_time = InterfaceWrapper::current_time();
+        let mut perf = PerfMeasurement::start();
         // Receive bytes
         while receive_bytes < MAX_RECEIVE_BYTES {
             if let Some(rx_token) = self.receive(InterfaceWrapper::current_time()) {
@@ -66,9 +74,24 @@ impl DeviceWrapper {
                     "Receive: {}.{:03}GBytes, Bandwidth: {}.{:03}Gbits/sec.",
                     gib, mib, gb, mb
                 );
+                log_perf(
+                    perf.stop(),
+                    ((receive_bytes - past_receive_bytes) / STANDARD_MTU).max(1),
+                );
+                perf = PerfMeasurement::start();
                 past_time = current_time;
                 past_receive_bytes = receive_bytes;
             }
         }
     }
 }
+
+fn log_perf(c: PerfCounters, packets: usize) {
+    let packets = packets as u64;
+    info!(
+        "  per packet: {} cycles, {} instructions, {} cache misses",
+        c.cycles / packets,
+        c.instructions / packets,
+        c.cache_misses / packets
+    );
+}
@@ -1,19 +1,50 @@
-use super::addr::from_core_sockaddr;
+use super::addr::{from_core_sockaddr, into_core_sockaddr};
 use crate::net_impl::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
 use crate::smoltcp_impl::tcp::{STATE_CLOSED, STATE_CONNECTING};
-use axio::PollState;
+use alloc::boxed::Box;
+use axasync::io::BytesMut;
+use axasync::time::Sleep;
 use core::future::Future;
 use core::net::SocketAddr;
 use core::pin::Pin;
-use core::task::{Context, Poll};
+use core::task::{Context, Poll, ready};
 use smoltcp::socket::tcp::{ConnectError, Socket};
 
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 
 use super::TcpSocket;
 
+/// Fails a receive of an accepted socket that is still waiting for its first
+/// byte at its handshake deadline, see [`TcpSocket::set_handshake_timeout`].
+fn poll_first_byte(
+    socket: &TcpSocket,
+    timer: &mut Option<Pin<Box<Sleep>>>,
+    res: Poll<AxResult<usize>>,
+    cx: &mut Context<'_>,
+) -> Poll<AxResult<usize>> {
+    let Some(deadline) = socket.first_byte_deadline() else {
+        return res;
+    };
+    if res.is_ready() {
+        socket.clear_first_byte_deadline();
+        return res;
+    }
+    let timer = timer.get_or_insert_with(|| Box::pin(Sleep::until(deadline)));
+    ready!(timer.as_mut().poll(cx));
+    Poll::Ready(ax_err!(Io, "socket recv() failed: handshake timed out"))
+}
+
+/// Records the activity of a transfer that moved data, see
+/// [`TcpSocket::idle_time`].
+fn record_activity(socket: &TcpSocket, res: &Poll<AxResult<usize>>) {
+    if matches!(res, Poll::Ready(Ok(n)) if *n > 0) {
+        socket.record_activity();
+    }
+}
+
 pub struct RecvFuture<'a> {
     socket: &'a TcpSocket,
+    timer: Option<Pin<Box<Sleep>>>,
     buf: &'a mut [u8],
     init: bool,
 }
@@ -22,6 +53,7 @@ impl<'a> RecvFuture<'a> {
     pub fn new(socket: &'a TcpSocket, buf: &'a mut [u8]) -> Self {
         Self {
             socket,
+            timer: None,
             buf,
             init: false,
         }
@@ -42,9 +74,11 @@ impl<'a> Future for RecvFuture<'a> {
                 return Poll::Ready(ax_err!(NotConnected, "socket recv() failed"));
             }
         }
+        ready!(axasync::poll_proceed(cx));
 
         let handle = this.socket.handle();
-        SOCKET_SET.with_socket_mut::<Socket, _, _>(handle, |socket| {
+        this.socket.hand_off();
+        let res = SOCKET_SET.poll_socket_mut::<Socket, _, _>(handle, cx, |socket| {
             if !socket.is_active() {
                 return Poll::Ready(ax_err!(ConnectionRefused, "socket recv() failed"));
             } else if !socket.may_recv() {
@@ -59,7 +93,82 @@ impl<'a> Future for RecvFuture<'a> {
                 socket.register_recv_waker(cx.waker());
                 return Poll::Pending;
             }
-        })
+        });
+        record_activity(this.socket, &res);
+        poll_first_byte(this.socket, &mut this.timer, res, cx)
+    }
+}
+
+pub struct RecvBufFuture<'a> {
+    socket: &'a TcpSocket,
+    timer: Option<Pin<Box<Sleep>>>,
+    buf: &'a mut BytesMut,
+    init: bool,
+}
+
+impl<'a> RecvBufFuture<'a> {
+    pub fn new(socket: &'a TcpSocket, buf: &'a mut BytesMut) -> Self {
+        Self {
+            socket,
+            timer: None,
+            buf,
+            init: false,
+        }
+    }
+}
+
+impl<'a> Future for RecvBufFuture<'a> {
+    type Output = AxResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        trace!("recv_buf poll");
+        let this = self.get_mut();
+        if !this.init {
+            this.init = true;
+            if this.socket.is_connecting() {
+                return Poll::Ready(Err(AxError::WouldBlock));
+            } else if !this.socket.is_connected() {
+                return Poll::Ready(ax_err!(NotConnected, "socket recv() failed"));
+            }
+        }
+        ready!(axasync::poll_proceed(cx));
+
+        let handle = this.socket.handle();
+        this.socket.hand_off();
+        let res = SOCKET_SET.poll_socket_mut::<Socket, _, _>(handle, cx, |socket| {
+            if !socket.is_active() {
+                return Poll::Ready(ax_err!(ConnectionRefused, "socket recv() failed"));
+            } else if !socket.may_recv() {
+                return Poll::Ready(Ok(0));
+            } else if socket.recv_queue() == 0 {
+                socket.register_recv_waker(cx.waker());
+                return Poll::Pending;
+            }
+            // Copy straight from the receive ring, which may wrap around.
+            this.buf.reserve(socket.recv_queue());
+            let mut total = 0;
+            loop {
+                let buf = &mut *this.buf;
+                let res = socket.recv(|data| {
+                    let spare = buf.spare_capacity_mut();
+                    let n = data.len().min(spare.len());
+                    for (dst, src) in spare.iter_mut().zip(&data[..n]) {
+                        dst.write(*src);
+                    }
+                    // SAFETY: The first `n` bytes of the spare capacity are
+                    // written.
+                    unsafe { buf.advance_mut(n) };
+                    (n, n)
+                });
+                match res {
+                    Ok(0) => return Poll::Ready(Ok(total)),
+                    Ok(n) => total += n,
+                    Err(_) => return Poll::Ready(ax_err!(BadState, "socket recv() failed")),
+                }
+            }
+        });
+        record_activity(this.socket, &res);
+        poll_first_byte(this.socket, &mut this.timer, res, cx)
     }
 }
 
@@ -93,9 +202,11 @@ impl<'a> Future for SendFuture<'a> {
                 return Poll::Ready(ax_err!(NotConnected, "socket send() failed"));
             }
         }
+        ready!(axasync::poll_proceed(cx));
 
         let handle = this.socket.handle();
-        SOCKET_SET.with_socket_mut::<Socket, _, _>(handle, |socket| {
+        this.socket.hand_off();
+        let res = SOCKET_SET.poll_socket_mut::<Socket, _, _>(handle, cx, |socket| {
             if !socket.is_active() || !socket.may_send() {
                 return Poll::Ready(ax_err!(ConnectionReset, "socket send() failed"));
             } else if socket.can_send() {
@@ -108,7 +219,9 @@ impl<'a> Future for SendFuture<'a> {
                 socket.register_send_waker(cx.waker());
                 return Poll::Pending;
             }
-        })
+        });
+        record_activity(this.socket, &res);
+        res
     }
 }
 
@@ -127,7 +240,7 @@ impl<'a> AcceptFuture<'a> {
 }
 
 impl<'a> Future for AcceptFuture<'a> {
-    type Output = AxResult<TcpSocket>;
+    type Output = AxResult<(TcpSocket, SocketAddr)>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
@@ -138,6 +251,18 @@ impl<'a> Future for AcceptFuture<'a> {
             }
         }
 
+        // Paused while the memory is short, the connections wait in the
+        // backlog meanwhile.
+        #[cfg(feature = "memwatch")]
+        {
+            use axasync::mem::{MemoryPressure, poll_pressure_changed, pressure};
+            if pressure() == MemoryPressure::Critical
+                && poll_pressure_changed(MemoryPressure::Critical, cx).is_pending()
+            {
+                return Poll::Pending;
+            }
+        }
+
         // SOCKET_SET.poll_interfaces();
         let local_port = this.socket.local_addr().unwrap().port();
         let (handle, (local_addr, peer_addr)) = match LISTEN_TABLE.accept(local_port) {
@@ -149,13 +274,21 @@ impl<'a> Future for AcceptFuture<'a> {
         };
 
         trace!("TCP socket accepted a new connection {}", peer_addr);
-        Poll::Ready(Ok(TcpSocket::new_connected(handle, local_addr, peer_addr)))
+        let socket =
+            TcpSocket::new_connected(handle, local_addr, peer_addr, this.socket.buffer_sizes());
+        socket.set_handshake_timeout(this.socket.handshake_timeout());
+        socket.start_first_byte_timeout(this.socket.handshake_timeout());
+        socket.record_activity();
+        // Owned by the accepting task, until handed to another one.
+        socket.hand_off();
+        Poll::Ready(Ok((socket, into_core_sockaddr(peer_addr))))
     }
 }
 
 pub struct ConnectFuture<'a> {
     socket: &'a TcpSocket,
     remote_addr: SocketAddr,
+    timer: Option<Pin<Box<Sleep>>>,
     init: bool,
 }
 
@@ -164,6 +297,7 @@ impl<'a> ConnectFuture<'a> {
         Self {
             socket,
             remote_addr,
+            timer: None,
             init: false,
         }
     }
@@ -181,8 +315,10 @@ impl<'a> Future for ConnectFuture<'a> {
                 .socket
                 .update_state(STATE_CLOSED, STATE_CONNECTING, || {
                     // SAFETY: no other threads can read or write these fields.
-                    let handle = unsafe { this.socket.handle.get().read() }
-                        .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
+                    let handle = unsafe { this.socket.handle.get().read() }.unwrap_or_else(|| {
+                        let (rx_buf_len, tx_buf_len) = this.socket.buffer_sizes();
+                        SOCKET_SET.add(SocketSetWrapper::new_tcp_socket(rx_buf_len, tx_buf_len))
+                    });
 
                     // TODO: check remote addr unreachable
                     let remote_endpoint = from_core_sockaddr(this.remote_addr);
@@ -223,20 +359,29 @@ impl<'a> Future for ConnectFuture<'a> {
             }
         }
 
-        let PollState { writable, .. } = this.socket.poll_connect()?;
+        let handle = this.socket.handle();
+        this.socket.hand_off();
+        let writable = ready!(
+            SOCKET_SET.poll_socket_mut::<Socket, _, _>(handle, cx, |socket| {
+                let writable = this.socket.update_connect_state(handle, socket);
+                if !writable {
+                    socket.register_recv_waker(cx.waker());
+                }
+                Poll::Ready(writable)
+            })
+        );
         if !writable {
-            let handle = unsafe {
-                this.socket
-                    .handle
-                    .get()
-                    .read()
-                    .expect("handle should be initialized")
-            };
-            SOCKET_SET.with_socket_mut::<Socket, _, _>(handle, |socket| {
-                socket.register_recv_waker(cx.waker());
-            });
+            if let Some(timeout) = this.socket.handshake_timeout() {
+                let timer = this
+                    .timer
+                    .get_or_insert_with(|| Box::pin(Sleep::new(timeout)));
+                if timer.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(ax_err!(Io, "socket connect() failed: timed out"));
+                }
+            }
             return Poll::Pending;
         } else if this.socket.is_connected() {
+            this.socket.record_activity();
             return Poll::Ready(Ok(()));
         } else {
             return Poll::Ready(ax_err!(ConnectionRefused, "socket connect() failed"));
@@ -0,0 +1,118 @@
+//! Detection of the sockets left open by the tasks that exited.
+//!
+//! Each socket is owned by the task that created or accepted it, then by the
+//! task it was handed to: the next one that awaits an I/O on it, e.g. the
+//! handler spawned for an accepted connection. The other operations, e.g. an
+//! abort by a reaper task, do not take it over. When a task exits, completed or aborted, the sockets
+//! it still owns are held by a value that outlived it, e.g. a stream stored
+//! in a global or kept alive by a reference cycle. They are reported, and
+//! closed with [`LeakPolicy::Close`].
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use axasync::{TaskExit, TaskId};
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::Socket;
+use spin::Mutex;
+
+use super::SOCKET_SET;
+
+/// What to do with the sockets left open by a task that exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LeakPolicy {
+    /// Log a warning for each of them.
+    Report,
+    /// Log a warning, and close them: the TCP connections are reset, the UDP
+    /// sockets unbound. Their handles are freed when they are dropped.
+    Close,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(LeakPolicy::Report as u8);
+/// The owner of each socket created or used by a task.
+static OWNERS: Mutex<BTreeMap<SocketHandle, TaskId>> = Mutex::new(BTreeMap::new());
+
+/// Sets what to do with the sockets left open by a task that exited. It is
+/// [`LeakPolicy::Report`] by default.
+pub fn set_leak_policy(policy: LeakPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+fn leak_policy() -> LeakPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        0 => LeakPolicy::Report,
+        _ => LeakPolicy::Close,
+    }
+}
+
+/// Registers the exit hook that looks for the sockets left open.
+pub(crate) fn init() {
+    axasync::register_exit_hook(on_task_exit);
+}
+
+/// Makes the current task, if any, the owner of the socket `handle`.
+pub(crate) fn claim(handle: SocketHandle) {
+    if let Some(task) = axasync::current_task_id() {
+        OWNERS.lock().insert(handle, task);
+    }
+}
+
+/// The owner of a socket as last seen by its async I/O, so that they only
+/// lock the owners when the socket is handed to another task.
+pub(crate) struct Owner(AtomicU64);
+
+impl Owner {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    /// Makes the current task the owner of the socket `handle`, on the first
+    /// async I/O it awaits on it.
+    pub(crate) fn hand_off(&self, handle: SocketHandle) {
+        let Some(task) = axasync::current_task_id() else {
+            return;
+        };
+        if self.0.swap(task.as_u64(), Ordering::Relaxed) != task.as_u64() {
+            OWNERS.lock().insert(handle, task);
+        }
+    }
+}
+
+/// Forgets the owner of the socket `handle`, it is destroyed.
+pub(crate) fn release(handle: SocketHandle) {
+    OWNERS.lock().remove(&handle);
+}
+
+fn on_task_exit(id: TaskId, exit: TaskExit) {
+    let mut leaked = Vec::new();
+    OWNERS.lock().retain(|handle, owner| {
+        if *owner == id {
+            leaked.push(*handle);
+        }
+        *owner != id
+    });
+
+    let close = leak_policy() == LeakPolicy::Close;
+    for handle in leaked {
+        warn!("task {}: {} with socket {} still open", id, exit, handle);
+        if close {
+            close_socket(handle);
+        }
+    }
+}
+
+/// Closes the socket `handle`, but leaves it in the set for its owner.
+fn close_socket(handle: SocketHandle) {
+    let mut set = SOCKET_SET.lock();
+    let Some((_, socket)) = set.iter_mut().find(|(h, _)| *h == handle) else {
+        return;
+    };
+    match socket {
+        Socket::Tcp(socket) => socket.abort(),
+        Socket::Udp(socket) => socket.close(),
+        _ => {}
+    }
+    debug!("socket {}: closed after a leak", handle);
+}
@@ -2,25 +2,39 @@ use alloc::{boxed::Box, collections::VecDeque};
 use core::ops::{Deref, DerefMut};
 
 use axerrno::{AxError, AxResult, ax_err};
+use axhal::time::monotonic_time_nanos;
 use axsync::Mutex;
 use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
+use super::stats::{self, PortListenStats};
 use super::{LISTEN_QUEUE_SIZE, SOCKET_SET, SocketSetWrapper};
 
 const PORT_NUM: usize = 65536;
 
+struct SynEntry {
+    handle: SocketHandle,
+    /// When the SYN came, in monotonic nanoseconds.
+    arrived: u64,
+}
+
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
-    syn_queue: VecDeque<SocketHandle>,
+    syn_queue: VecDeque<SynEntry>,
+    /// Receive and send buffer sizes of the accepted sockets.
+    buffer_sizes: (usize, usize),
+    /// Number of connections dropped because the SYN queue was full.
+    overflows: u64,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(listen_endpoint: IpListenEndpoint, buffer_sizes: (usize, usize)) -> Self {
         Self {
             listen_endpoint,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            buffer_sizes,
+            overflows: 0,
         }
     }
 
@@ -35,9 +49,11 @@ impl ListenTableEntry {
 
 impl Drop for ListenTableEntry {
     fn drop(&mut self) {
-        for &handle in &self.syn_queue {
-            SOCKET_SET.remove(handle);
+        for syn in &self.syn_queue {
+            SOCKET_SET.remove(syn.handle);
         }
+        stats::record_syn_dequeued(self.syn_queue.len());
+        stats::record_listen(false);
     }
 }
 
@@ -61,12 +77,20 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    pub fn listen(
+        &self,
+        listen_endpoint: IpListenEndpoint,
+        buffer_sizes: (usize, usize),
+    ) -> AxResult {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
+            *entry = Some(Box::new(ListenTableEntry::new(
+                listen_endpoint,
+                buffer_sizes,
+            )));
+            stats::record_listen(true);
             Ok(())
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
@@ -80,7 +104,7 @@ impl ListenTable {
 
     pub fn can_accept(&self, port: u16) -> AxResult<bool> {
         if let Some(entry) = self.tcp[port as usize].lock().deref() {
-            Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
+            Ok(entry.syn_queue.iter().any(|syn| is_connected(syn.handle)))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
@@ -92,8 +116,8 @@ impl ListenTable {
             let (idx, addr_tuple) = syn_queue
                 .iter()
                 .enumerate()
-                .find_map(|(idx, &handle)| {
-                    is_connected(handle).then(|| (idx, get_addr_tuple(handle)))
+                .find_map(|(idx, syn)| {
+                    is_connected(syn.handle).then(|| (idx, get_addr_tuple(syn.handle)))
                 })
                 .ok_or(AxError::WouldBlock)?; // wait for connection
             if idx > 0 {
@@ -103,13 +127,31 @@ impl ListenTable {
                     syn_queue.len()
                 );
             }
-            let handle = syn_queue.swap_remove_front(idx).unwrap();
-            Ok((handle, addr_tuple))
+            let syn = syn_queue.swap_remove_front(idx).unwrap();
+            stats::record_syn_dequeued(1);
+            stats::record_accept(monotonic_time_nanos().saturating_sub(syn.arrived));
+            Ok((syn.handle, addr_tuple))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
     }
 
+    /// Returns the SYN queue of `port`, or `None` if it is not listened on.
+    pub fn port_stats(&self, port: u16) -> Option<PortListenStats> {
+        let entry = self.tcp[port as usize].lock();
+        let entry = entry.as_ref()?;
+        Some(PortListenStats {
+            backlog: entry.syn_queue.len(),
+            established: entry
+                .syn_queue
+                .iter()
+                .filter(|syn| is_connected(syn.handle))
+                .count(),
+            capacity: LISTEN_QUEUE_SIZE,
+            overflows: entry.overflows,
+        })
+    }
+
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
@@ -123,17 +165,24 @@ impl ListenTable {
             }
             if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
                 // SYN queue is full, drop the packet
-                warn!("SYN queue overflow!");
+                warn!("SYN queue overflow on port {}!", dst.port);
+                entry.overflows += 1;
+                stats::record_syn_overflow(dst.port);
                 return;
             }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
+            let (rx_buf_len, tx_buf_len) = entry.buffer_sizes;
+            let mut socket = SocketSetWrapper::new_tcp_socket(rx_buf_len, tx_buf_len);
             if socket.listen(entry.listen_endpoint).is_ok() {
                 let handle = sockets.add(socket);
                 debug!(
                     "TCP socket {}: prepare for connection {} -> {}",
                     handle, src, entry.listen_endpoint
                 );
-                entry.syn_queue.push_back(handle);
+                entry.syn_queue.push_back(SynEntry {
+                    handle,
+                    arrived: monotonic_time_nanos(),
+                });
+                stats::record_syn_queued(1);
             }
         }
     }
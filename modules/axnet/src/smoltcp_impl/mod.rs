@@ -3,51 +3,55 @@ mod bench;
 mod dns;
 #[cfg(feature = "async")]
 mod future;
+#[cfg(feature = "leak-detect")]
+mod leak;
 mod listen_table;
+mod stats;
 mod tcp;
 mod udp;
 
 use alloc::vec;
+#[cfg(feature = "async")]
+use alloc::vec::Vec;
 use core::cell::RefCell;
-use core::ops::DerefMut;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(feature = "async")]
+use core::task::{Context, Poll, Waker};
 
 use axdriver::prelude::*;
 use axdriver_net::{DevError, NetBufPtr};
-use axhal::time::{NANOS_PER_MICROS, wall_time_nanos};
-use axsync::Mutex;
+use axhal::time::{NANOS_PER_MICROS, monotonic_time_nanos, wall_time_nanos};
+use axsync::lockstat::LockStat;
+use axsync::{Mutex, MutexGuard};
 use lazyinit::LazyInit;
 use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
 use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, Ipv4Address};
 
 use self::listen_table::ListenTable;
 
 pub use self::dns::dns_query;
-pub use self::tcp::TcpSocket;
+#[cfg(feature = "leak-detect")]
+pub use self::leak::{LeakPolicy, set_leak_policy};
+pub use self::stats::{
+    ACCEPT_WAIT_BUCKETS_MICROS, ListenStats, PollStats, PortListenStats, listen_stats, poll_stats,
+    port_listen_stats, reset_listen_stats, reset_poll_stats, set_listen_overflow_handler,
+};
+pub use self::tcp::{TcpSocket, set_default_handshake_timeout};
 pub use self::udp::UdpSocket;
 
-macro_rules! env_or_default {
-    ($key:literal) => {
-        match option_env!($key) {
-            Some(val) => val,
-            None => "",
-        }
-    };
-}
-
-const IP: &str = env_or_default!("AX_IP");
-const GATEWAY: &str = env_or_default!("AX_GW");
-const DNS_SEVER: &str = "8.8.8.8";
-const IP_PREFIX: u8 = 24;
-
 const STANDARD_MTU: usize = 1500;
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
 
 const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
+/// The size the TCP buffers shrink to under critical memory pressure.
+#[cfg(feature = "memwatch")]
+const TCP_MIN_BUF_LEN: usize = 4 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
 const LISTEN_QUEUE_SIZE: usize = 512;
@@ -55,8 +59,36 @@ const LISTEN_QUEUE_SIZE: usize = 512;
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
 static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
+static DNS_SERVER: LazyInit<IpAddress> = LazyInit::new();
 
-struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
+static SOCKET_SET_STAT: LockStat = LockStat::new("axnet::SOCKET_SET");
+
+/// All sockets of the stack.
+///
+/// smoltcp processes incoming packets against one [`SocketSet`], so the set
+/// cannot be split into independently locked shards: a packet handled with
+/// the wrong shard would be answered with a RST. Per-socket locks do not help
+/// either, as `Interface::poll` borrows the whole set mutably and the sockets
+/// are stored inline in it. Instead, the lock is only held for the socket
+/// operation itself, and concurrent polls are coalesced so that CPUs do not
+/// queue up behind the one already driving the NIC. A caller that needs the
+/// poll done before it goes on waits for its round, see
+/// [`poll_interfaces_and_wait`](Self::poll_interfaces_and_wait).
+///
+/// The polls of the async sockets never sleep on the lock, see
+/// [`poll_socket_mut`](Self::poll_socket_mut).
+struct SocketSetWrapper<'a> {
+    set: Mutex<SocketSet<'a>>,
+    /// The polls that found `set` locked, woken when it is unlocked.
+    #[cfg(feature = "async")]
+    waiters: spin::Mutex<Vec<Waker>>,
+    /// Whether a CPU is polling the interfaces.
+    polling: AtomicBool,
+    /// The generation of the last poll requested.
+    requested: AtomicU64,
+    /// The generation of the last request covered by a finished round.
+    completed: AtomicU64,
+}
 
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
@@ -71,35 +103,53 @@ struct InterfaceWrapper {
 
 impl<'a> SocketSetWrapper<'a> {
     fn new() -> Self {
-        Self(Mutex::new(SocketSet::new(vec![])))
+        Self {
+            set: Mutex::new(SocketSet::new(vec![])),
+            #[cfg(feature = "async")]
+            waiters: spin::Mutex::new(Vec::new()),
+            polling: AtomicBool::new(false),
+            requested: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+        }
     }
 
-    pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
-        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]);
-        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]);
+    pub fn new_tcp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::tcp::Socket<'a> {
+        #[cfg(feature = "memwatch")]
+        let (rx_buf_len, tx_buf_len) = (shrink_buf_len(rx_buf_len), shrink_buf_len(tx_buf_len));
+        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; rx_buf_len]);
+        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; tx_buf_len]);
         socket::tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer)
     }
 
-    pub fn new_udp_socket() -> socket::udp::Socket<'a> {
+    pub fn new_udp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::udp::Socket<'a> {
         let udp_rx_buffer = socket::udp::PacketBuffer::new(
             vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_RX_BUF_LEN],
+            vec![0; rx_buf_len],
         );
         let udp_tx_buffer = socket::udp::PacketBuffer::new(
             vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_TX_BUF_LEN],
+            vec![0; tx_buf_len],
         );
         socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
     }
 
     pub fn new_dns_socket() -> socket::dns::Socket<'a> {
-        let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
-        socket::dns::Socket::new(&[server_addr], vec![])
+        socket::dns::Socket::new(&[*DNS_SERVER], vec![])
+    }
+
+    /// Locks the set, sleeping while it is locked.
+    fn lock(&self) -> SocketSetGuard<'_, 'a> {
+        SocketSetGuard {
+            wrapper: self,
+            set: Some(SOCKET_SET_STAT.lock(&self.set)),
+        }
     }
 
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
-        let handle = self.0.lock().add(socket);
+        let handle = self.lock().add(socket);
         debug!("socket {}: created", handle);
+        #[cfg(feature = "leak-detect")]
+        leak::claim(handle);
         handle
     }
 
@@ -107,7 +157,7 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&T) -> R,
     {
-        let set = self.0.lock();
+        let set = self.lock();
         let socket = set.get(handle);
         f(socket)
     }
@@ -116,18 +166,152 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&mut T) -> R,
     {
-        let mut set = self.0.lock();
+        let mut set = self.lock();
         let socket = set.get_mut(handle);
         f(socket)
     }
 
+    /// Like [`with_socket_mut`](Self::with_socket_mut), for the poll of an
+    /// async socket: if the set is locked, e.g. by the CPU polling the
+    /// interfaces, it returns [`Poll::Pending`] and wakes the task once the
+    /// set is unlocked, instead of sleeping on the lock and stalling the
+    /// executor.
+    #[cfg(feature = "async")]
+    pub fn poll_socket_mut<T: AnySocket<'a>, R, F>(
+        &self,
+        handle: SocketHandle,
+        cx: &Context<'_>,
+        f: F,
+    ) -> Poll<R>
+    where
+        F: FnOnce(&mut T) -> Poll<R>,
+    {
+        let set = match self.set.try_lock() {
+            Some(set) => set,
+            None => {
+                self.waiters.lock().push(cx.waker().clone());
+                // Unlocked meanwhile, before the waker was seen.
+                match self.set.try_lock() {
+                    Some(set) => set,
+                    None => return Poll::Pending,
+                }
+            }
+        };
+        let mut set = SocketSetGuard {
+            wrapper: self,
+            set: Some(set),
+        };
+        f(set.get_mut(handle))
+    }
+
+    /// Polls the interfaces, or lets the CPU that is already polling do one
+    /// more round on our behalf, without waiting for it.
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        let generation = self.requested.fetch_add(1, Ordering::SeqCst) + 1;
+        self.try_poll_rounds(generation);
+    }
+
+    /// Polls the interfaces like [`poll_interfaces`](Self::poll_interfaces),
+    /// but returns only once a round started after the call has finished,
+    /// e.g. so that the segments queued before it are sent.
+    pub fn poll_interfaces_and_wait(&self) {
+        let generation = self.requested.fetch_add(1, Ordering::SeqCst) + 1;
+        while !self.try_poll_rounds(generation) {
+            axtask::yield_now();
+        }
+    }
+
+    /// Polls the interfaces until no round is requested, unless another CPU
+    /// is polling them: it checks the requests again after it finishes.
+    ///
+    /// Returns whether the round of the request `generation` has finished.
+    fn try_poll_rounds(&self, generation: u64) -> bool {
+        loop {
+            if self.completed.load(Ordering::SeqCst) >= self.requested.load(Ordering::SeqCst) {
+                return true;
+            }
+            if self
+                .polling
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                return self.completed.load(Ordering::SeqCst) >= generation;
+            }
+            // Another CPU may poll once it is dropped, also if the poll
+            // unwinds.
+            let _polling = PollingGuard(&self.polling);
+            // The requests from now on need another round.
+            let round = self.requested.load(Ordering::SeqCst);
+            // Wake the sockets' tasks after all the locks are released.
+            #[cfg(feature = "async")]
+            axasync::defer_wakes(|| ETH0.poll(self));
+            #[cfg(not(feature = "async"))]
+            ETH0.poll(self);
+            self.completed.store(round, Ordering::SeqCst);
+        }
     }
 
     pub fn remove(&self, handle: SocketHandle) {
-        self.0.lock().remove(handle);
+        self.lock().remove(handle);
         debug!("socket {}: destroyed", handle);
+        #[cfg(feature = "leak-detect")]
+        leak::release(handle);
+    }
+}
+
+/// Clears the flag of the CPU polling the interfaces when dropped, see
+/// [`SocketSetWrapper::try_poll_rounds`].
+struct PollingGuard<'s>(&'s AtomicBool);
+
+impl Drop for PollingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// The lock of the [`SocketSetWrapper`], which wakes the async polls that
+/// found the set locked once it is unlocked.
+struct SocketSetGuard<'s, 'a> {
+    wrapper: &'s SocketSetWrapper<'a>,
+    set: Option<MutexGuard<'s, SocketSet<'a>>>,
+}
+
+impl<'a> Deref for SocketSetGuard<'_, 'a> {
+    type Target = SocketSet<'a>;
+
+    fn deref(&self) -> &SocketSet<'a> {
+        self.set.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for SocketSetGuard<'_, 'a> {
+    fn deref_mut(&mut self) -> &mut SocketSet<'a> {
+        self.set.as_mut().unwrap()
+    }
+}
+
+impl Drop for SocketSetGuard<'_, '_> {
+    fn drop(&mut self) {
+        drop(self.set.take());
+        // After the unlock, see `poll_socket_mut`.
+        #[cfg(feature = "async")]
+        {
+            let waiters = core::mem::take(&mut *self.wrapper.waiters.lock());
+            axasync::wake_batch(waiters);
+        }
+        #[cfg(not(feature = "async"))]
+        let _ = self.wrapper;
+    }
+}
+
+/// Shrinks the size of a TCP buffer under memory pressure.
+#[cfg(feature = "memwatch")]
+fn shrink_buf_len(len: usize) -> usize {
+    use axasync::mem::{MemoryPressure, pressure};
+    match pressure() {
+        MemoryPressure::Normal => len,
+        MemoryPressure::Low => (len / 4).max(TCP_MIN_BUF_LEN).min(len),
+        MemoryPressure::Critical => len.min(TCP_MIN_BUF_LEN),
     }
 }
 
@@ -172,12 +356,14 @@ impl InterfaceWrapper {
         };
     }
 
-    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
+    pub fn poll(&self, sockets: &SocketSetWrapper) {
         let mut dev = self.dev.lock();
         let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
         let timestamp = Self::current_time();
+        let start = monotonic_time_nanos();
         iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        stats::record_poll(sockets.iter().count(), monotonic_time_nanos() - start);
     }
 }
 
@@ -203,6 +389,11 @@ impl Device for DeviceWrapper {
         let mut dev = self.inner.borrow_mut();
         if let Err(e) = dev.recycle_tx_buffers() {
             warn!("recycle_tx_buffers failed: {:?}", e);
+            #[cfg(feature = "async")]
+            crate::event::publish(crate::NetEvent::DeviceError {
+                op: "recycle_tx_buffers",
+                err: e,
+            });
             return None;
         }
 
@@ -214,6 +405,8 @@ impl Device for DeviceWrapper {
             Err(err) => {
                 if !matches!(err, DevError::Again) {
                     warn!("receive failed: {:?}", err);
+                    #[cfg(feature = "async")]
+                    crate::event::publish(crate::NetEvent::DeviceError { op: "receive", err });
                 }
                 return None;
             }
@@ -225,6 +418,11 @@ impl Device for DeviceWrapper {
         let mut dev = self.inner.borrow_mut();
         if let Err(e) = dev.recycle_tx_buffers() {
             warn!("recycle_tx_buffers failed: {:?}", e);
+            #[cfg(feature = "async")]
+            crate::event::publish(crate::NetEvent::DeviceError {
+                op: "recycle_tx_buffers",
+                err: e,
+            });
             return None;
         }
         if dev.can_transmit() {
@@ -319,36 +517,64 @@ pub fn bench_receive() {
 }
 
 pub(crate) fn init(net_dev: AxNetDevice, irq: u32) {
-    let ether_addr = EthernetAddress(net_dev.mac_address().0);
+    let config = crate::config::config();
+    let mac = net_dev.mac_address().0;
+    // The drivers cannot program the address filter of the NIC, frames sent
+    // to another address would be dropped.
+    if let Some(wanted) = config.mac.filter(|wanted| *wanted != mac) {
+        warn!(
+            "net.mac={} ignored, {} cannot change its MAC address",
+            EthernetAddress(wanted),
+            net_dev.device_name(),
+        );
+    }
+    let ether_addr = EthernetAddress(mac);
     let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
 
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
+    let ip = IpAddress::Ipv4(Ipv4Address(config.ip().octets()));
+    let prefix_len = config.prefix_len();
+    let gateway = IpAddress::Ipv4(Ipv4Address(config.gateway().octets()));
+    let dns = IpAddress::Ipv4(Ipv4Address(config.dns().octets()));
+    eth0.setup_ip_addr(ip, prefix_len);
     eth0.setup_gateway(gateway);
 
     ETH0.init_once(eth0);
+    DNS_SERVER.init_once(dns);
     SOCKET_SET.init_once(SocketSetWrapper::new());
     LISTEN_TABLE.init_once(ListenTable::new());
+    #[cfg(feature = "leak-detect")]
+    leak::init();
 
     info!("created net interface {:?}:", ETH0.name());
     info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
+    info!("  ip:       {}/{}", ip, prefix_len);
     info!("  gateway:  {}", gateway);
+    info!("  dns:      {}", dns);
+    info!("  hostname: {}", crate::config::hostname());
     info!("  IRQ:      {}", irq);
 
     // // for qemu virt eth0
     // axhal::irq::register_handler(irq as usize, handler);
 
+    // The handler polls the whole stack, at the lowest priority so that the
+    // timer and the other devices preempt it.
     // for visionfive2 eth0
     // axhal::irq::register_handler(6, eth_wake_irq);
     // axhal::irq::register_handler(5, eth_lpi);
-    axhal::irq::register_handler(7, handler);
+    axhal::irq::register_handler_with_priority(7, axhal::irq::LOWEST_IRQ_PRIORITY, handler);
 
     // for visionfive2 eth1
     // axhal::irq::register_handler(77, eth_wake_irq);
     // axhal::irq::register_handler(76, eth_lpi);
-    axhal::irq::register_handler(78, handler);
+    axhal::irq::register_handler_with_priority(78, axhal::irq::LOWEST_IRQ_PRIORITY, handler);
+
+    // Without interrupts, the async runtime polls the stack itself.
+    #[cfg(feature = "async")]
+    axasync::register_poll_hook(poll_interfaces);
+
+    // Registered after the drivers' hook, so that it runs before the NIC
+    // is stopped.
+    axhal::misc::on_shutdown(shutdown);
 
     // jh7110 uart0 input interrupt for test if PLIC is working
     // axhal::irq::register_handler(32, || {
@@ -361,10 +587,33 @@ pub(crate) fn init(net_dev: AxNetDevice, irq: u32) {
     // });
 }
 
+/// Resets the TCP connections and closes the UDP sockets, then transmits the
+/// resets, so that the peers do not wait for the connections of the stopped
+/// system.
+fn shutdown() {
+    let mut set = SOCKET_SET.lock();
+    let mut open = 0;
+    for (_, socket) in set.iter_mut() {
+        match socket {
+            socket::Socket::Tcp(socket) if socket.is_open() => socket.abort(),
+            socket::Socket::Udp(socket) if socket.is_open() => socket.close(),
+            _ => continue,
+        }
+        open += 1;
+    }
+    drop(set);
+    info!("shutdown: closed {} sockets", open);
+    // Not merged into a round in progress, which may have started before the
+    // aborts.
+    SOCKET_SET.poll_interfaces_and_wait();
+}
+
 fn handler() {
     info!("eth_irq called");
     let rx = { ETH0.dev.lock().inner.borrow_mut().clear_intr_status() };
     if rx {
+        #[cfg(feature = "async")]
+        crate::event::publish(crate::NetEvent::Received);
         SOCKET_SET.poll_interfaces();
     }
 }
@@ -0,0 +1,183 @@
+//! Timing of the interface polls, and occupancy of the listen table.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use spin::Mutex;
+
+static POLLS: AtomicU64 = AtomicU64::new(0);
+static SOCKETS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_NANOS: AtomicU64 = AtomicU64::new(0);
+static MIN_NANOS: AtomicU64 = AtomicU64::new(u64::MAX);
+static MAX_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Statistics of the polls of the network stack, see [`poll_stats`].
+///
+/// The durations only cover the processing by the stack, not the wait for
+/// its locks, so that they can be told apart from the scheduling latency of
+/// the tasks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollStats {
+    /// Number of polls of the interface.
+    pub polls: u64,
+    /// Number of sockets processed, summed over all the polls.
+    pub sockets: u64,
+    /// The shortest poll, in nanoseconds.
+    pub min_nanos: u64,
+    /// The average poll, in nanoseconds.
+    pub avg_nanos: u64,
+    /// The longest poll, in nanoseconds.
+    pub max_nanos: u64,
+}
+
+/// Records a poll that processed `sockets` sockets in `nanos` nanoseconds.
+pub(crate) fn record_poll(sockets: usize, nanos: u64) {
+    POLLS.fetch_add(1, Ordering::Relaxed);
+    SOCKETS.fetch_add(sockets as u64, Ordering::Relaxed);
+    TOTAL_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    MIN_NANOS.fetch_min(nanos, Ordering::Relaxed);
+    MAX_NANOS.fetch_max(nanos, Ordering::Relaxed);
+}
+
+/// Returns the statistics of the interface polls since boot, or since the
+/// last [`reset_poll_stats`].
+pub fn poll_stats() -> PollStats {
+    let polls = POLLS.load(Ordering::Relaxed);
+    if polls == 0 {
+        return PollStats::default();
+    }
+    PollStats {
+        polls,
+        sockets: SOCKETS.load(Ordering::Relaxed),
+        min_nanos: MIN_NANOS.load(Ordering::Relaxed),
+        avg_nanos: TOTAL_NANOS.load(Ordering::Relaxed) / polls,
+        max_nanos: MAX_NANOS.load(Ordering::Relaxed),
+    }
+}
+
+/// Clears the statistics of the interface polls.
+pub fn reset_poll_stats() {
+    POLLS.store(0, Ordering::Relaxed);
+    SOCKETS.store(0, Ordering::Relaxed);
+    TOTAL_NANOS.store(0, Ordering::Relaxed);
+    MIN_NANOS.store(u64::MAX, Ordering::Relaxed);
+    MAX_NANOS.store(0, Ordering::Relaxed);
+}
+
+/// The upper bounds of the buckets of [`ListenStats::accept_wait`], in
+/// microseconds. The last bucket counts the longer waits.
+pub const ACCEPT_WAIT_BUCKETS_MICROS: [u64; 5] = [100, 1_000, 10_000, 100_000, 1_000_000];
+
+static LISTENING_PORTS: AtomicUsize = AtomicUsize::new(0);
+static SYN_QUEUED: AtomicUsize = AtomicUsize::new(0);
+static SYN_OVERFLOWS: AtomicU64 = AtomicU64::new(0);
+static ACCEPTS: AtomicU64 = AtomicU64::new(0);
+static ACCEPT_WAIT: [AtomicU64; ACCEPT_WAIT_BUCKETS_MICROS.len() + 1] =
+    [const { AtomicU64::new(0) }; ACCEPT_WAIT_BUCKETS_MICROS.len() + 1];
+static OVERFLOW_HANDLER: Mutex<Option<fn(u16)>> = Mutex::new(None);
+
+/// Statistics of the listen table, see [`listen_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListenStats {
+    /// Number of ports listened on.
+    pub listening_ports: usize,
+    /// Number of connections in the SYN queues of all the ports, being
+    /// established or waiting to be accepted.
+    pub syn_queued: usize,
+    /// Number of connections dropped because their SYN queue was full.
+    pub syn_overflows: u64,
+    /// Number of connections accepted.
+    pub accepts: u64,
+    /// Number of connections accepted by the time they waited since their
+    /// SYN, by the buckets of [`ACCEPT_WAIT_BUCKETS_MICROS`].
+    pub accept_wait: [u64; ACCEPT_WAIT_BUCKETS_MICROS.len() + 1],
+}
+
+/// The SYN queue of a listening port, see [`port_listen_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PortListenStats {
+    /// Number of connections in the SYN queue.
+    pub backlog: usize,
+    /// Number of them that are established, waiting to be accepted.
+    pub established: usize,
+    /// The size of the SYN queue, the connections beyond are dropped.
+    pub capacity: usize,
+    /// Number of connections dropped because the SYN queue was full.
+    pub overflows: u64,
+}
+
+pub(crate) fn record_listen(listening: bool) {
+    if listening {
+        LISTENING_PORTS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        LISTENING_PORTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn record_syn_queued(queued: usize) {
+    SYN_QUEUED.fetch_add(queued, Ordering::Relaxed);
+}
+
+pub(crate) fn record_syn_dequeued(dequeued: usize) {
+    SYN_QUEUED.fetch_sub(dequeued, Ordering::Relaxed);
+}
+
+/// Records an accept of a connection whose SYN came `wait_nanos` ago.
+pub(crate) fn record_accept(wait_nanos: u64) {
+    ACCEPTS.fetch_add(1, Ordering::Relaxed);
+    let micros = wait_nanos / 1_000;
+    let bucket = ACCEPT_WAIT_BUCKETS_MICROS
+        .iter()
+        .position(|&bound| micros < bound)
+        .unwrap_or(ACCEPT_WAIT_BUCKETS_MICROS.len());
+    ACCEPT_WAIT[bucket].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records a connection to `port` dropped because its SYN queue was full,
+/// and calls the [overflow handler](set_listen_overflow_handler).
+pub(crate) fn record_syn_overflow(port: u16) {
+    SYN_OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    let handler = *OVERFLOW_HANDLER.lock();
+    if let Some(handler) = handler {
+        handler(port);
+    }
+}
+
+/// Sets the function called with the port of each connection dropped because
+/// the SYN queue of the port was full, or removes it with `None`.
+///
+/// It is called while the network stack is polled, it must not use the
+/// sockets.
+pub fn set_listen_overflow_handler(handler: Option<fn(u16)>) {
+    *OVERFLOW_HANDLER.lock() = handler;
+}
+
+/// Returns the statistics of the listen table since boot. The counters are
+/// cleared by [`reset_listen_stats`].
+pub fn listen_stats() -> ListenStats {
+    ListenStats {
+        listening_ports: LISTENING_PORTS.load(Ordering::Relaxed),
+        syn_queued: SYN_QUEUED.load(Ordering::Relaxed),
+        syn_overflows: SYN_OVERFLOWS.load(Ordering::Relaxed),
+        accepts: ACCEPTS.load(Ordering::Relaxed),
+        accept_wait: ACCEPT_WAIT
+            .each_ref()
+            .map(|count| count.load(Ordering::Relaxed)),
+    }
+}
+
+/// Returns the SYN queue of `port`, or `None` if it is not listened on.
+pub fn port_listen_stats(port: u16) -> Option<PortListenStats> {
+    if !super::LISTEN_TABLE.is_inited() {
+        return None;
+    }
+    super::LISTEN_TABLE.port_stats(port)
+}
+
+/// Clears the counters of the listen table, the occupancy is kept.
+pub fn reset_listen_stats() {
+    SYN_OVERFLOWS.store(0, Ordering::Relaxed);
+    ACCEPTS.store(0, Ordering::Relaxed);
+    for count in &ACCEPT_WAIT {
+        count.store(0, Ordering::Relaxed);
+    }
+}
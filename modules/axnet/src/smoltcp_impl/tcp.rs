@@ -1,20 +1,28 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
-use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use core::time::Duration;
 
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 use axio::PollState;
 use axsync::Mutex;
 
 #[cfg(feature = "async")]
-use super::future::{AcceptFuture, ConnectFuture, RecvFuture, SendFuture};
+use axasync::io::BytesMut;
+#[cfg(feature = "async")]
+use axhal::time::{TimeValue, monotonic_time_nanos};
+
+#[cfg(feature = "async")]
+use super::future::{AcceptFuture, ConnectFuture, RecvBufFuture, RecvFuture, SendFuture};
 
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
+#[cfg(feature = "leak-detect")]
+use super::leak::Owner;
+use super::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper, TCP_RX_BUF_LEN, TCP_TX_BUF_LEN};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -28,6 +36,28 @@ pub const STATE_CONNECTING: u8 = 2;
 const STATE_CONNECTED: u8 = 3;
 const STATE_LISTENING: u8 = 4;
 
+/// The handshake timeout of a socket that uses the default one.
+const HANDSHAKE_TIMEOUT_DEFAULT: u64 = 0;
+/// The handshake timeout of a socket that has none.
+const HANDSHAKE_TIMEOUT_NONE: u64 = u64::MAX;
+
+/// The handshake timeout of the sockets that do not set theirs, in
+/// nanoseconds, or [`HANDSHAKE_TIMEOUT_NONE`].
+static DEFAULT_HANDSHAKE_TIMEOUT: AtomicU64 = AtomicU64::new(HANDSHAKE_TIMEOUT_NONE);
+
+/// Encodes a handshake timeout, in nanoseconds.
+fn handshake_timeout_nanos(timeout: Option<Duration>) -> u64 {
+    timeout.map_or(HANDSHAKE_TIMEOUT_NONE, |timeout| {
+        (timeout.as_nanos() as u64).clamp(1, HANDSHAKE_TIMEOUT_NONE - 1)
+    })
+}
+
+/// Sets the handshake timeout of the TCP sockets that do not set their own,
+/// see [`TcpSocket::set_handshake_timeout`]. There is none by default.
+pub fn set_default_handshake_timeout(timeout: Option<Duration>) {
+    DEFAULT_HANDSHAKE_TIMEOUT.store(handshake_timeout_nanos(timeout), Ordering::Relaxed);
+}
+
 /// A TCP socket that provides POSIX-like APIs.
 ///
 /// - [`connect`] is for TCP clients.
@@ -44,6 +74,18 @@ pub struct TcpSocket {
     pub local_addr: UnsafeCell<IpEndpoint>,
     pub peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
+    rx_buf_len: usize,
+    tx_buf_len: usize,
+    /// See [`set_handshake_timeout`](Self::set_handshake_timeout).
+    handshake_timeout: AtomicU64,
+    /// Until when an accepted socket waits for its first byte, in monotonic
+    /// nanoseconds, `0` once it has received it.
+    first_byte_deadline: AtomicU64,
+    /// When the async operations last moved data, or the connection was
+    /// established, in monotonic nanoseconds, `0` if never.
+    last_activity: AtomicU64,
+    #[cfg(feature = "leak-detect")]
+    owner: Owner,
 }
 
 unsafe impl Sync for TcpSocket {}
@@ -51,12 +93,28 @@ unsafe impl Sync for TcpSocket {}
 impl TcpSocket {
     /// Creates a new TCP socket.
     pub const fn new() -> Self {
+        Self::with_buffers(TCP_RX_BUF_LEN, TCP_TX_BUF_LEN)
+    }
+
+    /// Creates a new TCP socket with the given receive and send buffer sizes
+    /// in bytes.
+    ///
+    /// The buffers are allocated when the socket connects. A listening socket
+    /// passes its sizes to the sockets it accepts.
+    pub const fn with_buffers(rx_buf_len: usize, tx_buf_len: usize) -> Self {
         Self {
             state: AtomicU8::new(STATE_CLOSED),
             handle: UnsafeCell::new(None),
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
+            rx_buf_len,
+            tx_buf_len,
+            handshake_timeout: AtomicU64::new(HANDSHAKE_TIMEOUT_DEFAULT),
+            first_byte_deadline: AtomicU64::new(0),
+            last_activity: AtomicU64::new(0),
+            #[cfg(feature = "leak-detect")]
+            owner: Owner::new(),
         }
     }
 
@@ -65,6 +123,7 @@ impl TcpSocket {
         handle: SocketHandle,
         local_addr: IpEndpoint,
         peer_addr: IpEndpoint,
+        (rx_buf_len, tx_buf_len): (usize, usize),
     ) -> Self {
         Self {
             state: AtomicU8::new(STATE_CONNECTED),
@@ -72,13 +131,109 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
+            rx_buf_len,
+            tx_buf_len,
+            handshake_timeout: AtomicU64::new(HANDSHAKE_TIMEOUT_DEFAULT),
+            first_byte_deadline: AtomicU64::new(0),
+            last_activity: AtomicU64::new(0),
+            #[cfg(feature = "leak-detect")]
+            owner: Owner::new(),
+        }
+    }
+
+    /// Returns the receive and send buffer sizes in bytes.
+    pub const fn buffer_sizes(&self) -> (usize, usize) {
+        (self.rx_buf_len, self.tx_buf_len)
+    }
+
+    /// Sets the timeout of the handshakes of this socket, instead of the
+    /// [default one](set_default_handshake_timeout), or disables it with
+    /// `None`.
+    ///
+    /// It bounds the time [`connect_async`](Self::connect_async) waits for
+    /// the connection. On a listening socket, it bounds the time each accepted
+    /// connection waits for its first byte in the async receives, so that
+    /// half-open connections, e.g. of port scanners, cannot pin the resources
+    /// of a server. Both fail with [`Io`](AxError::Io) when it expires.
+    pub fn set_handshake_timeout(&self, timeout: Option<Duration>) {
+        self.handshake_timeout
+            .store(handshake_timeout_nanos(timeout), Ordering::Relaxed);
+    }
+
+    /// Returns the handshake timeout of this socket, see
+    /// [`set_handshake_timeout`](Self::set_handshake_timeout).
+    pub fn handshake_timeout(&self) -> Option<Duration> {
+        let nanos = match self.handshake_timeout.load(Ordering::Relaxed) {
+            HANDSHAKE_TIMEOUT_DEFAULT => DEFAULT_HANDSHAKE_TIMEOUT.load(Ordering::Relaxed),
+            nanos => nanos,
+        };
+        (nanos != HANDSHAKE_TIMEOUT_NONE).then(|| Duration::from_nanos(nanos))
+    }
+
+    /// Starts waiting for the first byte of an accepted socket, for at most
+    /// `timeout`.
+    #[cfg(feature = "async")]
+    pub(crate) fn start_first_byte_timeout(&self, timeout: Option<Duration>) {
+        if let Some(timeout) = timeout {
+            let deadline =
+                monotonic_time_nanos().saturating_add(handshake_timeout_nanos(Some(timeout)));
+            self.first_byte_deadline.store(deadline, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns until when the socket waits for its first byte, if it does.
+    #[cfg(feature = "async")]
+    pub(crate) fn first_byte_deadline(&self) -> Option<TimeValue> {
+        match self.first_byte_deadline.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(TimeValue::from_nanos(nanos)),
         }
     }
 
+    /// Stops waiting for the first byte, once it is received.
+    #[cfg(feature = "async")]
+    pub(crate) fn clear_first_byte_deadline(&self) {
+        self.first_byte_deadline.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the time since the async operations last received or sent
+    /// data, or since the connection was established if they never did.
+    #[cfg(feature = "async")]
+    pub fn idle_time(&self) -> Duration {
+        let last = self.last_activity.load(Ordering::Relaxed);
+        Duration::from_nanos(monotonic_time_nanos().saturating_sub(last))
+    }
+
+    /// Records that the socket is active now, see
+    /// [`idle_time`](Self::idle_time).
+    #[cfg(feature = "async")]
+    pub(crate) fn record_activity(&self) {
+        self.last_activity
+            .store(monotonic_time_nanos(), Ordering::Relaxed);
+    }
+
+    /// Records that the socket is active now, unless it already was.
+    #[cfg(feature = "async")]
+    pub(crate) fn init_activity(&self) {
+        let _ = self.last_activity.compare_exchange(
+            0,
+            monotonic_time_nanos(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
     pub(crate) fn handle(&self) -> SocketHandle {
         unsafe { self.handle.get().read().unwrap() }
     }
 
+    /// Hands the socket to the current task, when it awaits an I/O on it.
+    #[cfg(feature = "async")]
+    pub(crate) fn hand_off(&self) {
+        #[cfg(feature = "leak-detect")]
+        self.owner.hand_off(self.handle());
+    }
+
     /// Returns the local address and port, or
     /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
     #[inline]
@@ -128,8 +283,12 @@ impl TcpSocket {
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
             // SAFETY: no other threads can read or write these fields.
-            let handle = unsafe { self.handle.get().read() }
-                .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
+            let handle = unsafe { self.handle.get().read() }.unwrap_or_else(|| {
+                SOCKET_SET.add(SocketSetWrapper::new_tcp_socket(
+                    self.rx_buf_len,
+                    self.tx_buf_len,
+                ))
+            });
 
             // TODO: check remote addr unreachable
             let remote_endpoint = from_core_sockaddr(remote_addr);
@@ -222,7 +381,7 @@ impl TcpSocket {
             unsafe {
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            LISTEN_TABLE.listen(bound_endpoint, self.buffer_sizes())?;
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
@@ -245,10 +404,17 @@ impl TcpSocket {
         self.block_on(|| {
             let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
-            Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
+            Ok(TcpSocket::new_connected(
+                handle,
+                local_addr,
+                peer_addr,
+                self.buffer_sizes(),
+            ))
         })
     }
 
+    /// Accepts a new connection asynchronously, resolves to the connected
+    /// socket and the address of the peer.
     #[cfg(feature = "async")]
     pub fn accept_async(&self) -> AcceptFuture {
         AcceptFuture::new(self)
@@ -287,6 +453,28 @@ impl TcpSocket {
         Ok(())
     }
 
+    /// Closes the connection at once with a reset, instead of the orderly
+    /// close of [`shutdown`](Self::shutdown).
+    ///
+    /// The pending receives and sends of the socket fail, which lets the
+    /// tasks waiting on a peer that went silent move on. Other states than
+    /// connected are left as is.
+    pub fn abort(&self) -> AxResult {
+        self.update_state(STATE_CONNECTED, STATE_CLOSED, || {
+            // SAFETY: `self.handle` should be initialized in a connected socket, and
+            // no other threads can read or write it.
+            let handle = unsafe { self.handle.get().read().unwrap() };
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                debug!("TCP socket {}: aborting", handle);
+                socket.abort();
+            });
+            unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
+            SOCKET_SET.poll_interfaces();
+            Ok(())
+        })
+        .unwrap_or(Ok(()))
+    }
+
     /// Receives data from the socket, stores it in the given buffer.
     pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
         if self.is_connecting() {
@@ -325,6 +513,14 @@ impl TcpSocket {
         RecvFuture::new(self, buf)
     }
 
+    /// Receives data asynchronously, appending it to `buf` without
+    /// zero-filling its spare capacity first. The buffer grows to take all
+    /// the received data.
+    #[cfg(feature = "async")]
+    pub fn recv_buf_async<'a>(&'a self, buf: &'a mut BytesMut) -> RecvBufFuture<'a> {
+        RecvBufFuture::new(self, buf)
+    }
+
     /// Transmits data in the given buffer.
     pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
         if self.is_connecting() {
@@ -450,33 +646,40 @@ impl TcpSocket {
     pub fn poll_connect(&self) -> AxResult<PollState> {
         // SAFETY: `self.handle` should be initialized above.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        let writable =
-            SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| match socket.state() {
-                State::SynSent => false, // wait for connection
-                State::Established => {
-                    self.set_state(STATE_CONNECTED); // connected
-                    debug!(
-                        "TCP socket {}: connected to {}",
-                        handle,
-                        socket.remote_endpoint().unwrap(),
-                    );
-                    true
-                }
-                _ => {
-                    unsafe {
-                        self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
-                        self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
-                    }
-                    self.set_state(STATE_CLOSED); // connection failed
-                    true
-                }
-            });
+        let writable = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+            self.update_connect_state(handle, socket)
+        });
         Ok(PollState {
             readable: false,
             writable,
         })
     }
 
+    /// Updates the state of a connecting socket from its smoltcp `socket`, and
+    /// returns whether the connection is established or failed.
+    pub(crate) fn update_connect_state(&self, handle: SocketHandle, socket: &tcp::Socket) -> bool {
+        match socket.state() {
+            State::SynSent => false, // wait for connection
+            State::Established => {
+                self.set_state(STATE_CONNECTED); // connected
+                debug!(
+                    "TCP socket {}: connected to {}",
+                    handle,
+                    socket.remote_endpoint().unwrap(),
+                );
+                true
+            }
+            _ => {
+                unsafe {
+                    self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
+                    self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
+                }
+                self.set_state(STATE_CLOSED); // connection failed
+                true
+            }
+        }
+    }
+
     fn poll_stream(&self) -> AxResult<PollState> {
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
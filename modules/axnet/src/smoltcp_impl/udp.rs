@@ -11,7 +11,9 @@ use smoltcp::socket::udp::{self, BindError, SendError};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{SOCKET_SET, SocketSetWrapper};
+#[cfg(feature = "leak-detect")]
+use super::leak::Owner;
+use super::{SOCKET_SET, SocketSetWrapper, UDP_RX_BUF_LEN, UDP_TX_BUF_LEN};
 
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
@@ -19,19 +21,29 @@ pub struct UdpSocket {
     local_addr: RwLock<Option<IpEndpoint>>,
     peer_addr: RwLock<Option<IpEndpoint>>,
     nonblock: AtomicBool,
+    #[cfg(feature = "leak-detect")]
+    owner: Owner,
 }
 
 impl UdpSocket {
     /// Creates a new UDP socket.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_udp_socket();
+        Self::with_buffers(UDP_RX_BUF_LEN, UDP_TX_BUF_LEN)
+    }
+
+    /// Creates a new UDP socket with the given receive and send buffer sizes
+    /// in bytes.
+    pub fn with_buffers(rx_buf_len: usize, tx_buf_len: usize) -> Self {
+        let socket = SocketSetWrapper::new_udp_socket(rx_buf_len, tx_buf_len);
         let handle = SOCKET_SET.add(socket);
         Self {
             handle,
             local_addr: RwLock::new(None),
             peer_addr: RwLock::new(None),
             nonblock: AtomicBool::new(false),
+            #[cfg(feature = "leak-detect")]
+            owner: Owner::new(),
         }
     }
 
@@ -118,6 +130,44 @@ impl UdpSocket {
         })
     }
 
+    /// Receives a single datagram message on the socket, waiting for it
+    /// without blocking the task. On success, returns the number of bytes
+    /// read and the origin.
+    #[cfg(feature = "async")]
+    pub async fn recv_from_async(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        if self.local_addr.read().is_none() {
+            return ax_err!(NotConnected, "socket recv_from() failed");
+        }
+        core::future::poll_fn(|cx| {
+            #[cfg(feature = "leak-detect")]
+            self.owner.hand_off(self.handle);
+            SOCKET_SET.poll_interfaces();
+            let res = core::task::ready!(SOCKET_SET.poll_socket_mut::<udp::Socket, _, _>(
+                self.handle,
+                cx,
+                |socket| {
+                    if !socket.can_recv() {
+                        return core::task::Poll::Ready(Err(AxError::WouldBlock));
+                    }
+                    core::task::Poll::Ready(match socket.recv_slice(buf) {
+                        Ok((len, meta)) => Ok((len, into_core_sockaddr(meta.endpoint))),
+                        Err(_) => ax_err!(BadState, "socket recv_from() failed"),
+                    })
+                }
+            ));
+            match res {
+                Err(AxError::WouldBlock) => {
+                    // smoltcp only supports wakers on TCP sockets, ask to be
+                    // polled again.
+                    cx.waker().wake_by_ref();
+                    core::task::Poll::Pending
+                }
+                res => core::task::Poll::Ready(res),
+            }
+        })
+        .await
+    }
+
     /// Receives a single datagram message on the socket, without removing it from
     /// the queue. On success, returns the number of bytes read and the origin.
     pub fn peek_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
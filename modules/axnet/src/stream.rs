@@ -0,0 +1,191 @@
+//! Cloneable async TCP streams.
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::net::SocketAddr;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use axasync::io::{AsyncRead, AsyncWrite, BytesMut};
+use axerrno::{AxError, AxResult};
+
+use crate::TcpSocket;
+
+/// A connected TCP socket for the [axasync] runtime, shared by cheap clones.
+///
+/// All the clones refer to the same connection, so one task can read from
+/// it while another writes to it. The connection is closed when the last
+/// clone is dropped, or by [`shutdown`](Self::shutdown).
+///
+/// [axasync]: https://arceos-org.github.io/arceos/axasync/index.html
+#[derive(Clone)]
+pub struct TcpStream {
+    socket: Arc<TcpSocket>,
+}
+
+impl TcpStream {
+    /// Connects to `addr`.
+    pub async fn connect(addr: SocketAddr) -> AxResult<Self> {
+        let socket = TcpSocket::new();
+        socket.connect_async(addr).await?;
+        Ok(socket.into())
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &TcpSocket {
+        &self.socket
+    }
+
+    /// Returns the underlying socket, shared with the clones.
+    pub(crate) fn shared_socket(&self) -> &Arc<TcpSocket> {
+        &self.socket
+    }
+
+    /// Returns the local address and port.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Returns the remote address and port.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Receives data into `buf`, returns the number of bytes read, `0` once
+    /// the peer has closed the connection.
+    pub async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.socket.recv_async(buf).await
+    }
+
+    /// Receives data and appends it to `buf`, which grows as needed, returns
+    /// the number of bytes read, `0` once the peer has closed the connection.
+    ///
+    /// Unlike [`read`](Self::read), it copies straight from the socket into
+    /// the buffer, without zero-filling it first.
+    pub async fn recv_buf(&self, buf: &mut BytesMut) -> AxResult<usize> {
+        self.socket.recv_buf_async(buf).await
+    }
+
+    /// Sends data from `buf`, returns the number of bytes written.
+    pub async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.socket.send_async(buf).await
+    }
+
+    /// Closes the connection, for all the clones.
+    pub fn shutdown(&self) -> AxResult {
+        self.socket.shutdown()
+    }
+
+    /// Splits the stream into a half that reads and a half that writes, to
+    /// be moved to different tasks.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        (OwnedReadHalf(self.clone()), OwnedWriteHalf(self))
+    }
+}
+
+impl From<TcpSocket> for TcpStream {
+    fn from(socket: TcpSocket) -> Self {
+        Self {
+            socket: Arc::new(socket),
+        }
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>> {
+        let mut recv = self.socket.recv_async(buf);
+        Pin::new(&mut recv).poll(cx)
+    }
+
+    fn poll_read_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<AxResult<usize>> {
+        let mut recv = self.socket.recv_buf_async(buf);
+        Pin::new(&mut recv).poll(cx)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        let mut send = self.socket.send_async(buf);
+        Pin::new(&mut send).poll(cx)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<AxResult> {
+        // Sent data is queued in the socket, the stack transmits it.
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The reading half of a [`TcpStream`], see [`TcpStream::into_split`].
+pub struct OwnedReadHalf(TcpStream);
+
+impl OwnedReadHalf {
+    /// Returns the stream the half was split from.
+    pub fn stream(&self) -> &TcpStream {
+        &self.0
+    }
+
+    /// Receives data into `buf`, see [`TcpStream::read`].
+    pub async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.0.read(buf).await
+    }
+
+    /// Receives data and appends it to `buf`, see [`TcpStream::recv_buf`].
+    pub async fn recv_buf(&self, buf: &mut BytesMut) -> AxResult<usize> {
+        self.0.recv_buf(buf).await
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+
+    fn poll_read_buf(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut self.0).poll_read_buf(cx, buf)
+    }
+}
+
+/// The writing half of a [`TcpStream`], see [`TcpStream::into_split`].
+pub struct OwnedWriteHalf(TcpStream);
+
+impl OwnedWriteHalf {
+    /// Returns the stream the half was split from.
+    pub fn stream(&self) -> &TcpStream {
+        &self.0
+    }
+
+    /// Sends data from `buf`, see [`TcpStream::write`].
+    pub async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.0.write(buf).await
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<AxResult<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+}
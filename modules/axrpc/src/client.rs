@@ -0,0 +1,67 @@
+use core::marker::PhantomData;
+
+use axasync::codec::{Framed, PostcardCodec};
+use axasync::futures_util::{SinkExt, StreamExt};
+use axasync::{AsyncRead, AsyncWrite};
+use axerrno::{AxResult, ax_err};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A client that sends requests of type `Req` over the byte stream `T`, and
+/// receives responses of type `Resp`.
+///
+/// Calls are sequential: each call waits for its response before returning.
+/// Responses to cancelled calls are discarded by the next call.
+pub struct Client<Req, Resp, T> {
+    framed: Framed<T, PostcardCodec<(u32, Resp)>>,
+    /// The sequence number of the next request.
+    next_seq: u32,
+    _marker: PhantomData<fn(Req)>,
+}
+
+impl<Req, Resp, T> Client<Req, Resp, T>
+where
+    Req: Serialize,
+    Resp: DeserializeOwned,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Creates a client that sends requests over `io`.
+    pub fn new(io: T) -> Self {
+        Self {
+            framed: Framed::new(io, PostcardCodec::new()),
+            next_seq: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the byte stream.
+    pub fn get_ref(&self) -> &T {
+        self.framed.get_ref()
+    }
+
+    /// Returns the byte stream.
+    pub fn into_inner(self) -> T {
+        self.framed.into_inner()
+    }
+
+    /// Sends `request`, and waits for its response.
+    ///
+    /// Fails with [`ConnectionReset`] if the server closes the connection.
+    ///
+    /// [`ConnectionReset`]: axerrno::AxError::ConnectionReset
+    pub async fn call(&mut self, request: &Req) -> AxResult<Resp> {
+        let seq = self.next_seq;
+        self.next_seq = seq.wrapping_add(1);
+        self.framed.send((seq, request)).await?;
+        loop {
+            let Some(frame) = self.framed.next().await else {
+                return ax_err!(ConnectionReset, "axrpc: server closed the connection");
+            };
+            let (resp_seq, response) = frame?;
+            if resp_seq == seq {
+                return Ok(response);
+            }
+            debug!("axrpc: discarding stale response {}", resp_seq);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Simple async RPC for [ArceOS](https://github.com/arceos-org/arceos).
+//!
+//! A service is a trait of async methods, declared with [`service!`]. The
+//! macro generates a request and a response enum, with one variant per
+//! method, a client stub that sends requests and awaits responses, and a
+//! server dispatcher that calls an implementation of the trait.
+//!
+//! Messages are encoded with [`PostcardCodec`], over any [`AsyncRead`] +
+//! [`AsyncWrite`] byte stream: a [`LocalStream`] between components of the
+//! same system, or a TCP socket between systems.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! axrpc::service! {
+//!     /// A calculator.
+//!     pub trait Calc {
+//!         fn add(a: i32, b: i32) -> i32;
+//!     }
+//! }
+//!
+//! struct MyCalc;
+//!
+//! impl Calc for MyCalc {
+//!     async fn add(&self, a: i32, b: i32) -> i32 {
+//!         a + b
+//!     }
+//! }
+//!
+//! let (client_io, server_io) = LocalStream::pair();
+//! axrpc::spawn_server(Arc::new(CalcServer(MyCalc)), server_io);
+//! let mut calc = CalcClient::new(client_io);
+//! assert_eq!(calc.add(1, 2).await?, 3);
+//! ```
+//!
+//! [`AsyncRead`]: axasync::AsyncRead
+//! [`AsyncWrite`]: axasync::AsyncWrite
+//! [`LocalStream`]: axasync::LocalStream
+//! [`PostcardCodec`]: axasync::codec::PostcardCodec
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod client;
+mod server;
+
+pub use self::client::Client;
+pub use self::server::{Service, serve, spawn_server};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use axasync::{AsyncRead, AsyncWrite};
+    pub use axerrno::{AxError, AxResult};
+    pub use core::future::Future;
+    pub use paste::paste;
+}
+
+#[doc(hidden)]
+pub use serde;
+
+/// Declares an RPC service.
+///
+/// For a trait `Name`, it generates:
+///
+/// - the trait itself, whose methods are `async` and take `&self`;
+/// - `NameRequest` and `NameResponse`, enums of the arguments and the return
+///   values of the methods, with a `CamelCase` variant per method;
+/// - `NameClient<T>`, a client stub over a byte stream `T`, with the same
+///   methods returning [`AxResult`];
+/// - `NameServer<S>`, which wraps an implementation of the trait into a
+///   [`Service`] for [`serve`].
+///
+/// Arguments and return values must implement `serde::Serialize` and
+/// `serde::Deserialize`. The enums derive them through the `serde` of this
+/// crate, so the caller does not need to depend on `serde`.
+#[macro_export]
+macro_rules! service {
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident {
+            $(
+                $(#[$method_attr:meta])*
+                fn $method:ident($($arg:ident: $arg_ty:ty),* $(,)?) -> $ret:ty;
+            )*
+        }
+    ) => {
+        $crate::__private::paste! {
+            $(#[$attr])*
+            $vis trait $name: Send + Sync + 'static {
+                $(
+                    $(#[$method_attr])*
+                    fn $method(
+                        &self,
+                        $($arg: $arg_ty),*
+                    ) -> impl $crate::__private::Future<Output = $ret> + Send;
+                )*
+            }
+
+            #[doc = "Requests of [`" $name "`]."]
+            #[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
+            #[serde(crate = "axrpc::serde")]
+            $vis enum [<$name Request>] {
+                $(
+                    #[doc = "Arguments of [`" $name "::" $method "`]."]
+                    [<$method:camel>] { $($arg: $arg_ty),* },
+                )*
+            }
+
+            #[doc = "Responses of [`" $name "`]."]
+            #[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
+            #[serde(crate = "axrpc::serde")]
+            $vis enum [<$name Response>] {
+                $(
+                    #[doc = "Return value of [`" $name "::" $method "`]."]
+                    [<$method:camel>]($ret),
+                )*
+            }
+
+            #[doc = "A client of [`" $name "`]."]
+            $vis struct [<$name Client>]<T>(
+                $crate::Client<[<$name Request>], [<$name Response>], T>,
+            );
+
+            #[allow(dead_code)]
+            impl<T> [<$name Client>]<T>
+            where
+                T: $crate::__private::AsyncRead + $crate::__private::AsyncWrite + Unpin,
+            {
+                /// Creates a client that sends requests over `io`.
+                $vis fn new(io: T) -> Self {
+                    Self($crate::Client::new(io))
+                }
+
+                /// Returns the underlying client.
+                $vis fn inner(&mut self) -> &mut $crate::Client<
+                    [<$name Request>],
+                    [<$name Response>],
+                    T,
+                > {
+                    &mut self.0
+                }
+
+                $(
+                    $(#[$method_attr])*
+                    #[allow(unreachable_patterns)]
+                    $vis async fn $method(
+                        &mut self,
+                        $($arg: $arg_ty),*
+                    ) -> $crate::__private::AxResult<$ret> {
+                        let request = [<$name Request>]::[<$method:camel>] { $($arg),* };
+                        match self.0.call(&request).await? {
+                            [<$name Response>]::[<$method:camel>](ret) => Ok(ret),
+                            _ => Err($crate::__private::AxError::InvalidData),
+                        }
+                    }
+                )*
+            }
+
+            #[doc = "Serves [`" $name "`] with the implementation `S`."]
+            $vis struct [<$name Server>]<S>(pub S);
+
+            impl<S: $name> $crate::Service for [<$name Server>]<S> {
+                type Request = [<$name Request>];
+                type Response = [<$name Response>];
+
+                async fn call(&self, request: Self::Request) -> Self::Response {
+                    match request {
+                        $(
+                            [<$name Request>]::[<$method:camel>] { $($arg),* } => {
+                                [<$name Response>]::[<$method:camel>](
+                                    self.0.$method($($arg),*).await,
+                                )
+                            }
+                        )*
+                    }
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+extern crate self as axrpc;
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use axasync::LocalStream;
+    use axasync::futures_util::future::join;
+
+    crate::service! {
+        trait Calc {
+            fn add(a: i32, b: i32) -> i32;
+            fn greet(name: String) -> String;
+        }
+    }
+
+    struct MyCalc;
+
+    impl Calc for MyCalc {
+        async fn add(&self, a: i32, b: i32) -> i32 {
+            a + b
+        }
+
+        async fn greet(&self, name: String) -> String {
+            alloc::format!("hello, {}", name)
+        }
+    }
+
+    #[test]
+    fn test_local_rpc() {
+        let (client_io, server_io) = LocalStream::pair_with_capacity(16);
+        let server = CalcServer(MyCalc);
+        let client = async move {
+            let mut calc = CalcClient::new(client_io);
+            assert_eq!(calc.add(1, 2).await, Ok(3));
+            assert_eq!(
+                calc.greet("arceos".to_string()).await.as_deref(),
+                Ok("hello, arceos")
+            );
+        };
+        let (res, ()) = futures_executor::block_on(join(super::serve(&server, server_io), client));
+        assert_eq!(res, Ok(()));
+    }
+}
@@ -0,0 +1,52 @@
+use alloc::sync::Arc;
+use core::future::Future;
+
+use axasync::JoinHandle;
+use axasync::codec::{Framed, PostcardCodec};
+use axasync::futures_util::{SinkExt, StreamExt};
+use axasync::{AsyncRead, AsyncWrite};
+use axerrno::AxResult;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A handler of requests, usually generated by [`service!`](crate::service).
+pub trait Service: Send + Sync + 'static {
+    /// The requests.
+    type Request: DeserializeOwned + Send;
+    /// The responses.
+    type Response: Serialize + Send;
+
+    /// Handles a request.
+    fn call(&self, request: Self::Request) -> impl Future<Output = Self::Response> + Send;
+}
+
+/// Serves the requests received over `io` with `service`, one at a time, until
+/// the client closes the connection.
+pub async fn serve<S, T>(service: &S, io: T) -> AxResult
+where
+    S: Service,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(io, PostcardCodec::<(u32, S::Request)>::new());
+    while let Some(frame) = framed.next().await {
+        let (seq, request) = frame?;
+        let response = service.call(request).await;
+        framed.send((seq, response)).await?;
+    }
+    Ok(())
+}
+
+/// Spawns a task on the executor that serves the requests received over `io`.
+pub fn spawn_server<S, T>(service: Arc<S>, io: T) -> JoinHandle<AxResult>
+where
+    S: Service,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    axasync::spawn(async move {
+        let res = serve(&*service, io).await;
+        if let Err(e) = res {
+            warn!("axrpc: server failed: {:?}", e);
+        }
+        res
+    })
+}
@@ -3,5 +3,16 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("{}", info);
+    // Unwinds to the caller that catches it, e.g. the executor of an async
+    // task. It only returns if there is none.
+    #[cfg(feature = "unwind")]
+    {
+        let code = unwinding::panic::begin_panic(alloc::boxed::Box::new(()));
+        error!("no panic catcher, unwinding stopped: {:?}", code.0);
+    }
+    // Fatal, the output from now on is not recorded for a replay.
+    axlog::switch_console_on_panic();
+    // Only the hooks that need no lock nor state of the panicked code run.
+    axhal::misc::set_panicking();
     axhal::misc::terminate()
 }
@@ -13,6 +13,10 @@
 //! - `fs`: Enable filesystem support.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `unwind`: Unwind on panics, so that the panics of async tasks are caught
+//!   by their executor. The kernel must be built with `-C panic=unwind`.
+//! - `pstore`: Keep the console output in a memory region that survives warm
+//!   reboots, and print the output of the previous boot once the console is up.
 //!
 //! All the features are optional and disabled by default.
 
@@ -22,6 +26,9 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(feature = "unwind")]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 
@@ -31,6 +38,8 @@ mod mp;
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
+pub use axhal::misc::{on_panic_shutdown, on_shutdown};
+
 const LOGO: &str = r#"
        d8888                            .d88888b.   .d8888b.
       d88888                           d88P" "Y88b d88P  Y88b
@@ -83,10 +92,9 @@ impl axlog::LogIf for LogIfImpl {
     }
 }
 
-use core::{
-    ptr::{read_volatile, write_volatile},
-    sync::atomic::{AtomicUsize, Ordering},
-};
+#[cfg(target_arch = "riscv64")]
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 static INITED_CPUS: AtomicUsize = AtomicUsize::new(0);
 
@@ -105,6 +113,9 @@ fn is_init_ok() -> bool {
 /// and the secondary CPUs call [`rust_main_secondary`].
 #[cfg_attr(not(test), unsafe(no_mangle))]
 pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
+    #[cfg(feature = "pstore")]
+    init_pstore();
+
     ax_println!("{}", LOGO);
     ax_println!(
         "\
@@ -128,10 +139,18 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         chrono::DateTime::from_timestamp_nanos(axhal::time::wall_time_nanos() as _),
     );
 
+    axhal::init_bootargs(dtb);
+    let bootargs = axhal::bootargs();
+
     axlog::init();
     axlog::set_max_level(option_env!("AX_LOG").unwrap_or("")); // no effect if set `log-level-*` features
+    if let Some(level) = bootargs.get("loglevel") {
+        axlog::set_max_level(level);
+    }
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    info!("Command line: {:?}", bootargs.as_str());
+    axhal::init_dt_devices(dtb);
 
     info!("Found physcial memory regions:");
     for r in axhal::mem::memory_regions() {
@@ -174,12 +193,25 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         axfs::init_filesystems(all_devices.block);
 
         #[cfg(feature = "net")]
-        axnet::init_network(all_devices.net);
+        {
+            for (key, value) in bootargs.iter() {
+                if let Err(e) = axnet::config::set_bootarg(key, value.unwrap_or("")) {
+                    warn!("invalid boot argument {}: {:?}", key, e);
+                }
+            }
+            axnet::init_network(all_devices.net);
+        }
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
     }
 
+    // All console devices are up, stop recording the early output (if any).
+    axlog::switch_console();
+
+    #[cfg(feature = "pstore")]
+    axlog::dump_previous_boot_log();
+
     #[cfg(feature = "smp")]
     self::mp::start_secondary_cpus(cpu_id);
 
@@ -209,6 +241,21 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     }
 }
 
+#[cfg(feature = "pstore")]
+fn init_pstore() {
+    use axhal::mem::{phys_to_virt, pstore_region};
+
+    let (paddr, size) = pstore_region();
+    // SAFETY: The region is reserved by `axhal`, and linearly mapped.
+    unsafe { axlog::init_pstore(phys_to_virt(paddr).as_mut_ptr(), size) };
+    // Registered first, so that it runs last and keeps the output of the other
+    // hooks. It also keeps the output of a panic.
+    axhal::misc::on_panic_shutdown(|| {
+        let (paddr, size) = pstore_region();
+        axhal::cache::clean_dcache_range(phys_to_virt(paddr), size);
+    });
+}
+
 #[cfg(feature = "alloc")]
 fn init_allocator() {
     use axhal::mem::{MemRegionFlags, memory_regions, phys_to_virt};
@@ -275,7 +322,10 @@ fn init_interrupt() {
         #[cfg(feature = "multitask")]
         axtask::on_timer_tick();
         #[cfg(feature = "axasync-timer")]
-        axasync::check_timer_events();
+        {
+            axasync::check_timer_events();
+            axasync::on_timer_tick();
+        }
         // #[cfg(feature = "net")]
         // {
         //     let now_ns = axhal::time::monotonic_time_nanos();
@@ -286,6 +336,7 @@ fn init_interrupt() {
         // debug_78();
     });
     update_timer();
+    #[cfg(target_arch = "riscv64")]
     debug_print();
     // Enable IRQs before starting app
     axhal::arch::enable_irqs();
@@ -299,6 +350,7 @@ fn init_tls() {
     core::mem::forget(main_tls);
 }
 
+#[cfg(target_arch = "riscv64")]
 fn debug_print() {
     let plic_addr: usize = 0xFFFF_FFC0_0c00_0000;
     for i in [5, 6, 7, 76, 77, 78] {
@@ -330,6 +382,8 @@ fn debug_print() {
     trace!("uart DLH/IER: {:#x}", dlh_ier);
 }
 
+#[cfg(target_arch = "riscv64")]
+#[allow(dead_code)]
 fn debug_78() {
     let plic_addr: usize = 0xFFFF_FFC0_0c00_0000;
     let word = unsafe { read_volatile((plic_addr + 0x1000 + (78 / 32) * 4) as *const u32) };
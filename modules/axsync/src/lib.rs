@@ -4,18 +4,23 @@
 //!
 //! - [`Mutex`]: A mutual exclusion primitive.
 //! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
+//! - mod [`lockstat`]: lock contention statistics.
 //!
 //! # Cargo Features
 //!
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `lockstat`: Record acquisitions and contention of the locks instrumented
+//!   by [`lockstat::LockStat`].
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 
 pub use kspin as spin;
 
+pub mod lockstat;
+
 #[cfg(feature = "multitask")]
 mod mutex;
 
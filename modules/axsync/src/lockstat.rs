@@ -0,0 +1,210 @@
+//! Lock contention statistics.
+//!
+//! A lock is instrumented by acquiring it through a static [`LockStat`]:
+//!
+//! ```ignore
+//! static QUEUE_STAT: LockStat = LockStat::new("executor.ready_tasks");
+//!
+//! let queue = QUEUE_STAT.lock(&self.ready_tasks);
+//! ```
+//!
+//! Without the `lockstat` feature, [`LockStat::lock`] is the same as locking
+//! directly and nothing is recorded.
+
+#[cfg(feature = "lockstat")]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+/// The maximum number of instrumented locks, further ones are not recorded.
+pub const MAX_LOCK_STATS: usize = 64;
+
+#[cfg(feature = "lockstat")]
+static REGISTRY: [AtomicPtr<LockStat>; MAX_LOCK_STATS] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_LOCK_STATS];
+#[cfg(feature = "lockstat")]
+static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+/// A lock that can be instrumented by [`LockStat`].
+pub trait Lockable {
+    /// The guard returned by locking.
+    type Guard<'a>
+    where
+        Self: 'a;
+
+    /// Acquires the lock, blocking or spinning until it is available.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Tries to acquire the lock without waiting.
+    fn try_lock(&self) -> Option<Self::Guard<'_>>;
+}
+
+impl<T: ?Sized> Lockable for spin::Mutex<T> {
+    type Guard<'a>
+        = spin::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        spin::Mutex::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        spin::Mutex::try_lock(self)
+    }
+}
+
+impl<G: kernel_guard::BaseGuard, T: ?Sized> Lockable for kspin::BaseSpinLock<G, T> {
+    type Guard<'a>
+        = kspin::BaseSpinLockGuard<'a, G, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        kspin::BaseSpinLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        kspin::BaseSpinLock::try_lock(self)
+    }
+}
+
+#[cfg(feature = "multitask")]
+impl<T: ?Sized> Lockable for crate::Mutex<T> {
+    type Guard<'a>
+        = crate::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        crate::Mutex::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        crate::Mutex::try_lock(self)
+    }
+}
+
+/// Acquisition statistics of one lock.
+pub struct LockStat {
+    name: &'static str,
+    #[cfg(feature = "lockstat")]
+    registered: AtomicBool,
+    #[cfg(feature = "lockstat")]
+    acquisitions: AtomicU64,
+    #[cfg(feature = "lockstat")]
+    contentions: AtomicU64,
+    #[cfg(feature = "lockstat")]
+    wait_nanos: AtomicU64,
+}
+
+/// A snapshot of a [`LockStat`].
+#[derive(Debug, Clone, Copy)]
+pub struct LockStatSnapshot {
+    /// The name of the lock.
+    pub name: &'static str,
+    /// The number of acquisitions.
+    pub acquisitions: u64,
+    /// The number of acquisitions that found the lock held.
+    pub contentions: u64,
+    /// The total time spent waiting for the lock, in nanoseconds.
+    pub wait_nanos: u64,
+}
+
+impl LockStat {
+    /// Creates the statistics of the lock named `name`.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            #[cfg(feature = "lockstat")]
+            registered: AtomicBool::new(false),
+            #[cfg(feature = "lockstat")]
+            acquisitions: AtomicU64::new(0),
+            #[cfg(feature = "lockstat")]
+            contentions: AtomicU64::new(0),
+            #[cfg(feature = "lockstat")]
+            wait_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the name of the lock.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Acquires `lock` and records the acquisition.
+    #[inline]
+    pub fn lock<'a, L: Lockable + ?Sized>(&'static self, lock: &'a L) -> L::Guard<'a> {
+        #[cfg(feature = "lockstat")]
+        {
+            self.register();
+            self.acquisitions.fetch_add(1, Ordering::Relaxed);
+            if let Some(guard) = lock.try_lock() {
+                return guard;
+            }
+            let start = axhal::time::monotonic_time_nanos();
+            let guard = lock.lock();
+            let waited = axhal::time::monotonic_time_nanos() - start;
+            self.contentions.fetch_add(1, Ordering::Relaxed);
+            self.wait_nanos.fetch_add(waited, Ordering::Relaxed);
+            guard
+        }
+        #[cfg(not(feature = "lockstat"))]
+        lock.lock()
+    }
+
+    /// Returns a snapshot of the statistics.
+    pub fn snapshot(&self) -> LockStatSnapshot {
+        #[cfg(feature = "lockstat")]
+        {
+            LockStatSnapshot {
+                name: self.name,
+                acquisitions: self.acquisitions.load(Ordering::Relaxed),
+                contentions: self.contentions.load(Ordering::Relaxed),
+                wait_nanos: self.wait_nanos.load(Ordering::Relaxed),
+            }
+        }
+        #[cfg(not(feature = "lockstat"))]
+        LockStatSnapshot {
+            name: self.name,
+            acquisitions: 0,
+            contentions: 0,
+            wait_nanos: 0,
+        }
+    }
+
+    #[cfg(feature = "lockstat")]
+    fn register(&'static self) {
+        if self.registered.load(Ordering::Relaxed) || self.registered.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let idx = REGISTERED.fetch_add(1, Ordering::AcqRel);
+        if idx < MAX_LOCK_STATS {
+            REGISTRY[idx].store(self as *const _ as *mut _, Ordering::Release);
+        }
+    }
+}
+
+/// Calls `f` with the statistics of every instrumented lock that has been
+/// acquired at least once.
+pub fn for_each_lock_stat(mut f: impl FnMut(LockStatSnapshot)) {
+    #[cfg(feature = "lockstat")]
+    for slot in REGISTRY.iter() {
+        let stat = slot.load(Ordering::Acquire);
+        if !stat.is_null() {
+            f(unsafe { &*stat }.snapshot());
+        }
+    }
+    #[cfg(not(feature = "lockstat"))]
+    let _ = &mut f;
+}
+
+/// Clears the statistics of all instrumented locks.
+pub fn reset_lock_stats() {
+    #[cfg(feature = "lockstat")]
+    for slot in REGISTRY.iter() {
+        if let Some(stat) = unsafe { slot.load(Ordering::Acquire).as_ref() } {
+            stat.acquisitions.store(0, Ordering::Relaxed);
+            stat.contentions.store(0, Ordering::Relaxed);
+            stat.wait_nanos.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -92,6 +92,7 @@ impl<T: ?Sized> Mutex<T> {
                 }
             }
         }
+        current().lock_acquired();
         MutexGuard {
             lock: self,
             data: unsafe { &mut *self.data.get() },
@@ -109,6 +110,7 @@ impl<T: ?Sized> Mutex<T> {
             .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            current().lock_acquired();
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -133,6 +135,7 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        current().lock_released();
         self.wq.notify_one(true);
     }
 
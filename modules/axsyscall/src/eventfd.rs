@@ -0,0 +1,211 @@
+use alloc::sync::Arc;
+use core::any::Any;
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+
+use axasync::WakeBatch;
+use axerrno::{AxResult, ax_err};
+use axio::PollState;
+use spin::Mutex;
+
+use crate::FileLike;
+
+/// The largest value of the counter of an [`EventFd`].
+const MAX_COUNT: u64 = u64::MAX - 1;
+
+struct EventFdInner {
+    count: u64,
+    /// Tasks waiting for the counter to become nonzero.
+    readers: WakeBatch,
+    /// Tasks waiting for room in the counter.
+    writers: WakeBatch,
+}
+
+impl EventFdInner {
+    fn state(&self) -> PollState {
+        PollState {
+            readable: self.count > 0,
+            writable: self.count < MAX_COUNT,
+        }
+    }
+}
+
+/// An event counter, like Linux `eventfd`.
+///
+/// Writing adds to the counter, reading returns the counter and resets it to
+/// zero. In semaphore mode, reading returns `1` and decrements the counter
+/// instead. Reading waits while the counter is zero, writing waits while it
+/// would exceed `u64::MAX - 1`.
+pub struct EventFd {
+    semaphore: bool,
+    inner: Mutex<EventFdInner>,
+}
+
+impl EventFd {
+    /// Creates an event counter starting at `count`.
+    pub fn new(count: u64, semaphore: bool) -> Self {
+        Self {
+            semaphore,
+            inner: Mutex::new(EventFdInner {
+                count: count.min(MAX_COUNT),
+                readers: WakeBatch::new(),
+                writers: WakeBatch::new(),
+            }),
+        }
+    }
+
+    /// Attempts to take a value from the counter.
+    pub fn poll_take(&self, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut inner = self.inner.lock();
+        if inner.count == 0 {
+            inner.readers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let value = if self.semaphore { 1 } else { inner.count };
+        inner.count -= value;
+        let writers = core::mem::take(&mut inner.writers);
+        drop(inner);
+        writers.wake_all();
+        Poll::Ready(value)
+    }
+
+    /// Attempts to add `value` to the counter.
+    pub fn poll_add(&self, cx: &mut Context<'_>, value: u64) -> Poll<AxResult> {
+        if value > MAX_COUNT {
+            return Poll::Ready(ax_err!(InvalidInput, "eventfd value too large"));
+        }
+        let mut inner = self.inner.lock();
+        if value > MAX_COUNT - inner.count {
+            inner.writers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        inner.count += value;
+        let readers = if value > 0 {
+            core::mem::take(&mut inner.readers)
+        } else {
+            WakeBatch::new()
+        };
+        drop(inner);
+        readers.wake_all();
+        Poll::Ready(Ok(()))
+    }
+
+    /// Waits for the counter to become nonzero, and takes a value from it.
+    pub async fn take(&self) -> u64 {
+        poll_fn(|cx| self.poll_take(cx)).await
+    }
+
+    /// Adds `value` to the counter, waiting for room if needed.
+    pub async fn add(&self, value: u64) -> AxResult {
+        poll_fn(|cx| self.poll_add(cx, value)).await
+    }
+}
+
+impl FileLike for EventFd {
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        let Some(buf) = buf.first_chunk_mut::<8>() else {
+            return Poll::Ready(ax_err!(InvalidInput, "eventfd read buffer too small"));
+        };
+        self.poll_take(cx).map(|value| {
+            *buf = value.to_ne_bytes();
+            Ok(8)
+        })
+    }
+
+    fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        let Some(bytes) = buf.first_chunk::<8>() else {
+            return Poll::Ready(ax_err!(InvalidInput, "eventfd write buffer too small"));
+        };
+        self.poll_add(cx, u64::from_ne_bytes(*bytes))
+            .map(|res| res.map(|_| 8))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        Ok(self.inner.lock().state())
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> AxResult<PollState> {
+        let mut inner = self.inner.lock();
+        let state = inner.state();
+        if !state.readable {
+            inner.readers.push(cx.waker().clone());
+        }
+        if !state.writable {
+            inner.writers.push(cx.waker().clone());
+        }
+        Ok(state)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::Ordering;
+    use core::task::Waker;
+
+    use axerrno::AxError;
+
+    use super::*;
+    use crate::tests::flag_waker;
+
+    #[test]
+    fn counter() {
+        let eventfd = EventFd::new(0, false);
+        let (waker, woken) = flag_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(eventfd.poll_take(&mut cx).is_pending());
+        assert_eq!(eventfd.poll_add(&mut cx, 2), Poll::Ready(Ok(())));
+        assert_eq!(eventfd.poll_add(&mut cx, 3), Poll::Ready(Ok(())));
+        assert!(woken.swap(false, Ordering::SeqCst));
+        assert_eq!(eventfd.poll_take(&mut cx), Poll::Ready(5));
+        assert!(!eventfd.poll_state().unwrap().readable);
+    }
+
+    #[test]
+    fn semaphore() {
+        let eventfd = EventFd::new(2, true);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(eventfd.poll_take(&mut cx), Poll::Ready(1));
+        assert_eq!(eventfd.poll_take(&mut cx), Poll::Ready(1));
+        assert!(eventfd.poll_take(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn full_counter() {
+        let eventfd = EventFd::new(MAX_COUNT, false);
+        let (waker, woken) = flag_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(
+            eventfd.poll_add(&mut cx, u64::MAX),
+            Poll::Ready(Err(AxError::InvalidInput))
+        );
+        let state = eventfd.poll_ready(&mut cx).unwrap();
+        assert!(state.readable && !state.writable);
+        assert!(eventfd.poll_add(&mut cx, 1).is_pending());
+        assert_eq!(eventfd.poll_take(&mut cx), Poll::Ready(MAX_COUNT));
+        assert!(woken.load(Ordering::SeqCst));
+        assert_eq!(eventfd.poll_add(&mut cx, 1), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn read_write_buffers() {
+        let eventfd = EventFd::new(0, false);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(
+            eventfd.poll_write(&mut cx, &[0; 4]),
+            Poll::Ready(Err(AxError::InvalidInput))
+        );
+        assert_eq!(
+            eventfd.poll_write(&mut cx, &7u64.to_ne_bytes()),
+            Poll::Ready(Ok(8))
+        );
+        let mut buf = [0; 8];
+        assert_eq!(eventfd.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(8)));
+        assert_eq!(u64::from_ne_bytes(buf), 7);
+    }
+}
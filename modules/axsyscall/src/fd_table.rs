@@ -0,0 +1,147 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::ffi::c_int;
+use core::task::{Context, Poll};
+
+use axerrno::{AxResult, LinuxError, LinuxResult};
+use axio::PollState;
+use spin::Mutex;
+
+use crate::stdio::{Stdin, Stdout};
+
+/// The maximum number of open file descriptors.
+pub const AX_FILE_LIMIT: usize = 1024;
+
+/// An object that can be referred to by a file descriptor.
+///
+/// The `poll_*` methods follow [`Future::poll`]: they return
+/// [`Poll::Pending`] and arrange for `cx` to be woken when the operation
+/// cannot complete yet.
+pub trait FileLike: Send + Sync {
+    /// Attempts to read into `buf`.
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>>;
+
+    /// Attempts to write `buf`.
+    fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>>;
+
+    /// Returns whether the object is readable or writable now.
+    fn poll_state(&self) -> AxResult<PollState>;
+
+    /// Returns whether the object is readable or writable now, as
+    /// [`poll_state`](Self::poll_state) does, and arranges for `cx` to be
+    /// woken when that may have changed, e.g. for `poll`.
+    ///
+    /// The default asks to be polled again at once, for the objects whose
+    /// readiness has no waker.
+    fn poll_ready(&self, cx: &mut Context<'_>) -> AxResult<PollState> {
+        cx.waker().wake_by_ref();
+        self.poll_state()
+    }
+
+    /// Converts to [`Any`], for syscalls that only apply to some kinds of
+    /// objects (e.g. `accept`).
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+struct FdTable {
+    files: Vec<Option<Arc<dyn FileLike>>>,
+}
+
+impl FdTable {
+    fn new() -> Self {
+        let stdin: Arc<dyn FileLike> = Arc::new(Stdin);
+        let stdout: Arc<dyn FileLike> = Arc::new(Stdout);
+        // stdin, stdout, stderr
+        let files = alloc::vec![Some(stdin), Some(stdout.clone()), Some(stdout)];
+        Self { files }
+    }
+
+    fn get(&self, fd: c_int) -> Option<&Arc<dyn FileLike>> {
+        let fd = usize::try_from(fd).ok()?;
+        self.files.get(fd)?.as_ref()
+    }
+
+    /// Installs `f` at the lowest free file descriptor.
+    fn add(&mut self, f: Arc<dyn FileLike>) -> Option<c_int> {
+        let fd = match self.files.iter().position(Option::is_none) {
+            Some(fd) => fd,
+            None if self.files.len() < AX_FILE_LIMIT => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+            None => return None,
+        };
+        self.files[fd] = Some(f);
+        Some(fd as c_int)
+    }
+
+    fn remove(&mut self, fd: c_int) -> Option<Arc<dyn FileLike>> {
+        let fd = usize::try_from(fd).ok()?;
+        self.files.get_mut(fd)?.take()
+    }
+}
+
+static FD_TABLE: Mutex<Option<FdTable>> = Mutex::new(None);
+
+fn with_fd_table<R>(f: impl FnOnce(&mut FdTable) -> R) -> R {
+    let mut table = FD_TABLE.lock();
+    f(table.get_or_insert_with(FdTable::new))
+}
+
+/// Returns the object referred to by `fd`.
+pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+    with_fd_table(|table| table.get(fd).cloned()).ok_or(LinuxError::EBADF)
+}
+
+/// Installs `f` at the lowest free file descriptor, and returns it.
+pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
+    with_fd_table(|table| table.add(f)).ok_or(LinuxError::EMFILE)
+}
+
+/// Removes `fd` from the table.
+///
+/// The object is dropped when no other file descriptor refers to it.
+pub fn close_file_like(fd: c_int) -> LinuxResult {
+    let f = with_fd_table(|table| table.remove(fd)).ok_or(LinuxError::EBADF)?;
+    // Dropped outside the table lock, closing may take a while.
+    drop(f);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pipe;
+
+    fn pipe_end() -> Arc<dyn FileLike> {
+        Arc::new(Pipe::new().0)
+    }
+
+    #[test]
+    fn lowest_free_fd() {
+        let mut table = FdTable::new();
+        assert!(table.get(0).is_some() && table.get(2).is_some());
+        assert_eq!(table.add(pipe_end()), Some(3));
+        assert_eq!(table.add(pipe_end()), Some(4));
+        assert!(table.remove(3).is_some());
+        assert!(table.get(3).is_none());
+        // Reused before growing.
+        assert_eq!(table.add(pipe_end()), Some(3));
+        assert!(table.remove(3).is_some());
+        assert!(table.remove(3).is_none());
+        assert!(table.remove(-1).is_none());
+        assert!(table.get(-1).is_none());
+    }
+
+    #[test]
+    fn file_limit() {
+        let mut table = FdTable::new();
+        for fd in 3..AX_FILE_LIMIT {
+            assert_eq!(table.add(pipe_end()), Some(fd as c_int));
+        }
+        assert_eq!(table.add(pipe_end()), None);
+        assert!(table.remove(10).is_some());
+        assert_eq!(table.add(pipe_end()), Some(10));
+    }
+}
@@ -0,0 +1,49 @@
+use alloc::sync::Arc;
+use core::any::Any;
+use core::task::{Context, Poll};
+
+use axerrno::AxResult;
+use axfs::fops::OpenOptions;
+use axio::PollState;
+use axsync::Mutex;
+
+use crate::FileLike;
+
+/// A regular file referred to by a file descriptor.
+///
+/// File systems complete I/O synchronously, so its operations are always
+/// ready.
+pub struct File {
+    inner: Mutex<axfs::fops::File>,
+}
+
+impl File {
+    /// Opens the file at `path`.
+    pub fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
+        let inner = axfs::fops::File::open(path, opts)?;
+        Ok(Self {
+            inner: Mutex::new(inner),
+        })
+    }
+}
+
+impl FileLike for File {
+    fn poll_read(&self, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        Poll::Ready(self.inner.lock().read(buf))
+    }
+
+    fn poll_write(&self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        Poll::Ready(self.inner.lock().write(buf))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
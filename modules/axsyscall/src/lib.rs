@@ -0,0 +1,89 @@
+//! POSIX-like syscalls for [ArceOS](https://github.com/arceos-org/arceos),
+//! backed by the async runtime.
+//!
+//! Every open object (console, pipe, socket, file) implements [`FileLike`]
+//! with poll-style methods and is installed in a file descriptor table. The
+//! `sys_*` functions are blocking shims: they drive the object's futures to
+//! completion with [`axasync::block_on`], so code written against the
+//! classic read/write/accept/poll model runs unchanged over the async core.
+//!
+//! Syscalls follow the Linux convention: they return a non-negative value on
+//! success, and the negated errno on failure.
+//!
+//! # Cargo Features
+//!
+//! - `net`: Socket file descriptors, backed by the async sockets of [axnet].
+//! - `fs`: Regular file descriptors, backed by [axfs].
+//! - `timer`: Wake tasks waiting on a [`TimerFd`] or on a `sys_poll` timeout
+//!   from the timer interrupt. Without it, they cannot be armed.
+//!
+//! [axnet]: https://arceos-org.github.io/arceos/axnet/index.html
+//! [axfs]: https://arceos-org.github.io/arceos/axfs/index.html
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+/// Runs the body of a syscall, and converts its [`LinuxResult`] to the return
+/// value.
+///
+/// [`LinuxResult`]: axerrno::LinuxResult
+macro_rules! syscall_body {
+    ($fn: ident, $($stmt: tt)*) => {{
+        #[allow(clippy::redundant_closure_call)]
+        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
+        match res {
+            Ok(_) => trace!(concat!(stringify!($fn), " => {:?}"), res),
+            Err(_) => debug!(concat!(stringify!($fn), " => {:?}"), res),
+        }
+        match res {
+            Ok(v) => v as isize,
+            Err(e) => e.as_syscall_ret(),
+        }
+    }};
+}
+
+mod eventfd;
+mod fd_table;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "net")]
+mod net;
+mod pipe;
+mod stdio;
+mod sys;
+mod timerfd;
+
+pub use self::eventfd::EventFd;
+pub use self::fd_table::{AX_FILE_LIMIT, FileLike, add_file_like, close_file_like, get_file_like};
+#[cfg(feature = "fs")]
+pub use self::fs::File;
+#[cfg(feature = "net")]
+pub use self::net::Socket;
+pub use self::pipe::{PIPE_BUF_SIZE, Pipe};
+pub use self::sys::*;
+pub use self::timerfd::{TimerFd, TimerSpec};
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use alloc::task::Wake;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::Waker;
+
+    struct FlagWaker(Arc<AtomicBool>);
+
+    impl Wake for FlagWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns a waker that sets the returned flag when woken.
+    pub(crate) fn flag_waker() -> (Waker, Arc<AtomicBool>) {
+        let woken = Arc::new(AtomicBool::new(false));
+        (Waker::from(Arc::new(FlagWaker(woken.clone()))), woken)
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::sync::Arc;
+use core::any::Any;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use axerrno::{AxError, AxResult};
+use axio::PollState;
+use axnet::{TcpSocket, UdpSocket};
+
+use crate::FileLike;
+
+/// A socket referred to by a file descriptor.
+///
+/// It is also an [`AsyncRead`](axasync::AsyncRead) and
+/// [`AsyncWrite`](axasync::AsyncWrite) byte stream, e.g. to be framed with
+/// [`axasync::codec`].
+pub enum Socket {
+    /// A TCP socket.
+    Tcp(TcpSocket),
+    /// A UDP socket, in non-blocking mode.
+    Udp(UdpSocket),
+}
+
+impl Socket {
+    /// Creates a TCP socket.
+    pub fn tcp() -> Self {
+        Self::Tcp(TcpSocket::new())
+    }
+
+    /// Creates a UDP socket.
+    pub fn udp() -> Self {
+        let socket = UdpSocket::new();
+        socket.set_nonblocking(true);
+        Self::Udp(socket)
+    }
+
+    /// Attempts to accept a connection on a listening TCP socket.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<AxResult<TcpSocket>> {
+        match self {
+            Self::Tcp(socket) => {
+                axnet::poll_interfaces();
+                Pin::new(&mut socket.accept_async())
+                    .poll(cx)
+                    .map_ok(|(socket, _)| socket)
+            }
+            Self::Udp(_) => Poll::Ready(Err(AxError::OperationNotSupportedOnEndpoint)),
+        }
+    }
+}
+
+/// Maps [`AxError::WouldBlock`] of a non-blocking operation to
+/// [`Poll::Pending`].
+fn poll_nonblocking<T>(cx: &mut Context<'_>, res: axio::Result<T>) -> Poll<AxResult<T>> {
+    match res {
+        Err(axio::Error::WouldBlock) => {
+            // smoltcp only supports wakers on TCP sockets, ask to be polled
+            // again.
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        res => Poll::Ready(res),
+    }
+}
+
+impl FileLike for Socket {
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        axnet::poll_interfaces();
+        match self {
+            Self::Tcp(socket) => Pin::new(&mut socket.recv_async(buf)).poll(cx),
+            Self::Udp(socket) => poll_nonblocking(cx, socket.recv(buf)),
+        }
+    }
+
+    fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        let res = match self {
+            Self::Tcp(socket) => Pin::new(&mut socket.send_async(buf)).poll(cx),
+            Self::Udp(socket) => poll_nonblocking(cx, socket.send(buf)),
+        };
+        // Transmit what has been queued.
+        axnet::poll_interfaces();
+        res
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        axnet::poll_interfaces();
+        match self {
+            Self::Tcp(socket) => socket.poll(),
+            Self::Udp(socket) => socket.poll(),
+        }
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl axasync::AsyncRead for Socket {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<usize>> {
+        FileLike::poll_read(&*self, cx, buf)
+    }
+}
+
+impl axasync::AsyncWrite for Socket {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        FileLike::poll_write(&*self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<AxResult> {
+        axnet::poll_interfaces();
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,221 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::task::{Context, Poll};
+
+use axasync::WakeBatch;
+use axerrno::{AxResult, ax_err};
+use axio::PollState;
+use spin::Mutex;
+
+use crate::FileLike;
+
+/// The capacity of a pipe in bytes.
+pub const PIPE_BUF_SIZE: usize = 4096;
+
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    /// Tasks waiting for data.
+    readers: WakeBatch,
+    /// Tasks waiting for space.
+    writers: WakeBatch,
+    reader_closed: bool,
+    writer_closed: bool,
+}
+
+/// One end of a pipe.
+pub struct Pipe {
+    readable: bool,
+    buffer: Arc<Mutex<PipeBuffer>>,
+}
+
+impl Pipe {
+    /// Creates a pipe, returns its read end and write end.
+    pub fn new() -> (Pipe, Pipe) {
+        let buffer = Arc::new(Mutex::new(PipeBuffer {
+            data: VecDeque::with_capacity(PIPE_BUF_SIZE),
+            readers: WakeBatch::new(),
+            writers: WakeBatch::new(),
+            reader_closed: false,
+            writer_closed: false,
+        }));
+        let read_end = Pipe {
+            readable: true,
+            buffer: buffer.clone(),
+        };
+        let write_end = Pipe {
+            readable: false,
+            buffer,
+        };
+        (read_end, write_end)
+    }
+
+    fn state(&self, pipe: &PipeBuffer) -> PollState {
+        if self.readable {
+            PollState {
+                readable: !pipe.data.is_empty() || pipe.writer_closed,
+                writable: false,
+            }
+        } else {
+            PollState {
+                readable: false,
+                writable: pipe.data.len() < PIPE_BUF_SIZE || pipe.reader_closed,
+            }
+        }
+    }
+}
+
+impl FileLike for Pipe {
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        if !self.readable {
+            return Poll::Ready(ax_err!(BadFileNumber, "write end of pipe is not readable"));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut pipe = self.buffer.lock();
+        if pipe.data.is_empty() {
+            if pipe.writer_closed {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.readers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(pipe.data.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.data.drain(..n)) {
+            *dst = src;
+        }
+        let writers = core::mem::take(&mut pipe.writers);
+        drop(pipe);
+        writers.wake_all();
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        if self.readable {
+            return Poll::Ready(ax_err!(BadFileNumber, "read end of pipe is not writable"));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut pipe = self.buffer.lock();
+        if pipe.reader_closed {
+            return Poll::Ready(ax_err!(BrokenPipe, "read end of pipe is closed"));
+        }
+        let n = buf.len().min(PIPE_BUF_SIZE - pipe.data.len());
+        if n == 0 {
+            pipe.writers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        pipe.data.extend(&buf[..n]);
+        let readers = core::mem::take(&mut pipe.readers);
+        drop(pipe);
+        readers.wake_all();
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        Ok(self.state(&self.buffer.lock()))
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> AxResult<PollState> {
+        let mut pipe = self.buffer.lock();
+        let state = self.state(&pipe);
+        if self.readable && !state.readable {
+            pipe.readers.push(cx.waker().clone());
+        } else if !self.readable && !state.writable {
+            pipe.writers.push(cx.waker().clone());
+        }
+        Ok(state)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut pipe = self.buffer.lock();
+        // Wake the other end, it sees EOF or EPIPE.
+        let wakers = if self.readable {
+            pipe.reader_closed = true;
+            core::mem::take(&mut pipe.writers)
+        } else {
+            pipe.writer_closed = true;
+            core::mem::take(&mut pipe.readers)
+        };
+        drop(pipe);
+        wakers.wake_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering;
+
+    use axerrno::AxError;
+
+    use crate::tests::flag_waker;
+
+    #[test]
+    fn read_write() {
+        let (read_end, write_end) = Pipe::new();
+        let (waker, woken) = flag_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0; 8];
+
+        assert!(read_end.poll_read(&mut cx, &mut buf).is_pending());
+        assert_eq!(write_end.poll_write(&mut cx, b"hello"), Poll::Ready(Ok(5)));
+        assert!(woken.swap(false, Ordering::SeqCst));
+        assert_eq!(read_end.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(5)));
+        assert_eq!(&buf[..5], b"hello");
+    }
+
+    #[test]
+    fn full_pipe() {
+        let (read_end, write_end) = Pipe::new();
+        let (waker, woken) = flag_waker();
+        let mut cx = Context::from_waker(&waker);
+        let data = [1; PIPE_BUF_SIZE + 1];
+
+        assert_eq!(
+            write_end.poll_write(&mut cx, &data),
+            Poll::Ready(Ok(PIPE_BUF_SIZE))
+        );
+        assert!(!write_end.poll_ready(&mut cx).unwrap().writable);
+        assert!(write_end.poll_write(&mut cx, &data).is_pending());
+        assert_eq!(
+            read_end.poll_read(&mut cx, &mut [0; 16]),
+            Poll::Ready(Ok(16))
+        );
+        assert!(woken.load(Ordering::SeqCst));
+        assert!(write_end.poll_state().unwrap().writable);
+    }
+
+    #[test]
+    fn closed_ends() {
+        let (read_end, write_end) = Pipe::new();
+        let (waker, woken) = flag_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(!read_end.poll_ready(&mut cx).unwrap().readable);
+        drop(write_end);
+        // Woken by the close, and sees EOF.
+        assert!(woken.load(Ordering::SeqCst));
+        assert!(read_end.poll_state().unwrap().readable);
+        assert_eq!(read_end.poll_read(&mut cx, &mut [0; 4]), Poll::Ready(Ok(0)));
+
+        let (read_end, write_end) = Pipe::new();
+        drop(read_end);
+        assert_eq!(
+            write_end.poll_write(&mut cx, b"x"),
+            Poll::Ready(Err(AxError::BrokenPipe))
+        );
+        assert_eq!(
+            write_end.poll_read(&mut cx, &mut [0; 4]),
+            Poll::Ready(Err(AxError::BadFileNumber))
+        );
+    }
+}
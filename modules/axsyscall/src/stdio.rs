@@ -0,0 +1,68 @@
+use alloc::sync::Arc;
+use core::any::Any;
+use core::task::{Context, Poll};
+
+use axerrno::AxResult;
+use axio::PollState;
+
+use crate::FileLike;
+
+/// The console input, file descriptor 0.
+pub struct Stdin;
+
+/// The console output, file descriptors 1 and 2.
+pub struct Stdout;
+
+impl FileLike for Stdin {
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        match axhal::console::read_bytes(buf) {
+            0 => {
+                // The console has no interrupt-driven waker, ask to be polled
+                // again.
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            n => Poll::Ready(Ok(n)),
+        }
+    }
+
+    fn poll_write(&self, _cx: &mut Context<'_>, _buf: &[u8]) -> Poll<AxResult<usize>> {
+        Poll::Ready(axerrno::ax_err!(BadFileNumber, "stdin is not writable"))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl FileLike for Stdout {
+    fn poll_read(&self, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        Poll::Ready(axerrno::ax_err!(BadFileNumber, "stdout is not readable"))
+    }
+
+    fn poll_write(&self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        axhal::console::write_bytes(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        Ok(PollState {
+            readable: false,
+            writable: true,
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
@@ -0,0 +1,332 @@
+//! The syscall shims.
+
+use alloc::sync::Arc;
+use core::ffi::c_int;
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+#[cfg(feature = "timer")]
+use core::time::Duration;
+
+use axerrno::{AxResult, LinuxError, LinuxResult};
+
+use crate::{EventFd, Pipe, TimerFd, TimerSpec, add_file_like, close_file_like, get_file_like};
+
+/// There is data to read.
+pub const POLLIN: i16 = 0x001;
+/// Writing is now possible.
+pub const POLLOUT: i16 = 0x004;
+/// Error condition.
+pub const POLLERR: i16 = 0x008;
+/// Invalid file descriptor.
+pub const POLLNVAL: i16 = 0x020;
+
+/// A file descriptor to be waited on by [`sys_poll`], same as `struct pollfd`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollFd {
+    /// The file descriptor.
+    pub fd: c_int,
+    /// The requested events.
+    pub events: i16,
+    /// The returned events.
+    pub revents: i16,
+}
+
+/// Drives a poll-style operation to completion.
+///
+/// When called from an async task, it fails with `EINTR` once the task is
+/// signaled (see [`axasync::signal`]).
+fn block_on<T>(mut f: impl FnMut(&mut Context<'_>) -> Poll<AxResult<T>>) -> AxResult<T> {
+    axasync::block_on(axasync::interruptible(poll_fn(|cx| f(cx))))?
+}
+
+/// Reads from `fd` into `buf`, blocking until some data is available.
+///
+/// Returns the number of bytes read, `0` at end of file.
+pub fn sys_read(fd: c_int, buf: &mut [u8]) -> isize {
+    trace!("sys_read <= fd: {}, len: {}", fd, buf.len());
+    syscall_body!(sys_read, {
+        let f = get_file_like(fd)?;
+        Ok(block_on(|cx| f.poll_read(cx, buf))?)
+    })
+}
+
+/// Writes `buf` to `fd`, blocking until some of it can be written.
+///
+/// Returns the number of bytes written.
+pub fn sys_write(fd: c_int, buf: &[u8]) -> isize {
+    trace!("sys_write <= fd: {}, len: {}", fd, buf.len());
+    syscall_body!(sys_write, {
+        let f = get_file_like(fd)?;
+        Ok(block_on(|cx| f.poll_write(cx, buf))?)
+    })
+}
+
+/// Closes `fd`.
+pub fn sys_close(fd: c_int) -> isize {
+    debug!("sys_close <= {}", fd);
+    syscall_body!(sys_close, close_file_like(fd).map(|_| 0))
+}
+
+/// Duplicates `fd` to the lowest free file descriptor.
+pub fn sys_dup(fd: c_int) -> isize {
+    debug!("sys_dup <= {}", fd);
+    syscall_body!(sys_dup, {
+        let f = get_file_like(fd)?;
+        Ok(add_file_like(f)?)
+    })
+}
+
+/// Creates a pipe, stores its read end in `fds[0]` and its write end in
+/// `fds[1]`.
+pub fn sys_pipe(fds: &mut [c_int; 2]) -> isize {
+    debug!("sys_pipe");
+    syscall_body!(sys_pipe, {
+        let (read_end, write_end) = Pipe::new();
+        let read_fd = add_file_like(Arc::new(read_end))?;
+        let write_fd = add_file_like(Arc::new(write_end)).inspect_err(|_| {
+            close_file_like(read_fd).ok();
+        })?;
+        *fds = [read_fd, write_fd];
+        Ok(0)
+    })
+}
+
+/// Read from an [`EventFd`] in semaphore mode.
+pub const EFD_SEMAPHORE: c_int = 1;
+
+/// Creates an [`EventFd`] with the initial value `initval`, returns its file
+/// descriptor.
+pub fn sys_eventfd(initval: u32, flags: c_int) -> isize {
+    debug!("sys_eventfd <= initval: {}, flags: {:#x}", initval, flags);
+    syscall_body!(sys_eventfd, {
+        if flags & !EFD_SEMAPHORE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let eventfd = EventFd::new(initval as u64, flags & EFD_SEMAPHORE != 0);
+        Ok(add_file_like(Arc::new(eventfd))?)
+    })
+}
+
+fn get_timerfd(fd: c_int) -> LinuxResult<Arc<TimerFd>> {
+    get_file_like(fd)?
+        .into_any()
+        .downcast::<TimerFd>()
+        .map_err(|_| LinuxError::EINVAL)
+}
+
+/// Creates a disarmed [`TimerFd`], returns its file descriptor.
+pub fn sys_timerfd_create() -> isize {
+    debug!("sys_timerfd_create");
+    syscall_body!(sys_timerfd_create, {
+        Ok(add_file_like(Arc::new(TimerFd::new()))?)
+    })
+}
+
+/// Arms or disarms the [`TimerFd`] `fd`, and stores the previous setting in
+/// `old` if given.
+///
+/// Without the `timer` feature, arming it fails, see [`TimerFd::set`].
+pub fn sys_timerfd_settime(fd: c_int, new: &TimerSpec, old: Option<&mut TimerSpec>) -> isize {
+    debug!("sys_timerfd_settime <= {} {:?}", fd, new);
+    syscall_body!(sys_timerfd_settime, {
+        let prev = get_timerfd(fd)?.set(*new)?;
+        if let Some(old) = old {
+            *old = prev;
+        }
+        Ok(0)
+    })
+}
+
+/// Stores the current setting of the [`TimerFd`] `fd` in `curr`.
+pub fn sys_timerfd_gettime(fd: c_int, curr: &mut TimerSpec) -> isize {
+    syscall_body!(sys_timerfd_gettime, {
+        *curr = get_timerfd(fd)?.get();
+        Ok(0)
+    })
+}
+
+/// Waits for one of `fds` to become ready.
+///
+/// `timeout_ms` is the maximum time to wait in milliseconds, negative to wait
+/// forever. Returns the number of file descriptors with nonzero `revents`,
+/// `0` on timeout.
+///
+/// The caller sleeps until one of the objects wakes it, see
+/// [`FileLike::poll_ready`](crate::FileLike::poll_ready), or the timeout
+/// expires. Without the `timer` feature, nothing would wake it at the
+/// timeout: a timeout other than `0` or negative fails with `EINVAL`.
+pub fn sys_poll(fds: &mut [PollFd], timeout_ms: c_int) -> isize {
+    trace!("sys_poll <= nfds: {}, timeout: {}", fds.len(), timeout_ms);
+    syscall_body!(sys_poll, {
+        #[cfg(not(feature = "timer"))]
+        if timeout_ms > 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        // Armed once, at the first poll, and cancelled when the call returns.
+        #[cfg(feature = "timer")]
+        let mut timeout = core::pin::pin!(u64::try_from(timeout_ms).ok().map(|ms| {
+            axasync::time::Sleep::new(Duration::from_millis(ms))
+        }));
+        let ready = block_on(|cx| {
+            let ready = poll_fds(fds, cx);
+            if ready > 0 {
+                return Poll::Ready(Ok(ready));
+            }
+            #[cfg(feature = "timer")]
+            if let Some(sleep) = timeout.as_mut().as_pin_mut() {
+                if sleep.poll(cx).is_ready() {
+                    return Poll::Ready(Ok(0));
+                }
+            }
+            #[cfg(not(feature = "timer"))]
+            if timeout_ms == 0 {
+                return Poll::Ready(Ok(0));
+            }
+            Poll::Pending
+        })?;
+        Ok(ready)
+    })
+}
+
+/// Fills in the `revents` of `fds`, returns the number of ready ones, and
+/// arranges for `cx` to be woken when they may change.
+fn poll_fds(fds: &mut [PollFd], cx: &mut Context<'_>) -> usize {
+    let mut ready = 0;
+    for pfd in fds.iter_mut() {
+        pfd.revents = 0;
+        if pfd.fd < 0 {
+            continue;
+        }
+        match get_file_like(pfd.fd).map(|f| f.poll_ready(cx)) {
+            Ok(Ok(state)) => {
+                if state.readable && pfd.events & POLLIN != 0 {
+                    pfd.revents |= POLLIN;
+                }
+                if state.writable && pfd.events & POLLOUT != 0 {
+                    pfd.revents |= POLLOUT;
+                }
+            }
+            Ok(Err(_)) => pfd.revents |= POLLERR,
+            Err(_) => pfd.revents |= POLLNVAL,
+        }
+        if pfd.revents != 0 {
+            ready += 1;
+        }
+    }
+    ready
+}
+
+/// Opens the file at `path`, returns its file descriptor.
+#[cfg(feature = "fs")]
+pub fn sys_open(path: &str, opts: &axfs::fops::OpenOptions) -> isize {
+    debug!("sys_open <= {:?}", path);
+    syscall_body!(sys_open, {
+        let file = crate::File::open(path, opts)?;
+        Ok(add_file_like(Arc::new(file))?)
+    })
+}
+
+#[cfg(feature = "net")]
+mod net {
+    use alloc::sync::Arc;
+    use core::ffi::c_int;
+    use core::future::Future;
+    use core::net::SocketAddr;
+    use core::pin::Pin;
+
+    use axerrno::{LinuxError, LinuxResult};
+
+    use super::block_on;
+    use crate::{Socket, add_file_like, get_file_like};
+
+    /// Stream socket (TCP).
+    pub const SOCK_STREAM: c_int = 1;
+    /// Datagram socket (UDP).
+    pub const SOCK_DGRAM: c_int = 2;
+
+    fn get_socket(fd: c_int) -> LinuxResult<Arc<Socket>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Socket>()
+            .map_err(|_| LinuxError::ENOTSOCK)
+    }
+
+    /// Creates a socket of type `ty` ([`SOCK_STREAM`] or [`SOCK_DGRAM`]),
+    /// returns its file descriptor.
+    pub fn sys_socket(ty: c_int) -> isize {
+        debug!("sys_socket <= {}", ty);
+        syscall_body!(sys_socket, {
+            let socket = match ty {
+                SOCK_STREAM => Socket::tcp(),
+                SOCK_DGRAM => Socket::udp(),
+                _ => return Err(LinuxError::ESOCKTNOSUPPORT),
+            };
+            Ok(add_file_like(Arc::new(socket))?)
+        })
+    }
+
+    /// Binds the socket `fd` to `addr`.
+    pub fn sys_bind(fd: c_int, addr: SocketAddr) -> isize {
+        debug!("sys_bind <= {} {}", fd, addr);
+        syscall_body!(sys_bind, {
+            let res = match &*get_socket(fd)? {
+                Socket::Tcp(socket) => socket.bind(addr),
+                Socket::Udp(socket) => socket.bind(addr),
+            };
+            res?;
+            Ok(0)
+        })
+    }
+
+    /// Connects the socket `fd` to `addr`, blocking until the connection is
+    /// established.
+    pub fn sys_connect(fd: c_int, addr: SocketAddr) -> isize {
+        debug!("sys_connect <= {} {}", fd, addr);
+        syscall_body!(sys_connect, {
+            match &*get_socket(fd)? {
+                Socket::Tcp(socket) => {
+                    let mut fut = socket.connect_async(addr);
+                    block_on(|cx| {
+                        axnet::poll_interfaces();
+                        Pin::new(&mut fut).poll(cx)
+                    })?
+                }
+                Socket::Udp(socket) => socket.connect(addr)?,
+            }
+            Ok(0)
+        })
+    }
+
+    /// Starts listening on the TCP socket `fd`.
+    pub fn sys_listen(fd: c_int) -> isize {
+        debug!("sys_listen <= {}", fd);
+        syscall_body!(sys_listen, {
+            match &*get_socket(fd)? {
+                Socket::Tcp(socket) => socket.listen()?,
+                Socket::Udp(_) => return Err(LinuxError::EOPNOTSUPP),
+            }
+            Ok(0)
+        })
+    }
+
+    /// Accepts a connection on the listening socket `fd`, blocking until one
+    /// arrives.
+    ///
+    /// Returns the file descriptor of the connected socket, and stores the
+    /// address of the peer in `addr` if given.
+    pub fn sys_accept(fd: c_int, addr: Option<&mut SocketAddr>) -> isize {
+        debug!("sys_accept <= {}", fd);
+        syscall_body!(sys_accept, {
+            let listener = get_socket(fd)?;
+            let socket = block_on(|cx| listener.poll_accept(cx))?;
+            if let Some(addr) = addr {
+                *addr = socket.peer_addr()?;
+            }
+            Ok(add_file_like(Arc::new(Socket::Tcp(socket)))?)
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+pub use self::net::*;
@@ -0,0 +1,243 @@
+#[cfg(feature = "timer")]
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::any::Any;
+#[cfg(feature = "timer")]
+use core::future::Future;
+use core::future::poll_fn;
+#[cfg(feature = "timer")]
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+
+use axasync::WakeBatch;
+#[cfg(feature = "timer")]
+use axasync::time::Sleep;
+use axerrno::{AxResult, ax_err};
+use axhal::time::{TimeValue, monotonic_time};
+use axio::PollState;
+use spin::Mutex;
+
+use crate::FileLike;
+
+/// The setting of a [`TimerFd`], same as `struct itimerspec`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimerSpec {
+    /// The period of the timer, zero for a one-shot timer.
+    pub interval: Duration,
+    /// The time until the next expiration, zero if the timer is disarmed.
+    pub value: Duration,
+}
+
+struct TimerFdInner {
+    /// The next expiration, [`None`] if disarmed.
+    deadline: Option<TimeValue>,
+    interval: Duration,
+    /// Expirations that have not been read.
+    expirations: u64,
+    /// Tasks waiting for the timer to expire.
+    readers: WakeBatch,
+    /// The timer of the next expiration, armed with the waker of the last
+    /// reader, who wakes the others. Allocated once, it is only moved to
+    /// another deadline when the deadline changes.
+    #[cfg(feature = "timer")]
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl TimerFdInner {
+    /// Accounts the expirations up to `now`, and returns the readers to wake
+    /// if the timer expired.
+    fn update(&mut self, now: TimeValue) -> WakeBatch {
+        let Some(deadline) = self.deadline else {
+            return WakeBatch::new();
+        };
+        if now < deadline {
+            return WakeBatch::new();
+        }
+        if self.interval.is_zero() {
+            self.expirations += 1;
+            self.deadline = None;
+        } else {
+            let periods = (now - deadline).as_nanos() / self.interval.as_nanos() + 1;
+            self.expirations += periods as u64;
+            let advance = periods * self.interval.as_nanos();
+            self.deadline = Some(deadline + Duration::from_nanos(advance as u64));
+        }
+        core::mem::take(&mut self.readers)
+    }
+}
+
+/// A timer that counts its expirations, like Linux `timerfd`.
+///
+/// Reading returns the number of expirations since the last read, waiting
+/// until the timer expires if it is zero. The waiting readers are woken by a
+/// timer of [`axasync`], so it can only be armed with the `timer` feature.
+pub struct TimerFd {
+    inner: Mutex<TimerFdInner>,
+}
+
+impl TimerFd {
+    /// Creates a disarmed timer.
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(TimerFdInner {
+                deadline: None,
+                interval: Duration::ZERO,
+                expirations: 0,
+                readers: WakeBatch::new(),
+                #[cfg(feature = "timer")]
+                timer: None,
+            }),
+        }
+    }
+
+    /// Arms the timer to expire after `spec.value` and then every
+    /// `spec.interval`, or disarms it if `spec.value` is zero.
+    ///
+    /// Returns the previous setting. Unread expirations are discarded.
+    ///
+    /// Without the `timer` feature, nothing would wake the readers at the
+    /// expiration: arming it fails with [`Unsupported`](axerrno::AxError::Unsupported).
+    pub fn set(&self, spec: TimerSpec) -> AxResult<TimerSpec> {
+        #[cfg(not(feature = "timer"))]
+        if !spec.value.is_zero() {
+            return ax_err!(Unsupported, "timerfd armed without the timer feature");
+        }
+        let now = monotonic_time();
+        let mut inner = self.inner.lock();
+        let old = Self::spec(&inner, now);
+        inner.expirations = 0;
+        inner.interval = spec.interval;
+        inner.deadline = if spec.value.is_zero() {
+            None
+        } else {
+            Some(now + spec.value)
+        };
+        // Disarmed, its timer is cancelled.
+        #[cfg(feature = "timer")]
+        if inner.deadline.is_none() {
+            inner.timer = None;
+        }
+        // Let waiting readers wait for the new deadline.
+        let readers = core::mem::take(&mut inner.readers);
+        drop(inner);
+        readers.wake_all();
+        Ok(old)
+    }
+
+    /// Returns the current setting.
+    pub fn get(&self) -> TimerSpec {
+        let now = monotonic_time();
+        let mut inner = self.inner.lock();
+        let expired = inner.update(now);
+        let spec = Self::spec(&inner, now);
+        drop(inner);
+        expired.wake_all();
+        spec
+    }
+
+    fn spec(inner: &TimerFdInner, now: TimeValue) -> TimerSpec {
+        TimerSpec {
+            interval: inner.interval,
+            value: inner
+                .deadline
+                .map_or(Duration::ZERO, |d| d.saturating_sub(now)),
+        }
+    }
+
+    /// Attempts to take the expirations since the last call.
+    pub fn poll_expirations(&self, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut inner = self.inner.lock();
+        let expired = inner.update(monotonic_time());
+        let poll = if inner.expirations > 0 {
+            Poll::Ready(core::mem::take(&mut inner.expirations))
+        } else {
+            Self::register(&mut inner, cx);
+            Poll::Pending
+        };
+        drop(inner);
+        expired.wake_all();
+        poll
+    }
+
+    /// Arranges for `cx` to be woken at the next expiration, or by `set`
+    /// while disarmed.
+    fn register(inner: &mut TimerFdInner, cx: &mut Context<'_>) {
+        inner.readers.push(cx.waker().clone());
+        #[cfg(feature = "timer")]
+        match inner.deadline {
+            Some(deadline) => {
+                let timer = inner
+                    .timer
+                    .get_or_insert_with(|| Box::pin(Sleep::until(deadline)));
+                // Moved only if the deadline changed, the old entry is
+                // cancelled.
+                if timer.deadline() != deadline {
+                    timer.as_mut().reset_until(deadline);
+                }
+                // Not expired, `update` saw the deadline ahead.
+                let _ = timer.as_mut().poll(cx);
+            }
+            None => inner.timer = None,
+        }
+    }
+
+    /// Waits for the timer to expire, and returns the number of expirations
+    /// since the last call.
+    pub async fn expirations(&self) -> u64 {
+        poll_fn(|cx| self.poll_expirations(cx)).await
+    }
+}
+
+impl Default for TimerFd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileLike for TimerFd {
+    fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        let Some(buf) = buf.first_chunk_mut::<8>() else {
+            return Poll::Ready(ax_err!(InvalidInput, "timerfd read buffer too small"));
+        };
+        self.poll_expirations(cx).map(|n| {
+            *buf = n.to_ne_bytes();
+            Ok(8)
+        })
+    }
+
+    fn poll_write(&self, _cx: &mut Context<'_>, _buf: &[u8]) -> Poll<AxResult<usize>> {
+        Poll::Ready(ax_err!(InvalidInput, "timerfd is not writable"))
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        let mut inner = self.inner.lock();
+        let expired = inner.update(monotonic_time());
+        let readable = inner.expirations > 0;
+        drop(inner);
+        expired.wake_all();
+        Ok(PollState {
+            readable,
+            writable: false,
+        })
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> AxResult<PollState> {
+        let mut inner = self.inner.lock();
+        let expired = inner.update(monotonic_time());
+        let readable = inner.expirations > 0;
+        if !readable {
+            Self::register(&mut inner, cx);
+        }
+        drop(inner);
+        expired.wake_all();
+        Ok(PollState {
+            readable,
+            writable: false,
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
@@ -53,6 +53,17 @@ impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     }
 }
 
+/// Preempts the current task on the IRQ return path, if an IRQ handler
+/// requested it by [`axhal::trap::set_need_resched`].
+#[cfg(feature = "preempt")]
+#[axhal::trap::register_trap_handler(axhal::trap::RESCHED)]
+fn resched_on_irq_return() {
+    if let Some(curr) = current_may_uninit() {
+        curr.set_preempt_pending(true);
+        crate::task::TaskInner::current_check_preempt_pending();
+    }
+}
+
 /// Gets the current task, or returns [`None`] if the current task is not
 /// initialized.
 pub fn current_may_uninit() -> Option<CurrentTask> {
@@ -262,7 +262,13 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
             // we just ingiore the `resched` flag.
             if resched && cpu_id == this_cpu_id() {
                 #[cfg(feature = "preempt")]
-                crate::current().set_preempt_pending(true);
+                {
+                    crate::current().set_preempt_pending(true);
+                    // Woken by an IRQ handler, e.g. a `WaitQueue` notified
+                    // by a driver, it preempts the interrupted task on the
+                    // IRQ return rather than at the next tick.
+                    axhal::trap::set_need_resched();
+                }
             }
         }
     }
@@ -1,11 +1,8 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, AtomicUsize, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
-
 use kspin::SpinNoIrq;
 use memory_addr::{VirtAddr, align_up_4k};
 
@@ -66,6 +63,13 @@ pub struct TaskInner {
     #[cfg(feature = "preempt")]
     preempt_disable_count: AtomicUsize,
 
+    /// Depth of the nested sections in which the task must not block.
+    nonblocking_count: AtomicUsize,
+    /// Number of the sleeping locks held by the task.
+    held_locks: AtomicUsize,
+    /// What the task is polling, set by the async runtime, `0` if nothing.
+    poll_context: AtomicUsize,
+
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
 
@@ -209,6 +213,71 @@ impl TaskInner {
     pub fn set_cpumask(&self, cpumask: AxCpuMask) {
         *self.cpumask.lock() = cpumask
     }
+
+    /// Marks the task as running code that must not block, e.g. the poll of
+    /// an async task, until the matching [`exit_nonblocking`].
+    ///
+    /// Sections can be nested. Blocking primitives may check
+    /// [`is_nonblocking`] to report misuse.
+    ///
+    /// [`exit_nonblocking`]: Self::exit_nonblocking
+    /// [`is_nonblocking`]: Self::is_nonblocking
+    #[inline]
+    pub fn enter_nonblocking(&self) {
+        self.nonblocking_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Ends a section started by [`enter_nonblocking`](Self::enter_nonblocking).
+    #[inline]
+    pub fn exit_nonblocking(&self) {
+        self.nonblocking_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Returns whether the task is in a section in which it must not block.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking_count.load(Ordering::Relaxed) != 0
+    }
+
+    /// Records that the task acquired a sleeping lock, e.g. an
+    /// `axsync::Mutex`, until the matching [`lock_released`].
+    ///
+    /// [`lock_released`]: Self::lock_released
+    #[inline]
+    pub fn lock_acquired(&self) {
+        self.held_locks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the task released a lock, see
+    /// [`lock_acquired`](Self::lock_acquired).
+    #[inline]
+    pub fn lock_released(&self) {
+        self.held_locks.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of the sleeping locks held by the task, e.g. to
+    /// check that a poll does not return with a lock held across an await.
+    #[inline]
+    pub fn held_locks(&self) -> usize {
+        self.held_locks.load(Ordering::Relaxed)
+    }
+
+    /// Sets what the task is polling, an address opaque to axtask, and
+    /// returns the previous one, see [`poll_context`](Self::poll_context).
+    ///
+    /// The async runtime records the async task it polls on the axtask
+    /// rather than on the CPU, as another axtask may preempt the poll.
+    #[inline]
+    pub fn swap_poll_context(&self, context: usize) -> usize {
+        self.poll_context.swap(context, Ordering::AcqRel)
+    }
+
+    /// Returns what the task is polling, `0` if nothing, see
+    /// [`swap_poll_context`](Self::swap_poll_context).
+    #[inline]
+    pub fn poll_context(&self) -> usize {
+        self.poll_context.load(Ordering::Acquire)
+    }
 }
 
 // private methods
@@ -232,6 +301,9 @@ impl TaskInner {
             need_resched: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
             preempt_disable_count: AtomicUsize::new(0),
+            nonblocking_count: AtomicUsize::new(0),
+            held_locks: AtomicUsize::new(0),
+            poll_context: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
             kstack: None,
@@ -374,7 +446,7 @@ impl TaskInner {
     }
 
     #[cfg(feature = "preempt")]
-    fn current_check_preempt_pending() {
+    pub(crate) fn current_check_preempt_pending() {
         use kernel_guard::NoPreemptIrqSave;
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
@@ -28,6 +28,7 @@ endif
 
 qemu_args-x86_64 := \
   -machine $(machine) \
+  -device isa-debug-exit,iobase=0xf4,iosize=0x04 \
   -kernel $(OUT_ELF)
 
 qemu_args-riscv64 := \
@@ -92,6 +92,8 @@ int seteuid(uid_t);
 int setgid(gid_t);
 int setegid(gid_t);
 
+int gethostname(char *, size_t);
+
 long sysconf(int);
 
 #define _SC_ARG_MAX                      0
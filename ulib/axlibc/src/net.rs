@@ -1,7 +1,7 @@
 use arceos_posix_api::{
-    sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
-    sys_getsockname, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto, sys_shutdown,
-    sys_socket,
+    sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_gethostname,
+    sys_getpeername, sys_getsockname, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto,
+    sys_shutdown, sys_socket,
 };
 use core::ffi::{c_char, c_int, c_void};
 
@@ -153,6 +153,12 @@ pub unsafe extern "C" fn getaddrinfo(
     }
 }
 
+/// Get the host name.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gethostname(name: *mut c_char, len: usize) -> c_int {
+    e(sys_gethostname(name, len))
+}
+
 /// Free queried `addrinfo` struct
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn freeaddrinfo(res: *mut ctypes::addrinfo) {
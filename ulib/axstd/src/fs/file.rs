@@ -1,4 +1,8 @@
+#[cfg(feature = "fs-async")]
+use crate::io::Error;
 use crate::io::{Result, SeekFrom, prelude::*};
+#[cfg(feature = "fs-async")]
+use alloc::{sync::Arc, vec};
 use core::fmt;
 
 use arceos_api::fs as api;
@@ -162,6 +166,54 @@ impl File {
     pub fn metadata(&self) -> Result<Metadata> {
         api::ax_file_attr(&self.inner).map(Metadata)
     }
+
+    /// Reads a number of bytes starting from a given offset.
+    ///
+    /// It does not update the cursor of the file, so the readers of a shared
+    /// file do not need to serialize on its position.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        api::ax_read_file_at(&self.inner, offset, buf)
+    }
+
+    /// Writes a number of bytes starting from a given offset.
+    ///
+    /// It does not update the cursor of the file.
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        api::ax_write_file_at(&self.inner, offset, buf)
+    }
+
+    /// Reads a number of bytes starting from a given offset, like
+    /// [`read_at`](Self::read_at), without blocking the async task.
+    ///
+    /// The read runs on a worker of [`axasync::spawn_blocking`], which shares
+    /// the file, and the bytes are copied to `buf` once it returns.
+    #[cfg(feature = "fs-async")]
+    pub async fn read_at_async(self: &Arc<Self>, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let file = self.clone();
+        let mut data = vec![0; buf.len()];
+        let (len, data) = axasync::spawn_blocking(move || {
+            let len = file.read_at(&mut data, offset)?;
+            Ok::<_, Error>((len, data))
+        })
+        .await
+        .map_err(|_| Error::Interrupted)??;
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    /// Writes a number of bytes starting from a given offset, like
+    /// [`write_at`](Self::write_at), without blocking the async task.
+    ///
+    /// The bytes are copied, and written by a worker of
+    /// [`axasync::spawn_blocking`], which shares the file.
+    #[cfg(feature = "fs-async")]
+    pub async fn write_at_async(self: &Arc<Self>, buf: &[u8], offset: u64) -> Result<usize> {
+        let file = self.clone();
+        let data = buf.to_vec();
+        axasync::spawn_blocking(move || file.write_at(&data, offset))
+            .await
+            .map_err(|_| Error::Interrupted)?
+    }
 }
 
 impl Read for File {
@@ -0,0 +1,228 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::io::{Error, Result, Write};
+use crate::time::Instant;
+
+const DEFAULT_BUF_SIZE: usize = 1024;
+
+/// When a [`BufWriter`] flushes before its buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// Flush once the oldest buffered data has waited this long.
+    pub idle: Option<Duration>,
+    /// Flush once this many bytes are buffered.
+    pub watermark: Option<usize>,
+}
+
+impl FlushPolicy {
+    /// Only flush when the buffer is full, or on [`flush`](Write::flush).
+    pub const fn never() -> Self {
+        Self {
+            idle: None,
+            watermark: None,
+        }
+    }
+
+    /// Flushes buffered data that has waited for `idle`.
+    pub const fn idle(mut self, idle: Duration) -> Self {
+        self.idle = Some(idle);
+        self
+    }
+
+    /// Flushes as soon as `watermark` bytes are buffered.
+    pub const fn watermark(mut self, watermark: usize) -> Self {
+        self.watermark = Some(watermark);
+        self
+    }
+}
+
+/// Statistics of a [`BufWriter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufWriterStats {
+    /// Bytes written to the inner writer.
+    pub bytes_written: u64,
+    /// Flushes because the buffer was full.
+    pub full_flushes: u64,
+    /// Flushes because the watermark was reached.
+    pub watermark_flushes: u64,
+    /// Flushes because the data was idle.
+    pub idle_flushes: u64,
+    /// Flushes requested by [`flush`](Write::flush).
+    pub explicit_flushes: u64,
+}
+
+/// Adds buffering to a writer, with an optional [`FlushPolicy`].
+///
+/// Interactive protocols should not wait for a full buffer: a watermark
+/// bounds the amount of buffered data, and an idle duration bounds how long
+/// it stays buffered. There is no background timer, so the idle duration is
+/// checked on every write, and by [`flush_if_idle`](Self::flush_if_idle),
+/// which the owner should call periodically (e.g. from its event loop).
+///
+/// Buffered data is flushed when the writer is dropped, errors are ignored.
+pub struct BufWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    policy: FlushPolicy,
+    /// When the oldest buffered byte was written.
+    buffered_since: Option<Instant>,
+    stats: BufWriterStats,
+}
+
+impl<W: Write> BufWriter<W> {
+    /// Creates a writer with a 1 KiB buffer that only flushes when full.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    /// Creates a writer with a buffer of `capacity` bytes.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            policy: FlushPolicy::never(),
+            buffered_since: None,
+            stats: BufWriterStats::default(),
+        }
+    }
+
+    /// Sets the flush policy.
+    pub fn with_policy(mut self, policy: FlushPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the flush policy.
+    pub fn policy(&self) -> FlushPolicy {
+        self.policy
+    }
+
+    /// Changes the flush policy.
+    pub fn set_policy(&mut self, policy: FlushPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns the statistics.
+    pub fn stats(&self) -> BufWriterStats {
+        self.stats
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    ///
+    /// Writing to it directly bypasses the buffered data.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the buffered data.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Returns the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Flushes the buffer, and returns the inner writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush_buf()?;
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is not dropped, each field is read once.
+        unsafe {
+            drop(core::ptr::read(&this.buf));
+            Ok(core::ptr::read(&this.inner))
+        }
+    }
+
+    /// Returns whether the buffered data has been idle longer than the idle
+    /// duration of the policy.
+    pub fn is_idle(&self) -> bool {
+        match (self.policy.idle, self.buffered_since) {
+            (Some(idle), Some(since)) => since.elapsed() >= idle,
+            _ => false,
+        }
+    }
+
+    /// Flushes the buffer if its data has been idle too long.
+    ///
+    /// Returns whether it flushed.
+    pub fn flush_if_idle(&mut self) -> Result<bool> {
+        if !self.is_idle() {
+            return Ok(false);
+        }
+        self.stats.idle_flushes += 1;
+        self.flush_buf()?;
+        Ok(true)
+    }
+
+    /// Writes all buffered data to the inner writer.
+    fn flush_buf(&mut self) -> Result<()> {
+        let mut written = 0;
+        let ret = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => break Err(Error::WriteZero),
+                Ok(n) => written += n,
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..written);
+        self.stats.bytes_written += written as u64;
+        if self.buf.is_empty() {
+            self.buffered_since = None;
+        }
+        ret
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.flush_if_idle()?;
+        if self.buf.len() + buf.len() > self.buf.capacity() {
+            self.stats.full_flushes += 1;
+            self.flush_buf()?;
+        }
+        let n = if buf.len() >= self.buf.capacity() {
+            // Too large to be buffered.
+            let n = self.inner.write(buf)?;
+            self.stats.bytes_written += n as u64;
+            n
+        } else {
+            if self.buf.is_empty() {
+                self.buffered_since = Some(Instant::now());
+            }
+            self.buf.extend_from_slice(buf);
+            buf.len()
+        };
+        if self
+            .policy
+            .watermark
+            .is_some_and(|watermark| self.buf.len() >= watermark)
+        {
+            self.stats.watermark_flushes += 1;
+            self.flush_buf()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stats.explicit_flushes += 1;
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
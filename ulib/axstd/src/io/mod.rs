@@ -1,10 +1,15 @@
 //! Traits, helpers, and type definitions for core I/O functionality.
 
+#[cfg(feature = "alloc")]
+mod buffered;
 mod stdio;
 
 pub use axio::prelude;
 pub use axio::{BufRead, BufReader, Error, Read, Seek, SeekFrom, Write};
 
+#[cfg(feature = "alloc")]
+pub use self::buffered::{BufWriter, BufWriterStats, FlushPolicy};
+
 #[doc(hidden)]
 pub use self::stdio::__print_impl;
 pub use self::stdio::{Stdin, StdinLock, Stdout, StdoutLock, stdin, stdout};
@@ -15,6 +15,7 @@
 //!     - `fp_simd`: Enable floating point and SIMD support.
 //! - Interrupts:
 //!     - `irq`: Enable interrupt handling support.
+//!     - `mmio`: Enable awaiting the interrupts of MMIO devices, see [`mmio`].
 //! - Memory
 //!     - `alloc`: Enable dynamic memory allocation.
 //!     - `alloc-tlsf`: Use the TLSF allocator.
@@ -27,8 +28,13 @@
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `lockstat`: Record lock contention statistics.
+//!     - `async`: Let [`thread::block_in_place`] hand the other async tasks to
+//!       another thread, for threads that run the axasync executor.
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
+//!     - `fs-async`: Enable the async positional reads and writes of
+//!       [`fs::File`], run on the blocking workers of axasync.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
@@ -73,5 +79,7 @@ pub mod time;
 
 #[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(feature = "mmio")]
+pub mod mmio;
 #[cfg(feature = "net")]
 pub mod net;
@@ -5,23 +5,25 @@
 // Re-export the relevant types from axasync
 pub use axasync::mmio::{MmioEvent, MmioEventHandler, MmioEventId, MmioWakerSet};
 
-/// Wait for an MMIO event to occur.
+/// Wait for an MMIO event to occur, and return its payload.
 ///
-/// This function is a convenience wrapper around `MmioEvent::new`.
+/// This function is a convenience wrapper around `MmioEvent::new`. It returns `None`
+/// if the event cannot be registered.
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// use axstd::mmio::{wait_for_event, MmioEventHandler};
 /// use alloc::sync::Arc;
 ///
 /// async fn example(device: Arc<impl MmioEventHandler>) {
 ///     // Wait for an MMIO event
-///     let event_id = wait_for_event(&device).await;
-///     println!("Event {} occurred!", event_id);
+///     if wait_for_event(&device).await.is_some() {
+///         println!("Event occurred!");
+///     }
 /// }
 /// ```
-pub async fn wait_for_event<H: MmioEventHandler>(device: &alloc::sync::Arc<H>) -> MmioEventId {
+pub async fn wait_for_event<H: MmioEventHandler>(device: &alloc::sync::Arc<H>) -> Option<H::Data> {
     MmioEvent::new(device.clone()).await
 }
 
@@ -31,7 +33,7 @@ pub async fn wait_for_event<H: MmioEventHandler>(device: &alloc::sync::Arc<H>) -
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// use axstd::mmio::{wait_for_specific_event, MmioEventHandler};
 /// use alloc::sync::Arc;
 ///
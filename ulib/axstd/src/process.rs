@@ -5,6 +5,11 @@
 //! will shutdown the whole system.
 
 /// Shutdown the whole system.
-pub fn exit(_exit_code: i32) -> ! {
-    arceos_api::sys::ax_terminate();
+///
+/// In QEMU, `exit_code` becomes the exit status of the emulator (see
+/// [`axhal::misc::exit`] for the devices it needs).
+///
+/// [`axhal::misc::exit`]: https://arceos-org.github.io/arceos/axhal/misc/fn.exit.html
+pub fn exit(exit_code: i32) -> ! {
+    arceos_api::sys::ax_exit(exit_code as u32);
 }
@@ -27,6 +27,8 @@ pub fn exit(exit_code: i32) -> ! {
 /// Current thread is going to sleep for the given duration.
 ///
 /// If one of `multitask` or `irq` features is not enabled, it uses busy-wait
+/// instead. In the poll of an async task, where it stalls the executor, it
+/// warns and sleeps as [`block_in_place`] does: await `axasync::sleep`
 /// instead.
 pub fn sleep(dur: core::time::Duration) {
     sleep_until(arceos_api::time::ax_wall_time() + dur);
@@ -39,3 +41,23 @@ pub fn sleep(dur: core::time::Duration) {
 pub fn sleep_until(deadline: arceos_api::time::AxTimeValue) {
     api::ax_sleep_until(deadline);
 }
+
+/// Runs the blocking function `f` in place, in the poll of an async task.
+///
+/// The executor is told that the poll blocks: with the `async` feature,
+/// another thread runs its other tasks until `f` returns. Outside of an
+/// async task, or without `multitask`, it just calls `f`.
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    #[cfg(feature = "multitask")]
+    {
+        let mut f = Some(f);
+        let mut res = None;
+        api::ax_block_in_place(&mut || res = f.take().map(|f| f()));
+        res.expect("block_in_place: `f` was not called")
+    }
+    #[cfg(not(feature = "multitask"))]
+    f()
+}